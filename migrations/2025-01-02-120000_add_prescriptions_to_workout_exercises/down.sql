@@ -0,0 +1,5 @@
+ALTER TABLE workout_exercises
+    DROP COLUMN rest_seconds,
+    DROP COLUMN weight,
+    DROP COLUMN reps,
+    DROP COLUMN sets;
@@ -0,0 +1,5 @@
+ALTER TABLE workout_exercises
+    ADD COLUMN sets INTEGER CHECK (sets > 0),
+    ADD COLUMN reps INTEGER CHECK (reps > 0),
+    ADD COLUMN weight DOUBLE PRECISION CHECK (weight >= 0),
+    ADD COLUMN rest_seconds INTEGER CHECK (rest_seconds >= 0);
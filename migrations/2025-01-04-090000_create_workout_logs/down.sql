@@ -0,0 +1,2 @@
+DROP TABLE workout_log_sets;
+DROP TABLE workout_logs;
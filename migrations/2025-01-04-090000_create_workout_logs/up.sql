@@ -0,0 +1,27 @@
+CREATE TABLE workout_logs (
+    id BIGSERIAL PRIMARY KEY,
+    uuid UUID NOT NULL UNIQUE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workout_id BIGINT NOT NULL REFERENCES workouts(id) ON DELETE CASCADE,
+    performed_at TIMESTAMP NOT NULL,
+    notes TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE workout_log_sets (
+    id BIGSERIAL PRIMARY KEY,
+    workout_log_id BIGINT NOT NULL REFERENCES workout_logs(id) ON DELETE CASCADE,
+    exercise_id BIGINT NOT NULL REFERENCES exercises(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    set_number INTEGER NOT NULL CHECK (set_number > 0),
+    reps INTEGER NOT NULL CHECK (reps >= 0),
+    weight DOUBLE PRECISION CHECK (weight >= 0),
+    rpe DOUBLE PRECISION CHECK (rpe >= 1 AND rpe <= 10),
+    UNIQUE (workout_log_id, exercise_id, set_number)
+);
+
+CREATE INDEX workout_logs_user_id_performed_at_idx ON workout_logs(user_id, performed_at);
+CREATE INDEX workout_logs_workout_id_idx ON workout_logs(workout_id);
+CREATE INDEX workout_log_sets_workout_log_id_idx ON workout_log_sets(workout_log_id);
+CREATE INDEX workout_log_sets_exercise_id_idx ON workout_log_sets(exercise_id);
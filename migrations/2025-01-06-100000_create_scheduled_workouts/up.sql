@@ -0,0 +1,15 @@
+CREATE TABLE scheduled_workouts (
+    id BIGSERIAL PRIMARY KEY,
+    uuid UUID NOT NULL UNIQUE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workout_id BIGINT NOT NULL REFERENCES workouts(id) ON DELETE CASCADE,
+    scheduled_at TIMESTAMP NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'planned'
+        CHECK (status IN ('planned', 'completed', 'skipped', 'missed')),
+    notes TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX scheduled_workouts_user_id_scheduled_at_idx ON scheduled_workouts(user_id, scheduled_at);
+CREATE INDEX scheduled_workouts_workout_id_idx ON scheduled_workouts(workout_id);
@@ -0,0 +1,2 @@
+DROP TABLE recurring_schedule_exceptions;
+DROP TABLE recurring_schedules;
@@ -0,0 +1,26 @@
+CREATE TABLE recurring_schedules (
+    id BIGSERIAL PRIMARY KEY,
+    uuid UUID NOT NULL UNIQUE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    workout_id BIGINT NOT NULL REFERENCES workouts(id) ON DELETE CASCADE,
+    starts_at TIMESTAMP NOT NULL,
+    rrule VARCHAR NOT NULL,
+    notes TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE recurring_schedule_exceptions (
+    id BIGSERIAL PRIMARY KEY,
+    uuid UUID NOT NULL UNIQUE,
+    recurring_schedule_id BIGINT NOT NULL REFERENCES recurring_schedules(id) ON DELETE CASCADE,
+    occurrence_at TIMESTAMP NOT NULL,
+    action VARCHAR NOT NULL CHECK (action IN ('skip', 'reschedule')),
+    rescheduled_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (recurring_schedule_id, occurrence_at),
+    CHECK ((action = 'reschedule') = (rescheduled_at IS NOT NULL))
+);
+
+CREATE INDEX recurring_schedules_user_id_idx ON recurring_schedules(user_id);
+CREATE INDEX recurring_schedules_workout_id_idx ON recurring_schedules(workout_id);
@@ -0,0 +1,17 @@
+CREATE TABLE personal_records (
+    id BIGSERIAL PRIMARY KEY,
+    uuid UUID NOT NULL UNIQUE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    exercise_id BIGINT NOT NULL REFERENCES exercises(id) ON DELETE CASCADE,
+    workout_log_id BIGINT NOT NULL REFERENCES workout_logs(id) ON DELETE CASCADE,
+    record_type VARCHAR(32) NOT NULL CHECK (record_type IN ('max_weight', 'max_reps', 'estimated_one_rep_max', 'max_volume')),
+    value DOUBLE PRECISION NOT NULL,
+    reps INTEGER,
+    weight DOUBLE PRECISION,
+    achieved_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX personal_records_user_id_achieved_at_idx ON personal_records(user_id, achieved_at);
+CREATE INDEX personal_records_exercise_id_idx ON personal_records(exercise_id);
+CREATE INDEX personal_records_workout_log_id_idx ON personal_records(workout_log_id);
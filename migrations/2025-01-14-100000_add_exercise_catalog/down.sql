@@ -0,0 +1,5 @@
+DELETE FROM exercises WHERE user_id IS NULL;
+
+DROP INDEX exercises_catalog_name_idx;
+
+ALTER TABLE exercises ALTER COLUMN user_id SET NOT NULL;
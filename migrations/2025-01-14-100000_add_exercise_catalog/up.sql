@@ -0,0 +1,30 @@
+-- Exercises without an owner form the global catalog, readable by every user
+ALTER TABLE exercises ALTER COLUMN user_id DROP NOT NULL;
+
+CREATE UNIQUE INDEX exercises_catalog_name_idx ON exercises(name) WHERE user_id IS NULL;
+
+INSERT INTO exercises (uuid, user_id, name, description, created_at, updated_at) VALUES
+    ('520a9db6-835f-5d51-94b4-27d4df5658eb', NULL, 'Back Squat', 'Barbell squat with the bar resting on the upper back.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('3d87563c-3fc7-5376-917a-fb5cb81c2daa', NULL, 'Front Squat', 'Barbell squat with the bar racked on the front of the shoulders.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('788b6c40-3671-5b26-b72d-7956e1bd8dd0', NULL, 'Deadlift', 'Conventional barbell deadlift from the floor.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('7d00d1ab-4698-55ba-baea-c339a337d24a', NULL, 'Romanian Deadlift', 'Hip hinge with a barbell, lowering to mid-shin with soft knees.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('b319a0ed-12ad-5a91-9813-0039b57cdce7', NULL, 'Bench Press', 'Barbell press from the chest while lying on a flat bench.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('bec9bae6-8158-5203-8055-00cbc3c05a49', NULL, 'Incline Bench Press', 'Barbell bench press on a bench inclined to 30-45 degrees.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('e5b1b02d-e9a3-50d3-b49e-daba0b565f90', NULL, 'Overhead Press', 'Standing strict barbell press from the shoulders to overhead.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('14d35fa4-5b9c-5156-8076-34be0742e925', NULL, 'Barbell Row', 'Bent-over row pulling a barbell to the lower chest.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('e8257f1c-ade1-5fb5-be76-98f8767c7458', NULL, 'Pull-Up', 'Pull from a dead hang until the chin clears the bar, overhand grip.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('10c6442d-b4b5-5d96-aa96-238c8ec09ccf', NULL, 'Chin-Up', 'Pull-up with an underhand, shoulder-width grip.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('d73043b7-ab34-5c5f-a133-38b4bf4c700c', NULL, 'Push-Up', 'Bodyweight press from the floor keeping a rigid plank.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('0b036ee6-163d-5373-95fc-0ec839475c02', NULL, 'Dip', 'Bodyweight press on parallel bars.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('38c3f3e7-dbaa-5532-b563-51f0ebeff40f', NULL, 'Lat Pulldown', 'Cable pulldown to the upper chest with a wide grip.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('f46f4419-920a-5e22-a893-382444b142c7', NULL, 'Seated Cable Row', 'Seated cable row to the torso with a neutral grip.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('3c3e3178-a594-53b8-b596-cf6196444e72', NULL, 'Dumbbell Lunge', 'Alternating forward lunges holding a dumbbell in each hand.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('a086070c-998f-5951-a22a-b4c4742c1218', NULL, 'Leg Press', 'Machine leg press through a full range of motion.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('aef3e80d-f485-5f73-b378-8ff90f47ddcf', NULL, 'Hip Thrust', 'Barbell hip extension with the upper back on a bench.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('59336389-8455-5762-bf60-05388c3b846d', NULL, 'Dumbbell Curl', 'Standing biceps curl with dumbbells.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('07e707c6-3eca-5a0f-b26b-eb35768e9475', NULL, 'Triceps Pushdown', 'Cable elbow extension with a bar or rope attachment.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('23754d72-8b96-5f7f-952f-ff1cae6e6dcd', NULL, 'Lateral Raise', 'Dumbbell shoulder abduction to shoulder height.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('5ded67c6-a5ee-5d9a-b284-f80cc5ac05a9', NULL, 'Calf Raise', 'Standing calf raise through a full range of motion.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('ea4bd34e-567b-534f-a607-6aa12bebc1b0', NULL, 'Plank', 'Isometric front plank hold on the forearms.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('27b46207-de8c-5ed6-8a62-4c12e381e8d8', NULL, 'Hanging Leg Raise', 'Raise the legs while hanging from a bar.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP),
+    ('e7567940-32d6-585e-bb1f-4eea611c246d', NULL, 'Kettlebell Swing', 'Two-handed hip hinge swing to chest height.', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP);
@@ -0,0 +1,4 @@
+DROP TABLE exercise_muscle_groups;
+DROP TABLE muscle_groups;
+
+ALTER TABLE exercises DROP COLUMN category;
@@ -0,0 +1,129 @@
+CREATE TABLE muscle_groups (
+    id BIGSERIAL PRIMARY KEY,
+    slug VARCHAR(32) NOT NULL UNIQUE,
+    name VARCHAR NOT NULL
+);
+
+CREATE TABLE exercise_muscle_groups (
+    exercise_id BIGINT NOT NULL REFERENCES exercises(id) ON DELETE CASCADE,
+    muscle_group_id BIGINT NOT NULL REFERENCES muscle_groups(id) ON DELETE CASCADE,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('primary', 'secondary')),
+    PRIMARY KEY (exercise_id, muscle_group_id)
+);
+
+CREATE INDEX exercise_muscle_groups_muscle_group_id_idx ON exercise_muscle_groups(muscle_group_id);
+
+ALTER TABLE exercises ADD COLUMN category VARCHAR(32) NOT NULL DEFAULT 'strength'
+    CHECK (category IN ('strength', 'cardio', 'flexibility', 'stability', 'plyometric'));
+
+CREATE INDEX exercises_category_idx ON exercises(category);
+
+INSERT INTO muscle_groups (slug, name) VALUES
+    ('chest', 'Chest'),
+    ('upper_back', 'Upper Back'),
+    ('lats', 'Lats'),
+    ('lower_back', 'Lower Back'),
+    ('shoulders', 'Shoulders'),
+    ('biceps', 'Biceps'),
+    ('triceps', 'Triceps'),
+    ('forearms', 'Forearms'),
+    ('abs', 'Abs'),
+    ('obliques', 'Obliques'),
+    ('quadriceps', 'Quadriceps'),
+    ('hamstrings', 'Hamstrings'),
+    ('glutes', 'Glutes'),
+    ('calves', 'Calves'),
+    ('adductors', 'Adductors'),
+    ('hip_flexors', 'Hip Flexors');
+
+-- Classify the seeded catalog
+UPDATE exercises SET category = 'stability' WHERE user_id IS NULL AND name = 'Plank';
+UPDATE exercises SET category = 'plyometric' WHERE user_id IS NULL AND name = 'Kettlebell Swing';
+
+INSERT INTO exercise_muscle_groups (exercise_id, muscle_group_id, role)
+SELECT e.id, m.id, v.role
+FROM (VALUES
+    ('Back Squat', 'quadriceps', 'primary'),
+    ('Back Squat', 'glutes', 'primary'),
+    ('Back Squat', 'hamstrings', 'secondary'),
+    ('Back Squat', 'lower_back', 'secondary'),
+    ('Back Squat', 'adductors', 'secondary'),
+    ('Front Squat', 'quadriceps', 'primary'),
+    ('Front Squat', 'glutes', 'secondary'),
+    ('Front Squat', 'upper_back', 'secondary'),
+    ('Front Squat', 'abs', 'secondary'),
+    ('Deadlift', 'hamstrings', 'primary'),
+    ('Deadlift', 'glutes', 'primary'),
+    ('Deadlift', 'lower_back', 'primary'),
+    ('Deadlift', 'quadriceps', 'secondary'),
+    ('Deadlift', 'upper_back', 'secondary'),
+    ('Deadlift', 'forearms', 'secondary'),
+    ('Romanian Deadlift', 'hamstrings', 'primary'),
+    ('Romanian Deadlift', 'glutes', 'primary'),
+    ('Romanian Deadlift', 'lower_back', 'secondary'),
+    ('Romanian Deadlift', 'forearms', 'secondary'),
+    ('Bench Press', 'chest', 'primary'),
+    ('Bench Press', 'triceps', 'secondary'),
+    ('Bench Press', 'shoulders', 'secondary'),
+    ('Incline Bench Press', 'chest', 'primary'),
+    ('Incline Bench Press', 'shoulders', 'primary'),
+    ('Incline Bench Press', 'triceps', 'secondary'),
+    ('Overhead Press', 'shoulders', 'primary'),
+    ('Overhead Press', 'triceps', 'secondary'),
+    ('Overhead Press', 'upper_back', 'secondary'),
+    ('Overhead Press', 'abs', 'secondary'),
+    ('Barbell Row', 'upper_back', 'primary'),
+    ('Barbell Row', 'lats', 'primary'),
+    ('Barbell Row', 'biceps', 'secondary'),
+    ('Barbell Row', 'lower_back', 'secondary'),
+    ('Barbell Row', 'forearms', 'secondary'),
+    ('Pull-Up', 'lats', 'primary'),
+    ('Pull-Up', 'biceps', 'secondary'),
+    ('Pull-Up', 'upper_back', 'secondary'),
+    ('Pull-Up', 'forearms', 'secondary'),
+    ('Chin-Up', 'lats', 'primary'),
+    ('Chin-Up', 'biceps', 'primary'),
+    ('Chin-Up', 'upper_back', 'secondary'),
+    ('Chin-Up', 'forearms', 'secondary'),
+    ('Push-Up', 'chest', 'primary'),
+    ('Push-Up', 'triceps', 'secondary'),
+    ('Push-Up', 'shoulders', 'secondary'),
+    ('Push-Up', 'abs', 'secondary'),
+    ('Dip', 'triceps', 'primary'),
+    ('Dip', 'chest', 'primary'),
+    ('Dip', 'shoulders', 'secondary'),
+    ('Lat Pulldown', 'lats', 'primary'),
+    ('Lat Pulldown', 'biceps', 'secondary'),
+    ('Lat Pulldown', 'upper_back', 'secondary'),
+    ('Seated Cable Row', 'upper_back', 'primary'),
+    ('Seated Cable Row', 'lats', 'primary'),
+    ('Seated Cable Row', 'biceps', 'secondary'),
+    ('Dumbbell Lunge', 'quadriceps', 'primary'),
+    ('Dumbbell Lunge', 'glutes', 'primary'),
+    ('Dumbbell Lunge', 'hamstrings', 'secondary'),
+    ('Dumbbell Lunge', 'adductors', 'secondary'),
+    ('Leg Press', 'quadriceps', 'primary'),
+    ('Leg Press', 'glutes', 'secondary'),
+    ('Leg Press', 'hamstrings', 'secondary'),
+    ('Hip Thrust', 'glutes', 'primary'),
+    ('Hip Thrust', 'hamstrings', 'secondary'),
+    ('Dumbbell Curl', 'biceps', 'primary'),
+    ('Dumbbell Curl', 'forearms', 'secondary'),
+    ('Triceps Pushdown', 'triceps', 'primary'),
+    ('Lateral Raise', 'shoulders', 'primary'),
+    ('Calf Raise', 'calves', 'primary'),
+    ('Plank', 'abs', 'primary'),
+    ('Plank', 'obliques', 'secondary'),
+    ('Plank', 'shoulders', 'secondary'),
+    ('Hanging Leg Raise', 'abs', 'primary'),
+    ('Hanging Leg Raise', 'hip_flexors', 'primary'),
+    ('Hanging Leg Raise', 'obliques', 'secondary'),
+    ('Hanging Leg Raise', 'forearms', 'secondary'),
+    ('Kettlebell Swing', 'glutes', 'primary'),
+    ('Kettlebell Swing', 'hamstrings', 'primary'),
+    ('Kettlebell Swing', 'lower_back', 'secondary'),
+    ('Kettlebell Swing', 'shoulders', 'secondary'),
+    ('Kettlebell Swing', 'abs', 'secondary')
+) AS v(exercise, muscle, role)
+JOIN exercises e ON e.name = v.exercise AND e.user_id IS NULL
+JOIN muscle_groups m ON m.slug = v.muscle;
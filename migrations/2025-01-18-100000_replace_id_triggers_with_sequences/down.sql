@@ -0,0 +1,89 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE workouts ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE workouts_id_seq;
+
+CREATE OR REPLACE FUNCTION workouts_id_handler()
+RETURNS TRIGGER AS $$
+DECLARE
+    next_id BIGINT;
+BEGIN
+    LOCK TABLE workouts IN EXCLUSIVE MODE;
+    SELECT COALESCE(MAX(id), 0) + 1 INTO next_id FROM workouts;
+    NEW.id := next_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER workouts_id_trigger
+    BEFORE INSERT ON workouts
+    FOR EACH ROW
+    EXECUTE FUNCTION workouts_id_handler();
+
+ALTER TABLE temp_sessions ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE temp_sessions_id_seq;
+
+CREATE OR REPLACE FUNCTION temp_sessions_id_handler()
+RETURNS TRIGGER AS $$
+DECLARE
+    next_id BIGINT;
+BEGIN
+    LOCK TABLE temp_sessions IN EXCLUSIVE MODE;
+    SELECT COALESCE(MAX(id), 0) + 1 INTO next_id FROM temp_sessions;
+    NEW.id := next_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER temp_sessions_id_trigger
+    BEFORE INSERT ON temp_sessions
+    FOR EACH ROW
+    EXECUTE FUNCTION temp_sessions_id_handler();
+
+ALTER TABLE sessions ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE sessions_id_seq;
+
+CREATE OR REPLACE FUNCTION sessions_id_handler()
+RETURNS TRIGGER AS $$
+DECLARE
+    next_id BIGINT;
+BEGIN
+    LOCK TABLE sessions IN EXCLUSIVE MODE;
+    SELECT COALESCE(MAX(id), 0) + 1 INTO next_id FROM sessions;
+    NEW.id := next_id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER sessions_id_trigger
+    BEFORE INSERT ON sessions
+    FOR EACH ROW
+    EXECUTE FUNCTION sessions_id_handler();
+
+ALTER TABLE users ALTER COLUMN id DROP DEFAULT;
+ALTER SEQUENCE users_id_seq OWNED BY NONE;
+
+CREATE OR REPLACE FUNCTION users_id_handler()
+RETURNS TRIGGER AS $$
+DECLARE
+	next_id BIGINT;
+BEGIN
+	-- Lock the table to prevent concurrent inserts
+	LOCK TABLE users IN EXCLUSIVE MODE;
+	
+	-- Check for duplicate email before getting next ID
+	IF EXISTS (SELECT 1 FROM users WHERE email = NEW.email) THEN
+		RAISE unique_violation USING MESSAGE = 'duplicate key value violates unique constraint "users_email_key"';
+	END IF;
+	
+	-- Get next ID only if no conflicts
+	SELECT COALESCE(MAX(id), 0) + 1 INTO next_id FROM users;
+	NEW.id := next_id;
+	
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_id_trigger
+	BEFORE INSERT ON users
+	FOR EACH ROW
+	EXECUTE FUNCTION users_id_handler();
@@ -0,0 +1,28 @@
+-- The id triggers took an EXCLUSIVE lock and computed MAX(id) + 1 on every insert,
+-- serializing concurrent inserts. Column defaults backed by sequences don't.
+-- Each sequence starts after the current maximum so existing ids are kept.
+
+DROP TRIGGER users_id_trigger ON users;
+DROP FUNCTION users_id_handler();
+-- users_id_seq was created alongside the table but never used
+ALTER SEQUENCE users_id_seq OWNED BY users.id;
+SELECT setval('users_id_seq', COALESCE((SELECT MAX(id) FROM users), 0) + 1, false);
+ALTER TABLE users ALTER COLUMN id SET DEFAULT nextval('users_id_seq');
+
+DROP TRIGGER sessions_id_trigger ON sessions;
+DROP FUNCTION sessions_id_handler();
+CREATE SEQUENCE sessions_id_seq NO CYCLE OWNED BY sessions.id;
+SELECT setval('sessions_id_seq', COALESCE((SELECT MAX(id) FROM sessions), 0) + 1, false);
+ALTER TABLE sessions ALTER COLUMN id SET DEFAULT nextval('sessions_id_seq');
+
+DROP TRIGGER temp_sessions_id_trigger ON temp_sessions;
+DROP FUNCTION temp_sessions_id_handler();
+CREATE SEQUENCE temp_sessions_id_seq NO CYCLE OWNED BY temp_sessions.id;
+SELECT setval('temp_sessions_id_seq', COALESCE((SELECT MAX(id) FROM temp_sessions), 0) + 1, false);
+ALTER TABLE temp_sessions ALTER COLUMN id SET DEFAULT nextval('temp_sessions_id_seq');
+
+DROP TRIGGER workouts_id_trigger ON workouts;
+DROP FUNCTION workouts_id_handler();
+CREATE SEQUENCE workouts_id_seq NO CYCLE OWNED BY workouts.id;
+SELECT setval('workouts_id_seq', COALESCE((SELECT MAX(id) FROM workouts), 0) + 1, false);
+ALTER TABLE workouts ALTER COLUMN id SET DEFAULT nextval('workouts_id_seq');
@@ -0,0 +1,7 @@
+DROP INDEX sessions_user_id_idx;
+
+ALTER TABLE sessions
+    DROP COLUMN ip_address,
+    DROP COLUMN user_agent,
+    DROP COLUMN last_seen_at,
+    DROP COLUMN uuid;
@@ -0,0 +1,13 @@
+ALTER TABLE sessions
+    ADD COLUMN uuid UUID UNIQUE,
+    ADD COLUMN last_seen_at TIMESTAMP,
+    ADD COLUMN user_agent TEXT,
+    ADD COLUMN ip_address VARCHAR(45);
+
+UPDATE sessions SET uuid = gen_random_uuid(), last_seen_at = created_at;
+
+ALTER TABLE sessions
+    ALTER COLUMN uuid SET NOT NULL,
+    ALTER COLUMN last_seen_at SET NOT NULL;
+
+CREATE INDEX sessions_user_id_idx ON sessions(user_id);
@@ -0,0 +1,8 @@
+ALTER TABLE sessions
+    ADD COLUMN remember_me BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN absolute_expires_at TIMESTAMP;
+
+-- Existing sessions keep their fixed expiry as the hard limit
+UPDATE sessions SET absolute_expires_at = expires_at;
+
+ALTER TABLE sessions ALTER COLUMN absolute_expires_at SET NOT NULL;
@@ -0,0 +1,9 @@
+-- Failed login attempts, counted per account (normalized email) and per client IP
+CREATE TABLE login_throttles (
+    scope VARCHAR(16) NOT NULL CHECK (scope IN ('account', 'ip')),
+    key VARCHAR NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,9 @@
+DELETE FROM login_throttles WHERE scope = 'two_factor';
+ALTER TABLE login_throttles
+    DROP CONSTRAINT login_throttles_scope_check,
+    ADD CONSTRAINT login_throttles_scope_check CHECK (scope IN ('account', 'ip'));
+
+ALTER TABLE sessions DROP COLUMN two_factor_pending;
+
+DROP TABLE recovery_codes;
+DROP TABLE totp_credentials;
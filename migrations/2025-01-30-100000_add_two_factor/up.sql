@@ -0,0 +1,26 @@
+-- The secret is needed in plain form to compute codes; a credential counts once confirmed
+CREATE TABLE totp_credentials (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret VARCHAR NOT NULL,
+    confirmed_at TIMESTAMP,
+    last_used_step BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE recovery_codes (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX recovery_codes_user_id_idx ON recovery_codes (user_id);
+
+-- Sessions of users with two-factor authentication wait here until the second step succeeds
+ALTER TABLE sessions ADD COLUMN two_factor_pending BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Wrong second factors are throttled apart from passwords, which reset on a correct login
+ALTER TABLE login_throttles
+    DROP CONSTRAINT login_throttles_scope_check,
+    ADD CONSTRAINT login_throttles_scope_check CHECK (scope IN ('account', 'ip', 'two_factor'));
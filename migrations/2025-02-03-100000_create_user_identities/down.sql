@@ -0,0 +1,2 @@
+DROP TABLE oidc_login_attempts;
+DROP TABLE user_identities;
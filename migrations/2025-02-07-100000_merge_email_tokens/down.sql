@@ -0,0 +1,41 @@
+CREATE TABLE password_reset_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR NOT NULL UNIQUE,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX password_reset_tokens_user_id_idx ON password_reset_tokens (user_id);
+
+CREATE TABLE email_verification_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR NOT NULL UNIQUE,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX email_verification_tokens_user_id_idx ON email_verification_tokens (user_id, created_at);
+
+CREATE TABLE login_link_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR NOT NULL UNIQUE,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX login_link_tokens_user_id_idx ON login_link_tokens (user_id);
+
+INSERT INTO password_reset_tokens (user_id, token_hash, expires_at, used_at, created_at)
+SELECT user_id, token_hash, expires_at, used_at, created_at FROM email_tokens WHERE purpose = 'password_reset';
+INSERT INTO email_verification_tokens (user_id, token_hash, expires_at, used_at, created_at)
+SELECT user_id, token_hash, expires_at, used_at, created_at FROM email_tokens WHERE purpose = 'email_verification';
+INSERT INTO login_link_tokens (user_id, token_hash, expires_at, used_at, created_at)
+SELECT user_id, token_hash, expires_at, used_at, created_at FROM email_tokens WHERE purpose = 'login_link';
+
+DROP TABLE email_tokens;
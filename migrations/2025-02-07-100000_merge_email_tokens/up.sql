@@ -0,0 +1,23 @@
+-- Password reset, email verification and login link tokens only differ in what they are for
+CREATE TABLE email_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    purpose VARCHAR(32) NOT NULL CHECK (purpose IN ('password_reset', 'email_verification', 'login_link')),
+    token_hash VARCHAR NOT NULL UNIQUE,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX email_tokens_user_id_idx ON email_tokens (user_id, purpose, created_at);
+
+INSERT INTO email_tokens (user_id, purpose, token_hash, expires_at, used_at, created_at)
+SELECT user_id, 'password_reset', token_hash, expires_at, used_at, created_at FROM password_reset_tokens;
+INSERT INTO email_tokens (user_id, purpose, token_hash, expires_at, used_at, created_at)
+SELECT user_id, 'email_verification', token_hash, expires_at, used_at, created_at FROM email_verification_tokens;
+INSERT INTO email_tokens (user_id, purpose, token_hash, expires_at, used_at, created_at)
+SELECT user_id, 'login_link', token_hash, expires_at, used_at, created_at FROM login_link_tokens;
+
+DROP TABLE password_reset_tokens;
+DROP TABLE email_verification_tokens;
+DROP TABLE login_link_tokens;
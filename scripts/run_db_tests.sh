@@ -0,0 +1,16 @@
+#!/bin/bash
+
+# Runs the tests that need a database, against the one in DATABASE_URL (or .env)
+set -e
+
+if [ -z "$DATABASE_URL" ] && [ -f .env ]; then
+    DATABASE_URL=$(grep -E '^DATABASE_URL=' .env | cut -d= -f2-)
+fi
+if [ -z "$DATABASE_URL" ]; then
+    echo "DATABASE_URL is not set" >&2
+    exit 1
+fi
+export DATABASE_URL
+
+diesel migration run --database-url "$DATABASE_URL"
+cargo test "$@" -- --ignored
@@ -3,8 +3,8 @@
 # Install dependencies if needed
 npm install
 
-# Start the server in background
-cargo run &
+# Start the server in background, keeping emails in files
+MAIL_TRANSPORT=${MAIL_TRANSPORT:-file} MAIL_DIR=${MAIL_DIR:-target/mail} cargo run &
 SERVER_PID=$!
 
 # Wait for server to start
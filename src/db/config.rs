@@ -1,12 +1,50 @@
-use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool, PoolError, PooledConnection};
 use dotenvy::dotenv;
 use diesel::PgConnection;
-use std::env;
+use std::{env, time::Duration};
 
-pub fn establish_connection() -> PgConnection {
-    dotenv().ok();
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+pub type DbConnection = PooledConnection<ConnectionManager<PgConnection>>;
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+/// Settings for the shared connection pool, read from the environment by `from_env`.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub database_url: String,
+    pub max_size: u32,
+    pub min_idle: Option<u32>,
+    /// How long a request waits for a free connection before giving up.
+    pub connection_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+}
+
+impl PoolConfig {
+    pub fn from_env() -> Self {
+        dotenv().ok();
+
+        Self {
+            database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
+            max_size: env_var("DATABASE_POOL_MAX_SIZE").unwrap_or(10),
+            min_idle: env_var("DATABASE_POOL_MIN_IDLE"),
+            connection_timeout: Duration::from_secs(env_var("DATABASE_POOL_CONNECTION_TIMEOUT_SECS").unwrap_or(5)),
+            idle_timeout: Some(Duration::from_secs(env_var("DATABASE_POOL_IDLE_TIMEOUT_SECS").unwrap_or(600))),
+            max_lifetime: Some(Duration::from_secs(env_var("DATABASE_POOL_MAX_LIFETIME_SECS").unwrap_or(1800))),
+        }
+    }
+}
+
+pub(crate) fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().map(|value| {
+        value.parse().unwrap_or_else(|_| panic!("{} must be a number, got {}", name, value))
+    })
+}
+
+pub fn create_pool(config: &PoolConfig) -> Result<DbPool, PoolError> {
+    Pool::builder()
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .connection_timeout(config.connection_timeout)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+        .build(ConnectionManager::<PgConnection>::new(&config.database_url))
 }
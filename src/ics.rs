@@ -0,0 +1,135 @@
+//! Minimal RFC 5545 (iCalendar) rendering of scheduled workouts.
+
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::models::{
+    recurring_schedule::{ExceptionAction, RecurringScheduleDetail},
+    scheduled_workout::{ScheduleStatus, ScheduledWorkout},
+    workout::Workout,
+};
+
+const PRODID: &str = "-//fitness-workout-tracker//Scheduled Workouts//EN";
+const UID_DOMAIN: &str = "fitness-workout-tracker";
+/// Scheduled workouts have no end time, so events are given a fixed length.
+const EVENT_DURATION: &str = "PT1H";
+/// Content lines longer than this many octets are folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Renders one-off and recurring scheduled workouts as a `VCALENDAR`. All times are naive UTC
+/// and are written in the UTC form (`...Z`).
+pub fn render_calendar(
+    scheduled: &[(ScheduledWorkout, Workout)],
+    recurring: &[RecurringScheduleDetail],
+) -> String {
+    let mut calendar = Calendar::default();
+    calendar.line("BEGIN:VCALENDAR");
+    calendar.line("VERSION:2.0");
+    calendar.line(&format!("PRODID:{}", PRODID));
+    calendar.line("CALSCALE:GREGORIAN");
+    calendar.line("METHOD:PUBLISH");
+    calendar.line("X-WR-CALNAME:Workouts");
+
+    for (scheduled, workout) in scheduled {
+        calendar.line("BEGIN:VEVENT");
+        calendar.line(&format!("UID:{}", uid(scheduled.uuid)));
+        calendar.line(&format!("DTSTAMP:{}", format_datetime(scheduled.updated_at)));
+        calendar.line(&format!("DTSTART:{}", format_datetime(scheduled.scheduled_at)));
+        calendar.line(&format!("DURATION:{}", EVENT_DURATION));
+        calendar.event_text(workout, scheduled.notes.as_deref());
+        if scheduled.status == ScheduleStatus::Skipped.as_str() {
+            calendar.line("STATUS:CANCELLED");
+        } else {
+            calendar.line("STATUS:CONFIRMED");
+        }
+        calendar.line("END:VEVENT");
+    }
+
+    for detail in recurring {
+        let schedule = &detail.schedule;
+        calendar.line("BEGIN:VEVENT");
+        calendar.line(&format!("UID:{}", uid(schedule.uuid)));
+        calendar.line(&format!("DTSTAMP:{}", format_datetime(schedule.updated_at)));
+        calendar.line(&format!("DTSTART:{}", format_datetime(schedule.starts_at)));
+        calendar.line(&format!("DURATION:{}", EVENT_DURATION));
+        calendar.line(&format!("RRULE:{}", schedule.rrule));
+        for exception in detail.exceptions.iter().filter(|e| e.action == ExceptionAction::Skip.as_str()) {
+            calendar.line(&format!("EXDATE:{}", format_datetime(exception.occurrence_at)));
+        }
+        calendar.event_text(&detail.workout, schedule.notes.as_deref());
+        calendar.line("END:VEVENT");
+
+        // Rescheduled occurrences override the generated instance through RECURRENCE-ID
+        for exception in detail.exceptions.iter().filter(|e| e.action == ExceptionAction::Reschedule.as_str()) {
+            let Some(rescheduled_at) = exception.rescheduled_at else { continue };
+            calendar.line("BEGIN:VEVENT");
+            calendar.line(&format!("UID:{}", uid(schedule.uuid)));
+            calendar.line(&format!("DTSTAMP:{}", format_datetime(exception.created_at)));
+            calendar.line(&format!("RECURRENCE-ID:{}", format_datetime(exception.occurrence_at)));
+            calendar.line(&format!("DTSTART:{}", format_datetime(rescheduled_at)));
+            calendar.line(&format!("DURATION:{}", EVENT_DURATION));
+            calendar.event_text(&detail.workout, schedule.notes.as_deref());
+            calendar.line("END:VEVENT");
+        }
+    }
+
+    calendar.line("END:VCALENDAR");
+    calendar.output
+}
+
+#[derive(Default)]
+struct Calendar {
+    output: String,
+}
+
+impl Calendar {
+    /// Appends a content line, folding it and terminating it with CRLF.
+    fn line(&mut self, line: &str) {
+        let mut octets = 0;
+        for c in line.chars() {
+            if octets + c.len_utf8() > MAX_LINE_OCTETS {
+                self.output.push_str("\r\n ");
+                // The leading space of a continuation line counts towards its length
+                octets = 1;
+            }
+            self.output.push(c);
+            octets += c.len_utf8();
+        }
+        self.output.push_str("\r\n");
+    }
+
+    fn event_text(&mut self, workout: &Workout, notes: Option<&str>) {
+        self.line(&format!("SUMMARY:{}", escape_text(&workout.name)));
+        let description = [workout.description.as_deref(), notes]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if !description.is_empty() {
+            self.line(&format!("DESCRIPTION:{}", escape_text(&description)));
+        }
+    }
+}
+
+fn uid(uuid: Uuid) -> String {
+    format!("{}@{}", uuid, UID_DOMAIN)
+}
+
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -1,6 +1,15 @@
+// Models expose `new` constructors that return their `Insertable` counterpart.
+#![allow(clippy::new_ret_no_self)]
+
 pub mod models;
 pub mod repositories;
 pub mod routes;
 pub mod schema;
 pub mod db;
 pub mod middleware;
+pub mod tokens;
+pub mod mailer;
+pub mod totp;
+pub mod oidc;
+
+pub mod ics;
\ No newline at end of file
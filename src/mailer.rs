@@ -0,0 +1,251 @@
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::{authentication::Credentials, client::{Tls, TlsParameters}},
+    Message, SmtpTransport, Transport,
+};
+use std::{
+    env, fmt, fs,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+use crate::{db::config::env_var, models::email_token::{EMAIL_VERIFICATION_LIFETIME, LOGIN_LINK_LIFETIME, PASSWORD_RESET_LIFETIME}};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailerError {
+    InvalidAddress(String),
+    DeliveryFailed(String),
+}
+
+impl fmt::Display for MailerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailerError::InvalidAddress(address) => write!(f, "invalid email address {}", address),
+            MailerError::DeliveryFailed(reason) => write!(f, "delivery failed: {}", reason),
+        }
+    }
+}
+
+pub trait Mailer: Send + Sync + 'static {
+    fn send(&self, email: &Email) -> Result<(), MailerError>;
+}
+
+/// Keeps emails local instead of delivering them: each one is written to a file in `dir`. Meant
+/// for development and tests.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<(), MailerError> {
+        let contents = format!("To: {}\nSubject: {}\n\n{}\n", email.to, email.subject, email.body);
+        let file_name = format!("{}-{}.eml", chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"), uuid::Uuid::new_v4());
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(self.dir.join(file_name), contents))
+            .map_err(|err| MailerError::DeliveryFailed(err.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// Plain text, only sensible for a relay on the same host.
+    None,
+    /// Upgrades the connection with STARTTLS and refuses servers that don't support it.
+    StartTls,
+    /// TLS from the first byte (SMTPS).
+    Tls,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls: SmtpTls,
+    /// Sender address, e.g. `Fitness Tracker <no-reply@example.com>`.
+    pub from: String,
+    pub timeout: Duration,
+}
+
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig) -> Result<Self, MailerError> {
+        let tls = match config.tls {
+            SmtpTls::None => Tls::None,
+            SmtpTls::StartTls => Tls::Required(Self::tls_parameters(&config.host)?),
+            SmtpTls::Tls => Tls::Wrapper(Self::tls_parameters(&config.host)?),
+        };
+        let mut builder = SmtpTransport::builder_dangerous(&config.host)
+            .port(config.port)
+            .tls(tls)
+            .timeout(Some(config.timeout));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: parse_mailbox(&config.from)?,
+        })
+    }
+
+    fn tls_parameters(host: &str) -> Result<TlsParameters, MailerError> {
+        TlsParameters::new(host.to_string()).map_err(|err| MailerError::DeliveryFailed(err.to_string()))
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<(), MailerError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(parse_mailbox(&email.to)?)
+            .subject(&email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())
+            .map_err(|err| MailerError::InvalidAddress(err.to_string()))?;
+
+        self.transport
+            .send(&message)
+            .map(|_| ())
+            .map_err(|err| MailerError::DeliveryFailed(err.to_string()))
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, MailerError> {
+    address.parse().map_err(|_| MailerError::InvalidAddress(address.to_string()))
+}
+
+#[derive(Debug, Clone)]
+pub enum MailTransport {
+    File(PathBuf),
+    Smtp(SmtpConfig),
+}
+
+/// Mail settings, read from the environment by `from_env`.
+#[derive(Debug, Clone)]
+pub struct MailerConfig {
+    pub transport: MailTransport,
+    /// Base URL of the frontend, which links in emails point to.
+    pub app_url: String,
+}
+
+impl MailerConfig {
+    /// Panics when `MAIL_TRANSPORT` is unset, so that links with tokens in them never end up
+    /// somewhere nobody chose.
+    pub fn from_env() -> Self {
+        let transport = match env::var("MAIL_TRANSPORT").as_deref() {
+            Ok("smtp") => MailTransport::Smtp(SmtpConfig {
+                host: env::var("SMTP_HOST").unwrap_or_else(|_| "localhost".to_string()),
+                port: env_var("SMTP_PORT").unwrap_or(587),
+                username: env::var("SMTP_USERNAME").ok(),
+                password: env::var("SMTP_PASSWORD").ok(),
+                tls: match env::var("SMTP_TLS").as_deref() {
+                    Ok("none") => SmtpTls::None,
+                    Ok("tls") => SmtpTls::Tls,
+                    Ok("starttls") | Err(_) => SmtpTls::StartTls,
+                    Ok(other) => panic!("SMTP_TLS must be none, starttls or tls, got {}", other),
+                },
+                from: env::var("MAIL_FROM").unwrap_or_else(|_| "Fitness Tracker <no-reply@localhost>".to_string()),
+                timeout: Duration::from_secs(env_var("SMTP_TIMEOUT_SECS").unwrap_or(10)),
+            }),
+            Ok("file") => MailTransport::File(PathBuf::from(
+                env::var("MAIL_DIR").expect("MAIL_DIR must be set when MAIL_TRANSPORT is file"),
+            )),
+            Ok(other) => panic!("MAIL_TRANSPORT must be file or smtp, got {}", other),
+            Err(_) => panic!("MAIL_TRANSPORT must be set to file or smtp"),
+        };
+
+        Self {
+            transport,
+            app_url: env::var("APP_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
+        }
+    }
+}
+
+pub fn create_mailer(transport: &MailTransport) -> Result<Arc<dyn Mailer>, MailerError> {
+    Ok(match transport {
+        MailTransport::File(dir) => Arc::new(FileMailer::new(dir.clone())),
+        MailTransport::Smtp(config) => Arc::new(SmtpMailer::new(config)?),
+    })
+}
+
+/// Writes the emails the API sends and hands them to a `Mailer`.
+pub struct Outbox {
+    mailer: Arc<dyn Mailer>,
+    app_url: String,
+}
+
+impl Outbox {
+    pub fn new(mailer: Arc<dyn Mailer>, app_url: String) -> Self {
+        Self {
+            mailer,
+            app_url: app_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn send_password_reset(&self, to: &str, token: &str) -> Result<(), MailerError> {
+        self.mailer.send(&Email {
+            to: to.to_string(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Someone asked to reset the password of your Fitness Tracker account. \
+                 Open the link below within {} minutes to choose a new one:\n\n\
+                 {}/reset-password?token={}\n\n\
+                 If this wasn't you, you can ignore this email and your password stays the same.",
+                PASSWORD_RESET_LIFETIME.num_minutes(),
+                self.app_url,
+                token,
+            ),
+        })
+    }
+
+    pub fn send_email_verification(&self, to: &str, token: &str) -> Result<(), MailerError> {
+        self.mailer.send(&Email {
+            to: to.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Welcome to Fitness Tracker! Open the link below within {} hours to confirm \
+                 that this is your email address:\n\n\
+                 {}/verify-email?token={}\n\n\
+                 If you didn't create an account, you can ignore this email.",
+                EMAIL_VERIFICATION_LIFETIME.num_hours(),
+                self.app_url,
+                token,
+            ),
+        })
+    }
+
+    pub fn send_login_link(&self, to: &str, token: &str) -> Result<(), MailerError> {
+        self.mailer.send(&Email {
+            to: to.to_string(),
+            subject: "Your login link".to_string(),
+            body: format!(
+                "Open the link below within {} minutes to log in to your Fitness Tracker \
+                 account. It works once.\n\n\
+                 {}/login-link?token={}\n\n\
+                 If you didn't ask to log in, you can ignore this email.",
+                LOGIN_LINK_LIFETIME.num_minutes(),
+                self.app_url,
+                token,
+            ),
+        })
+    }
+}
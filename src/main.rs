@@ -1,41 +1,93 @@
 use actix_web::{App, HttpServer, web};
 use fitness_workout_tracker_api_rust::{
-    middleware::{csrf::CsrfProtection, session::SessionProtection}, repositories::{auth_repository::PgAuthRepository, exercise_repository::PgExerciseRepository, workout_exercise_repository::PgWorkoutExerciseRepository, workout_repository::PgWorkoutRepository}, routes
+    db::config::{create_pool, PoolConfig},
+    mailer::{create_mailer, MailerConfig, Outbox},
+    oidc::{IdentityProvider, OidcClient, OidcConfig},
+    models::session::SessionConfig,
+    middleware::{csrf::CsrfProtection, session::{EmailVerificationPolicy, SessionProtection}}, repositories::{auth_repository::PgAuthRepository, calendar_feed_repository::PgCalendarFeedRepository, exercise_repository::PgExerciseRepository, personal_record_repository::PgPersonalRecordRepository, recurring_schedule_repository::PgRecurringScheduleRepository, report_repository::PgReportRepository, schedule_repository::PgScheduleRepository, workout_exercise_repository::PgWorkoutExerciseRepository, workout_log_repository::PgWorkoutLogRepository, workout_repository::PgWorkoutRepository}, routes
 };
-use std::env;
+use std::{env, sync::Arc};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    dotenvy::dotenv().ok();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let address = format!("127.0.0.1:{}", port);
 
-    let auth_repo = web::Data::new(PgAuthRepository::new());
-    let workout_repo = web::Data::new(PgWorkoutRepository::new());
-    let exercise_repo = web::Data::new(PgExerciseRepository::new());
-    let workout_exercise_repo = web::Data::new(PgWorkoutExerciseRepository::new());
+    let pool = create_pool(&PoolConfig::from_env()).expect("Failed to create database connection pool");
+
+    let mailer_config = MailerConfig::from_env();
+    let mailer = create_mailer(&mailer_config.transport).expect("Failed to set up the mailer");
+    let identity_provider = OidcConfig::from_env(&mailer_config.app_url).map(|config| {
+        let client = OidcClient::new(config).expect("Failed to set up the OIDC client");
+        web::Data::from(Arc::new(client) as Arc<dyn IdentityProvider>)
+    });
+    let outbox = web::Data::new(Outbox::new(mailer, mailer_config.app_url));
+    let verification_policy = EmailVerificationPolicy::from_env();
+
+    let auth_repo = web::Data::new(PgAuthRepository::new(pool.clone(), SessionConfig::from_env()));
+    let workout_repo = web::Data::new(PgWorkoutRepository::new(pool.clone()));
+    let exercise_repo = web::Data::new(PgExerciseRepository::new(pool.clone()));
+    let workout_exercise_repo = web::Data::new(PgWorkoutExerciseRepository::new(pool.clone()));
+    let workout_log_repo = web::Data::new(PgWorkoutLogRepository::new(pool.clone()));
+    let schedule_repo = web::Data::new(PgScheduleRepository::new(pool.clone()));
+    let recurring_schedule_repo = web::Data::new(PgRecurringScheduleRepository::new(pool.clone()));
+    let calendar_feed_repo = web::Data::new(PgCalendarFeedRepository::new(pool.clone()));
+    let report_repo = web::Data::new(PgReportRepository::new(pool.clone()));
+    let personal_record_repo = web::Data::new(PgPersonalRecordRepository::new(pool.clone()));
 
     println!("Server starting at http://{}", address);
     
     HttpServer::new(move || {
         App::new()
-            .wrap(CsrfProtection::<PgAuthRepository>::new())
+            .wrap(
+                CsrfProtection::<PgAuthRepository>::new()
+                    .ignore(["/workouts", "/exercises", "/muscle-groups", "/records", "/schedule", "/recurring-schedules", "/calendar", "/reports"])
+            )
             .app_data(auth_repo.clone())
+            .app_data(outbox.clone())
+            .configure(|cfg| {
+                // Single sign-on stays off without a provider
+                if let Some(identity_provider) = &identity_provider {
+                    cfg.app_data(identity_provider.clone());
+                }
+            })
+            .app_data(schedule_repo.clone())
+            .app_data(recurring_schedule_repo.clone())
+            .app_data(calendar_feed_repo.clone())
             .service(routes::auth::get_scope::<PgAuthRepository>())
+            .service(routes::calendar::get_resource_feed::<PgCalendarFeedRepository, PgScheduleRepository, PgRecurringScheduleRepository>())
             .service(
                 web::scope("")
                     .wrap(
                         SessionProtection::<PgAuthRepository>::new()
                             .ignore(["/health", "/echo", "/"])
+                            .verification_policy(verification_policy)
                     )
                     .app_data(workout_repo.clone())
                     .app_data(exercise_repo.clone())
                     .app_data(workout_exercise_repo.clone())
+                    .app_data(workout_log_repo.clone())
+                    .app_data(report_repo.clone())
+                    .app_data(personal_record_repo.clone())
                     .service(routes::workout_exercise::get_scope_workout_id_exercises_exercise_id::<PgWorkoutExerciseRepository>())
                     .service(routes::workout_exercise::get_scope_workout_id_exercises::<PgWorkoutExerciseRepository>())
+                    .service(routes::workout_log::get_scope_workout_id_logs_log_id::<PgWorkoutLogRepository>())
+                    .service(routes::workout_log::get_scope_workout_id_logs::<PgWorkoutLogRepository>())
+                    .service(routes::personal_record::get_scope_exercise_id_records::<PgPersonalRecordRepository>())
+                    .service(routes::exercise::get_scope_exercise_id_fork::<PgExerciseRepository>())
                     .service(routes::exercise::get_scope_exercise_id::<PgExerciseRepository>())
                     .service(routes::exercise::get_scope::<PgExerciseRepository>())
+                    .service(routes::exercise::get_scope_muscle_groups::<PgExerciseRepository>())
                     .service(routes::workout::get_scope_workout_id::<PgWorkoutRepository>())
                     .service(routes::workout::get_scope::<PgWorkoutRepository>())
+                    .service(routes::schedule::get_scope::<PgScheduleRepository>())
+                    .service(routes::recurring_schedule::get_scope::<PgRecurringScheduleRepository>())
+                    .service(routes::personal_record::get_scope::<PgPersonalRecordRepository>())
+                    .service(routes::report::get_scope::<PgReportRepository>())
+                    .service(routes::calendar::get_scope::<PgCalendarFeedRepository, PgScheduleRepository, PgRecurringScheduleRepository>())
                     .wrap(actix_web::middleware::DefaultHeaders::new())
                     .service(routes::general::get_scope())
             )
@@ -1,28 +1,57 @@
 use std::{
-  marker::PhantomData, pin::Pin, task::{Context, Poll}
+  marker::PhantomData, rc::Rc, task::{Context, Poll}
 };
-use pin_project::pin_project;
-use actix_utils::future::{ok, Either, Ready};
+use actix_utils::future::{ok, Ready};
 use actix_web::{
     body::{EitherBody, MessageBody}, dev::{Service, ServiceRequest, ServiceResponse, Transform}, web, Error, HttpResponse
 };
-use crate::repositories::auth_repository::AuthRepository;
-use futures::{ready, Future};
+use crate::{repositories::auth_repository::{AuthError, AuthRepository}, routes::{run_blocking, service_unavailable}};
+use futures::future::LocalBoxFuture;
 
-pub struct CsrfProtection<T: AuthRepository>(PhantomData<T>);
+pub struct CsrfProtection<T: AuthRepository> {
+    ignored_paths: Vec<String>,
+    _phantom: PhantomData<T>,
+}
 
 impl<T: AuthRepository> CsrfProtection<T> {
     pub fn new() -> Self {
-        Self(PhantomData)
+        Self {
+            ignored_paths: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Path prefixes of the endpoints token-authenticated clients use. Requests to them skip the
+    /// CSRF check unless they carry the session cookie: browsers never attach bearer tokens on
+    /// their own, so only cookie-authenticated requests can be forged cross-site.
+    pub fn ignore<I>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.ignored_paths = paths.into_iter().map(Into::into).collect();
+        self
     }
 }
 
+impl<T: AuthRepository> Default for CsrfProtection<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `path` is `prefix` itself or lies below it, so `/workouts` covers `/workouts/1` but
+/// not `/workouts-archive`.
+fn covers(prefix: &str, path: &str) -> bool {
+    path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
 impl<S, B, T> Transform<S, ServiceRequest> for CsrfProtection<T>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: MessageBody + 'static,
-    T: AuthRepository + 'static,
+    T: AuthRepository,
 {
     type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
@@ -32,90 +61,71 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ok(CsrfMiddleware { 
-            service,
+            service: Rc::new(service),
+            ignored_paths: Rc::new(self.ignored_paths.clone()),
             _phantom: PhantomData,
         })
     }
 }
 
 pub struct CsrfMiddleware<S, T> {
-    service: S,
+    service: Rc<S>,
+    ignored_paths: Rc<Vec<String>>,
     _phantom: PhantomData<T>,
 }
 
 impl<S, B, T> Service<ServiceRequest> for CsrfMiddleware<S, T>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: MessageBody + 'static,
-    T: AuthRepository + 'static,
+    T: AuthRepository,
 {
     type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
-    type Future = Either<CsrfFuture<S, B>, Ready<Result<Self::Response, Self::Error>>>;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.service.poll_ready(cx)
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        if req.method() == "POST" {
-            let session_id = req.cookie("session_id").map(|c| c.value().to_string());
-            let csrf_token = req.headers().get("x-csrf-token").and_then(|h| h.to_str().ok()).map(String::from);
+        let service = Rc::clone(&self.service);
+        let ignored_paths = Rc::clone(&self.ignored_paths);
 
-            if let (Some(session_id), Some(csrf_token)) = (session_id, csrf_token) {
-                if let Some(repo) = req.app_data::<web::Data<T>>() {
-                    if repo.validate_csrf(&session_id, &csrf_token).is_err() {
-                        let res = HttpResponse::Unauthorized()
-                            .json(serde_json::json!({
-                                "error": "Invalid CSRF token"
-                            }));
-                        return Either::right(ok(req.into_response(res)
-                            .map_into_boxed_body()
-                            .map_into_right_body()));
+        Box::pin(async move {
+            let ignored = ignored_paths.iter().any(|prefix| covers(prefix, req.path()))
+                && req.cookie("session_id").is_none();
+            if !ignored && !req.method().is_safe() {
+                let session_id = req.cookie("session_id").map(|c| c.value().to_string());
+                let csrf_token = req.headers().get("x-csrf-token").and_then(|h| h.to_str().ok()).map(String::from);
+
+                if let (Some(session_id), Some(csrf_token)) = (session_id, csrf_token) {
+                    if let Some(repo) = req.app_data::<web::Data<T>>().cloned() {
+                        match run_blocking(&repo, move |repo| repo.validate_csrf(&session_id, &csrf_token)).await {
+                            Ok(()) => {},
+                            Err(AuthError::ConnectionUnavailable) => {
+                                return Ok(req.into_response(service_unavailable()).map_into_right_body());
+                            },
+                            Err(_) => {
+                                let res = HttpResponse::Unauthorized()
+                                    .json(serde_json::json!({
+                                        "error": "Invalid CSRF token"
+                                    }));
+                                return Ok(req.into_response(res).map_into_right_body());
+                            },
+                        }
                     }
+                } else {
+                    let res = HttpResponse::Unauthorized()
+                        .json(serde_json::json!({
+                            "error": "Missing CSRF token or session"
+                        }));
+                    return Ok(req.into_response(res).map_into_right_body());
                 }
-            } else {
-                let res = HttpResponse::Unauthorized()
-                    .json(serde_json::json!({
-                        "error": "Missing CSRF token or session"
-                    }));
-                return Either::right(ok(req.into_response(res)
-                    .map_into_boxed_body()
-                    .map_into_right_body()));
             }
-        }
 
-        Either::left(CsrfFuture {
-          fut: self.service.call(req),
-          _phantom: PhantomData,
+            service.call(req).await.map(ServiceResponse::map_into_left_body)
         })
     }
-} 
-
-#[pin_project]
-pub struct CsrfFuture<S, B>
-where
-  S: Service<ServiceRequest>,
-{
-  #[pin]
-  fut: S::Future,
-  _phantom: PhantomData<B>,
 }
-
-impl<S, B> Future for CsrfFuture<S, B>
-where
-  B: MessageBody,
-  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
-{
-  type Output = Result<ServiceResponse<EitherBody<B>>, Error>;
-
-  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-    let res = match ready!(self.project().fut.poll(cx)) {
-        Ok(res) => res,
-        Err(err) => return Poll::Ready(Err(err.into())),
-    };
-
-    Poll::Ready(Ok(res.map_into_left_body()))
-  }
-}
\ No newline at end of file
@@ -1,16 +1,79 @@
 use std::{
-    marker::PhantomData, pin::Pin, task::{Context, Poll}
+    marker::PhantomData, rc::Rc, task::{Context, Poll}
 };
-use pin_project::pin_project;
-use actix_utils::future::{ok, Either, Ready};
+use actix_utils::future::{ok, Ready};
 use actix_web::{
-    body::{EitherBody, MessageBody}, dev::{Service, ServiceRequest, ServiceResponse, Transform}, web, Error, HttpMessage, HttpResponse
+    body::{EitherBody, MessageBody}, dev::{Service, ServiceRequest, ServiceResponse, Transform}, http::{header, Method}, web, Error, HttpMessage, HttpResponse
 };
-use crate::repositories::auth_repository::AuthRepository;
-use futures::{ready, Future};
+use crate::{repositories::auth_repository::{AuthError, AuthRepository}, routes::{run_blocking, service_unavailable}};
+use futures::future::LocalBoxFuture;
+
+/// What users who haven't verified their email address yet may do on protected routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmailVerificationPolicy {
+    /// Unverified users may do everything.
+    #[default]
+    Optional,
+    /// Unverified users may read, but not change anything.
+    ReadOnly,
+    /// Unverified users are turned away until they verify.
+    Required,
+}
+
+impl EmailVerificationPolicy {
+    pub fn from_env() -> Self {
+        match std::env::var("EMAIL_VERIFICATION").as_deref() {
+            Ok("optional") | Err(_) => Self::Optional,
+            Ok("read_only") => Self::ReadOnly,
+            Ok("required") => Self::Required,
+            Ok(other) => panic!("EMAIL_VERIFICATION must be optional, read_only or required, got {}", other),
+        }
+    }
+
+    fn requires_verification(&self, method: &Method) -> bool {
+        match self {
+            Self::Optional => false,
+            Self::ReadOnly => !method.is_safe(),
+            Self::Required => true,
+        }
+    }
+}
+
+/// The token of an `Authorization: Bearer` header, which scripts and integrations use instead
+/// of the session cookie.
+pub(crate) fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// The scope an API token needs for a request, going by the resource its path belongs to.
+/// `None` for paths API tokens can't be used on.
+fn required_scope(path: &str, method: &Method) -> Option<String> {
+    let resource = match path.trim_start_matches('/').split('/').next()? {
+        "exercises" if path.ends_with("/records") => "records",
+        "records" => "records",
+        "workouts" => "workouts",
+        "exercises" | "muscle-groups" => "exercises",
+        "schedule" | "recurring-schedules" | "calendar" => "schedule",
+        "reports" => "reports",
+        _ => return None,
+    };
+    let access = if method.is_safe() { "read" } else { "write" };
+    Some(format!("{}:{}", resource, access))
+}
+
+/// How a request authenticates: with the browser session cookie or a personal access token.
+enum Credentials {
+    Session(String),
+    ApiToken(String),
+}
 
 pub struct SessionProtection<T: AuthRepository> {
     ignored_paths: Vec<String>,
+    verification_policy: EmailVerificationPolicy,
     _phantom: PhantomData<T>,
 }
 
@@ -18,10 +81,16 @@ impl<T: AuthRepository> SessionProtection<T> {
     pub fn new() -> Self {
         Self {
             ignored_paths: Vec::new(),
+            verification_policy: EmailVerificationPolicy::default(),
             _phantom: PhantomData,
         }
     }
 
+    pub fn verification_policy(mut self, policy: EmailVerificationPolicy) -> Self {
+        self.verification_policy = policy;
+        self
+    }
+
     pub fn ignore<I>(mut self, paths: I) -> Self 
     where
         I: IntoIterator,
@@ -32,12 +101,18 @@ impl<T: AuthRepository> SessionProtection<T> {
     }
 }
 
+impl<T: AuthRepository> Default for SessionProtection<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<S, B, T> Transform<S, ServiceRequest> for SessionProtection<T>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: MessageBody + 'static,
-    T: AuthRepository + 'static,
+    T: AuthRepository,
 {
     type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
@@ -47,8 +122,9 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ok(SessionMiddleware {
-            service,
-            ignored_paths: self.ignored_paths.clone(),
+            service: Rc::new(service),
+            ignored_paths: Rc::new(self.ignored_paths.clone()),
+            verification_policy: self.verification_policy,
             _phantom: PhantomData,
         })
     }
@@ -56,87 +132,106 @@ where
 
 // TODO: We might be able to use actix_session instead
 pub struct SessionMiddleware<S, T> {
-    service: S,
-    ignored_paths: Vec<String>,
+    service: Rc<S>,
+    ignored_paths: Rc<Vec<String>>,
+    verification_policy: EmailVerificationPolicy,
     _phantom: PhantomData<T>,
 }
 
 impl<S, B, T> Service<ServiceRequest> for SessionMiddleware<S, T>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: MessageBody + 'static,
-    T: AuthRepository + 'static,
+    T: AuthRepository,
 {
     type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
-    type Future = Either<SessionFuture<S, B>, Ready<Result<Self::Response, Self::Error>>>;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.service.poll_ready(cx)
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        // Skip session check for ignored paths
-        if self.ignored_paths.iter().any(|path| req.path() == path) {
-            return Either::left(SessionFuture {
-                fut: self.service.call(req),
-                _phantom: PhantomData,
-            });
-        }
+        let service = Rc::clone(&self.service);
+        let ignored_paths = Rc::clone(&self.ignored_paths);
+        let verification_policy = self.verification_policy;
+
+        Box::pin(async move {
+            // Skip session check for ignored paths
+            if ignored_paths.iter().any(|path| req.path() == path) {
+                return service.call(req).await.map(ServiceResponse::map_into_left_body);
+            }
+
+            // A bearer token takes precedence, so its scopes can't be sidestepped with a cookie
+            let credentials = match (bearer_token(&req), req.cookie("session_id")) {
+                (Some(token), _) => Credentials::ApiToken(token),
+                (None, Some(session)) => Credentials::Session(session.value().to_string()),
+                (None, None) => {
+                    let res = HttpResponse::Unauthorized()
+                        .json(serde_json::json!({
+                            "error": "Authentication required"
+                        }));
+                    return Ok(req.into_response(res).map_into_right_body());
+                },
+            };
+            let uses_api_token = matches!(credentials, Credentials::ApiToken(_));
 
-        if let Some(session) = req.cookie("session_id") {
-            if let Some(repo) = req.app_data::<web::Data<T>>() {
-                if let Ok(user_id) = repo.validate_session(session.value()) {
-                    req.extensions_mut().insert(user_id);
-                    return Either::left(SessionFuture {
-                        fut: self.service.call(req),
-                        _phantom: PhantomData,
-                    });
+            if let Some(repo) = req.app_data::<web::Data<T>>().cloned() {
+                let requires_verification = verification_policy.requires_verification(req.method());
+                let scope = required_scope(req.path(), req.method());
+                let required = scope.clone();
+                let validated = run_blocking(&repo, move |repo| {
+                    let (user_id, permitted) = match credentials {
+                        Credentials::Session(session_token) => (repo.validate_session(&session_token)?, true),
+                        Credentials::ApiToken(token) => {
+                            let api_token = repo.authenticate_api_token(&token)?;
+                            (api_token.user_id, required.is_some_and(|scope| api_token.allows(&scope)))
+                        },
+                    };
+                    let verified = !permitted || !requires_verification || repo.email_verified(user_id)?;
+                    Ok((user_id, permitted, verified))
+                });
+                match validated.await {
+                    Ok((_, false, _)) => {
+                        let res = HttpResponse::Forbidden()
+                            .json(serde_json::json!({
+                                "error": "Insufficient scope",
+                                "required_scope": scope
+                            }));
+                        return Ok(req.into_response(res).map_into_right_body());
+                    },
+                    Ok((_, true, false)) => {
+                        let res = HttpResponse::Forbidden()
+                            .json(serde_json::json!({
+                                "error": "Email address not verified"
+                            }));
+                        return Ok(req.into_response(res).map_into_right_body());
+                    },
+                    Ok((user_id, true, true)) => {
+                        req.extensions_mut().insert(user_id);
+                        return service.call(req).await.map(ServiceResponse::map_into_left_body);
+                    },
+                    Err(AuthError::ConnectionUnavailable) => {
+                        return Ok(req.into_response(service_unavailable()).map_into_right_body());
+                    },
+                    Err(AuthError::TwoFactorRequired) => {
+                        let res = HttpResponse::Unauthorized()
+                            .json(serde_json::json!({
+                                "error": "Two-factor authentication required"
+                            }));
+                        return Ok(req.into_response(res).map_into_right_body());
+                    },
+                    Err(_) => {},
                 }
             }
+
             let res = HttpResponse::Unauthorized()
                 .json(serde_json::json!({
-                    "error": "Invalid session"
-                }));
-            Either::right(ok(req.into_response(res)
-                .map_into_boxed_body()
-                .map_into_right_body()))
-        } else {
-            let res = HttpResponse::Unauthorized()
-                .json(serde_json::json!({
-                    "error": "Authentication required"
+                    "error": if uses_api_token { "Invalid token" } else { "Invalid session" }
                 }));
-            Either::right(ok(req.into_response(res)
-                .map_into_boxed_body()
-                .map_into_right_body()))
-        }
+            Ok(req.into_response(res).map_into_right_body())
+        })
     }
 }
-
-#[pin_project]
-pub struct SessionFuture<S, B>
-where
-    S: Service<ServiceRequest>,
-{
-    #[pin]
-    fut: S::Future,
-    _phantom: PhantomData<B>,
-}
-
-impl<S, B> Future for SessionFuture<S, B>
-where
-    B: MessageBody,
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
-{
-    type Output = Result<ServiceResponse<EitherBody<B>>, Error>;
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let res = match ready!(self.project().fut.poll(cx)) {
-            Ok(res) => res,
-            Err(err) => return Poll::Ready(Err(err.into())),
-        };
-
-        Poll::Ready(Ok(res.map_into_left_body()))
-    }
-} 
\ No newline at end of file
@@ -0,0 +1,89 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// Marks API tokens so they are recognizable, e.g. by secret scanners.
+pub const API_TOKEN_PREFIX: &str = "fwt_";
+/// Every scope a token may be given. Reading and writing are granted separately.
+pub const API_TOKEN_SCOPES: &[&str] = &[
+    "workouts:read",
+    "workouts:write",
+    "exercises:read",
+    "exercises:write",
+    "records:read",
+    "schedule:read",
+    "schedule:write",
+    "reports:read",
+];
+pub const MAX_NAME_LENGTH: usize = 100;
+/// Tokens may live at most this long; tokens without an expiry are allowed as well.
+pub const MAX_LIFETIME: Duration = Duration::days(365);
+
+#[derive(Debug, Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::public::api_tokens)]
+pub struct ApiToken {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub name: String,
+    pub token_hash: String,
+    /// Space separated, like OAuth scopes.
+    pub scopes: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::api_tokens)]
+pub struct NewApiToken {
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiToken {
+    pub fn new(user_id: i64, name: String, token: &str, scopes: &[String], expires_at: Option<NaiveDateTime>, now: NaiveDateTime) -> NewApiToken {
+        NewApiToken {
+            uuid: Uuid::new_v4(),
+            user_id,
+            name,
+            token_hash: crate::tokens::hash_token(token),
+            scopes: scopes.join(" "),
+            expires_at,
+            created_at: now,
+        }
+    }
+
+    pub fn scopes(&self) -> Vec<&str> {
+        self.scopes.split_whitespace().collect()
+    }
+
+    pub fn allows(&self, scope: &str) -> bool {
+        self.scopes.split_whitespace().any(|granted| granted == scope)
+    }
+
+    pub fn is_usable(&self, now: NaiveDateTime) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    /// Like sessions, `last_used_at` is updated at most once a minute.
+    pub fn needs_touch(&self, now: NaiveDateTime) -> bool {
+        self.last_used_at.is_none_or(|last_used_at| now - last_used_at >= Duration::minutes(1))
+    }
+}
+
+pub fn generate_api_token() -> String {
+    format!("{}{}", API_TOKEN_PREFIX, crate::tokens::generate_token())
+}
+
+/// Returns the first scope that isn't one of `API_TOKEN_SCOPES`.
+pub fn unknown_scope(scopes: &[String]) -> Option<&str> {
+    scopes.iter()
+        .map(String::as_str)
+        .find(|scope| !API_TOKEN_SCOPES.contains(scope))
+}
@@ -0,0 +1,29 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+#[derive(Debug, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::calendar_feed_tokens)]
+pub struct CalendarFeedToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::calendar_feed_tokens)]
+pub struct NewCalendarFeedToken {
+    pub user_id: i64,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl CalendarFeedToken {
+    pub fn new(user_id: i64, token: &str) -> NewCalendarFeedToken {
+        NewCalendarFeedToken {
+            user_id,
+            token_hash: crate::tokens::hash_token(token),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
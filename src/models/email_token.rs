@@ -0,0 +1,88 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+
+/// How long a reset link stays usable after it was requested.
+pub const PASSWORD_RESET_LIFETIME: Duration = Duration::hours(1);
+/// How long a verification link stays usable after it was sent.
+pub const EMAIL_VERIFICATION_LIFETIME: Duration = Duration::hours(24);
+/// How long a login link stays usable after it was sent. Links log in without a password, so
+/// they are kept shorter-lived than password reset links.
+pub const LOGIN_LINK_LIFETIME: Duration = Duration::minutes(15);
+
+/// Minimum time between two emails of the same purpose to the same account.
+pub const RESEND_INTERVAL: Duration = Duration::minutes(1);
+/// At most `RESEND_LIMIT` emails of the same purpose are sent to an account per `RESEND_WINDOW`.
+pub const RESEND_LIMIT: usize = 5;
+pub const RESEND_WINDOW: Duration = Duration::hours(1);
+
+/// What an emailed token can be redeemed for. Tokens of one purpose are never accepted for another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    LoginLink,
+}
+
+impl EmailTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTokenPurpose::PasswordReset => "password_reset",
+            EmailTokenPurpose::EmailVerification => "email_verification",
+            EmailTokenPurpose::LoginLink => "login_link",
+        }
+    }
+
+    pub fn lifetime(&self) -> Duration {
+        match self {
+            EmailTokenPurpose::PasswordReset => PASSWORD_RESET_LIFETIME,
+            EmailTokenPurpose::EmailVerification => EMAIL_VERIFICATION_LIFETIME,
+            EmailTokenPurpose::LoginLink => LOGIN_LINK_LIFETIME,
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::email_tokens)]
+pub struct EmailToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub purpose: String,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::email_tokens)]
+pub struct NewEmailToken {
+    pub user_id: i64,
+    pub purpose: String,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl EmailToken {
+    pub fn new(user_id: i64, purpose: EmailTokenPurpose, token: &str, now: NaiveDateTime) -> NewEmailToken {
+        NewEmailToken {
+            user_id,
+            purpose: purpose.as_str().to_string(),
+            token_hash: crate::tokens::hash_token(token),
+            expires_at: now + purpose.lifetime(),
+            created_at: now,
+        }
+    }
+}
+
+/// How long to wait before another email may be sent, given when the previous ones of the same
+/// purpose within `RESEND_WINDOW` were sent, newest first.
+pub fn resend_retry_after(sent_at: &[NaiveDateTime], now: NaiveDateTime) -> Option<Duration> {
+    let interval = sent_at.first().map(|latest| *latest + RESEND_INTERVAL - now);
+    let window = sent_at.get(RESEND_LIMIT - 1).map(|oldest| *oldest + RESEND_WINDOW - now);
+
+    interval.into_iter()
+        .chain(window)
+        .filter(|wait| *wait > Duration::zero())
+        .max()
+}
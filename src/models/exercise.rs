@@ -1,53 +1,146 @@
+use std::collections::HashSet;
+
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::models::muscle_group::{ExerciseMuscleGroup, MuscleGroup, MuscleRole};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExerciseCategory {
+    #[default]
+    Strength,
+    Cardio,
+    Flexibility,
+    Stability,
+    Plyometric,
+}
+
+impl ExerciseCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExerciseCategory::Strength => "strength",
+            ExerciseCategory::Cardio => "cardio",
+            ExerciseCategory::Flexibility => "flexibility",
+            ExerciseCategory::Stability => "stability",
+            ExerciseCategory::Plyometric => "plyometric",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone)]
 #[diesel(table_name = crate::schema::public::exercises)]
 pub struct Exercise {
     pub id: i64,
     pub uuid: Uuid,
-    pub user_id: i64,
+    /// `None` for exercises in the global catalog.
+    pub user_id: Option<i64>,
     pub name: String,
     pub description: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub category: String,
 }
 
 #[derive(Insertable, Clone)]
 #[diesel(table_name = crate::schema::public::exercises)]
 pub struct NewExercise {
     pub uuid: Uuid,
-    pub user_id: i64,
+    pub user_id: Option<i64>,
     pub name: String,
     pub description: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub category: String,
+}
+
+/// An exercise together with the muscle groups it trains.
+#[derive(Debug, Clone)]
+pub struct ExerciseDetail {
+    pub exercise: Exercise,
+    pub muscle_groups: Vec<(MuscleGroup, ExerciseMuscleGroup)>,
+}
+
+impl ExerciseDetail {
+    /// Slugs of the muscle groups trained in the given role.
+    pub fn muscles(&self, role: MuscleRole) -> Vec<String> {
+        self.muscle_groups.iter()
+            .filter(|(_, mapping)| mapping.role == role.as_str())
+            .map(|(muscle_group, _)| muscle_group.slug.clone())
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateExercise {
     pub name: String,
     pub description: Option<String>,
+    #[serde(default)]
+    pub category: ExerciseCategory,
+    #[serde(default)]
+    pub primary_muscles: Vec<String>,
+    #[serde(default)]
+    pub secondary_muscles: Vec<String>,
+}
+
+impl CreateExercise {
+    pub fn is_valid(&self) -> bool {
+        !self.name.trim().is_empty() && muscles_are_valid(&self.primary_muscles, &self.secondary_muscles)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateExercise {
     pub name: Option<String>,
     pub description: Option<String>,
+    pub category: Option<ExerciseCategory>,
+    /// Replaces the primary muscle groups when present.
+    pub primary_muscles: Option<Vec<String>>,
+    /// Replaces the secondary muscle groups when present.
+    pub secondary_muscles: Option<Vec<String>>,
+}
+
+impl UpdateExercise {
+    pub fn is_valid(&self) -> bool {
+        let no_muscles = vec![];
+        self.name.as_ref().is_none_or(|name| !name.trim().is_empty())
+            && muscles_are_valid(
+                self.primary_muscles.as_ref().unwrap_or(&no_muscles),
+                self.secondary_muscles.as_ref().unwrap_or(&no_muscles),
+            )
+    }
+}
+
+/// A muscle group may appear at most once across the primary and secondary lists.
+pub fn muscles_are_valid(primary: &[String], secondary: &[String]) -> bool {
+    let mut seen = HashSet::new();
+    primary.iter().chain(secondary).all(|muscle| seen.insert(muscle.as_str()))
+}
+
+/// Query parameters for `GET /exercises`. `muscle` matches primary and secondary muscle groups.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ExerciseFilter {
+    pub category: Option<ExerciseCategory>,
+    pub muscle: Option<String>,
 }
 
 impl Exercise {
-    pub fn new(user_id: i64, name: String, description: Option<String>) -> NewExercise {
+    pub fn new(user_id: i64, name: String, description: Option<String>, category: ExerciseCategory) -> NewExercise {
         let now = chrono::Utc::now().naive_utc();
         NewExercise {
             uuid: Uuid::new_v4(),
-            user_id,
+            user_id: Some(user_id),
             name,
             description,
             created_at: now,
             updated_at: now,
+            category: category.as_str().to_string(),
         }
     }
-} 
\ No newline at end of file
+
+    pub fn is_catalog(&self) -> bool {
+        self.user_id.is_none()
+    }
+}
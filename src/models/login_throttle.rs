@@ -0,0 +1,109 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleScope {
+    /// Failures for one email address, whichever client sends them.
+    Account,
+    /// Failures from one client IP, whichever accounts they target.
+    Ip,
+    /// Wrong second factors for one account. Kept apart from `Account`, which a correct
+    /// password resets.
+    TwoFactor,
+}
+
+impl ThrottleScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleScope::Account => "account",
+            ThrottleScope::Ip => "ip",
+            ThrottleScope::TwoFactor => "two_factor",
+        }
+    }
+
+    /// Many members may log in from one address, such as a gym's Wi-Fi, so IPs get more
+    /// attempts before backing off than a single account does.
+    pub fn policy(&self) -> ThrottlePolicy {
+        match self {
+            ThrottleScope::Account | ThrottleScope::TwoFactor => ThrottlePolicy {
+                free_attempts: 5,
+                base_delay: Duration::seconds(30),
+                max_delay: Duration::minutes(15),
+                reset_after: Duration::hours(1),
+            },
+            ThrottleScope::Ip => ThrottlePolicy {
+                free_attempts: 20,
+                base_delay: Duration::seconds(30),
+                max_delay: Duration::hours(1),
+                reset_after: Duration::hours(1),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottlePolicy {
+    /// Failures allowed before the first lockout.
+    pub free_attempts: i32,
+    /// Lockout after the first failure past `free_attempts`, doubling with each further one.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Failures older than this are forgotten.
+    pub reset_after: Duration,
+}
+
+impl ThrottlePolicy {
+    pub fn lockout(&self, failures: i32) -> Option<Duration> {
+        let excess = failures - self.free_attempts;
+        if excess <= 0 {
+            return None;
+        }
+        // 2^20 times any sensible base delay is already far beyond `max_delay`
+        let delay = self.base_delay * 2_i32.pow(excess.min(20) as u32 - 1);
+        Some(delay.min(self.max_delay))
+    }
+}
+
+#[derive(Debug, Queryable, Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::login_throttles)]
+pub struct LoginThrottle {
+    pub scope: String,
+    pub key: String,
+    pub failures: i32,
+    pub last_failure_at: NaiveDateTime,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl LoginThrottle {
+    pub fn new(scope: ThrottleScope, key: String, now: NaiveDateTime) -> Self {
+        Self {
+            scope: scope.as_str().to_string(),
+            key,
+            failures: 0,
+            last_failure_at: now,
+            locked_until: None,
+        }
+    }
+
+    /// How long the client has to wait before trying again, if it is locked out.
+    pub fn retry_after(&self, now: NaiveDateTime) -> Option<Duration> {
+        self.locked_until
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| locked_until - now)
+    }
+
+    /// Counts another failure, locking the key once the policy's free attempts are used up.
+    pub fn record_failure(&mut self, policy: &ThrottlePolicy, now: NaiveDateTime) {
+        if now - self.last_failure_at > policy.reset_after {
+            self.failures = 0;
+        }
+        self.failures += 1;
+        self.last_failure_at = now;
+        self.locked_until = policy.lockout(self.failures).map(|delay| now + delay);
+    }
+}
+
+/// Throttle keys are compared case-insensitively so `A@b.com` and `a@b.com` share a counter.
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
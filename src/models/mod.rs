@@ -1,6 +1,20 @@
 pub mod user;
 pub mod session;
+pub mod login_throttle;
+pub mod email_token;
+pub mod two_factor;
+pub mod api_token;
+pub mod user_identity;
+pub mod oidc_login_attempt;
 pub mod temp_session;
 pub mod workout;
 pub mod exercise;
-pub mod workout_exercise;
\ No newline at end of file
+pub mod workout_exercise;
+pub mod workout_log;
+pub mod scheduled_workout;
+pub mod recurrence_rule;
+pub mod recurring_schedule;
+pub mod calendar_feed_token;
+pub mod report;
+pub mod personal_record;
+pub mod muscle_group;
\ No newline at end of file
@@ -0,0 +1,45 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MuscleRole {
+    Primary,
+    Secondary,
+}
+
+impl MuscleRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MuscleRole::Primary => "primary",
+            MuscleRole::Secondary => "secondary",
+        }
+    }
+}
+
+/// An entry of the muscle group taxonomy, seeded by migration and identified by its `slug`.
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::muscle_groups)]
+pub struct MuscleGroup {
+    pub id: i64,
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::exercise_muscle_groups)]
+pub struct ExerciseMuscleGroup {
+    pub exercise_id: i64,
+    pub muscle_group_id: i64,
+    pub role: String,
+}
+
+impl ExerciseMuscleGroup {
+    pub fn new(exercise_id: i64, muscle_group_id: i64, role: MuscleRole) -> Self {
+        Self {
+            exercise_id,
+            muscle_group_id,
+            role: role.as_str().to_string(),
+        }
+    }
+}
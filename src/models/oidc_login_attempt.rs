@@ -0,0 +1,45 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+
+/// How long a sign-in at the identity provider may take.
+pub const OIDC_LOGIN_LIFETIME: Duration = Duration::minutes(10);
+
+/// A sign-in that was sent to the identity provider and waits for its callback.
+#[derive(Debug, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::oidc_login_attempts)]
+pub struct OidcLoginAttempt {
+    pub id: i64,
+    pub state_hash: String,
+    /// Expected in the ID token, so a token issued for another login can't be slipped in.
+    pub nonce: String,
+    /// The PKCE secret whose hash went out with the authorization request.
+    pub code_verifier: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::oidc_login_attempts)]
+pub struct NewOidcLoginAttempt {
+    pub state_hash: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl OidcLoginAttempt {
+    pub fn new(state: &str, nonce: String, code_verifier: String, now: NaiveDateTime) -> NewOidcLoginAttempt {
+        NewOidcLoginAttempt {
+            state_hash: crate::tokens::hash_token(state),
+            nonce,
+            code_verifier,
+            expires_at: now + OIDC_LOGIN_LIFETIME,
+            created_at: now,
+        }
+    }
+
+    pub fn is_usable(&self, now: NaiveDateTime) -> bool {
+        self.expires_at > now
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{report::estimated_one_rep_max, workout_log::{WorkoutLog, WorkoutLogSet}};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordType {
+    /// Heaviest weight lifted for at least one rep.
+    MaxWeight,
+    /// Most reps performed at a given weight (or without weight).
+    MaxReps,
+    EstimatedOneRepMax,
+    /// Most volume (reps times weight) moved for the exercise in a single session.
+    MaxVolume,
+}
+
+impl RecordType {
+    pub const ALL: [RecordType; 4] = [
+        RecordType::MaxWeight,
+        RecordType::MaxReps,
+        RecordType::EstimatedOneRepMax,
+        RecordType::MaxVolume,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::MaxWeight => "max_weight",
+            RecordType::MaxReps => "max_reps",
+            RecordType::EstimatedOneRepMax => "estimated_one_rep_max",
+            RecordType::MaxVolume => "max_volume",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::personal_records)]
+pub struct PersonalRecord {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub exercise_id: i64,
+    pub workout_log_id: i64,
+    pub record_type: String,
+    pub value: f64,
+    pub reps: Option<i32>,
+    pub weight: Option<f64>,
+    pub achieved_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::schema::public::personal_records)]
+pub struct NewPersonalRecord {
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub exercise_id: i64,
+    pub workout_log_id: i64,
+    pub record_type: String,
+    pub value: f64,
+    pub reps: Option<i32>,
+    pub weight: Option<f64>,
+    pub achieved_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl PersonalRecord {
+    pub fn new(log: &WorkoutLog, exercise_id: i64, record_type: RecordType, value: f64, reps: Option<i32>, weight: Option<f64>) -> NewPersonalRecord {
+        NewPersonalRecord {
+            uuid: Uuid::new_v4(),
+            user_id: log.user_id,
+            exercise_id,
+            workout_log_id: log.id,
+            record_type: record_type.as_str().to_string(),
+            value,
+            reps,
+            weight,
+            achieved_at: log.performed_at,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// Records set by `sets` (all of one exercise, from `log`) that beat the `previous` records
+    /// of that exercise. Records are compared against the best so far regardless of when they
+    /// were achieved, so a backdated log only sets a record if it beats the current one.
+    pub fn detect(log: &WorkoutLog, exercise_id: i64, sets: &[WorkoutLogSet], previous: &[PersonalRecord]) -> Vec<NewPersonalRecord> {
+        let best = |record_type: RecordType, weight: Option<Option<f64>>| previous.iter()
+            .filter(|record| record.record_type == record_type.as_str())
+            .filter(|record| weight.is_none_or(|weight| record.weight == weight))
+            .map(|record| record.value)
+            .fold(None, |best: Option<f64>, value| Some(best.map_or(value, |best| best.max(value))));
+        let beats = |value: f64, best: Option<f64>| best.is_none_or(|best| value > best);
+
+        let mut records = vec![];
+        let performed = sets.iter().filter(|set| set.reps > 0);
+
+        if let Some(set) = performed.clone()
+            .filter(|set| set.weight.is_some_and(|weight| weight > 0.0))
+            .max_by(|a, b| a.weight.partial_cmp(&b.weight).unwrap().then(a.reps.cmp(&b.reps)))
+        {
+            let weight = set.weight.unwrap();
+            if beats(weight, best(RecordType::MaxWeight, None)) {
+                records.push(Self::new(log, exercise_id, RecordType::MaxWeight, weight, Some(set.reps), set.weight));
+            }
+        }
+
+        let mut weights: Vec<Option<f64>> = vec![];
+        for set in performed.clone() {
+            if !weights.contains(&set.weight) {
+                weights.push(set.weight);
+            }
+        }
+        for weight in weights {
+            let reps = performed.clone().filter(|set| set.weight == weight).map(|set| set.reps).max().unwrap();
+            if beats(reps as f64, best(RecordType::MaxReps, Some(weight))) {
+                records.push(Self::new(log, exercise_id, RecordType::MaxReps, reps as f64, Some(reps), weight));
+            }
+        }
+
+        if let Some((set, one_rep_max)) = performed.clone()
+            .filter_map(|set| estimated_one_rep_max(set.reps, set.weight).map(|one_rep_max| (set, one_rep_max)))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        {
+            if beats(one_rep_max, best(RecordType::EstimatedOneRepMax, None)) {
+                records.push(Self::new(log, exercise_id, RecordType::EstimatedOneRepMax, one_rep_max, Some(set.reps), set.weight));
+            }
+        }
+
+        let volume: f64 = performed.map(|set| set.reps as f64 * set.weight.unwrap_or(0.0)).sum();
+        if volume > 0.0 && beats(volume, best(RecordType::MaxVolume, None)) {
+            records.push(Self::new(log, exercise_id, RecordType::MaxVolume, volume, None, None));
+        }
+
+        records
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordFeedQuery {
+    pub limit: Option<i64>,
+}
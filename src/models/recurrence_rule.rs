@@ -0,0 +1,347 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Weekday};
+
+/// Upper bound on the occurrences listed for one window, and on `COUNT`.
+const MAX_OCCURRENCES: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// The subset of RFC 5545 `RRULE` supported for recurring schedules:
+/// `FREQ` (DAILY, WEEKLY, MONTHLY), `INTERVAL`, `BYDAY` (plain weekdays), `COUNT` and `UNTIL`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidRecurrenceRule(pub String);
+
+impl RecurrenceRule {
+    pub fn parse(rule: &str) -> Result<Self, InvalidRecurrenceRule> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = vec![];
+        let mut count = None;
+        let mut until = None;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| InvalidRecurrenceRule(format!("Malformed rule part '{}'", part)))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(InvalidRecurrenceRule(format!("Unsupported FREQ '{}'", value))),
+                    });
+                },
+                "INTERVAL" => {
+                    interval = value.parse::<u32>()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| InvalidRecurrenceRule(format!("Invalid INTERVAL '{}'", value)))?;
+                },
+                "BYDAY" => {
+                    by_day = value.split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<Vec<_>, _>>()?;
+                },
+                "COUNT" => {
+                    count = Some(value.parse::<u32>()
+                        .ok()
+                        .filter(|count| *count > 0 && *count as usize <= MAX_OCCURRENCES)
+                        .ok_or_else(|| InvalidRecurrenceRule(format!("Invalid COUNT '{}'", value)))?);
+                },
+                "UNTIL" => until = Some(parse_until(value)?),
+                _ => return Err(InvalidRecurrenceRule(format!("Unsupported rule part '{}'", key))),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| InvalidRecurrenceRule("FREQ is required".to_string()))?;
+        if count.is_some() && until.is_some() {
+            return Err(InvalidRecurrenceRule("COUNT and UNTIL cannot be combined".to_string()));
+        }
+        if frequency == Frequency::Monthly && !by_day.is_empty() {
+            return Err(InvalidRecurrenceRule("BYDAY is not supported for MONTHLY rules".to_string()));
+        }
+
+        by_day.sort_by_key(|day| day.num_days_from_monday());
+        by_day.dedup();
+
+        Ok(Self { frequency, interval, by_day, count, until })
+    }
+
+    /// The occurrences of a series starting at `dtstart` that fall within `[from, before)`, in
+    /// chronological order.
+    pub fn occurrences(&self, dtstart: NaiveDateTime, from: NaiveDateTime, before: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let mut occurrences = vec![];
+        let end = self.until.map_or(before, |until| {
+            // UNTIL is inclusive, `before` is exclusive
+            before.min(until + Duration::seconds(1))
+        });
+
+        // COUNT needs every earlier occurrence counted, otherwise the periods before the window
+        // can be skipped
+        let mut generated = 0;
+        let mut period = if self.count.is_some() { 0 } else { self.first_period_reaching(dtstart, from) };
+        while occurrences.len() < MAX_OCCURRENCES {
+            let candidates = match self.candidates(dtstart, period) {
+                Some(candidates) => candidates,
+                None => break,
+            };
+            if candidates.first().is_some_and(|first| *first >= end) {
+                break;
+            }
+            for candidate in candidates {
+                if candidate < dtstart {
+                    continue;
+                }
+                if candidate >= end || self.count.is_some_and(|count| generated >= count) || occurrences.len() >= MAX_OCCURRENCES {
+                    return occurrences;
+                }
+                generated += 1;
+                if candidate >= from {
+                    occurrences.push(candidate);
+                }
+            }
+            period += 1;
+        }
+
+        occurrences
+    }
+
+    pub fn is_occurrence(&self, dtstart: NaiveDateTime, at: NaiveDateTime) -> bool {
+        self.occurrences(dtstart, at, at + Duration::seconds(1)) == [at]
+    }
+
+    /// A period no later than the first one with occurrences at or after `from`.
+    fn first_period_reaching(&self, dtstart: NaiveDateTime, from: NaiveDateTime) -> u32 {
+        let periods = match self.frequency {
+            Frequency::Daily => (from.date() - dtstart.date()).num_days(),
+            Frequency::Weekly => (from.date() - dtstart.date()).num_weeks(),
+            Frequency::Monthly => {
+                (from.year() as i64 - dtstart.year() as i64) * 12 + from.month() as i64 - dtstart.month() as i64
+            },
+        };
+        // One period early, as the week of `dtstart` starts before it
+        (periods / self.interval as i64 - 1).clamp(0, u32::MAX as i64) as u32
+    }
+
+    /// Candidate occurrences in the `period`-th frequency period after `dtstart`, or `None` once
+    /// the dates run past what chrono can represent.
+    fn candidates(&self, dtstart: NaiveDateTime, period: u32) -> Option<Vec<NaiveDateTime>> {
+        let step = period.checked_mul(self.interval)?;
+        let time = dtstart.time();
+        match self.frequency {
+            Frequency::Daily => {
+                let day = dtstart.date().checked_add_signed(Duration::days(step as i64))?;
+                if self.by_day.is_empty() || self.by_day.contains(&day.weekday()) {
+                    Some(vec![day.and_time(time)])
+                } else {
+                    Some(vec![])
+                }
+            },
+            Frequency::Weekly => {
+                let week_start = dtstart.date()
+                    - Duration::days(dtstart.weekday().num_days_from_monday() as i64);
+                let week_start = week_start.checked_add_signed(Duration::weeks(step as i64))?;
+                let days = if self.by_day.is_empty() { vec![dtstart.weekday()] } else { self.by_day.clone() };
+                Some(days.into_iter()
+                    .map(|day| (week_start + Duration::days(day.num_days_from_monday() as i64)).and_time(time))
+                    .collect())
+            },
+            Frequency::Monthly => {
+                let month_start = NaiveDate::from_ymd_opt(dtstart.year(), dtstart.month(), 1)?
+                    .checked_add_months(Months::new(step))?;
+                // Months without the start day (e.g. the 31st) are skipped, as in RFC 5545
+                Some(month_start.with_day(dtstart.day())
+                    .map(|day| vec![day.and_time(time)])
+                    .unwrap_or_default())
+            },
+        }
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days = self.by_day.iter().map(|day| weekday_code(*day)).collect::<Vec<_>>();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, InvalidRecurrenceRule> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(InvalidRecurrenceRule(format!("Unsupported BYDAY value '{}'", value))),
+    }
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_until(value: &str) -> Result<NaiveDateTime, InvalidRecurrenceRule> {
+    let value = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .or_else(|_| {
+            // A date-only UNTIL covers the whole day
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(|date| date.and_hms_opt(23, 59, 59).unwrap())
+        })
+        .map_err(|_| InvalidRecurrenceRule(format!("Invalid UNTIL '{}'", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let rule = RecurrenceRule::parse("RRULE:FREQ=weekly;INTERVAL=2;BYDAY=FR,MO,MO;COUNT=10").unwrap();
+        assert_eq!(rule.frequency, Frequency::Weekly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(rule.by_day, vec![Weekday::Mon, Weekday::Fri]);
+        assert_eq!(rule.count, Some(10));
+        assert_eq!(rule.to_string(), "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;COUNT=10");
+
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20250131").unwrap();
+        assert_eq!(rule.until, Some(at("2025-01-31T23:59:59")));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_rules() {
+        for (rule, reason) in [
+            ("FREQ=YEARLY", "Unsupported FREQ 'YEARLY'"),
+            ("INTERVAL=2", "FREQ is required"),
+            ("FREQ=MONTHLY;BYDAY=MO", "BYDAY is not supported for MONTHLY rules"),
+            ("FREQ=DAILY;COUNT=3;UNTIL=20250131", "COUNT and UNTIL cannot be combined"),
+            ("FREQ=DAILY;COUNT=0", "Invalid COUNT '0'"),
+            ("FREQ=DAILY;COUNT=5001", "Invalid COUNT '5001'"),
+            ("FREQ=DAILY;INTERVAL=0", "Invalid INTERVAL '0'"),
+            ("FREQ=WEEKLY;BYDAY=1MO", "Unsupported BYDAY value '1MO'"),
+            ("FREQ=DAILY;BYHOUR=9", "Unsupported rule part 'BYHOUR'"),
+            ("FREQ=DAILY;UNTIL=soon", "Invalid UNTIL 'soon'"),
+            ("FREQ", "Malformed rule part 'FREQ'"),
+        ] {
+            assert_eq!(RecurrenceRule::parse(rule), Err(InvalidRecurrenceRule(reason.to_string())), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_weekly_occurrences() {
+        let rule = RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=MO,WE").unwrap();
+        // A Wednesday
+        let dtstart = at("2025-01-01T07:00:00");
+        assert_eq!(rule.occurrences(dtstart, at("2024-12-01T00:00:00"), at("2025-01-09T00:00:00")), vec![
+            at("2025-01-01T07:00:00"),
+            at("2025-01-06T07:00:00"),
+            at("2025-01-08T07:00:00"),
+        ]);
+    }
+
+    #[test]
+    fn test_monthly_occurrences_skip_short_months() {
+        let rule = RecurrenceRule::parse("FREQ=MONTHLY").unwrap();
+        let dtstart = at("2025-01-31T18:00:00");
+        assert_eq!(rule.occurrences(dtstart, dtstart, at("2025-06-01T00:00:00")), vec![
+            at("2025-01-31T18:00:00"),
+            at("2025-03-31T18:00:00"),
+            at("2025-05-31T18:00:00"),
+        ]);
+    }
+
+    #[test]
+    fn test_count_includes_occurrences_before_the_window() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY;INTERVAL=2;COUNT=4").unwrap();
+        let dtstart = at("2025-01-01T07:00:00");
+        assert_eq!(rule.occurrences(dtstart, at("2025-01-04T00:00:00"), at("2025-02-01T00:00:00")), vec![
+            at("2025-01-05T07:00:00"),
+            at("2025-01-07T07:00:00"),
+        ]);
+    }
+
+    #[test]
+    fn test_until_is_inclusive() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20250103T070000Z").unwrap();
+        let dtstart = at("2025-01-01T07:00:00");
+        assert_eq!(rule.occurrences(dtstart, dtstart, at("2025-02-01T00:00:00")).len(), 3);
+    }
+
+    #[test]
+    fn test_open_ended_rules_reach_far_windows() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY").unwrap();
+        let dtstart = at("2025-01-01T07:00:00");
+        assert_eq!(rule.occurrences(dtstart, at("2045-03-01T00:00:00"), at("2045-03-03T00:00:00")), vec![
+            at("2045-03-01T07:00:00"),
+            at("2045-03-02T07:00:00"),
+        ]);
+        assert!(rule.is_occurrence(dtstart, at("2045-03-01T07:00:00")));
+        assert!(!rule.is_occurrence(dtstart, at("2045-03-01T08:00:00")));
+        assert!(!rule.is_occurrence(dtstart, at("2024-12-31T07:00:00")));
+
+        let weekly = RecurrenceRule::parse("FREQ=WEEKLY;INTERVAL=3;BYDAY=SU").unwrap();
+        assert!(weekly.is_occurrence(dtstart, at("2025-01-05T07:00:00")));
+        assert!(weekly.is_occurrence(dtstart, at("2045-01-08T07:00:00")));
+        assert!(!weekly.is_occurrence(dtstart, at("2045-01-01T07:00:00")));
+    }
+
+    #[test]
+    fn test_occurrences_are_capped_per_window() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY").unwrap();
+        let dtstart = at("2025-01-01T07:00:00");
+        let occurrences = rule.occurrences(dtstart, at("2040-01-01T00:00:00"), at("2080-01-01T00:00:00"));
+        assert_eq!(occurrences.len(), MAX_OCCURRENCES);
+        assert_eq!(occurrences[0], at("2040-01-01T07:00:00"));
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{recurrence_rule::RecurrenceRule, workout::Workout};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExceptionAction {
+    Skip,
+    Reschedule,
+}
+
+impl ExceptionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExceptionAction::Skip => "skip",
+            ExceptionAction::Reschedule => "reschedule",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::recurring_schedules)]
+pub struct RecurringSchedule {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub workout_id: i64,
+    pub starts_at: NaiveDateTime,
+    pub rrule: String,
+    pub notes: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::recurring_schedules)]
+pub struct NewRecurringSchedule {
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub workout_id: i64,
+    pub starts_at: NaiveDateTime,
+    pub rrule: String,
+    pub notes: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl RecurringSchedule {
+    pub fn new(user_id: i64, workout_id: i64, starts_at: NaiveDateTime, rule: &RecurrenceRule, notes: Option<String>) -> NewRecurringSchedule {
+        let now = chrono::Utc::now().naive_utc();
+        NewRecurringSchedule {
+            uuid: Uuid::new_v4(),
+            user_id,
+            workout_id,
+            starts_at,
+            rrule: rule.to_string(),
+            notes,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn rule(&self) -> RecurrenceRule {
+        // Rules are validated before they are stored
+        RecurrenceRule::parse(&self.rrule).expect("stored recurrence rule is valid")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::recurring_schedule_exceptions)]
+pub struct RecurringScheduleException {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub recurring_schedule_id: i64,
+    pub occurrence_at: NaiveDateTime,
+    pub action: String,
+    pub rescheduled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::recurring_schedule_exceptions)]
+pub struct NewRecurringScheduleException {
+    pub uuid: Uuid,
+    pub recurring_schedule_id: i64,
+    pub occurrence_at: NaiveDateTime,
+    pub action: String,
+    pub rescheduled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl RecurringScheduleException {
+    pub fn new(recurring_schedule_id: i64, exception: &CreateScheduleException) -> NewRecurringScheduleException {
+        NewRecurringScheduleException {
+            uuid: Uuid::new_v4(),
+            recurring_schedule_id,
+            occurrence_at: exception.occurrence_at,
+            action: exception.action.as_str().to_string(),
+            rescheduled_at: exception.rescheduled_at,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// A recurring schedule with its workout and per-occurrence exceptions.
+#[derive(Debug, Clone)]
+pub struct RecurringScheduleDetail {
+    pub schedule: RecurringSchedule,
+    pub workout: Workout,
+    pub exceptions: Vec<RecurringScheduleException>,
+}
+
+/// A concrete occurrence of a recurring schedule. `occurrence_at` is the slot generated by the
+/// rule and `starts_at` is when it actually takes place after any reschedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub occurrence_at: NaiveDateTime,
+    pub starts_at: NaiveDateTime,
+    pub rescheduled: bool,
+}
+
+impl RecurringScheduleDetail {
+    /// Occurrences starting within `[from, to)` with skip and reschedule exceptions applied.
+    pub fn occurrences(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<Occurrence> {
+        let mut occurrences: Vec<Occurrence> = self.schedule.rule()
+            .occurrences(self.schedule.starts_at, from, to)
+            .into_iter()
+            .filter(|at| !self.exceptions.iter().any(|e| e.occurrence_at == *at))
+            .map(|at| Occurrence { occurrence_at: at, starts_at: at, rescheduled: false })
+            .collect();
+
+        occurrences.extend(self.exceptions.iter()
+            .filter(|e| e.action == ExceptionAction::Reschedule.as_str())
+            .filter_map(|e| e.rescheduled_at.map(|starts_at| (e.occurrence_at, starts_at)))
+            .filter(|(_, starts_at)| *starts_at >= from && *starts_at < to)
+            .map(|(occurrence_at, starts_at)| Occurrence { occurrence_at, starts_at, rescheduled: true }));
+
+        occurrences.sort_by_key(|occurrence| occurrence.starts_at);
+        occurrences
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecurringSchedule {
+    pub workout_uuid: Uuid,
+    pub starts_at: NaiveDateTime,
+    pub rrule: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleException {
+    pub occurrence_at: NaiveDateTime,
+    pub action: ExceptionAction,
+    pub rescheduled_at: Option<NaiveDateTime>,
+}
+
+impl CreateScheduleException {
+    pub fn is_valid(&self) -> bool {
+        match self.action {
+            ExceptionAction::Skip => self.rescheduled_at.is_none(),
+            ExceptionAction::Reschedule => self.rescheduled_at.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OccurrenceRange {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
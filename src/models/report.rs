@@ -0,0 +1,123 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::models::{exercise::Exercise, workout_log::{WorkoutLog, WorkoutLogSet}};
+
+/// Inclusive `from` / exclusive `to` range over `performed_at` for progress reports.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ReportRange {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl ReportRange {
+    pub fn is_valid(&self) -> bool {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) => from < to,
+            _ => true,
+        }
+    }
+
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at < to)
+    }
+}
+
+/// Estimated one-rep max using the Epley formula. Sets without weight or reps have none.
+pub fn estimated_one_rep_max(reps: i32, weight: Option<f64>) -> Option<f64> {
+    let weight = weight.filter(|weight| *weight > 0.0)?;
+    match reps {
+        reps if reps <= 0 => None,
+        1 => Some(weight),
+        reps => Some(weight * (1.0 + reps as f64 / 30.0)),
+    }
+}
+
+/// The heaviest logged set of an exercise, ranked by estimated one-rep max.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BestSet {
+    pub log_uuid: Uuid,
+    pub performed_at: NaiveDateTime,
+    pub reps: i32,
+    pub weight: f64,
+    pub estimated_one_rep_max: f64,
+}
+
+/// Per-session figures used for the progress trend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionProgress {
+    pub log_uuid: Uuid,
+    pub performed_at: NaiveDateTime,
+    pub volume: f64,
+    pub estimated_one_rep_max: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExerciseProgress {
+    pub exercise: Exercise,
+    pub session_count: usize,
+    pub set_count: usize,
+    pub total_reps: i64,
+    pub total_volume: f64,
+    pub best_set: Option<BestSet>,
+    pub sessions: Vec<SessionProgress>,
+}
+
+impl ExerciseProgress {
+    /// Aggregates the logged sets of a single exercise. Volume is reps times weight, so sets
+    /// logged without a weight count towards reps but not volume.
+    pub fn from_sets(exercise: Exercise, sets: &[(WorkoutLog, WorkoutLogSet)]) -> Self {
+        let mut sessions: Vec<SessionProgress> = vec![];
+        let mut best_set: Option<BestSet> = None;
+        let mut total_reps = 0;
+        let mut total_volume = 0.0;
+
+        for (log, set) in sets {
+            let volume = set.reps as f64 * set.weight.unwrap_or(0.0);
+            let one_rep_max = estimated_one_rep_max(set.reps, set.weight);
+            total_reps += set.reps as i64;
+            total_volume += volume;
+
+            if let Some(one_rep_max) = one_rep_max {
+                if best_set.as_ref().is_none_or(|best| one_rep_max > best.estimated_one_rep_max) {
+                    best_set = Some(BestSet {
+                        log_uuid: log.uuid,
+                        performed_at: log.performed_at,
+                        reps: set.reps,
+                        weight: set.weight.unwrap_or_default(),
+                        estimated_one_rep_max: one_rep_max,
+                    });
+                }
+            }
+
+            match sessions.iter_mut().find(|session| session.log_uuid == log.uuid) {
+                Some(session) => {
+                    session.volume += volume;
+                    session.estimated_one_rep_max = match (session.estimated_one_rep_max, one_rep_max) {
+                        (Some(a), Some(b)) => Some(a.max(b)),
+                        (a, b) => a.or(b),
+                    };
+                },
+                None => sessions.push(SessionProgress {
+                    log_uuid: log.uuid,
+                    performed_at: log.performed_at,
+                    volume,
+                    estimated_one_rep_max: one_rep_max,
+                }),
+            }
+        }
+
+        sessions.sort_by_key(|session| session.performed_at);
+
+        Self {
+            exercise,
+            session_count: sessions.len(),
+            set_count: sets.len(),
+            total_reps,
+            total_volume,
+            best_set,
+            sessions,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleStatus {
+    Planned,
+    Completed,
+    Skipped,
+    Missed,
+}
+
+impl ScheduleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleStatus::Planned => "planned",
+            ScheduleStatus::Completed => "completed",
+            ScheduleStatus::Skipped => "skipped",
+            ScheduleStatus::Missed => "missed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::scheduled_workouts)]
+pub struct ScheduledWorkout {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub workout_id: i64,
+    pub scheduled_at: NaiveDateTime,
+    pub status: String,
+    pub notes: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::scheduled_workouts)]
+pub struct NewScheduledWorkout {
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub workout_id: i64,
+    pub scheduled_at: NaiveDateTime,
+    pub status: String,
+    pub notes: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ScheduledWorkout {
+    pub fn new(user_id: i64, workout_id: i64, scheduled_at: NaiveDateTime, notes: Option<String>) -> NewScheduledWorkout {
+        let now = chrono::Utc::now().naive_utc();
+        NewScheduledWorkout {
+            uuid: Uuid::new_v4(),
+            user_id,
+            workout_id,
+            scheduled_at,
+            status: ScheduleStatus::Planned.as_str().to_string(),
+            notes,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduledWorkout {
+    pub workout_uuid: Uuid,
+    pub scheduled_at: NaiveDateTime,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateScheduledWorkout {
+    pub scheduled_at: Option<NaiveDateTime>,
+    pub status: Option<ScheduleStatus>,
+    pub notes: Option<String>,
+}
+
+/// Inclusive `from` / exclusive `to` range and status filter for listing scheduled workouts.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ScheduleFilter {
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    pub status: Option<ScheduleStatus>,
+}
@@ -1,5 +1,8 @@
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime};
 use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{db::config::env_var, tokens};
 
 #[derive(Queryable, Selectable, Clone)]
 #[diesel(table_name = crate::schema::public::sessions)]
@@ -10,6 +13,15 @@ pub struct Session {
     pub csrf_token: String,
     pub expires_at: NaiveDateTime,
     pub created_at: NaiveDateTime,
+    pub uuid: Uuid,
+    pub last_seen_at: NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub remember_me: bool,
+    /// Hard limit that sliding expiry never extends past.
+    pub absolute_expires_at: NaiveDateTime,
+    /// The password checked out, but the second factor hasn't been given yet.
+    pub two_factor_pending: bool,
 }
 
 #[derive(Insertable, Clone)]
@@ -20,17 +32,128 @@ pub struct NewSession {
     pub csrf_token: String,
     pub expires_at: NaiveDateTime,
     pub created_at: NaiveDateTime,
+    pub uuid: Uuid,
+    pub last_seen_at: NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub remember_me: bool,
+    pub absolute_expires_at: NaiveDateTime,
+    pub two_factor_pending: bool,
+}
+
+/// How long a login may take to complete its second factor.
+pub const TWO_FACTOR_PENDING_LIFETIME: Duration = Duration::minutes(5);
+
+impl NewSession {
+    /// Holds the session back until the second factor is verified, which has to happen soon.
+    pub fn pending_two_factor(self) -> Self {
+        Self {
+            expires_at: self.expires_at.min(self.created_at + TWO_FACTOR_PENDING_LIFETIME),
+            two_factor_pending: true,
+            ..self
+        }
+    }
+}
+
+/// How long a session stays valid without requests (`idle`) and in total (`absolute`).
+#[derive(Debug, Clone, Copy)]
+pub struct SessionLifetime {
+    pub idle: Duration,
+    pub absolute: Duration,
+}
+
+/// Session timeouts, read from the environment by `from_env`.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionConfig {
+    pub standard: SessionLifetime,
+    /// Applies to logins with "remember me" checked.
+    pub remember_me: SessionLifetime,
+}
+
+impl SessionConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let minutes = |name: &str, default: Duration| {
+            env_var(name).map_or(default, Duration::minutes)
+        };
+
+        Self {
+            standard: SessionLifetime {
+                idle: minutes("SESSION_IDLE_TIMEOUT_MINS", defaults.standard.idle),
+                absolute: minutes("SESSION_ABSOLUTE_TIMEOUT_MINS", defaults.standard.absolute),
+            },
+            remember_me: SessionLifetime {
+                idle: minutes("SESSION_REMEMBER_ME_IDLE_TIMEOUT_MINS", defaults.remember_me.idle),
+                absolute: minutes("SESSION_REMEMBER_ME_ABSOLUTE_TIMEOUT_MINS", defaults.remember_me.absolute),
+            },
+        }
+    }
+
+    pub fn lifetime(&self, remember_me: bool) -> SessionLifetime {
+        if remember_me { self.remember_me } else { self.standard }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            standard: SessionLifetime {
+                idle: Duration::hours(24),
+                absolute: Duration::days(7),
+            },
+            remember_me: SessionLifetime {
+                idle: Duration::days(30),
+                absolute: Duration::days(90),
+            },
+        }
+    }
+}
+
+/// The device a session was started from, shown when listing sessions.
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
 }
 
 impl Session {
-    pub fn new(user_id: i64, session_id: String, csrf_token: String) -> NewSession {
+    pub fn new(user_id: i64, session_id: String, csrf_token: String, remember_me: bool, config: &SessionConfig) -> NewSession {
         let now = chrono::Utc::now().naive_utc();
+        let lifetime = config.lifetime(remember_me);
+        let absolute_expires_at = now + lifetime.absolute;
         NewSession {
             user_id,
             token: session_id,
             csrf_token,
-            expires_at: now + chrono::Duration::hours(24),
+            expires_at: absolute_expires_at.min(now + lifetime.idle),
             created_at: now,
+            uuid: Uuid::new_v4(),
+            last_seen_at: now,
+            user_agent: None,
+            ip_address: None,
+            remember_me,
+            absolute_expires_at,
+            two_factor_pending: false,
         }
     }
-} 
\ No newline at end of file
+
+    /// The expiry after activity at `now`: the idle timeout restarts, capped by the absolute limit.
+    pub fn sliding_expiry(&self, now: NaiveDateTime, config: &SessionConfig) -> NaiveDateTime {
+        self.absolute_expires_at.min(now + config.lifetime(self.remember_me).idle)
+    }
+
+    /// `last_seen_at` and the sliding expiry are refreshed at most once a minute so that not
+    /// every request writes.
+    pub fn needs_touch(&self, now: NaiveDateTime) -> bool {
+        now - self.last_seen_at >= Duration::minutes(1)
+    }
+
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        now < self.expires_at
+    }
+
+    /// Whether `csrf_token` is the session's current CSRF token and the session is still active.
+    pub fn accepts_csrf(&self, csrf_token: &str, now: NaiveDateTime) -> bool {
+        self.is_active(now) && tokens::constant_time_eq(&self.csrf_token, csrf_token)
+    }
+}
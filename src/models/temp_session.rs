@@ -1,6 +1,8 @@
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 
+use crate::tokens;
+
 #[derive(Queryable, Selectable, Clone)]
 #[diesel(table_name = crate::schema::public::temp_sessions)]
 pub struct TempSession {
@@ -30,4 +32,12 @@ impl TempSession {
             expires_at: now + chrono::Duration::minutes(5),
         }
     }
-} 
\ No newline at end of file
+
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        now < self.expires_at
+    }
+
+    pub fn accepts_csrf(&self, csrf_token: &str, now: NaiveDateTime) -> bool {
+        self.is_active(now) && tokens::constant_time_eq(&self.csrf_token, csrf_token)
+    }
+}
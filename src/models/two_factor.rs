@@ -0,0 +1,85 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Number of recovery codes handed out when two-factor authentication is enabled.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(Debug, Queryable, Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::totp_credentials)]
+pub struct TotpCredential {
+    pub user_id: i64,
+    /// Base32 encoded shared secret.
+    pub secret: String,
+    /// Set once the user proved their authenticator app works; until then logins ignore it.
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+impl TotpCredential {
+    pub fn new(user_id: i64, secret: String, now: NaiveDateTime) -> Self {
+        Self {
+            user_id,
+            secret,
+            confirmed_at: None,
+            last_used_step: None,
+            created_at: now,
+        }
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
+
+#[derive(Debug, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::recovery_codes)]
+pub struct RecoveryCode {
+    pub id: i64,
+    pub user_id: i64,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::recovery_codes)]
+pub struct NewRecoveryCode {
+    pub user_id: i64,
+    pub code_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl RecoveryCode {
+    pub fn new(user_id: i64, code_hash: String, now: NaiveDateTime) -> NewRecoveryCode {
+        NewRecoveryCode {
+            user_id,
+            code_hash,
+            created_at: now,
+        }
+    }
+}
+
+/// The second login step: a code from the authenticator app, or one of the recovery codes.
+#[derive(Debug, Clone)]
+pub enum TwoFactorCode {
+    Totp(String),
+    Recovery(String),
+}
+
+/// A random recovery code like `k7m2p-x9qrt`, avoiding characters that are easily confused.
+pub fn generate_recovery_code() -> String {
+    let characters = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rand::random::<usize>() % RECOVERY_CODE_ALPHABET.len()] as char)
+        .collect::<String>();
+    format!("{}-{}", &characters[..5], &characters[5..])
+}
+
+/// Recovery codes are compared without the dash, whitespace or case users may type them with.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
@@ -11,6 +11,7 @@ pub struct User {
     pub password_hash: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub email_verified_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Clone)]
@@ -24,6 +25,10 @@ pub struct NewUser {
 }
 
 impl User {
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
     pub fn new(email: String, password_hash: String) -> NewUser {
         let now = chrono::Utc::now().naive_utc();
         NewUser {
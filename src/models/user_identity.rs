@@ -0,0 +1,41 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Links an account at an external identity provider to a user.
+#[derive(Debug, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::user_identities)]
+pub struct UserIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub issuer: String,
+    /// The provider's stable id for the account (`sub`), unlike the email address.
+    pub subject: String,
+    /// Email address the provider reported at the last login.
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::user_identities)]
+pub struct NewUserIdentity {
+    pub user_id: i64,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: NaiveDateTime,
+}
+
+impl UserIdentity {
+    pub fn new(user_id: i64, issuer: String, subject: String, email: Option<String>, now: NaiveDateTime) -> NewUserIdentity {
+        NewUserIdentity {
+            user_id,
+            issuer,
+            subject,
+            email,
+            created_at: now,
+            last_login_at: now,
+        }
+    }
+}
@@ -2,17 +2,66 @@ use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone)]
 #[diesel(table_name = crate::schema::public::workout_exercises)]
 pub struct WorkoutExercise {
     pub workout_id: i64,
     pub exercise_id: i64,
     pub user_id: i64,
     pub order: i32,
+    pub sets: Option<i32>,
+    pub reps: Option<i32>,
+    pub weight: Option<f64>,
+    pub rest_seconds: Option<i32>,
+}
+
+impl WorkoutExercise {
+    pub fn new(workout_id: i64, exercise_id: i64, user_id: i64, order: i32, prescription: Prescription) -> Self {
+        Self {
+            workout_id,
+            exercise_id,
+            user_id,
+            order,
+            sets: prescription.sets,
+            reps: prescription.reps,
+            weight: prescription.weight,
+            rest_seconds: prescription.rest_seconds,
+        }
+    }
+
+    pub fn prescription(&self) -> Prescription {
+        Prescription {
+            sets: self.sets,
+            reps: self.reps,
+            weight: self.weight,
+            rest_seconds: self.rest_seconds,
+        }
+    }
+}
+
+/// Target sets, reps, weight and rest for an exercise within a workout.
+/// Every field is optional so that e.g. a stretch can omit weight and reps.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Prescription {
+    pub sets: Option<i32>,
+    pub reps: Option<i32>,
+    pub weight: Option<f64>,
+    pub rest_seconds: Option<i32>,
+}
+
+impl Prescription {
+    pub fn is_valid(&self) -> bool {
+        self.sets.is_none_or(|sets| sets > 0)
+            && self.reps.is_none_or(|reps| reps > 0)
+            && self.weight.is_none_or(|weight| weight.is_finite() && weight >= 0.0)
+            && self.rest_seconds.is_none_or(|rest| rest >= 0)
+    }
 }
 
 #[derive(Deserialize)]
 pub struct AddExerciseRequest {
     pub exercise_uuid: Uuid,
     pub order: i32,
-}
\ No newline at end of file
+    #[serde(flatten)]
+    pub prescription: Prescription,
+}
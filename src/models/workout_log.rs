@@ -0,0 +1,127 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{exercise::Exercise, personal_record::PersonalRecord};
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::workout_logs)]
+pub struct WorkoutLog {
+    pub id: i64,
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub workout_id: i64,
+    pub performed_at: NaiveDateTime,
+    pub notes: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::workout_logs)]
+pub struct NewWorkoutLog {
+    pub uuid: Uuid,
+    pub user_id: i64,
+    pub workout_id: i64,
+    pub performed_at: NaiveDateTime,
+    pub notes: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl WorkoutLog {
+    pub fn new(user_id: i64, workout_id: i64, performed_at: Option<NaiveDateTime>, notes: Option<String>) -> NewWorkoutLog {
+        let now = chrono::Utc::now().naive_utc();
+        NewWorkoutLog {
+            uuid: Uuid::new_v4(),
+            user_id,
+            workout_id,
+            performed_at: performed_at.unwrap_or(now),
+            notes,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
+#[diesel(table_name = crate::schema::public::workout_log_sets)]
+pub struct WorkoutLogSet {
+    pub id: i64,
+    pub workout_log_id: i64,
+    pub exercise_id: i64,
+    pub user_id: i64,
+    pub set_number: i32,
+    pub reps: i32,
+    pub weight: Option<f64>,
+    pub rpe: Option<f64>,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = crate::schema::public::workout_log_sets)]
+pub struct NewWorkoutLogSet {
+    pub workout_log_id: i64,
+    pub exercise_id: i64,
+    pub user_id: i64,
+    pub set_number: i32,
+    pub reps: i32,
+    pub weight: Option<f64>,
+    pub rpe: Option<f64>,
+}
+
+impl WorkoutLogSet {
+    pub fn new(workout_log_id: i64, exercise_id: i64, user_id: i64, set: &CreateWorkoutLogSet) -> NewWorkoutLogSet {
+        NewWorkoutLogSet {
+            workout_log_id,
+            exercise_id,
+            user_id,
+            set_number: set.set_number,
+            reps: set.reps,
+            weight: set.weight,
+            rpe: set.rpe,
+        }
+    }
+}
+
+/// A logged workout together with the sets performed in it and the personal records they set,
+/// each paired with its exercise.
+#[derive(Debug, Clone)]
+pub struct WorkoutLogDetail {
+    pub log: WorkoutLog,
+    pub sets: Vec<(Exercise, WorkoutLogSet)>,
+    pub personal_records: Vec<(Exercise, PersonalRecord)>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWorkoutLog {
+    pub performed_at: Option<NaiveDateTime>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub sets: Vec<CreateWorkoutLogSet>,
+}
+
+impl CreateWorkoutLog {
+    pub fn is_valid(&self) -> bool {
+        let mut keys = std::collections::HashSet::new();
+        self.sets.iter().all(|set| set.is_valid() && keys.insert((set.exercise_uuid, set.set_number)))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CreateWorkoutLogSet {
+    pub exercise_uuid: Uuid,
+    pub set_number: i32,
+    pub reps: i32,
+    pub weight: Option<f64>,
+    pub rpe: Option<f64>,
+}
+
+impl CreateWorkoutLogSet {
+    pub fn is_valid(&self) -> bool {
+        self.set_number > 0
+            && self.reps >= 0
+            && self.weight.is_none_or(|weight| weight.is_finite() && weight >= 0.0)
+            && self.rpe.is_none_or(|rpe| (1.0..=10.0).contains(&rpe))
+    }
+}
@@ -0,0 +1,304 @@
+//! Sign-in with an OpenID Connect provider using the authorization code flow with PKCE.
+
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
+use std::{
+    env, fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use ureq::{http::Response, Agent, Body};
+use url::{form_urlencoded, Url};
+use crate::{db::config::env_var, tokens};
+
+/// Allowance for clocks that run slightly apart when checking the ID token's expiry.
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// The discovery document and token responses are small JSON bodies; anything larger is refused.
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+/// Provider settings, read from the environment by `from_env`.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// The provider's discovery document is read from `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    /// Confidential clients authenticate at the token endpoint; public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// Where the provider sends the browser back to, usually a frontend page that posts the
+    /// `code` and `state` it receives to `/auth/oidc/callback`.
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub timeout: Duration,
+}
+
+impl OidcConfig {
+    /// `None` unless `OIDC_ISSUER` is set, which leaves single sign-on turned off.
+    pub fn from_env(app_url: &str) -> Option<Self> {
+        let issuer = env::var("OIDC_ISSUER").ok()?;
+        Some(Self {
+            issuer,
+            client_id: env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set along with OIDC_ISSUER"),
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_uri: env::var("OIDC_REDIRECT_URI")
+                .unwrap_or_else(|_| format!("{}/oidc/callback", app_url.trim_end_matches('/'))),
+            scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| "openid email profile".to_string()),
+            timeout: Duration::from_secs(env_var("OIDC_TIMEOUT_SECS").unwrap_or(10)),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum OidcError {
+    InvalidConfig(String),
+    /// The provider couldn't be reached or refused the request.
+    Request(String),
+    /// The provider's answer was malformed or failed validation.
+    InvalidResponse(String),
+}
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OidcError::InvalidConfig(reason) => write!(f, "invalid OIDC configuration: {}", reason),
+            OidcError::Request(reason) => write!(f, "request to the identity provider failed: {}", reason),
+            OidcError::InvalidResponse(reason) => write!(f, "invalid response from the identity provider: {}", reason),
+        }
+    }
+}
+
+/// Who the provider says signed in, taken from a validated ID token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityClaims {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+}
+
+pub trait IdentityProvider: Send + Sync + 'static {
+    /// The provider's sign-in page for a new login, which the browser is sent to.
+    fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, OidcError>;
+    /// Redeems the authorization code from the callback and returns the claims of the ID token,
+    /// which has to carry `nonce`.
+    fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdentityClaims, OidcError>;
+}
+
+/// A random PKCE code verifier (RFC 7636), 256 bits in base64url.
+pub fn generate_code_verifier() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+}
+
+/// The `S256` code challenge sent along with the authorization request.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    token_endpoint_auth_methods_supported: Option<Vec<String>>,
+}
+
+impl ProviderMetadata {
+    /// HTTP Basic is the default client authentication when the provider doesn't list any.
+    fn supports_basic_auth(&self) -> bool {
+        self.token_endpoint_auth_methods_supported
+            .as_ref()
+            .is_none_or(|methods| methods.iter().any(|method| method == "client_secret_basic"))
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    nonce: Option<String>,
+    azp: Option<String>,
+    email: Option<String>,
+    /// Some providers send the flag as a string.
+    email_verified: Option<serde_json::Value>,
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    agent: Agent,
+    /// Discovered on first use; a failed discovery is retried by the next login.
+    metadata: Mutex<Option<Arc<ProviderMetadata>>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Result<Self, OidcError> {
+        check_url(&config.issuer).map_err(OidcError::InvalidConfig)?;
+        // Error statuses are read like any other answer, so their body can be reported
+        let agent = Agent::config_builder()
+            .timeout_global(Some(config.timeout))
+            .http_status_as_error(false)
+            .build()
+            .new_agent();
+
+        Ok(Self {
+            config,
+            agent,
+            metadata: Mutex::new(None),
+        })
+    }
+
+    fn metadata(&self) -> Result<Arc<ProviderMetadata>, OidcError> {
+        if let Some(metadata) = self.metadata.lock().unwrap().as_ref() {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+        let response = self.agent.get(&url).header("Accept", "application/json").call();
+        let metadata = read_json::<ProviderMetadata>(&url, response)?;
+        // The document has to be about the configured issuer (OpenID Connect Discovery 1.0, 4.3)
+        if metadata.issuer != self.config.issuer {
+            return Err(OidcError::InvalidResponse(format!("discovery document is for issuer {}", metadata.issuer)));
+        }
+        check_url(&metadata.authorization_endpoint)
+            .and_then(|_| check_url(&metadata.token_endpoint))
+            .map_err(OidcError::InvalidResponse)?;
+
+        let metadata = Arc::new(metadata);
+        *self.metadata.lock().unwrap() = Some(metadata.clone());
+        Ok(metadata)
+    }
+}
+
+impl IdentityProvider for OidcClient {
+    fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, OidcError> {
+        let metadata = self.metadata()?;
+        let mut url = Url::parse(&metadata.authorization_endpoint).map_err(|err| OidcError::InvalidResponse(err.to_string()))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdentityClaims, OidcError> {
+        let metadata = self.metadata()?;
+        let mut form = form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("code_verifier", code_verifier)
+            .append_pair("client_id", &self.config.client_id);
+
+        let mut request = self.agent.post(&metadata.token_endpoint)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/x-www-form-urlencoded");
+        match &self.config.client_secret {
+            Some(secret) if metadata.supports_basic_auth() => {
+                // Both parts are form encoded first (RFC 6749, 2.3.1)
+                let credentials = format!("{}:{}", form_encode(&self.config.client_id), form_encode(secret));
+                request = request.header("Authorization", format!("Basic {}", STANDARD.encode(credentials)));
+            },
+            Some(secret) => {
+                form.append_pair("client_secret", secret);
+            },
+            None => {},
+        }
+
+        let response = read_json::<TokenResponse>(&metadata.token_endpoint, request.send(form.finish()))?;
+        validate_id_token(&response.id_token, &metadata.issuer, &self.config.client_id, nonce, chrono::Utc::now().timestamp())
+    }
+}
+
+/// Checks the claims of an ID token received straight from the token endpoint. Its signature
+/// isn't checked: the TLS connection to the provider already vouches for the token, which
+/// OpenID Connect Core 1.0, 3.1.3.7 allows in place of verifying the signature.
+fn validate_id_token(id_token: &str, issuer: &str, client_id: &str, nonce: &str, now: i64) -> Result<IdentityClaims, OidcError> {
+    let invalid = |reason: &str| OidcError::InvalidResponse(reason.to_string());
+    let parts = id_token.split('.').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return Err(invalid("ID token isn't a JWT"));
+    }
+    let claims = URL_SAFE_NO_PAD
+        .decode(parts[1].trim_end_matches('='))
+        .ok()
+        .and_then(|payload| serde_json::from_slice::<IdTokenClaims>(&payload).ok())
+        .ok_or_else(|| invalid("ID token has malformed claims"))?;
+
+    if claims.iss != issuer {
+        return Err(invalid("ID token is from another issuer"));
+    }
+    let audiences = match &claims.aud {
+        Audience::One(audience) => vec![audience.as_str()],
+        Audience::Many(audiences) => audiences.iter().map(String::as_str).collect(),
+    };
+    if !audiences.contains(&client_id) || (audiences.len() > 1 && claims.azp.as_deref() != Some(client_id)) {
+        return Err(invalid("ID token is for another client"));
+    }
+    if claims.exp + CLOCK_SKEW_SECONDS < now {
+        return Err(invalid("ID token has expired"));
+    }
+    if !claims.nonce.as_deref().is_some_and(|claimed| tokens::constant_time_eq(claimed, nonce)) {
+        return Err(invalid("ID token belongs to another login"));
+    }
+
+    Ok(IdentityClaims {
+        issuer: claims.iss,
+        subject: claims.sub,
+        email: claims.email,
+        email_verified: match claims.email_verified {
+            Some(serde_json::Value::Bool(verified)) => verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        },
+    })
+}
+
+/// Provider endpoints have to use TLS, except on this machine so a local mock issuer can stand in.
+fn check_url(url: &str) -> Result<(), String> {
+    let parsed = Url::parse(url).map_err(|err| format!("{}: {}", url, err))?;
+    match (parsed.scheme(), parsed.host()) {
+        ("https", Some(_)) => Ok(()),
+        ("http", Some(url::Host::Domain("localhost"))) => Ok(()),
+        ("http", Some(url::Host::Ipv4(ip))) if ip.is_loopback() => Ok(()),
+        ("http", Some(url::Host::Ipv6(ip))) if ip.is_loopback() => Ok(()),
+        _ => Err(format!("{} must be an https URL", url)),
+    }
+}
+
+fn form_encode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// The JSON body of a successful answer from `url`.
+fn read_json<T: DeserializeOwned>(url: &str, response: Result<Response<Body>, ureq::Error>) -> Result<T, OidcError> {
+    let request_error = |err: ureq::Error| OidcError::Request(format!("{}: {}", url, err));
+    let mut response = response.map_err(request_error)?;
+    let body = response.body_mut()
+        .with_config()
+        .limit(MAX_RESPONSE_BYTES)
+        .read_to_vec()
+        .map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(OidcError::Request(format!("{} answered {}: {}", url, response.status().as_u16(), String::from_utf8_lossy(&body))));
+    }
+    serde_json::from_slice(&body).map_err(|err| OidcError::InvalidResponse(format!("{}: {}", url, err)))
+}
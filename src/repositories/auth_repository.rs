@@ -1,5 +1,7 @@
-use diesel::prelude::*;
-use crate::{db, models::user::User, models::session::Session, models::temp_session::TempSession};
+use diesel::{prelude::*, r2d2::PoolError};
+use std::fmt;
+use uuid::Uuid;
+use crate::{db::config::DbPool, models::login_throttle::{account_key, LoginThrottle, ThrottleScope}, models::email_token::{resend_retry_after, EmailToken, EmailTokenPurpose, RESEND_LIMIT, RESEND_WINDOW}, models::api_token::{generate_api_token, ApiToken}, models::oidc_login_attempt::OidcLoginAttempt, models::user_identity::UserIdentity, oidc::{generate_code_verifier, IdentityClaims}, models::two_factor::{generate_recovery_code, normalize_recovery_code, RecoveryCode, TotpCredential, TwoFactorCode, RECOVERY_CODE_COUNT}, models::user::User, models::session::{NewSession, Session, SessionClient, SessionConfig}, models::temp_session::TempSession, tokens, totp};
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString, PasswordHash},
     Argon2, PasswordHasher, PasswordVerifier,
@@ -9,10 +11,48 @@ use argon2::{
 pub enum AuthError {
     DuplicateEmail,
     DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
     InvalidCredentials,
     InvalidSession,
     InvalidCsrf,
+    InvalidPassword,
+    /// A single-use token that is unknown, expired or already used.
+    InvalidToken,
+    /// The email address is verified already.
+    AlreadyVerified,
+    /// The session waits for the second factor of its login.
+    TwoFactorRequired,
+    InvalidTwoFactorCode,
+    TwoFactorAlreadyEnabled,
+    TwoFactorNotEnabled,
+    /// The identity provider didn't share an email address to create the account with.
+    MissingEmail,
     NotFound,
+    /// Too many failed logins for the account or client; retry after the given time.
+    TooManyAttempts(chrono::Duration),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::DuplicateEmail => write!(f, "email address is taken"),
+            AuthError::DatabaseError(err) => write!(f, "database error: {}", err),
+            AuthError::ConnectionUnavailable => write!(f, "no database connection available"),
+            AuthError::InvalidCredentials => write!(f, "invalid credentials"),
+            AuthError::InvalidSession => write!(f, "invalid session"),
+            AuthError::InvalidCsrf => write!(f, "invalid CSRF token"),
+            AuthError::InvalidPassword => write!(f, "invalid password"),
+            AuthError::InvalidToken => write!(f, "invalid or expired token"),
+            AuthError::AlreadyVerified => write!(f, "email address is verified already"),
+            AuthError::TwoFactorRequired => write!(f, "second factor required"),
+            AuthError::InvalidTwoFactorCode => write!(f, "invalid two-factor code"),
+            AuthError::TwoFactorAlreadyEnabled => write!(f, "two-factor authentication is enabled already"),
+            AuthError::TwoFactorNotEnabled => write!(f, "two-factor authentication is not enabled"),
+            AuthError::MissingEmail => write!(f, "identity provider shared no email address"),
+            AuthError::NotFound => write!(f, "not found"),
+            AuthError::TooManyAttempts(retry_after) => write!(f, "too many attempts, retry in {} seconds", retry_after.num_seconds()),
+        }
+    }
 }
 
 impl From<diesel::result::Error> for AuthError {
@@ -28,29 +68,289 @@ impl From<diesel::result::Error> for AuthError {
     }
 }
 
-pub trait AuthRepository {
+impl From<PoolError> for AuthError {
+    fn from(_: PoolError) -> AuthError {
+        AuthError::ConnectionUnavailable
+    }
+}
+
+pub trait AuthRepository: Send + Sync + 'static {
     fn create_temp_session(&self, csrf_token: String) -> Result<TempSession, AuthError>;
-    fn create_session(&self, user_id: i64, session_id: String, csrf_token: String) -> Result<Session, AuthError>;
+    /// Starts an authenticated session under a freshly generated token. The pre-login temp session
+    /// or earlier session identified by `previous_session_id` is discarded, so a session id
+    /// planted before authentication is never promoted.
+    fn create_session(&self, user_id: i64, previous_session_id: Option<&str>, csrf_token: String, client: SessionClient, remember_me: bool) -> Result<Session, AuthError>;
+    /// Checks `csrf_token` against the authenticated session when `session_id` belongs to one,
+    /// otherwise against the pre-login temp session.
     fn validate_csrf(&self, session_id: &str, csrf_token: &str) -> Result<(), AuthError>;
-    fn verify_credentials(&self, email: String, password: String) -> Result<User, AuthError>;
+    fn session_csrf_token(&self, session_token: &str) -> Result<String, AuthError>;
+    /// Replaces the CSRF token of an active session.
+    fn rotate_csrf_token(&self, session_token: &str, csrf_token: String) -> Result<(), AuthError>;
+    /// Checks a login attempt, counting failures per account and per `ip_address` and
+    /// refusing attempts while either is locked out.
+    fn verify_credentials(&self, email: String, password: String, ip_address: Option<String>) -> Result<User, AuthError>;
     fn create_user(&self, email: String, password: String) -> Result<User, AuthError>;
+    /// Returns the user of an active session and slides its expiry forward.
     fn validate_session(&self, session_token: &str) -> Result<i64, AuthError>;
     fn invalidate_session(&self, session_token: &str) -> Result<(), AuthError>;
     fn delete_user(&self, session_token: &str) -> Result<(), AuthError>;
+    /// Active sessions of the user owning `session_token`, most recently seen first.
+    fn list_sessions(&self, session_token: &str) -> Result<Vec<Session>, AuthError>;
+    fn revoke_session(&self, session_token: &str, session_uuid: Uuid) -> Result<(), AuthError>;
+    /// Ends all sessions of the user except `session_token` itself and returns how many were ended.
+    fn revoke_other_sessions(&self, session_token: &str) -> Result<usize, AuthError>;
+    /// Replaces the password of the session's user once `current_password` checks out, and ends
+    /// all of the user's other sessions and their API tokens.
+    fn change_password(&self, session_token: &str, current_password: String, new_password: String) -> Result<(), AuthError>;
+    /// Issues a password reset token for the account registered under `email`, if there is one
+    /// and it wasn't sent too many reset emails lately. Returns the user together with the plain
+    /// token, which is never persisted.
+    fn create_password_reset(&self, email: String) -> Result<Option<(User, String)>, AuthError>;
+    /// Sets a new password using a reset token, which is used up along with any other pending
+    /// reset tokens of the user. All of the user's sessions and API tokens end.
+    fn reset_password(&self, token: &str, new_password: String) -> Result<(), AuthError>;
+    /// Issues an email verification token for the user and returns it with the user. Fails with
+    /// `TooManyAttempts` while the resend limits apply.
+    fn create_email_verification(&self, user_id: i64) -> Result<(User, String), AuthError>;
+    /// Marks the email address of the token's user as verified, using up all of their
+    /// verification tokens.
+    fn verify_email(&self, token: &str) -> Result<User, AuthError>;
+    fn email_verified(&self, user_id: i64) -> Result<bool, AuthError>;
+    /// Starts TOTP enrollment for the session's user with a new secret, replacing an unconfirmed
+    /// one, and returns the user with the secret.
+    fn begin_totp_enrollment(&self, session_token: &str) -> Result<(User, String), AuthError>;
+    /// Turns two-factor authentication on once `code` matches the enrolled secret. Returns new
+    /// recovery codes, which are only stored hashed.
+    fn confirm_totp_enrollment(&self, session_token: &str, code: &str) -> Result<Vec<String>, AuthError>;
+    /// Completes a login waiting for its second factor. The session gets a new token and its
+    /// regular lifetime. Wrong codes are throttled per account.
+    fn verify_two_factor(&self, session_token: &str, code: TwoFactorCode) -> Result<(User, Session), AuthError>;
+    /// Turns two-factor authentication off once `password` checks out, dropping the recovery codes.
+    fn disable_totp(&self, session_token: &str, password: String) -> Result<(), AuthError>;
+    /// Creates a personal access token for the session's user and returns it with the plain
+    /// token, which is never persisted.
+    fn create_api_token(&self, session_token: &str, name: String, scopes: Vec<String>, expires_at: Option<chrono::NaiveDateTime>) -> Result<(ApiToken, String), AuthError>;
+    fn list_api_tokens(&self, session_token: &str) -> Result<Vec<ApiToken>, AuthError>;
+    fn revoke_api_token(&self, session_token: &str, token_uuid: Uuid) -> Result<(), AuthError>;
+    /// Looks up an unexpired personal access token and records that it was used. Fails with
+    /// `InvalidToken` otherwise.
+    fn authenticate_api_token(&self, token: &str) -> Result<ApiToken, AuthError>;
+    /// Starts a sign-in with the identity provider: stores a new nonce and PKCE code verifier
+    /// and returns them with the plain `state`, under which `take_oidc_login` finds them again.
+    fn create_oidc_login(&self) -> Result<(String, OidcLoginAttempt), AuthError>;
+    /// Removes and returns the unexpired sign-in started under `state`, or fails with `InvalidToken`.
+    fn take_oidc_login(&self, state: &str) -> Result<OidcLoginAttempt, AuthError>;
+    /// Returns the user linked to the external identity. An unknown identity is linked to the
+    /// account with its email address if the provider verified that address, and otherwise
+    /// gets a new account; an unverified address that is taken fails with `DuplicateEmail`.
+    /// Linking an account whose address was never verified resets its password and ends its
+    /// sessions, API tokens, two-factor setup and other linked identities.
+    fn sign_in_with_identity(&self, claims: IdentityClaims) -> Result<User, AuthError>;
+    /// Issues a login link token for the account registered under `email`, if there is one and
+    /// it wasn't sent too many links lately. Returns the user together with the plain token,
+    /// which is never persisted.
+    fn create_login_link(&self, email: String) -> Result<Option<(User, String)>, AuthError>;
+    /// Redeems a login link token, using up all of the user's pending links, and returns the user
+    /// to start a session for. Following the link proves access to the mailbox, so the email
+    /// address counts as verified.
+    fn redeem_login_link(&self, token: &str) -> Result<User, AuthError>;
+}
+
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+fn hash_matches(hash: &str, secret: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|parsed_hash| Argon2::default().verify_password(secret.as_bytes(), &parsed_hash).is_ok())
 }
 
-pub struct PgAuthRepository;
+fn password_matches(user: &User, password: &str) -> bool {
+    hash_matches(&user.password_hash, password)
+}
+
+pub struct PgAuthRepository {
+    pool: DbPool,
+    session_config: SessionConfig,
+}
 
 impl PgAuthRepository {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(pool: DbPool, session_config: SessionConfig) -> Self {
+        Self { pool, session_config }
+    }
+
+    fn find_active_session(conn: &mut PgConnection, session_token: &str, now: chrono::NaiveDateTime) -> Result<Session, AuthError> {
+        use crate::schema::public::sessions;
+
+        sessions::table
+            .filter(sessions::token.eq(session_token))
+            .filter(sessions::expires_at.gt(now))
+            .filter(sessions::two_factor_pending.eq(false))
+            .first::<Session>(conn)
+            .map_err(|_| AuthError::InvalidSession)
+    }
+
+    /// Checks the password of a user who is already logged in, under the same account throttle
+    /// as logins so that a stolen session can't guess it faster.
+    fn reauthenticate(conn: &mut PgConnection, user: &User, password: &str, now: chrono::NaiveDateTime) -> Result<(), AuthError> {
+        let account = [(ThrottleScope::Account, account_key(&user.email))];
+        if let Some(retry_after) = Self::find_lockout(conn, &account, now)? {
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
+        if !password_matches(user, password) {
+            Self::record_failures(conn, &account, now)?;
+            return Err(AuthError::InvalidCredentials);
+        }
+        Ok(())
+    }
+
+    fn has_two_factor(conn: &mut PgConnection, user_id: i64) -> Result<bool, AuthError> {
+        use crate::schema::public::totp_credentials;
+
+        diesel::select(diesel::dsl::exists(
+            totp_credentials::table
+                .filter(totp_credentials::user_id.eq(user_id))
+                .filter(totp_credentials::confirmed_at.is_not_null()),
+        ))
+        .get_result::<bool>(conn)
+        .map_err(AuthError::from)
+    }
+
+    /// The longest remaining lockout among `keys`.
+    fn find_lockout(conn: &mut PgConnection, keys: &[(ThrottleScope, String)], now: chrono::NaiveDateTime) -> Result<Option<chrono::Duration>, AuthError> {
+        use crate::schema::public::login_throttles;
+
+        let mut lockout = None;
+        for (scope, key) in keys {
+            let throttle = login_throttles::table
+                .find((scope.as_str(), key))
+                .first::<LoginThrottle>(conn)
+                .optional()
+                .map_err(AuthError::from)?;
+            lockout = lockout.max(throttle.and_then(|throttle| throttle.retry_after(now)));
+        }
+        Ok(lockout)
+    }
+
+    fn record_failures(conn: &mut PgConnection, keys: &[(ThrottleScope, String)], now: chrono::NaiveDateTime) -> Result<(), AuthError> {
+        use crate::schema::public::login_throttles;
+
+        // Row locks keep the counters exact when several processes see failures at once
+        conn.transaction(|conn| {
+            for (scope, key) in keys {
+                diesel::insert_into(login_throttles::table)
+                    .values(&LoginThrottle::new(*scope, key.clone(), now))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .map_err(AuthError::from)?;
+
+                let mut throttle = login_throttles::table
+                    .find((scope.as_str(), key))
+                    .for_update()
+                    .first::<LoginThrottle>(conn)
+                    .map_err(AuthError::from)?;
+                throttle.record_failure(&scope.policy(), now);
+
+                diesel::update(login_throttles::table.find((scope.as_str(), key)))
+                    .set((
+                        login_throttles::failures.eq(throttle.failures),
+                        login_throttles::last_failure_at.eq(throttle.last_failure_at),
+                        login_throttles::locked_until.eq(throttle.locked_until),
+                    ))
+                    .execute(conn)
+                    .map_err(AuthError::from)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Issues a token of `purpose` to the user, or fails with `TooManyAttempts` while the resend
+    /// limits apply. Callers lock the user row first, so concurrent requests can't slip past them.
+    fn issue_email_token(conn: &mut PgConnection, user_id: i64, purpose: EmailTokenPurpose, now: chrono::NaiveDateTime) -> Result<String, AuthError> {
+        use crate::schema::public::email_tokens;
+
+        let sent_at = email_tokens::table
+            .filter(email_tokens::user_id.eq(user_id))
+            .filter(email_tokens::purpose.eq(purpose.as_str()))
+            .filter(email_tokens::created_at.gt(now - RESEND_WINDOW))
+            .order(email_tokens::created_at.desc())
+            .limit(RESEND_LIMIT as i64)
+            .select(email_tokens::created_at)
+            .load::<chrono::NaiveDateTime>(conn)
+            .map_err(AuthError::from)?;
+        if let Some(retry_after) = resend_retry_after(&sent_at, now) {
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
+
+        let token = tokens::generate_token();
+        diesel::insert_into(email_tokens::table)
+            .values(EmailToken::new(user_id, purpose, &token, now))
+            .execute(conn)
+            .map_err(AuthError::from)?;
+        Ok(token)
+    }
+
+    /// Issues a token of `purpose` to the account registered under `email`. Unknown addresses
+    /// and requests beyond the resend limits get `None` instead of an error, as an error would
+    /// tell that the account exists.
+    fn issue_email_token_quietly(conn: &mut PgConnection, email: String, purpose: EmailTokenPurpose, now: chrono::NaiveDateTime) -> Result<Option<(User, String)>, AuthError> {
+        use crate::schema::public::users;
+
+        conn.transaction(|conn| {
+            let Some(user) = users::table
+                .filter(users::email.eq(email))
+                .for_update()
+                .first::<User>(conn)
+                .optional()
+                .map_err(AuthError::from)? else {
+                return Ok(None);
+            };
+
+            match Self::issue_email_token(conn, user.id, purpose, now) {
+                Ok(token) => Ok(Some((user, token))),
+                Err(AuthError::TooManyAttempts(_)) => Ok(None),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Redeems an unexpired token of `purpose`, using it up along with the user's other pending
+    /// tokens of that purpose, and returns the user it was issued to.
+    fn redeem_email_token(conn: &mut PgConnection, token: &str, purpose: EmailTokenPurpose, now: chrono::NaiveDateTime) -> Result<i64, AuthError> {
+        use crate::schema::public::email_tokens;
+
+        // Claiming the token in a single update keeps two concurrent requests from both using it
+        let user_id = diesel::update(email_tokens::table)
+            .filter(email_tokens::token_hash.eq(tokens::hash_token(token)))
+            .filter(email_tokens::purpose.eq(purpose.as_str()))
+            .filter(email_tokens::used_at.is_null())
+            .filter(email_tokens::expires_at.gt(now))
+            .set(email_tokens::used_at.eq(now))
+            .returning(email_tokens::user_id)
+            .get_result::<i64>(conn)
+            .optional()
+            .map_err(AuthError::from)?
+            .ok_or(AuthError::InvalidToken)?;
+
+        diesel::update(email_tokens::table)
+            .filter(email_tokens::user_id.eq(user_id))
+            .filter(email_tokens::purpose.eq(purpose.as_str()))
+            .filter(email_tokens::used_at.is_null())
+            .set(email_tokens::used_at.eq(now))
+            .execute(conn)
+            .map_err(AuthError::from)?;
+        Ok(user_id)
     }
 }
 
 impl AuthRepository for PgAuthRepository {
     fn create_temp_session(&self, csrf_token: String) -> Result<TempSession, AuthError> {
         use crate::schema::public::temp_sessions;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let session_id = uuid::Uuid::new_v4().to_string();
         let new_temp_session = TempSession::new(session_id.clone(), csrf_token);
@@ -66,29 +366,58 @@ impl AuthRepository for PgAuthRepository {
             .map_err(AuthError::from)
     }
 
-    fn create_session(&self, user_id: i64, session_id: String, csrf_token: String) -> Result<Session, AuthError> {
-        use crate::schema::public::sessions;
-        let mut conn = db::config::establish_connection();
+    fn create_session(&self, user_id: i64, previous_session_id: Option<&str>, csrf_token: String, client: SessionClient, remember_me: bool) -> Result<Session, AuthError> {
+        use crate::schema::public::{sessions, temp_sessions};
+        let mut conn = self.pool.get()?;
 
-        let new_session = Session::new(user_id, session_id, csrf_token);
-        let token = new_session.token.clone(); // Clone token before insert
+        conn.transaction(|conn| {
+            if let Some(previous_session_id) = previous_session_id {
+                diesel::delete(temp_sessions::table)
+                    .filter(temp_sessions::session_id.eq(previous_session_id))
+                    .execute(conn)
+                    .map_err(AuthError::from)?;
+                diesel::delete(sessions::table)
+                    .filter(sessions::token.eq(previous_session_id))
+                    .execute(conn)
+                    .map_err(AuthError::from)?;
+            }
 
-        diesel::insert_into(sessions::table)
-            .values(&new_session)
-            .execute(&mut conn)
-            .map_err(AuthError::from)?;
+            let mut new_session = NewSession {
+                user_agent: client.user_agent,
+                ip_address: client.ip_address,
+                ..Session::new(user_id, tokens::generate_token(), csrf_token, remember_me, &self.session_config)
+            };
+            if Self::has_two_factor(conn, user_id)? {
+                new_session = new_session.pending_two_factor();
+            }
 
-        sessions::table
-            .filter(sessions::token.eq(token))
-            .first(&mut conn)
-            .map_err(AuthError::from)
+            diesel::insert_into(sessions::table)
+                .values(&new_session)
+                .get_result::<Session>(conn)
+                .map_err(AuthError::from)
+        })
     }
 
     fn validate_csrf(&self, session_id: &str, csrf_token: &str) -> Result<(), AuthError> {
-        use crate::schema::public::temp_sessions;
-        let mut conn = db::config::establish_connection();
+        use crate::schema::public::{sessions, temp_sessions};
+        let mut conn = self.pool.get()?;
         let now = chrono::Utc::now().naive_utc();
 
+        // Once logged in, the session's own token applies; the handshake token expires after minutes
+        let session = sessions::table
+            .filter(sessions::token.eq(session_id))
+            .filter(sessions::expires_at.gt(now))
+            .first::<Session>(&mut conn)
+            .optional()
+            .map_err(AuthError::from)?;
+        if let Some(session) = session {
+            return if session.accepts_csrf(csrf_token, now) {
+                Ok(())
+            } else {
+                Err(AuthError::InvalidCsrf)
+            };
+        }
+
         // Delete expired sessions first
         diesel::delete(temp_sessions::table)
             .filter(temp_sessions::expires_at.lt(now))
@@ -96,39 +425,87 @@ impl AuthRepository for PgAuthRepository {
             .map_err(AuthError::from)?;
         
         // Then validate the current session
-        temp_sessions::table
+        let temp_session = temp_sessions::table
             .filter(temp_sessions::dsl::session_id.eq(session_id))
             .filter(temp_sessions::dsl::expires_at.gt(now))
-            .filter(temp_sessions::dsl::csrf_token.eq(csrf_token))
             .first::<TempSession>(&mut conn)
             .map_err(|_| AuthError::InvalidSession)?;
 
+        if temp_session.accepts_csrf(csrf_token, now) {
+            Ok(())
+        } else {
+            Err(AuthError::InvalidCsrf)
+        }
+    }
+
+    fn session_csrf_token(&self, session_token: &str) -> Result<String, AuthError> {
+        use crate::schema::public::sessions;
+        let mut conn = self.pool.get()?;
+
+        sessions::table
+            .filter(sessions::token.eq(session_token))
+            .filter(sessions::expires_at.gt(chrono::Utc::now().naive_utc()))
+            .select(sessions::csrf_token)
+            .first::<String>(&mut conn)
+            .map_err(|_| AuthError::InvalidSession)
+    }
+
+    fn rotate_csrf_token(&self, session_token: &str, csrf_token: String) -> Result<(), AuthError> {
+        use crate::schema::public::sessions;
+        let mut conn = self.pool.get()?;
+
+        let updated = diesel::update(sessions::table)
+            .filter(sessions::token.eq(session_token))
+            .filter(sessions::expires_at.gt(chrono::Utc::now().naive_utc()))
+            .set(sessions::csrf_token.eq(csrf_token))
+            .execute(&mut conn)
+            .map_err(AuthError::from)?;
+
+        if updated == 0 {
+            return Err(AuthError::InvalidSession);
+        }
         Ok(())
     }
 
-    fn verify_credentials(&self, email: String, password: String) -> Result<User, AuthError> {
-        use crate::schema::public::users;
-        let mut conn = db::config::establish_connection();
+    fn verify_credentials(&self, email: String, password: String, ip_address: Option<String>) -> Result<User, AuthError> {
+        use crate::schema::public::{login_throttles, users};
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let account = (ThrottleScope::Account, account_key(&email));
+        let mut keys = vec![account.clone()];
+        keys.extend(ip_address.map(|ip_address| (ThrottleScope::Ip, ip_address)));
+
+        if let Some(retry_after) = Self::find_lockout(&mut conn, &keys, now)? {
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
 
         let user = users::table
             .filter(users::email.eq(email))
             .first::<User>(&mut conn)
-            .map_err(|_| AuthError::InvalidCredentials)?;
+            .optional()
+            .map_err(AuthError::from)?;
 
-        let argon2 = Argon2::default();
-        let parsed_hash = PasswordHash::new(&user.password_hash)
-            .map_err(|_| AuthError::InvalidCredentials)?;
+        let verified = user.filter(|user| password_matches(user, &password));
 
-        if argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok() {
-            Ok(user)
-        } else {
-            Err(AuthError::InvalidCredentials)
+        match verified {
+            Some(user) => {
+                // Only the account is reset; one valid login must not clear an IP guessing at others
+                diesel::delete(login_throttles::table.find((account.0.as_str(), &account.1)))
+                    .execute(&mut conn)
+                    .map_err(AuthError::from)?;
+                Ok(user)
+            },
+            None => {
+                Self::record_failures(&mut conn, &keys, now)?;
+                Err(AuthError::InvalidCredentials)
+            },
         }
     }
 
     fn validate_session(&self, session_token: &str) -> Result<i64, AuthError> {
         use crate::schema::public::sessions;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
         let now = chrono::Utc::now().naive_utc();
 
         // Delete expired sessions first
@@ -143,13 +520,27 @@ impl AuthRepository for PgAuthRepository {
             .filter(sessions::expires_at.gt(now))
             .first::<Session>(&mut conn)
             .map_err(|_| AuthError::InvalidSession)?;
+        if session.two_factor_pending {
+            return Err(AuthError::TwoFactorRequired);
+        }
+
+        if session.needs_touch(now) {
+            diesel::update(sessions::table)
+                .filter(sessions::id.eq(session.id))
+                .set((
+                    sessions::last_seen_at.eq(now),
+                    sessions::expires_at.eq(session.sliding_expiry(now, &self.session_config)),
+                ))
+                .execute(&mut conn)
+                .map_err(AuthError::from)?;
+        }
 
         Ok(session.user_id)
     }
 
     fn invalidate_session(&self, session_token: &str) -> Result<(), AuthError> {
         use crate::schema::public::sessions;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
         diesel::delete(sessions::table)
             .filter(sessions::token.eq(session_token))
             .execute(&mut conn)
@@ -160,18 +551,11 @@ impl AuthRepository for PgAuthRepository {
 
     fn create_user(&self, email: String, password: String) -> Result<User, AuthError> {
         use crate::schema::public::users;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         // Wrap everything in a transaction
         conn.transaction(|conn| {
-            let salt = SaltString::generate(&mut OsRng);
-            let argon2 = Argon2::default();
-            let password_hash = argon2
-                .hash_password(password.as_bytes(), &salt)
-                .unwrap()
-                .to_string();
-
-            let new_user = User::new(email, password_hash);
+            let new_user = User::new(email, hash_password(&password));
 
             diesel::insert_into(users::table)
                 .values(&new_user)
@@ -186,21 +570,616 @@ impl AuthRepository for PgAuthRepository {
     }
 
     fn delete_user(&self, session_token: &str) -> Result<(), AuthError> {
-        use crate::schema::public::{users, sessions};
-        let mut conn = db::config::establish_connection();
+        use crate::schema::public::users;
+        let mut conn = self.pool.get()?;
 
         // First validate session
+        let session = Self::find_active_session(&mut conn, session_token, chrono::Utc::now().naive_utc())?;
+
+        // Delete user associated with session
+        diesel::delete(users::table)
+            .filter(users::id.eq(session.user_id))
+            .execute(&mut conn)
+            .map_err(AuthError::from)?;
+
+        Ok(())
+    }
+
+    fn list_sessions(&self, session_token: &str) -> Result<Vec<Session>, AuthError> {
+        use crate::schema::public::sessions;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        sessions::table
+            .filter(sessions::user_id.eq(session.user_id))
+            .filter(sessions::expires_at.gt(now))
+            .order((sessions::last_seen_at.desc(), sessions::id.desc()))
+            .load::<Session>(&mut conn)
+            .map_err(AuthError::from)
+    }
+
+    fn revoke_session(&self, session_token: &str, session_uuid: Uuid) -> Result<(), AuthError> {
+        use crate::schema::public::sessions;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        let deleted = diesel::delete(sessions::table)
+            .filter(sessions::user_id.eq(session.user_id))
+            .filter(sessions::uuid.eq(session_uuid))
+            .execute(&mut conn)
+            .map_err(AuthError::from)?;
+
+        if deleted == 0 {
+            return Err(AuthError::NotFound);
+        }
+        Ok(())
+    }
+
+    fn revoke_other_sessions(&self, session_token: &str) -> Result<usize, AuthError> {
+        use crate::schema::public::sessions;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        diesel::delete(sessions::table)
+            .filter(sessions::user_id.eq(session.user_id))
+            .filter(sessions::id.ne(session.id))
+            .execute(&mut conn)
+            .map_err(AuthError::from)
+    }
+
+    fn change_password(&self, session_token: &str, current_password: String, new_password: String) -> Result<(), AuthError> {
+        use crate::schema::public::{api_tokens, sessions, users};
+
+        if new_password.is_empty() {
+            return Err(AuthError::InvalidPassword);
+        }
+
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        let user = users::table
+            .find(session.user_id)
+            .first::<User>(&mut conn)
+            .map_err(AuthError::from)?;
+
+        Self::reauthenticate(&mut conn, &user, &current_password, now)?;
+
+        let password_hash = hash_password(&new_password);
+        conn.transaction(|conn| {
+            diesel::update(users::table.find(user.id))
+                .set((
+                    users::password_hash.eq(password_hash),
+                    users::updated_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            diesel::delete(sessions::table)
+                .filter(sessions::user_id.eq(user.id))
+                .filter(sessions::id.ne(session.id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+            diesel::delete(api_tokens::table)
+                .filter(api_tokens::user_id.eq(user.id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            Ok(())
+        })
+    }
+
+    fn create_password_reset(&self, email: String) -> Result<Option<(User, String)>, AuthError> {
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        Self::issue_email_token_quietly(&mut conn, email, EmailTokenPurpose::PasswordReset, now)
+    }
+
+    fn reset_password(&self, token: &str, new_password: String) -> Result<(), AuthError> {
+        use crate::schema::public::{api_tokens, login_throttles, sessions, users};
+
+        if new_password.is_empty() {
+            return Err(AuthError::InvalidPassword);
+        }
+
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+        let password_hash = hash_password(&new_password);
+
+        conn.transaction(|conn| {
+            let user_id = Self::redeem_email_token(conn, token, EmailTokenPurpose::PasswordReset, now)?;
+
+            let user = diesel::update(users::table.find(user_id))
+                .set((
+                    users::password_hash.eq(password_hash),
+                    users::updated_at.eq(now),
+                ))
+                .get_result::<User>(conn)
+                .map_err(AuthError::from)?;
+
+            diesel::delete(sessions::table)
+                .filter(sessions::user_id.eq(user_id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+            diesel::delete(api_tokens::table)
+                .filter(api_tokens::user_id.eq(user_id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            // Whoever proves access to the mailbox may log in again right away
+            diesel::delete(login_throttles::table.find((ThrottleScope::Account.as_str(), account_key(&user.email))))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            Ok(())
+        })
+    }
+
+    fn create_email_verification(&self, user_id: i64) -> Result<(User, String), AuthError> {
+        use crate::schema::public::users;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        conn.transaction(|conn| {
+            let user = users::table
+                .find(user_id)
+                .for_update()
+                .first::<User>(conn)
+                .map_err(AuthError::from)?;
+            if user.is_email_verified() {
+                return Err(AuthError::AlreadyVerified);
+            }
+
+            let token = Self::issue_email_token(conn, user_id, EmailTokenPurpose::EmailVerification, now)?;
+
+            Ok((user, token))
+        })
+    }
+
+    fn verify_email(&self, token: &str) -> Result<User, AuthError> {
+        use crate::schema::public::users;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        conn.transaction(|conn| {
+            let user_id = Self::redeem_email_token(conn, token, EmailTokenPurpose::EmailVerification, now)?;
+
+            diesel::update(users::table.find(user_id))
+                .set((
+                    users::email_verified_at.eq(now),
+                    users::updated_at.eq(now),
+                ))
+                .get_result::<User>(conn)
+                .map_err(AuthError::from)
+        })
+    }
+
+    fn email_verified(&self, user_id: i64) -> Result<bool, AuthError> {
+        use crate::schema::public::users;
+        let mut conn = self.pool.get()?;
+
+        users::table
+            .find(user_id)
+            .select(users::email_verified_at.is_not_null())
+            .first::<bool>(&mut conn)
+            .map_err(AuthError::from)
+    }
+
+    fn begin_totp_enrollment(&self, session_token: &str) -> Result<(User, String), AuthError> {
+        use crate::schema::public::{totp_credentials, users};
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        conn.transaction(|conn| {
+            let user = users::table
+                .find(session.user_id)
+                .for_update()
+                .first::<User>(conn)
+                .map_err(AuthError::from)?;
+            if Self::has_two_factor(conn, user.id)? {
+                return Err(AuthError::TwoFactorAlreadyEnabled);
+            }
+
+            let credential = TotpCredential::new(user.id, totp::generate_secret(), now);
+            diesel::insert_into(totp_credentials::table)
+                .values(&credential)
+                .on_conflict(totp_credentials::user_id)
+                .do_update()
+                .set((
+                    totp_credentials::secret.eq(&credential.secret),
+                    totp_credentials::created_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            Ok((user, credential.secret))
+        })
+    }
+
+    fn confirm_totp_enrollment(&self, session_token: &str, code: &str) -> Result<Vec<String>, AuthError> {
+        use crate::schema::public::{recovery_codes, totp_credentials};
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        let recovery = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect::<Vec<_>>();
+        let new_codes = recovery.iter()
+            .map(|code| RecoveryCode::new(session.user_id, hash_password(&normalize_recovery_code(code)), now))
+            .collect::<Vec<_>>();
+
+        conn.transaction(|conn| {
+            let credential = totp_credentials::table
+                .find(session.user_id)
+                .for_update()
+                .first::<TotpCredential>(conn)
+                .optional()
+                .map_err(AuthError::from)?
+                .ok_or(AuthError::TwoFactorNotEnabled)?;
+            if credential.is_confirmed() {
+                return Err(AuthError::TwoFactorAlreadyEnabled);
+            }
+            let step = totp::verify(&credential.secret, code, totp::step_at(now.and_utc().timestamp()), None)
+                .ok_or(AuthError::InvalidTwoFactorCode)?;
+
+            diesel::update(totp_credentials::table.find(session.user_id))
+                .set((
+                    totp_credentials::confirmed_at.eq(now),
+                    totp_credentials::last_used_step.eq(step),
+                ))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            diesel::delete(recovery_codes::table)
+                .filter(recovery_codes::user_id.eq(session.user_id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+            diesel::insert_into(recovery_codes::table)
+                .values(&new_codes)
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            Ok(recovery)
+        })
+    }
+
+    fn verify_two_factor(&self, session_token: &str, code: TwoFactorCode) -> Result<(User, Session), AuthError> {
+        use crate::schema::public::{login_throttles, recovery_codes, sessions, totp_credentials, users};
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
         let session = sessions::table
             .filter(sessions::token.eq(session_token))
+            .filter(sessions::expires_at.gt(now))
+            .filter(sessions::two_factor_pending.eq(true))
             .first::<Session>(&mut conn)
             .map_err(|_| AuthError::InvalidSession)?;
+        let user = users::table
+            .find(session.user_id)
+            .first::<User>(&mut conn)
+            .map_err(AuthError::from)?;
 
-        // Delete user associated with session
-        diesel::delete(users::table)
-            .filter(users::id.eq(session.user_id))
+        let throttle = [(ThrottleScope::TwoFactor, account_key(&user.email))];
+        if let Some(retry_after) = Self::find_lockout(&mut conn, &throttle, now)? {
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
+
+        let verified = conn.transaction(|conn| {
+            // The lock makes a code usable once even when it's sent twice at the same time
+            let credential = totp_credentials::table
+                .find(user.id)
+                .filter(totp_credentials::confirmed_at.is_not_null())
+                .for_update()
+                .first::<TotpCredential>(conn)
+                .map_err(|_| AuthError::TwoFactorNotEnabled)?;
+
+            match &code {
+                TwoFactorCode::Totp(code) => {
+                    let current_step = totp::step_at(now.and_utc().timestamp());
+                    let Some(step) = totp::verify(&credential.secret, code, current_step, credential.last_used_step) else {
+                        return Ok(None);
+                    };
+                    diesel::update(totp_credentials::table.find(user.id))
+                        .set(totp_credentials::last_used_step.eq(step))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                },
+                TwoFactorCode::Recovery(code) => {
+                    let code = normalize_recovery_code(code);
+                    let unused = recovery_codes::table
+                        .filter(recovery_codes::user_id.eq(user.id))
+                        .filter(recovery_codes::used_at.is_null())
+                        .load::<RecoveryCode>(conn)
+                        .map_err(AuthError::from)?;
+                    let Some(recovery_code) = unused.iter().find(|recovery_code| hash_matches(&recovery_code.code_hash, &code)) else {
+                        return Ok(None);
+                    };
+                    diesel::update(recovery_codes::table.find(recovery_code.id))
+                        .set(recovery_codes::used_at.eq(now))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                },
+            }
+
+            // The pending token was handed out before the second factor, so it's replaced
+            diesel::update(sessions::table.find(session.id))
+                .set((
+                    sessions::token.eq(tokens::generate_token()),
+                    sessions::two_factor_pending.eq(false),
+                    sessions::last_seen_at.eq(now),
+                    sessions::expires_at.eq(session.sliding_expiry(now, &self.session_config)),
+                ))
+                .get_result::<Session>(conn)
+                .map(Some)
+                .map_err(AuthError::from)
+        })?;
+
+        let Some(session) = verified else {
+            Self::record_failures(&mut conn, &throttle, now)?;
+            return Err(AuthError::InvalidTwoFactorCode);
+        };
+        diesel::delete(login_throttles::table.find((throttle[0].0.as_str(), &throttle[0].1)))
             .execute(&mut conn)
             .map_err(AuthError::from)?;
 
+        Ok((user, session))
+    }
+
+    fn disable_totp(&self, session_token: &str, password: String) -> Result<(), AuthError> {
+        use crate::schema::public::{recovery_codes, totp_credentials, users};
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        let user = users::table
+            .find(session.user_id)
+            .first::<User>(&mut conn)
+            .map_err(AuthError::from)?;
+        Self::reauthenticate(&mut conn, &user, &password, now)?;
+
+        conn.transaction(|conn| {
+            let deleted = diesel::delete(totp_credentials::table.find(user.id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+            if deleted == 0 {
+                return Err(AuthError::TwoFactorNotEnabled);
+            }
+
+            diesel::delete(recovery_codes::table)
+                .filter(recovery_codes::user_id.eq(user.id))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+            Ok(())
+        })
+    }
+
+    fn create_api_token(&self, session_token: &str, name: String, scopes: Vec<String>, expires_at: Option<chrono::NaiveDateTime>) -> Result<(ApiToken, String), AuthError> {
+        use crate::schema::public::api_tokens;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        let token = generate_api_token();
+        let api_token = diesel::insert_into(api_tokens::table)
+            .values(&ApiToken::new(session.user_id, name, &token, &scopes, expires_at, now))
+            .get_result::<ApiToken>(&mut conn)
+            .map_err(AuthError::from)?;
+
+        Ok((api_token, token))
+    }
+
+    fn list_api_tokens(&self, session_token: &str) -> Result<Vec<ApiToken>, AuthError> {
+        use crate::schema::public::api_tokens;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        api_tokens::table
+            .filter(api_tokens::user_id.eq(session.user_id))
+            .order(api_tokens::created_at.desc())
+            .load::<ApiToken>(&mut conn)
+            .map_err(AuthError::from)
+    }
+
+    fn revoke_api_token(&self, session_token: &str, token_uuid: Uuid) -> Result<(), AuthError> {
+        use crate::schema::public::api_tokens;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let session = Self::find_active_session(&mut conn, session_token, now)?;
+        let deleted = diesel::delete(api_tokens::table)
+            .filter(api_tokens::user_id.eq(session.user_id))
+            .filter(api_tokens::uuid.eq(token_uuid))
+            .execute(&mut conn)
+            .map_err(AuthError::from)?;
+        if deleted == 0 {
+            return Err(AuthError::NotFound);
+        }
+
         Ok(())
     }
-} 
\ No newline at end of file
+
+    fn authenticate_api_token(&self, token: &str) -> Result<ApiToken, AuthError> {
+        use crate::schema::public::api_tokens;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let api_token = api_tokens::table
+            .filter(api_tokens::token_hash.eq(tokens::hash_token(token)))
+            .first::<ApiToken>(&mut conn)
+            .optional()
+            .map_err(AuthError::from)?
+            .filter(|api_token| api_token.is_usable(now))
+            .ok_or(AuthError::InvalidToken)?;
+
+        if api_token.needs_touch(now) {
+            diesel::update(api_tokens::table.find(api_token.id))
+                .set(api_tokens::last_used_at.eq(now))
+                .execute(&mut conn)
+                .map_err(AuthError::from)?;
+        }
+
+        Ok(api_token)
+    }
+
+    fn create_oidc_login(&self) -> Result<(String, OidcLoginAttempt), AuthError> {
+        use crate::schema::public::oidc_login_attempts;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        diesel::delete(oidc_login_attempts::table)
+            .filter(oidc_login_attempts::expires_at.lt(now))
+            .execute(&mut conn)
+            .map_err(AuthError::from)?;
+
+        let state = tokens::generate_token();
+        let attempt = diesel::insert_into(oidc_login_attempts::table)
+            .values(&OidcLoginAttempt::new(&state, tokens::generate_token(), generate_code_verifier(), now))
+            .get_result::<OidcLoginAttempt>(&mut conn)
+            .map_err(AuthError::from)?;
+
+        Ok((state, attempt))
+    }
+
+    fn take_oidc_login(&self, state: &str) -> Result<OidcLoginAttempt, AuthError> {
+        use crate::schema::public::oidc_login_attempts;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        // Deleting right away means a state can only ever be redeemed once
+        diesel::delete(oidc_login_attempts::table)
+            .filter(oidc_login_attempts::state_hash.eq(tokens::hash_token(state)))
+            .get_result::<OidcLoginAttempt>(&mut conn)
+            .optional()
+            .map_err(AuthError::from)?
+            .filter(|attempt| attempt.is_usable(now))
+            .ok_or(AuthError::InvalidToken)
+    }
+
+    fn sign_in_with_identity(&self, claims: IdentityClaims) -> Result<User, AuthError> {
+        use crate::schema::public::{api_tokens, recovery_codes, sessions, totp_credentials, user_identities, users};
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        conn.transaction(|conn| {
+            let linked = diesel::update(user_identities::table)
+                .filter(user_identities::issuer.eq(&claims.issuer))
+                .filter(user_identities::subject.eq(&claims.subject))
+                .set((
+                    user_identities::email.eq(&claims.email),
+                    user_identities::last_login_at.eq(now),
+                ))
+                .returning(user_identities::user_id)
+                .get_result::<i64>(conn)
+                .optional()
+                .map_err(AuthError::from)?;
+            if let Some(user_id) = linked {
+                return users::table
+                    .find(user_id)
+                    .first::<User>(conn)
+                    .map_err(AuthError::from);
+            }
+
+            let email = claims.email.clone().ok_or(AuthError::MissingEmail)?;
+            let existing = users::table
+                .filter(users::email.eq(&email))
+                .for_update()
+                .first::<User>(conn)
+                .optional()
+                .map_err(AuthError::from)?;
+            let user = match existing {
+                // Only a provider that checked the address may take over the account behind it
+                Some(_) if !claims.email_verified => return Err(AuthError::DuplicateEmail),
+                Some(user) if user.is_email_verified() => user,
+                Some(user) => {
+                    // Whoever registered the address never proved owning it, so it may not have
+                    // been the owner. Their password and every way into the account they set up
+                    // are dropped before the owner gets it.
+                    diesel::delete(sessions::table.filter(sessions::user_id.eq(user.id)))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                    diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(user.id)))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                    diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user.id)))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                    diesel::delete(totp_credentials::table.filter(totp_credentials::user_id.eq(user.id)))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                    diesel::delete(user_identities::table.filter(user_identities::user_id.eq(user.id)))
+                        .execute(conn)
+                        .map_err(AuthError::from)?;
+                    diesel::update(users::table.find(user.id))
+                        .set((
+                            users::password_hash.eq(hash_password(&tokens::generate_token())),
+                            users::email_verified_at.eq(now),
+                            users::updated_at.eq(now),
+                        ))
+                        .get_result::<User>(conn)
+                        .map_err(AuthError::from)?
+                },
+                None => {
+                    // The account starts without a usable password; a password reset can add one
+                    let user = diesel::insert_into(users::table)
+                        .values(&User::new(email, hash_password(&tokens::generate_token())))
+                        .get_result::<User>(conn)
+                        .map_err(AuthError::from)?;
+                    if !claims.email_verified {
+                        user
+                    } else {
+                        diesel::update(users::table.find(user.id))
+                            .set(users::email_verified_at.eq(now))
+                            .get_result::<User>(conn)
+                            .map_err(AuthError::from)?
+                    }
+                },
+            };
+
+            diesel::insert_into(user_identities::table)
+                .values(&UserIdentity::new(user.id, claims.issuer, claims.subject, claims.email, now))
+                .execute(conn)
+                .map_err(AuthError::from)?;
+
+            Ok(user)
+        })
+    }
+
+    fn create_login_link(&self, email: String) -> Result<Option<(User, String)>, AuthError> {
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        Self::issue_email_token_quietly(&mut conn, email, EmailTokenPurpose::LoginLink, now)
+    }
+
+    fn redeem_login_link(&self, token: &str) -> Result<User, AuthError> {
+        use crate::schema::public::users;
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().naive_utc();
+
+        conn.transaction(|conn| {
+            let user_id = Self::redeem_email_token(conn, token, EmailTokenPurpose::LoginLink, now)?;
+
+            let user = users::table
+                .find(user_id)
+                .first::<User>(conn)
+                .map_err(AuthError::from)?;
+            if user.is_email_verified() {
+                return Ok(user);
+            }
+            diesel::update(users::table.find(user_id))
+                .set((
+                    users::email_verified_at.eq(now),
+                    users::updated_at.eq(now),
+                ))
+                .get_result::<User>(conn)
+                .map_err(AuthError::from)
+        })
+    }
+}
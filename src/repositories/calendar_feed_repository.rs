@@ -0,0 +1,103 @@
+use diesel::{prelude::*, r2d2::PoolError};
+use crate::{db::config::DbPool, models::calendar_feed_token::CalendarFeedToken, tokens};
+
+#[derive(Debug)]
+pub enum CalendarFeedError {
+    NotFound,
+    DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
+}
+
+impl From<diesel::result::Error> for CalendarFeedError {
+    fn from(err: diesel::result::Error) -> CalendarFeedError {
+        match err {
+            diesel::result::Error::NotFound => CalendarFeedError::NotFound,
+            _ => CalendarFeedError::DatabaseError(err),
+        }
+    }
+}
+
+impl From<PoolError> for CalendarFeedError {
+    fn from(_: PoolError) -> CalendarFeedError {
+        CalendarFeedError::ConnectionUnavailable
+    }
+}
+
+pub trait CalendarFeedRepository: Send + Sync + 'static {
+    /// Issues a new feed token for the user, replacing any existing one. Returns the stored
+    /// token together with the plain secret, which is never persisted.
+    fn rotate_feed_token(&self, user_id: i64) -> Result<(CalendarFeedToken, String), CalendarFeedError>;
+    fn get_feed_token(&self, user_id: i64) -> Result<CalendarFeedToken, CalendarFeedError>;
+    fn revoke_feed_token(&self, user_id: i64) -> Result<(), CalendarFeedError>;
+    fn find_user_by_feed_token(&self, token: &str) -> Result<i64, CalendarFeedError>;
+}
+
+pub struct PgCalendarFeedRepository {
+    pool: DbPool,
+}
+
+impl PgCalendarFeedRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl CalendarFeedRepository for PgCalendarFeedRepository {
+    fn rotate_feed_token(&self, user_id: i64) -> Result<(CalendarFeedToken, String), CalendarFeedError> {
+        use crate::schema::public::calendar_feed_tokens;
+        let mut conn = self.pool.get()?;
+
+        let token = tokens::generate_token();
+        let new_token = CalendarFeedToken::new(user_id, &token);
+
+        let feed_token = diesel::insert_into(calendar_feed_tokens::table)
+            .values(&new_token)
+            .on_conflict(calendar_feed_tokens::user_id)
+            .do_update()
+            .set((
+                calendar_feed_tokens::token_hash.eq(&new_token.token_hash),
+                calendar_feed_tokens::created_at.eq(new_token.created_at),
+            ))
+            .get_result::<CalendarFeedToken>(&mut conn)
+            .map_err(CalendarFeedError::from)?;
+
+        Ok((feed_token, token))
+    }
+
+    fn get_feed_token(&self, user_id: i64) -> Result<CalendarFeedToken, CalendarFeedError> {
+        use crate::schema::public::calendar_feed_tokens;
+        let mut conn = self.pool.get()?;
+
+        calendar_feed_tokens::table
+            .filter(calendar_feed_tokens::user_id.eq(user_id))
+            .first::<CalendarFeedToken>(&mut conn)
+            .map_err(CalendarFeedError::from)
+    }
+
+    fn revoke_feed_token(&self, user_id: i64) -> Result<(), CalendarFeedError> {
+        use crate::schema::public::calendar_feed_tokens;
+        let mut conn = self.pool.get()?;
+
+        let result = diesel::delete(calendar_feed_tokens::table)
+            .filter(calendar_feed_tokens::user_id.eq(user_id))
+            .execute(&mut conn)
+            .map_err(CalendarFeedError::from)?;
+
+        if result == 0 {
+            return Err(CalendarFeedError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    fn find_user_by_feed_token(&self, token: &str) -> Result<i64, CalendarFeedError> {
+        use crate::schema::public::calendar_feed_tokens;
+        let mut conn = self.pool.get()?;
+
+        calendar_feed_tokens::table
+            .filter(calendar_feed_tokens::token_hash.eq(tokens::hash_token(token)))
+            .select(calendar_feed_tokens::user_id)
+            .first::<i64>(&mut conn)
+            .map_err(CalendarFeedError::from)
+    }
+}
@@ -1,12 +1,20 @@
-use diesel::prelude::*;
+use diesel::{prelude::*, r2d2::PoolError};
 use uuid::Uuid;
-use crate::{db, models::exercise::{Exercise, CreateExercise, UpdateExercise}};
+use crate::{db::config::DbPool, models::{
+    exercise::{muscles_are_valid, CreateExercise, Exercise, ExerciseCategory, ExerciseDetail, ExerciseFilter, NewExercise, UpdateExercise},
+    muscle_group::{ExerciseMuscleGroup, MuscleGroup, MuscleRole},
+}};
 
 #[derive(Debug)]
 pub enum ExerciseError {
     NotFound,
     DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
     Unauthorized,
+    /// Catalog exercises are shared by all users and cannot be modified, only forked.
+    CatalogExercise,
+    InvalidExercise,
+    InvalidMuscleGroup(String),
 }
 
 impl From<diesel::result::Error> for ExerciseError {
@@ -18,83 +26,214 @@ impl From<diesel::result::Error> for ExerciseError {
     }
 }
 
-pub trait ExerciseRepository {
-    fn create_exercise(&self, user_id: i64, exercise: CreateExercise) -> Result<Exercise, ExerciseError>;
-    fn get_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<Exercise, ExerciseError>;
-    fn list_exercises(&self, user_id: i64) -> Result<Vec<Exercise>, ExerciseError>;
-    fn update_exercise(&self, user_id: i64, exercise_uuid: Uuid, exercise: UpdateExercise) -> Result<Exercise, ExerciseError>;
+impl From<PoolError> for ExerciseError {
+    fn from(_: PoolError) -> ExerciseError {
+        ExerciseError::ConnectionUnavailable
+    }
+}
+
+pub trait ExerciseRepository: Send + Sync + 'static {
+    fn create_exercise(&self, user_id: i64, exercise: CreateExercise) -> Result<ExerciseDetail, ExerciseError>;
+    fn get_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<ExerciseDetail, ExerciseError>;
+    /// The user's personal exercises merged with the global catalog, ordered by name.
+    fn list_exercises(&self, user_id: i64, filter: ExerciseFilter) -> Result<Vec<ExerciseDetail>, ExerciseError>;
+    fn update_exercise(&self, user_id: i64, exercise_uuid: Uuid, exercise: UpdateExercise) -> Result<ExerciseDetail, ExerciseError>;
     fn delete_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<(), ExerciseError>;
+    /// Copies a visible exercise, typically from the catalog, into a new personal exercise.
+    fn fork_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<ExerciseDetail, ExerciseError>;
+    fn list_muscle_groups(&self) -> Result<Vec<MuscleGroup>, ExerciseError>;
 }
 
-pub struct PgExerciseRepository;
+pub struct PgExerciseRepository {
+    pool: DbPool,
+}
 
 impl PgExerciseRepository {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
     }
-}
 
-impl ExerciseRepository for PgExerciseRepository {
-    fn create_exercise(&self, user_id: i64, exercise: CreateExercise) -> Result<Exercise, ExerciseError> {
+    fn find_visible(conn: &mut PgConnection, user_id: i64, exercise_uuid: Uuid) -> Result<Exercise, ExerciseError> {
         use crate::schema::public::exercises;
-        let mut conn = db::config::establish_connection();
 
-        let new_exercise = Exercise::new(user_id, exercise.name, exercise.description);
-        diesel::insert_into(exercises::table)
-            .values(&new_exercise)
-            .get_result(&mut conn)
+        exercises::table
+            .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
+            .filter(exercises::uuid.eq(exercise_uuid))
+            .first::<Exercise>(conn)
             .map_err(ExerciseError::from)
     }
 
-    fn get_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<Exercise, ExerciseError> {
-        use crate::schema::public::exercises;
-        let mut conn = db::config::establish_connection();
+    /// Finds an exercise the user may modify, rejecting catalog entries.
+    fn find_owned(conn: &mut PgConnection, user_id: i64, exercise_uuid: Uuid) -> Result<Exercise, ExerciseError> {
+        let exercise = Self::find_visible(conn, user_id, exercise_uuid)?;
+        if exercise.is_catalog() {
+            return Err(ExerciseError::CatalogExercise);
+        }
+        Ok(exercise)
+    }
 
-        exercises::table
-            .filter(exercises::user_id.eq(user_id))
-            .filter(exercises::uuid.eq(exercise_uuid))
-            .first::<Exercise>(&mut conn)
-            .map_err(ExerciseError::from)
+    fn load_details(conn: &mut PgConnection, exercises: Vec<Exercise>) -> Result<Vec<ExerciseDetail>, ExerciseError> {
+        use crate::schema::public::{exercise_muscle_groups, muscle_groups};
+
+        let exercise_ids = exercises.iter().map(|exercise| exercise.id).collect::<Vec<_>>();
+        let mappings = muscle_groups::table
+            .inner_join(exercise_muscle_groups::table)
+            .filter(exercise_muscle_groups::exercise_id.eq_any(&exercise_ids))
+            .order(muscle_groups::slug.asc())
+            .select((muscle_groups::all_columns, exercise_muscle_groups::all_columns))
+            .load::<(MuscleGroup, ExerciseMuscleGroup)>(conn)
+            .map_err(ExerciseError::from)?;
+
+        Ok(exercises.into_iter()
+            .map(|exercise| {
+                let muscle_groups = mappings.iter()
+                    .filter(|(_, mapping)| mapping.exercise_id == exercise.id)
+                    .cloned()
+                    .collect();
+                ExerciseDetail { exercise, muscle_groups }
+            })
+            .collect())
     }
 
-    fn list_exercises(&self, user_id: i64) -> Result<Vec<Exercise>, ExerciseError> {
+    fn load_detail(conn: &mut PgConnection, exercise: Exercise) -> Result<ExerciseDetail, ExerciseError> {
+        Self::load_details(conn, vec![exercise])
+            .map(|mut details| details.remove(0))
+    }
+
+    fn find_muscle_group(conn: &mut PgConnection, slug: &str) -> Result<MuscleGroup, ExerciseError> {
+        use crate::schema::public::muscle_groups;
+
+        muscle_groups::table
+            .filter(muscle_groups::slug.eq(slug))
+            .first::<MuscleGroup>(conn)
+            .map_err(|err| match err {
+                diesel::result::Error::NotFound => ExerciseError::InvalidMuscleGroup(slug.to_string()),
+                _ => ExerciseError::from(err),
+            })
+    }
+
+    /// Replaces the primary and secondary muscle groups of an exercise.
+    fn set_muscles(conn: &mut PgConnection, exercise_id: i64, primary: &[String], secondary: &[String]) -> Result<(), ExerciseError> {
+        use crate::schema::public::exercise_muscle_groups;
+
+        diesel::delete(exercise_muscle_groups::table)
+            .filter(exercise_muscle_groups::exercise_id.eq(exercise_id))
+            .execute(conn)
+            .map_err(ExerciseError::from)?;
+
+        let roles = primary.iter().map(|slug| (slug, MuscleRole::Primary))
+            .chain(secondary.iter().map(|slug| (slug, MuscleRole::Secondary)));
+        for (slug, role) in roles {
+            let muscle_group = Self::find_muscle_group(conn, slug)?;
+            diesel::insert_into(exercise_muscle_groups::table)
+                .values(&ExerciseMuscleGroup::new(exercise_id, muscle_group.id, role))
+                .execute(conn)
+                .map_err(ExerciseError::from)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ExerciseRepository for PgExerciseRepository {
+    fn create_exercise(&self, user_id: i64, exercise: CreateExercise) -> Result<ExerciseDetail, ExerciseError> {
         use crate::schema::public::exercises;
-        let mut conn = db::config::establish_connection();
 
-        exercises::table
-            .filter(exercises::user_id.eq(user_id))
+        if !exercise.is_valid() {
+            return Err(ExerciseError::InvalidExercise);
+        }
+
+        let mut conn = self.pool.get()?;
+
+        conn.transaction(|conn| {
+            let new_exercise = Exercise::new(user_id, exercise.name, exercise.description, exercise.category);
+            let created = diesel::insert_into(exercises::table)
+                .values(&new_exercise)
+                .get_result::<Exercise>(conn)
+                .map_err(ExerciseError::from)?;
+
+            Self::set_muscles(conn, created.id, &exercise.primary_muscles, &exercise.secondary_muscles)?;
+            Self::load_detail(conn, created)
+        })
+    }
+
+    fn get_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<ExerciseDetail, ExerciseError> {
+        let mut conn = self.pool.get()?;
+
+        let exercise = Self::find_visible(&mut conn, user_id, exercise_uuid)?;
+        Self::load_detail(&mut conn, exercise)
+    }
+
+    fn list_exercises(&self, user_id: i64, filter: ExerciseFilter) -> Result<Vec<ExerciseDetail>, ExerciseError> {
+        use crate::schema::public::{exercise_muscle_groups, exercises};
+        let mut conn = self.pool.get()?;
+
+        let mut query = exercises::table
+            .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
+            .order((exercises::name.asc(), exercises::id.asc()))
+            .into_boxed();
+
+        if let Some(category) = filter.category {
+            query = query.filter(exercises::category.eq(category.as_str()));
+        }
+        if let Some(muscle) = &filter.muscle {
+            let muscle_group = Self::find_muscle_group(&mut conn, muscle)?;
+            query = query.filter(exercises::id.eq_any(
+                exercise_muscle_groups::table
+                    .filter(exercise_muscle_groups::muscle_group_id.eq(muscle_group.id))
+                    .select(exercise_muscle_groups::exercise_id)
+            ));
+        }
+
+        let exercises = query
             .load::<Exercise>(&mut conn)
-            .map_err(ExerciseError::from)
+            .map_err(ExerciseError::from)?;
+
+        Self::load_details(&mut conn, exercises)
     }
 
-    fn update_exercise(&self, user_id: i64, exercise_uuid: Uuid, exercise: UpdateExercise) -> Result<Exercise, ExerciseError> {
+    fn update_exercise(&self, user_id: i64, exercise_uuid: Uuid, exercise: UpdateExercise) -> Result<ExerciseDetail, ExerciseError> {
         use crate::schema::public::exercises;
-        let mut conn = db::config::establish_connection();
 
-        let exercise_exists = exercises::table
-            .filter(exercises::user_id.eq(user_id))
-            .filter(exercises::uuid.eq(exercise_uuid))
-            .first::<Exercise>(&mut conn)
-            .map_err(ExerciseError::from)?;
+        if !exercise.is_valid() {
+            return Err(ExerciseError::InvalidExercise);
+        }
 
-        diesel::update(exercises::table)
-            .filter(exercises::uuid.eq(exercise_uuid))
-            .set((
-                exercises::name.eq(exercise.name.unwrap_or(exercise_exists.name)),
-                exercises::description.eq(exercise.description.or(exercise_exists.description)),
-                exercises::updated_at.eq(chrono::Utc::now().naive_utc()),
-            ))
-            .get_result(&mut conn)
-            .map_err(ExerciseError::from)
+        let mut conn = self.pool.get()?;
+
+        conn.transaction(|conn| {
+            let exercise_exists = Self::find_owned(conn, user_id, exercise_uuid)?;
+            let current = Self::load_detail(conn, exercise_exists.clone())?;
+
+            let primary = exercise.primary_muscles.unwrap_or_else(|| current.muscles(MuscleRole::Primary));
+            let secondary = exercise.secondary_muscles.unwrap_or_else(|| current.muscles(MuscleRole::Secondary));
+            if !muscles_are_valid(&primary, &secondary) {
+                return Err(ExerciseError::InvalidExercise);
+            }
+
+            let updated = diesel::update(exercises::table)
+                .filter(exercises::id.eq(exercise_exists.id))
+                .set((
+                    exercises::name.eq(exercise.name.unwrap_or(exercise_exists.name)),
+                    exercises::description.eq(exercise.description.or(exercise_exists.description)),
+                    exercises::category.eq(exercise.category.map_or(exercise_exists.category, |category| category.as_str().to_string())),
+                    exercises::updated_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .get_result::<Exercise>(conn)
+                .map_err(ExerciseError::from)?;
+
+            Self::set_muscles(conn, updated.id, &primary, &secondary)?;
+            Self::load_detail(conn, updated)
+        })
     }
 
     fn delete_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<(), ExerciseError> {
         use crate::schema::public::exercises;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
+        let exercise = Self::find_owned(&mut conn, user_id, exercise_uuid)?;
         let result = diesel::delete(exercises::table)
-            .filter(exercises::user_id.eq(user_id))
-            .filter(exercises::uuid.eq(exercise_uuid))
+            .filter(exercises::id.eq(exercise.id))
             .execute(&mut conn)
             .map_err(ExerciseError::from)?;
 
@@ -104,4 +243,41 @@ impl ExerciseRepository for PgExerciseRepository {
 
         Ok(())
     }
-} 
\ No newline at end of file
+
+    fn fork_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<ExerciseDetail, ExerciseError> {
+        use crate::schema::public::{exercise_muscle_groups, exercises};
+        let mut conn = self.pool.get()?;
+
+        conn.transaction(|conn| {
+            let source = Self::find_visible(conn, user_id, exercise_uuid)?;
+            let new_exercise = NewExercise {
+                category: source.category.clone(),
+                ..Exercise::new(user_id, source.name.clone(), source.description.clone(), ExerciseCategory::default())
+            };
+            let fork = diesel::insert_into(exercises::table)
+                .values(&new_exercise)
+                .get_result::<Exercise>(conn)
+                .map_err(ExerciseError::from)?;
+
+            let mappings = Self::load_detail(conn, source)?.muscle_groups.into_iter()
+                .map(|(_, mapping)| ExerciseMuscleGroup { exercise_id: fork.id, ..mapping })
+                .collect::<Vec<_>>();
+            diesel::insert_into(exercise_muscle_groups::table)
+                .values(&mappings)
+                .execute(conn)
+                .map_err(ExerciseError::from)?;
+
+            Self::load_detail(conn, fork)
+        })
+    }
+
+    fn list_muscle_groups(&self) -> Result<Vec<MuscleGroup>, ExerciseError> {
+        use crate::schema::public::muscle_groups;
+        let mut conn = self.pool.get()?;
+
+        muscle_groups::table
+            .order(muscle_groups::id.asc())
+            .load::<MuscleGroup>(&mut conn)
+            .map_err(ExerciseError::from)
+    }
+}
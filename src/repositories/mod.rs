@@ -1,4 +1,10 @@
 pub mod auth_repository;
 pub mod workout_repository;
 pub mod exercise_repository;
-pub mod workout_exercise_repository;
\ No newline at end of file
+pub mod workout_exercise_repository;
+pub mod workout_log_repository;
+pub mod schedule_repository;
+pub mod recurring_schedule_repository;
+pub mod calendar_feed_repository;
+pub mod report_repository;
+pub mod personal_record_repository;
\ No newline at end of file
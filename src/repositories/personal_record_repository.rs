@@ -0,0 +1,115 @@
+use diesel::{prelude::*, r2d2::PoolError};
+use uuid::Uuid;
+use crate::{db::config::DbPool, models::{
+    exercise::Exercise,
+    personal_record::PersonalRecord,
+    workout_log::{WorkoutLog, WorkoutLogSet},
+}};
+
+#[derive(Debug)]
+pub enum PersonalRecordError {
+    NotFound,
+    DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
+}
+
+impl From<diesel::result::Error> for PersonalRecordError {
+    fn from(err: diesel::result::Error) -> PersonalRecordError {
+        match err {
+            diesel::result::Error::NotFound => PersonalRecordError::NotFound,
+            _ => PersonalRecordError::DatabaseError(err),
+        }
+    }
+}
+
+impl From<PoolError> for PersonalRecordError {
+    fn from(_: PoolError) -> PersonalRecordError {
+        PersonalRecordError::ConnectionUnavailable
+    }
+}
+
+pub trait PersonalRecordRepository: Send + Sync + 'static {
+    /// The record history of an exercise, most recent first.
+    fn list_exercise_records(&self, user_id: i64, exercise_uuid: Uuid) -> Result<(Exercise, Vec<PersonalRecord>), PersonalRecordError>;
+    /// Records across all exercises, most recent first.
+    fn list_records(&self, user_id: i64, limit: i64) -> Result<Vec<(Exercise, PersonalRecord)>, PersonalRecordError>;
+}
+
+pub struct PgPersonalRecordRepository {
+    pool: DbPool,
+}
+
+impl PgPersonalRecordRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Detects and stores the records set by a freshly logged workout. Runs on the caller's
+    /// connection so that it shares the transaction that inserted the sets.
+    pub fn record_personal_records(conn: &mut PgConnection, log: &WorkoutLog) -> QueryResult<()> {
+        use crate::schema::public::{personal_records, workout_log_sets};
+
+        let sets = workout_log_sets::table
+            .filter(workout_log_sets::workout_log_id.eq(log.id))
+            .order((workout_log_sets::exercise_id.asc(), workout_log_sets::set_number.asc()))
+            .load::<WorkoutLogSet>(conn)?;
+
+        let mut exercise_ids = sets.iter().map(|set| set.exercise_id).collect::<Vec<_>>();
+        exercise_ids.dedup();
+
+        for exercise_id in exercise_ids {
+            let previous = personal_records::table
+                .filter(personal_records::user_id.eq(log.user_id))
+                .filter(personal_records::exercise_id.eq(exercise_id))
+                .load::<PersonalRecord>(conn)?;
+
+            let exercise_sets = sets.iter()
+                .filter(|set| set.exercise_id == exercise_id)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let records = PersonalRecord::detect(log, exercise_id, &exercise_sets, &previous);
+            diesel::insert_into(personal_records::table)
+                .values(&records)
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PersonalRecordRepository for PgPersonalRecordRepository {
+    fn list_exercise_records(&self, user_id: i64, exercise_uuid: Uuid) -> Result<(Exercise, Vec<PersonalRecord>), PersonalRecordError> {
+        use crate::schema::public::{exercises, personal_records};
+        let mut conn = self.pool.get()?;
+
+        let exercise = exercises::table
+            .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
+            .filter(exercises::uuid.eq(exercise_uuid))
+            .first::<Exercise>(&mut conn)
+            .map_err(PersonalRecordError::from)?;
+
+        let records = personal_records::table
+            .filter(personal_records::user_id.eq(user_id))
+            .filter(personal_records::exercise_id.eq(exercise.id))
+            .order((personal_records::achieved_at.desc(), personal_records::id.desc()))
+            .load::<PersonalRecord>(&mut conn)
+            .map_err(PersonalRecordError::from)?;
+
+        Ok((exercise, records))
+    }
+
+    fn list_records(&self, user_id: i64, limit: i64) -> Result<Vec<(Exercise, PersonalRecord)>, PersonalRecordError> {
+        use crate::schema::public::{exercises, personal_records};
+        let mut conn = self.pool.get()?;
+
+        exercises::table
+            .inner_join(personal_records::table.on(exercises::id.eq(personal_records::exercise_id)))
+            .filter(personal_records::user_id.eq(user_id))
+            .order((personal_records::achieved_at.desc(), personal_records::id.desc()))
+            .limit(limit)
+            .select((exercises::all_columns, personal_records::all_columns))
+            .load::<(Exercise, PersonalRecord)>(&mut conn)
+            .map_err(PersonalRecordError::from)
+    }
+}
@@ -0,0 +1,181 @@
+use diesel::{prelude::*, r2d2::PoolError};
+use uuid::Uuid;
+use crate::{db::config::DbPool, models::{
+    recurrence_rule::RecurrenceRule,
+    recurring_schedule::{CreateRecurringSchedule, CreateScheduleException, RecurringSchedule, RecurringScheduleDetail, RecurringScheduleException},
+    workout::Workout,
+}};
+
+#[derive(Debug)]
+pub enum RecurringScheduleError {
+    NotFound,
+    WorkoutNotFound,
+    InvalidRule(String),
+    InvalidException,
+    DuplicateException,
+    DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
+}
+
+impl From<diesel::result::Error> for RecurringScheduleError {
+    fn from(err: diesel::result::Error) -> RecurringScheduleError {
+        match err {
+            diesel::result::Error::NotFound => RecurringScheduleError::NotFound,
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => RecurringScheduleError::DuplicateException,
+            _ => RecurringScheduleError::DatabaseError(err),
+        }
+    }
+}
+
+impl From<PoolError> for RecurringScheduleError {
+    fn from(_: PoolError) -> RecurringScheduleError {
+        RecurringScheduleError::ConnectionUnavailable
+    }
+}
+
+pub trait RecurringScheduleRepository: Send + Sync + 'static {
+    fn create_recurring_schedule(&self, user_id: i64, schedule: CreateRecurringSchedule) -> Result<RecurringScheduleDetail, RecurringScheduleError>;
+    fn get_recurring_schedule(&self, user_id: i64, schedule_uuid: Uuid) -> Result<RecurringScheduleDetail, RecurringScheduleError>;
+    fn list_recurring_schedules(&self, user_id: i64) -> Result<Vec<RecurringScheduleDetail>, RecurringScheduleError>;
+    fn delete_recurring_schedule(&self, user_id: i64, schedule_uuid: Uuid) -> Result<(), RecurringScheduleError>;
+    fn add_exception(&self, user_id: i64, schedule_uuid: Uuid, exception: CreateScheduleException) -> Result<RecurringScheduleException, RecurringScheduleError>;
+    fn remove_exception(&self, user_id: i64, schedule_uuid: Uuid, exception_uuid: Uuid) -> Result<(), RecurringScheduleError>;
+}
+
+pub struct PgRecurringScheduleRepository {
+    pool: DbPool,
+}
+
+impl PgRecurringScheduleRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn load_detail(conn: &mut PgConnection, schedule: RecurringSchedule, workout: Workout) -> Result<RecurringScheduleDetail, RecurringScheduleError> {
+        use crate::schema::public::recurring_schedule_exceptions;
+
+        let exceptions = recurring_schedule_exceptions::table
+            .filter(recurring_schedule_exceptions::recurring_schedule_id.eq(schedule.id))
+            .order(recurring_schedule_exceptions::occurrence_at.asc())
+            .load::<RecurringScheduleException>(conn)
+            .map_err(RecurringScheduleError::from)?;
+
+        Ok(RecurringScheduleDetail { schedule, workout, exceptions })
+    }
+
+    fn find_schedule(conn: &mut PgConnection, user_id: i64, schedule_uuid: Uuid) -> Result<(RecurringSchedule, Workout), RecurringScheduleError> {
+        use crate::schema::public::{recurring_schedules, workouts};
+
+        recurring_schedules::table
+            .inner_join(workouts::table)
+            .filter(recurring_schedules::user_id.eq(user_id))
+            .filter(recurring_schedules::uuid.eq(schedule_uuid))
+            .select((recurring_schedules::all_columns, workouts::all_columns))
+            .first::<(RecurringSchedule, Workout)>(conn)
+            .map_err(RecurringScheduleError::from)
+    }
+}
+
+impl RecurringScheduleRepository for PgRecurringScheduleRepository {
+    fn create_recurring_schedule(&self, user_id: i64, schedule: CreateRecurringSchedule) -> Result<RecurringScheduleDetail, RecurringScheduleError> {
+        use crate::schema::public::{recurring_schedules, workouts};
+
+        let rule = RecurrenceRule::parse(&schedule.rrule)
+            .map_err(|err| RecurringScheduleError::InvalidRule(err.0))?;
+
+        let mut conn = self.pool.get()?;
+
+        let workout = workouts::table
+            .filter(workouts::user_id.eq(user_id))
+            .filter(workouts::uuid.eq(schedule.workout_uuid))
+            .first::<Workout>(&mut conn)
+            .optional()
+            .map_err(RecurringScheduleError::from)?
+            .ok_or(RecurringScheduleError::WorkoutNotFound)?;
+
+        let new_schedule = RecurringSchedule::new(user_id, workout.id, schedule.starts_at, &rule, schedule.notes);
+        let schedule = diesel::insert_into(recurring_schedules::table)
+            .values(&new_schedule)
+            .get_result::<RecurringSchedule>(&mut conn)
+            .map_err(RecurringScheduleError::from)?;
+
+        Ok(RecurringScheduleDetail { schedule, workout, exceptions: vec![] })
+    }
+
+    fn get_recurring_schedule(&self, user_id: i64, schedule_uuid: Uuid) -> Result<RecurringScheduleDetail, RecurringScheduleError> {
+        let mut conn = self.pool.get()?;
+
+        let (schedule, workout) = Self::find_schedule(&mut conn, user_id, schedule_uuid)?;
+        Self::load_detail(&mut conn, schedule, workout)
+    }
+
+    fn list_recurring_schedules(&self, user_id: i64) -> Result<Vec<RecurringScheduleDetail>, RecurringScheduleError> {
+        use crate::schema::public::{recurring_schedules, workouts};
+        let mut conn = self.pool.get()?;
+
+        let schedules = recurring_schedules::table
+            .inner_join(workouts::table)
+            .filter(recurring_schedules::user_id.eq(user_id))
+            .order(recurring_schedules::starts_at.asc())
+            .select((recurring_schedules::all_columns, workouts::all_columns))
+            .load::<(RecurringSchedule, Workout)>(&mut conn)
+            .map_err(RecurringScheduleError::from)?;
+
+        schedules.into_iter()
+            .map(|(schedule, workout)| Self::load_detail(&mut conn, schedule, workout))
+            .collect()
+    }
+
+    fn delete_recurring_schedule(&self, user_id: i64, schedule_uuid: Uuid) -> Result<(), RecurringScheduleError> {
+        use crate::schema::public::recurring_schedules;
+        let mut conn = self.pool.get()?;
+
+        let result = diesel::delete(recurring_schedules::table)
+            .filter(recurring_schedules::user_id.eq(user_id))
+            .filter(recurring_schedules::uuid.eq(schedule_uuid))
+            .execute(&mut conn)
+            .map_err(RecurringScheduleError::from)?;
+
+        if result == 0 {
+            return Err(RecurringScheduleError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    fn add_exception(&self, user_id: i64, schedule_uuid: Uuid, exception: CreateScheduleException) -> Result<RecurringScheduleException, RecurringScheduleError> {
+        use crate::schema::public::recurring_schedule_exceptions;
+        let mut conn = self.pool.get()?;
+
+        let (schedule, _) = Self::find_schedule(&mut conn, user_id, schedule_uuid)?;
+        if !exception.is_valid() || !schedule.rule().is_occurrence(schedule.starts_at, exception.occurrence_at) {
+            return Err(RecurringScheduleError::InvalidException);
+        }
+
+        diesel::insert_into(recurring_schedule_exceptions::table)
+            .values(&RecurringScheduleException::new(schedule.id, &exception))
+            .get_result::<RecurringScheduleException>(&mut conn)
+            .map_err(RecurringScheduleError::from)
+    }
+
+    fn remove_exception(&self, user_id: i64, schedule_uuid: Uuid, exception_uuid: Uuid) -> Result<(), RecurringScheduleError> {
+        use crate::schema::public::recurring_schedule_exceptions;
+        let mut conn = self.pool.get()?;
+
+        let (schedule, _) = Self::find_schedule(&mut conn, user_id, schedule_uuid)?;
+        let result = diesel::delete(recurring_schedule_exceptions::table)
+            .filter(recurring_schedule_exceptions::recurring_schedule_id.eq(schedule.id))
+            .filter(recurring_schedule_exceptions::uuid.eq(exception_uuid))
+            .execute(&mut conn)
+            .map_err(RecurringScheduleError::from)?;
+
+        if result == 0 {
+            return Err(RecurringScheduleError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use diesel::{prelude::*, r2d2::PoolError};
+use uuid::Uuid;
+use crate::{db::config::DbPool, models::{
+    exercise::Exercise,
+    report::{ExerciseProgress, ReportRange},
+    workout_log::{WorkoutLog, WorkoutLogSet},
+}};
+
+#[derive(Debug)]
+pub enum ReportError {
+    NotFound,
+    DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
+}
+
+impl From<diesel::result::Error> for ReportError {
+    fn from(err: diesel::result::Error) -> ReportError {
+        match err {
+            diesel::result::Error::NotFound => ReportError::NotFound,
+            _ => ReportError::DatabaseError(err),
+        }
+    }
+}
+
+impl From<PoolError> for ReportError {
+    fn from(_: PoolError) -> ReportError {
+        ReportError::ConnectionUnavailable
+    }
+}
+
+pub trait ReportRepository: Send + Sync + 'static {
+    /// Progress for every exercise with logged sets in the range, ordered by exercise name.
+    fn list_exercise_progress(&self, user_id: i64, range: &ReportRange) -> Result<Vec<ExerciseProgress>, ReportError>;
+    fn get_exercise_progress(&self, user_id: i64, exercise_uuid: Uuid, range: &ReportRange) -> Result<ExerciseProgress, ReportError>;
+}
+
+pub struct PgReportRepository {
+    pool: DbPool,
+}
+
+impl PgReportRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn load_logged_sets(conn: &mut PgConnection, user_id: i64, exercise_id: Option<i64>, range: &ReportRange) -> Result<Vec<(Exercise, WorkoutLog, WorkoutLogSet)>, ReportError> {
+        use crate::schema::public::{exercises, workout_logs, workout_log_sets};
+
+        let mut query = workout_log_sets::table
+            .inner_join(exercises::table.on(exercises::id.eq(workout_log_sets::exercise_id)))
+            .inner_join(workout_logs::table.on(workout_logs::id.eq(workout_log_sets::workout_log_id)))
+            .filter(workout_log_sets::user_id.eq(user_id))
+            .select((exercises::all_columns, workout_logs::all_columns, workout_log_sets::all_columns))
+            .order((exercises::name.asc(), exercises::id.asc(), workout_logs::performed_at.asc(), workout_log_sets::set_number.asc()))
+            .into_boxed();
+
+        if let Some(exercise_id) = exercise_id {
+            query = query.filter(workout_log_sets::exercise_id.eq(exercise_id));
+        }
+        if let Some(from) = range.from {
+            query = query.filter(workout_logs::performed_at.ge(from));
+        }
+        if let Some(to) = range.to {
+            query = query.filter(workout_logs::performed_at.lt(to));
+        }
+
+        query
+            .load::<(Exercise, WorkoutLog, WorkoutLogSet)>(conn)
+            .map_err(ReportError::from)
+    }
+}
+
+impl ReportRepository for PgReportRepository {
+    fn list_exercise_progress(&self, user_id: i64, range: &ReportRange) -> Result<Vec<ExerciseProgress>, ReportError> {
+        let mut conn = self.pool.get()?;
+
+        let mut grouped: Vec<(Exercise, Vec<(WorkoutLog, WorkoutLogSet)>)> = vec![];
+        for (exercise, log, set) in Self::load_logged_sets(&mut conn, user_id, None, range)? {
+            match grouped.last_mut() {
+                Some((last, sets)) if last.id == exercise.id => sets.push((log, set)),
+                _ => grouped.push((exercise, vec![(log, set)])),
+            }
+        }
+
+        Ok(grouped.into_iter()
+            .map(|(exercise, sets)| ExerciseProgress::from_sets(exercise, &sets))
+            .collect())
+    }
+
+    fn get_exercise_progress(&self, user_id: i64, exercise_uuid: Uuid, range: &ReportRange) -> Result<ExerciseProgress, ReportError> {
+        use crate::schema::public::exercises;
+        let mut conn = self.pool.get()?;
+
+        let exercise = exercises::table
+            .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
+            .filter(exercises::uuid.eq(exercise_uuid))
+            .first::<Exercise>(&mut conn)
+            .map_err(ReportError::from)?;
+
+        let sets = Self::load_logged_sets(&mut conn, user_id, Some(exercise.id), range)?
+            .into_iter()
+            .map(|(_, log, set)| (log, set))
+            .collect::<Vec<_>>();
+
+        Ok(ExerciseProgress::from_sets(exercise, &sets))
+    }
+}
@@ -0,0 +1,149 @@
+use diesel::{prelude::*, r2d2::PoolError};
+use uuid::Uuid;
+use crate::{db::config::DbPool, models::{scheduled_workout::{CreateScheduledWorkout, ScheduleFilter, ScheduledWorkout, UpdateScheduledWorkout}, workout::Workout}};
+
+#[derive(Debug)]
+pub enum ScheduleError {
+    NotFound,
+    WorkoutNotFound,
+    DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
+}
+
+impl From<diesel::result::Error> for ScheduleError {
+    fn from(err: diesel::result::Error) -> ScheduleError {
+        match err {
+            diesel::result::Error::NotFound => ScheduleError::NotFound,
+            _ => ScheduleError::DatabaseError(err),
+        }
+    }
+}
+
+impl From<PoolError> for ScheduleError {
+    fn from(_: PoolError) -> ScheduleError {
+        ScheduleError::ConnectionUnavailable
+    }
+}
+
+pub trait ScheduleRepository: Send + Sync + 'static {
+    fn schedule_workout(&self, user_id: i64, scheduled: CreateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError>;
+    fn get_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(ScheduledWorkout, Workout), ScheduleError>;
+    fn list_scheduled_workouts(&self, user_id: i64, filter: ScheduleFilter) -> Result<Vec<(ScheduledWorkout, Workout)>, ScheduleError>;
+    fn update_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid, scheduled: UpdateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError>;
+    fn delete_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(), ScheduleError>;
+}
+
+pub struct PgScheduleRepository {
+    pool: DbPool,
+}
+
+impl PgScheduleRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl ScheduleRepository for PgScheduleRepository {
+    fn schedule_workout(&self, user_id: i64, scheduled: CreateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        use crate::schema::public::{scheduled_workouts, workouts};
+        let mut conn = self.pool.get()?;
+
+        let workout = workouts::table
+            .filter(workouts::user_id.eq(user_id))
+            .filter(workouts::uuid.eq(scheduled.workout_uuid))
+            .first::<Workout>(&mut conn)
+            .optional()
+            .map_err(ScheduleError::from)?
+            .ok_or(ScheduleError::WorkoutNotFound)?;
+
+        let new_scheduled = ScheduledWorkout::new(user_id, workout.id, scheduled.scheduled_at, scheduled.notes);
+        let scheduled = diesel::insert_into(scheduled_workouts::table)
+            .values(&new_scheduled)
+            .get_result::<ScheduledWorkout>(&mut conn)
+            .map_err(ScheduleError::from)?;
+
+        Ok((scheduled, workout))
+    }
+
+    fn get_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        use crate::schema::public::{scheduled_workouts, workouts};
+        let mut conn = self.pool.get()?;
+
+        scheduled_workouts::table
+            .inner_join(workouts::table)
+            .filter(scheduled_workouts::user_id.eq(user_id))
+            .filter(scheduled_workouts::uuid.eq(scheduled_uuid))
+            .select((scheduled_workouts::all_columns, workouts::all_columns))
+            .first::<(ScheduledWorkout, Workout)>(&mut conn)
+            .map_err(ScheduleError::from)
+    }
+
+    fn list_scheduled_workouts(&self, user_id: i64, filter: ScheduleFilter) -> Result<Vec<(ScheduledWorkout, Workout)>, ScheduleError> {
+        use crate::schema::public::{scheduled_workouts, workouts};
+        let mut conn = self.pool.get()?;
+
+        let mut query = scheduled_workouts::table
+            .inner_join(workouts::table)
+            .filter(scheduled_workouts::user_id.eq(user_id))
+            .select((scheduled_workouts::all_columns, workouts::all_columns))
+            .order(scheduled_workouts::scheduled_at.asc())
+            .into_boxed();
+
+        if let Some(from) = filter.from {
+            query = query.filter(scheduled_workouts::scheduled_at.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(scheduled_workouts::scheduled_at.lt(to));
+        }
+        if let Some(status) = filter.status {
+            query = query.filter(scheduled_workouts::status.eq(status.as_str()));
+        }
+
+        query
+            .load::<(ScheduledWorkout, Workout)>(&mut conn)
+            .map_err(ScheduleError::from)
+    }
+
+    fn update_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid, scheduled: UpdateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        use crate::schema::public::{scheduled_workouts, workouts};
+        let mut conn = self.pool.get()?;
+
+        let (scheduled_exists, workout) = scheduled_workouts::table
+            .inner_join(workouts::table)
+            .filter(scheduled_workouts::user_id.eq(user_id))
+            .filter(scheduled_workouts::uuid.eq(scheduled_uuid))
+            .select((scheduled_workouts::all_columns, workouts::all_columns))
+            .first::<(ScheduledWorkout, Workout)>(&mut conn)
+            .map_err(ScheduleError::from)?;
+
+        let updated = diesel::update(scheduled_workouts::table)
+            .filter(scheduled_workouts::id.eq(scheduled_exists.id))
+            .set((
+                scheduled_workouts::scheduled_at.eq(scheduled.scheduled_at.unwrap_or(scheduled_exists.scheduled_at)),
+                scheduled_workouts::status.eq(scheduled.status.map(|status| status.as_str().to_string()).unwrap_or(scheduled_exists.status)),
+                scheduled_workouts::notes.eq(scheduled.notes.or(scheduled_exists.notes)),
+                scheduled_workouts::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<ScheduledWorkout>(&mut conn)
+            .map_err(ScheduleError::from)?;
+
+        Ok((updated, workout))
+    }
+
+    fn delete_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(), ScheduleError> {
+        use crate::schema::public::scheduled_workouts;
+        let mut conn = self.pool.get()?;
+
+        let result = diesel::delete(scheduled_workouts::table)
+            .filter(scheduled_workouts::user_id.eq(user_id))
+            .filter(scheduled_workouts::uuid.eq(scheduled_uuid))
+            .execute(&mut conn)
+            .map_err(ScheduleError::from)?;
+
+        if result == 0 {
+            return Err(ScheduleError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -1,14 +1,16 @@
-use diesel::prelude::*;
+use diesel::{prelude::*, r2d2::PoolError};
 use uuid::Uuid;
-use crate::{db, models::{exercise::Exercise, workout_exercise::WorkoutExercise}};
+use crate::{db::config::DbPool, models::{exercise::Exercise, workout_exercise::{Prescription, WorkoutExercise}}};
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
 pub enum WorkoutExerciseError {
     DatabaseError(String),
+    ConnectionUnavailable,
     NotFound,
     WorkoutNotFound,
     ExerciseNotFound,
+    InvalidPrescription,
     Unauthorized,
 }
 
@@ -29,24 +31,37 @@ impl From<diesel::result::Error> for WorkoutExerciseError {
     }
 }
 
-pub trait WorkoutExerciseRepository {
-    fn add_exercise_to_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid, order: i32) -> Result<(), WorkoutExerciseError>;
+impl From<PoolError> for WorkoutExerciseError {
+    fn from(_: PoolError) -> WorkoutExerciseError {
+        WorkoutExerciseError::ConnectionUnavailable
+    }
+}
+
+pub trait WorkoutExerciseRepository: Send + Sync + 'static {
+    fn add_exercise_to_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid, order: i32, prescription: Prescription) -> Result<(), WorkoutExerciseError>;
     fn remove_exercise_from_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid) -> Result<(), WorkoutExerciseError>;
-    fn list_workout_exercises(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<(Exercise, i32)>, WorkoutExerciseError>;
+    fn list_workout_exercises(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<(Exercise, WorkoutExercise)>, WorkoutExerciseError>;
 }
 
-pub struct PgWorkoutExerciseRepository;
+pub struct PgWorkoutExerciseRepository {
+    pool: DbPool,
+}
 
 impl PgWorkoutExerciseRepository {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
     }
 }
 
 impl WorkoutExerciseRepository for PgWorkoutExerciseRepository {
-    fn add_exercise_to_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid, order: i32) -> Result<(), WorkoutExerciseError> {
+    fn add_exercise_to_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid, order: i32, prescription: Prescription) -> Result<(), WorkoutExerciseError> {
         use crate::schema::public::{exercises, workouts, workout_exercises};
-        let mut conn = db::config::establish_connection();
+
+        if !prescription.is_valid() {
+            return Err(WorkoutExerciseError::InvalidPrescription);
+        }
+
+        let mut conn = self.pool.get()?;
 
         let workout_id = workouts::table
             .filter(workouts::user_id.eq(user_id))
@@ -55,18 +70,13 @@ impl WorkoutExerciseRepository for PgWorkoutExerciseRepository {
             .first::<i64>(&mut conn)
             .map_err(WorkoutExerciseError::from)?;
         let exercise_id = exercises::table
-            .filter(exercises::user_id.eq(user_id))
+            .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
             .filter(exercises::uuid.eq(exercise_uuid))
             .select(exercises::id)
             .first::<i64>(&mut conn)
             .map_err(WorkoutExerciseError::from)?;
 
-        let workout_exercise = WorkoutExercise {
-            workout_id,
-            exercise_id,
-            user_id,
-            order,
-        };
+        let workout_exercise = WorkoutExercise::new(workout_id, exercise_id, user_id, order, prescription);
 
         diesel::insert_into(workout_exercises::table)
             .values(&workout_exercise)
@@ -78,7 +88,7 @@ impl WorkoutExerciseRepository for PgWorkoutExerciseRepository {
 
     fn remove_exercise_from_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid) -> Result<(), WorkoutExerciseError> {
         use crate::schema::public::{exercises, workouts, workout_exercises};
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let workout_id = workouts::table
             .filter(workouts::user_id.eq(user_id))
@@ -87,7 +97,7 @@ impl WorkoutExerciseRepository for PgWorkoutExerciseRepository {
             .first::<i64>(&mut conn)
             .map_err(WorkoutExerciseError::from)?;
         let exercise_id = exercises::table
-            .filter(exercises::user_id.eq(user_id))
+            .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
             .filter(exercises::uuid.eq(exercise_uuid))
             .select(exercises::id)
             .first::<i64>(&mut conn)
@@ -107,9 +117,9 @@ impl WorkoutExerciseRepository for PgWorkoutExerciseRepository {
         Ok(())
     }
 
-    fn list_workout_exercises(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<(Exercise, i32)>, WorkoutExerciseError> {
+    fn list_workout_exercises(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<(Exercise, WorkoutExercise)>, WorkoutExerciseError> {
         use crate::schema::public::{exercises, workouts, workout_exercises};
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let workout_id = workouts::table
             .filter(workouts::user_id.eq(user_id))
@@ -124,8 +134,9 @@ impl WorkoutExerciseRepository for PgWorkoutExerciseRepository {
             ))
             .filter(workout_exercises::user_id.eq(user_id))
             .filter(workout_exercises::workout_id.eq(workout_id))
-            .select((exercises::all_columns, workout_exercises::order))
-            .load::<(Exercise, i32)>(&mut conn)
+            .order(workout_exercises::order.asc())
+            .select((exercises::all_columns, workout_exercises::all_columns))
+            .load::<(Exercise, WorkoutExercise)>(&mut conn)
             .map_err(WorkoutExerciseError::from)
     }
 } 
\ No newline at end of file
@@ -0,0 +1,180 @@
+use diesel::{prelude::*, r2d2::PoolError};
+use uuid::Uuid;
+use crate::{db::config::DbPool, models::{exercise::Exercise, personal_record::PersonalRecord, workout_log::{CreateWorkoutLog, WorkoutLog, WorkoutLogDetail, WorkoutLogSet}}, repositories::personal_record_repository::PgPersonalRecordRepository};
+
+#[derive(Debug)]
+pub enum WorkoutLogError {
+    NotFound,
+    WorkoutNotFound,
+    ExerciseNotFound,
+    InvalidSet,
+    DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
+}
+
+impl From<diesel::result::Error> for WorkoutLogError {
+    fn from(err: diesel::result::Error) -> WorkoutLogError {
+        match err {
+            diesel::result::Error::NotFound => WorkoutLogError::NotFound,
+            _ => WorkoutLogError::DatabaseError(err),
+        }
+    }
+}
+
+impl From<PoolError> for WorkoutLogError {
+    fn from(_: PoolError) -> WorkoutLogError {
+        WorkoutLogError::ConnectionUnavailable
+    }
+}
+
+pub trait WorkoutLogRepository: Send + Sync + 'static {
+    fn create_workout_log(&self, user_id: i64, workout_uuid: Uuid, log: CreateWorkoutLog) -> Result<WorkoutLogDetail, WorkoutLogError>;
+    fn get_workout_log(&self, user_id: i64, workout_uuid: Uuid, log_uuid: Uuid) -> Result<WorkoutLogDetail, WorkoutLogError>;
+    fn list_workout_logs(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<WorkoutLogDetail>, WorkoutLogError>;
+    fn delete_workout_log(&self, user_id: i64, workout_uuid: Uuid, log_uuid: Uuid) -> Result<(), WorkoutLogError>;
+}
+
+pub struct PgWorkoutLogRepository {
+    pool: DbPool,
+}
+
+impl PgWorkoutLogRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn find_workout_id(conn: &mut PgConnection, user_id: i64, workout_uuid: Uuid) -> Result<i64, WorkoutLogError> {
+        use crate::schema::public::workouts;
+
+        workouts::table
+            .filter(workouts::user_id.eq(user_id))
+            .filter(workouts::uuid.eq(workout_uuid))
+            .select(workouts::id)
+            .first::<i64>(conn)
+            .map_err(|err| match err {
+                diesel::result::Error::NotFound => WorkoutLogError::WorkoutNotFound,
+                _ => WorkoutLogError::from(err),
+            })
+    }
+
+    fn load_detail(conn: &mut PgConnection, log: WorkoutLog) -> Result<WorkoutLogDetail, WorkoutLogError> {
+        use crate::schema::public::{exercises, personal_records, workout_log_sets};
+
+        let sets = exercises::table
+            .inner_join(workout_log_sets::table.on(
+                exercises::id.eq(workout_log_sets::exercise_id)
+            ))
+            .filter(workout_log_sets::workout_log_id.eq(log.id))
+            .order((workout_log_sets::exercise_id.asc(), workout_log_sets::set_number.asc()))
+            .select((exercises::all_columns, workout_log_sets::all_columns))
+            .load::<(Exercise, WorkoutLogSet)>(conn)
+            .map_err(WorkoutLogError::from)?;
+
+        let personal_records = exercises::table
+            .inner_join(personal_records::table.on(
+                exercises::id.eq(personal_records::exercise_id)
+            ))
+            .filter(personal_records::workout_log_id.eq(log.id))
+            .order((personal_records::exercise_id.asc(), personal_records::id.asc()))
+            .select((exercises::all_columns, personal_records::all_columns))
+            .load::<(Exercise, PersonalRecord)>(conn)
+            .map_err(WorkoutLogError::from)?;
+
+        Ok(WorkoutLogDetail { log, sets, personal_records })
+    }
+}
+
+impl WorkoutLogRepository for PgWorkoutLogRepository {
+    fn create_workout_log(&self, user_id: i64, workout_uuid: Uuid, log: CreateWorkoutLog) -> Result<WorkoutLogDetail, WorkoutLogError> {
+        use crate::schema::public::{exercises, workout_logs, workout_log_sets};
+
+        if !log.is_valid() {
+            return Err(WorkoutLogError::InvalidSet);
+        }
+
+        let mut conn = self.pool.get()?;
+
+        conn.transaction(|conn| {
+            let workout_id = Self::find_workout_id(conn, user_id, workout_uuid)?;
+
+            let new_log = WorkoutLog::new(user_id, workout_id, log.performed_at, log.notes);
+            let workout_log = diesel::insert_into(workout_logs::table)
+                .values(&new_log)
+                .get_result::<WorkoutLog>(conn)
+                .map_err(WorkoutLogError::from)?;
+
+            for set in &log.sets {
+                let exercise_id = exercises::table
+                    .filter(exercises::user_id.eq(user_id).or(exercises::user_id.is_null()))
+                    .filter(exercises::uuid.eq(set.exercise_uuid))
+                    .select(exercises::id)
+                    .first::<i64>(conn)
+                    .map_err(|err| match err {
+                        diesel::result::Error::NotFound => WorkoutLogError::ExerciseNotFound,
+                        _ => WorkoutLogError::from(err),
+                    })?;
+
+                diesel::insert_into(workout_log_sets::table)
+                    .values(&WorkoutLogSet::new(workout_log.id, exercise_id, user_id, set))
+                    .execute(conn)
+                    .map_err(WorkoutLogError::from)?;
+            }
+
+            PgPersonalRecordRepository::record_personal_records(conn, &workout_log)
+                .map_err(WorkoutLogError::from)?;
+
+            Self::load_detail(conn, workout_log)
+        })
+    }
+
+    fn get_workout_log(&self, user_id: i64, workout_uuid: Uuid, log_uuid: Uuid) -> Result<WorkoutLogDetail, WorkoutLogError> {
+        use crate::schema::public::workout_logs;
+        let mut conn = self.pool.get()?;
+
+        let workout_id = Self::find_workout_id(&mut conn, user_id, workout_uuid)?;
+        let log = workout_logs::table
+            .filter(workout_logs::user_id.eq(user_id))
+            .filter(workout_logs::workout_id.eq(workout_id))
+            .filter(workout_logs::uuid.eq(log_uuid))
+            .first::<WorkoutLog>(&mut conn)
+            .map_err(WorkoutLogError::from)?;
+
+        Self::load_detail(&mut conn, log)
+    }
+
+    fn list_workout_logs(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<WorkoutLogDetail>, WorkoutLogError> {
+        use crate::schema::public::workout_logs;
+        let mut conn = self.pool.get()?;
+
+        let workout_id = Self::find_workout_id(&mut conn, user_id, workout_uuid)?;
+        let logs = workout_logs::table
+            .filter(workout_logs::user_id.eq(user_id))
+            .filter(workout_logs::workout_id.eq(workout_id))
+            .order(workout_logs::performed_at.desc())
+            .load::<WorkoutLog>(&mut conn)
+            .map_err(WorkoutLogError::from)?;
+
+        logs.into_iter()
+            .map(|log| Self::load_detail(&mut conn, log))
+            .collect()
+    }
+
+    fn delete_workout_log(&self, user_id: i64, workout_uuid: Uuid, log_uuid: Uuid) -> Result<(), WorkoutLogError> {
+        use crate::schema::public::workout_logs;
+        let mut conn = self.pool.get()?;
+
+        let workout_id = Self::find_workout_id(&mut conn, user_id, workout_uuid)?;
+        let result = diesel::delete(workout_logs::table)
+            .filter(workout_logs::user_id.eq(user_id))
+            .filter(workout_logs::workout_id.eq(workout_id))
+            .filter(workout_logs::uuid.eq(log_uuid))
+            .execute(&mut conn)
+            .map_err(WorkoutLogError::from)?;
+
+        if result == 0 {
+            return Err(WorkoutLogError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -1,11 +1,12 @@
-use diesel::prelude::*;
+use diesel::{prelude::*, r2d2::PoolError};
 use uuid::Uuid;
-use crate::{db, models::workout::{Workout, CreateWorkout, UpdateWorkout}};
+use crate::{db::config::DbPool, models::workout::{Workout, CreateWorkout, UpdateWorkout}};
 
 #[derive(Debug)]
 pub enum WorkoutError {
     NotFound,
     DatabaseError(diesel::result::Error),
+    ConnectionUnavailable,
     Unauthorized,
 }
 
@@ -18,7 +19,13 @@ impl From<diesel::result::Error> for WorkoutError {
     }
 }
 
-pub trait WorkoutRepository {
+impl From<PoolError> for WorkoutError {
+    fn from(_: PoolError) -> WorkoutError {
+        WorkoutError::ConnectionUnavailable
+    }
+}
+
+pub trait WorkoutRepository: Send + Sync + 'static {
     fn create_workout(&self, user_id: i64, workout: CreateWorkout) -> Result<Workout, WorkoutError>;
     fn get_workout(&self, user_id: i64, workout_uuid: Uuid) -> Result<Workout, WorkoutError>;
     fn list_workouts(&self, user_id: i64) -> Result<Vec<Workout>, WorkoutError>;
@@ -26,18 +33,20 @@ pub trait WorkoutRepository {
     fn delete_workout(&self, user_id: i64, workout_uuid: Uuid) -> Result<(), WorkoutError>;
 }
 
-pub struct PgWorkoutRepository;
+pub struct PgWorkoutRepository {
+    pool: DbPool,
+}
 
 impl PgWorkoutRepository {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
     }
 }
 
 impl WorkoutRepository for PgWorkoutRepository {
     fn create_workout(&self, user_id: i64, workout: CreateWorkout) -> Result<Workout, WorkoutError> {
         use crate::schema::public::workouts;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let new_workout = Workout::new(
             user_id,
@@ -52,7 +61,7 @@ impl WorkoutRepository for PgWorkoutRepository {
 
     fn get_workout(&self, user_id: i64, workout_uuid: Uuid) -> Result<Workout, WorkoutError> {
         use crate::schema::public::workouts;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let workout = workouts::table
             .filter(workouts::user_id.eq(user_id))
@@ -69,7 +78,7 @@ impl WorkoutRepository for PgWorkoutRepository {
 
     fn list_workouts(&self, user_id: i64) -> Result<Vec<Workout>, WorkoutError> {
         use crate::schema::public::workouts;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         workouts::table
             .filter(workouts::user_id.eq(user_id))
@@ -79,7 +88,7 @@ impl WorkoutRepository for PgWorkoutRepository {
 
     fn update_workout(&self, user_id: i64, workout_uuid: Uuid, workout: UpdateWorkout) -> Result<Workout, WorkoutError> {
         use crate::schema::public::workouts;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let workout_exists = workouts::table
             .filter(workouts::user_id.eq(user_id))
@@ -100,7 +109,7 @@ impl WorkoutRepository for PgWorkoutRepository {
 
     fn delete_workout(&self, user_id: i64, workout_uuid: Uuid) -> Result<(), WorkoutError> {
         use crate::schema::public::workouts;
-        let mut conn = db::config::establish_connection();
+        let mut conn = self.pool.get()?;
 
         let result = diesel::delete(workouts::table)
         .filter(workouts::user_id.eq(user_id))
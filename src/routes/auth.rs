@@ -1,7 +1,7 @@
 use actix_web::{cookie::{Cookie, SameSite}, http::StatusCode, web, HttpRequest, HttpResponse, Responder, Scope};
 use serde::{Deserialize, Serialize};
 use csrf::CsrfToken;
-use crate::{models::user::User, repositories::auth_repository::{AuthError, AuthRepository}};
+use crate::{mailer::{MailerError, Outbox}, oidc::{code_challenge, IdentityProvider, OidcError}, tokens, totp, models::{api_token::{unknown_scope, ApiToken, MAX_LIFETIME, MAX_NAME_LENGTH}, oidc_login_attempt::OIDC_LOGIN_LIFETIME, session::{Session, SessionClient}, two_factor::TwoFactorCode, user::User}, repositories::auth_repository::{AuthError, AuthRepository}, routes::{run_blocking, service_unavailable}};
 use time::Duration;
 
 #[derive(Serialize)]
@@ -19,54 +19,239 @@ pub struct RegisterRequest {
 pub struct LoginRequest {
     email: String,
     password: String,
+    /// Requests a long-lived session that survives browser restarts.
+    #[serde(default)]
+    remember_me: bool,
+}
+
+#[derive(Deserialize)]
+pub struct LoginLinkRequest {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginLinkVerifyRequest {
+    token: String,
+    #[serde(default)]
+    remember_me: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    token: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorConfirmRequest {
+    code: String,
+}
+
+/// Either the current code from the authenticator app or one of the recovery codes.
+#[derive(Deserialize)]
+pub struct TwoFactorVerifyRequest {
+    code: Option<String>,
+    recovery_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorDisableRequest {
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct TwoFactorEnrollmentResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiTokenRequest {
+    name: String,
+    scopes: Vec<String>,
+    /// Leave out for a token that doesn't expire.
+    expires_in_days: Option<i64>,
+}
+
+/// What the identity provider appended to the redirect URI.
+#[derive(Deserialize)]
+pub struct OidcCallbackRequest {
+    code: String,
+    state: String,
 }
 
 #[derive(Serialize)]
 pub struct LoginResponse {
     uuid: uuid::Uuid,
     email: String,
+    email_verified: bool,
+    /// The session only works for `/auth/2fa/verify` until the second factor is given.
+    two_factor_required: bool,
+    csrf_token: String,
 }
 
 impl LoginResponse {
-    fn new(user: &User) -> Self {
+    fn new(user: &User, session: &Session) -> Self {
         Self {
             uuid: user.uuid,
             email: user.email.clone(),
+            email_verified: user.is_email_verified(),
+            two_factor_required: session.two_factor_pending,
+            csrf_token: session.csrf_token.clone(),
         }
     }
 }
 
+#[derive(Serialize)]
+struct SessionResponse {
+    id: uuid::Uuid,
+    created_at: chrono::NaiveDateTime,
+    last_seen_at: chrono::NaiveDateTime,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    current: bool,
+}
+
+impl SessionResponse {
+    fn from(session: &Session, current_token: &str) -> Self {
+        Self {
+            id: session.uuid,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            user_agent: session.user_agent.clone(),
+            ip_address: session.ip_address.clone(),
+            current: session.token == current_token,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiTokenResponse {
+    id: uuid::Uuid,
+    name: String,
+    scopes: Vec<String>,
+    expires_at: Option<chrono::NaiveDateTime>,
+    last_used_at: Option<chrono::NaiveDateTime>,
+    created_at: chrono::NaiveDateTime,
+    /// Only included when the token is created; it can't be retrieved afterwards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+impl ApiTokenResponse {
+    fn from(api_token: &ApiToken, token: Option<String>) -> Self {
+        Self {
+            id: api_token.uuid,
+            name: api_token.name.clone(),
+            scopes: api_token.scopes().into_iter().map(String::from).collect(),
+            expires_at: api_token.expires_at,
+            last_used_at: api_token.last_used_at,
+            created_at: api_token.created_at,
+            token,
+        }
+    }
+}
+
+fn session_client(req: &HttpRequest) -> SessionClient {
+    SessionClient {
+        user_agent: req.headers()
+            .get("user-agent")
+            .and_then(|h| h.to_str().ok())
+            .map(String::from),
+        ip_address: req.peer_addr().map(|addr| addr.ip().to_string()),
+    }
+}
+
 fn create_auth_response(
     user: User, 
-    session_id: String,
+    session: Session,
     status: actix_web::http::StatusCode,
-) -> Result<HttpResponse, AuthError> {
-    let response = LoginResponse::new(&user);
+) -> HttpResponse {
+    let response = LoginResponse::new(&user, &session);
 
-    Ok(HttpResponse::build(status)
-        .cookie(
-            Cookie::build("session_id", session_id)
-                .http_only(true)
-                .secure(true)
-                .same_site(SameSite::Strict)
-                .finish()
-        )
-        .json(response))
+    let mut cookie = Cookie::build("session_id", session.token.clone())
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .finish();
+    // Without "remember me" the cookie ends with the browser session
+    if session.remember_me && !session.two_factor_pending {
+        let max_age = session.absolute_expires_at - chrono::Utc::now().naive_utc();
+        cookie.set_max_age(Duration::seconds(max_age.num_seconds()));
+    }
+
+    HttpResponse::build(status)
+        .cookie(cookie)
+        .json(response)
 }
 
 pub fn get_scope<T: AuthRepository + 'static>() -> Scope {
     web::scope("/auth")
         .route("/csrf-token", web::get().to(get_csrf_token::<T>))
+        .route("/csrf-token", web::post().to(rotate_csrf_token::<T>))
         .route("/register", web::post().to(register::<T>))
         .route("/login", web::post().to(login::<T>))
+        .route("/login/link", web::post().to(request_login_link::<T>))
+        .route("/login/link/verify", web::post().to(login_with_link::<T>))
         .route("/logout", web::post().to(logout::<T>))
         .route("/user", web::delete().to(delete_user::<T>))
+        .route("/password", web::post().to(change_password::<T>))
+        .route("/password/forgot", web::post().to(forgot_password::<T>))
+        .route("/password/reset", web::post().to(reset_password::<T>))
+        .route("/email/verify", web::post().to(verify_email::<T>))
+        .route("/email/verification", web::post().to(resend_email_verification::<T>))
+        .route("/2fa/enroll", web::post().to(enroll_two_factor::<T>))
+        .route("/2fa/confirm", web::post().to(confirm_two_factor::<T>))
+        .route("/2fa/verify", web::post().to(verify_two_factor::<T>))
+        .route("/2fa/disable", web::post().to(disable_two_factor::<T>))
+        .route("/sessions", web::get().to(list_sessions::<T>))
+        .route("/sessions", web::delete().to(revoke_other_sessions::<T>))
+        .route("/sessions/{session_id}", web::delete().to(revoke_session::<T>))
+        .route("/tokens", web::get().to(list_api_tokens::<T>))
+        .route("/tokens", web::post().to(create_api_token::<T>))
+        .route("/tokens/{token_id}", web::delete().to(revoke_api_token::<T>))
+        .route("/oidc/authorize", web::post().to(start_oidc_login::<T>))
+        .route("/oidc/callback", web::post().to(finish_oidc_login::<T>))
 }
 
-async fn get_csrf_token<T: AuthRepository>(repo: web::Data<T>) -> impl Responder {
+fn new_csrf_token() -> String {
     let random_bytes: Vec<u8> = (0..32).map(|_| rand::random::<u8>()).collect();
-    let token = CsrfToken::new(random_bytes);
-    let temp_session = repo.create_temp_session(token.b64_string()).unwrap();
+    CsrfToken::new(random_bytes).b64_string()
+}
+
+async fn get_csrf_token<T: AuthRepository>(req: HttpRequest, repo: web::Data<T>) -> impl Responder {
+    // A logged-in client keeps using its session's token instead of starting a new handshake
+    if let Some(cookie) = req.cookie("session_id") {
+        let session_token = cookie.value().to_string();
+        match run_blocking(&repo, move |repo| repo.session_csrf_token(&session_token)).await {
+            Ok(csrf_token) => return HttpResponse::Ok().json(TokenResponse { csrf_token }),
+            Err(AuthError::ConnectionUnavailable) => return service_unavailable(),
+            Err(_) => {},
+        }
+    }
+
+    let csrf_token = new_csrf_token();
+    let temp_session = match run_blocking(&repo, move |repo| repo.create_temp_session(csrf_token)).await {
+        Ok(temp_session) => temp_session,
+        Err(AuthError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
     HttpResponse::Ok()
         .cookie(
             Cookie::build("session_id", temp_session.session_id)
@@ -80,28 +265,56 @@ async fn get_csrf_token<T: AuthRepository>(repo: web::Data<T>) -> impl Responder
         })
 }
 
+async fn rotate_csrf_token<T: AuthRepository>(req: HttpRequest, repo: web::Data<T>) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let csrf_token = new_csrf_token();
+    let rotated = csrf_token.clone();
+    match run_blocking(&repo, move |repo| repo.rotate_csrf_token(&session_token, rotated)).await {
+        Ok(()) => HttpResponse::Ok().json(TokenResponse { csrf_token }),
+        Err(AuthError::InvalidSession) => {
+            HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "Invalid session"
+            }))
+        },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish()
+    }
+}
+
 async fn register<T: AuthRepository>(
     user_data: web::Json<RegisterRequest>,
     req: HttpRequest,
     repo: web::Data<T>,
+    outbox: web::Data<Outbox>,
 ) -> impl Responder {
-    match repo.create_user(user_data.email.clone(), user_data.password.clone()) {
+    let user_data = user_data.into_inner();
+    match run_blocking(&repo, move |repo| repo.create_user(user_data.email, user_data.password)).await {
         Ok(user) => {
-            let csrf_token = req.headers()
-                .get("x-csrf-token")
-                .and_then(|h| h.to_str().ok())
-                .unwrap()
-                .to_string();
-            let session_id = req.cookie("session_id").unwrap().value().to_string();
-            repo.create_session(user.id, session_id.clone(), csrf_token).unwrap();
-            create_auth_response(user, session_id, StatusCode::CREATED)
-                .unwrap_or_else(|_| HttpResponse::InternalServerError().finish())
+            let previous_session_id = req.cookie("session_id").map(|c| c.value().to_string());
+            let (user_id, client) = (user.id, session_client(&req));
+            match run_blocking(&repo, move |repo| repo.create_session(user_id, previous_session_id.as_deref(), new_csrf_token(), client, false)).await {
+                Ok(session) => {
+                    // The account is usable either way; the user can ask for another email later
+                    if let Err(err) = send_email_verification(&repo, &outbox, user_id).await {
+                        log::warn!("Failed to issue email verification: {}", err);
+                    }
+                    create_auth_response(user, session, StatusCode::CREATED)
+                },
+                Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+                Err(_) => HttpResponse::InternalServerError().finish(),
+            }
         },
         Err(AuthError::DuplicateEmail) => {
             HttpResponse::Conflict().json(serde_json::json!({
                 "error": "Email already exists"
             }))
         },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish()
     }
 }
@@ -111,38 +324,49 @@ async fn login<T: AuthRepository>(
     req: HttpRequest,
     repo: web::Data<T>,
 ) -> impl Responder {
-    match repo.verify_credentials(user_data.email.clone(), user_data.password.clone()) {
+    let user_data = user_data.into_inner();
+    let remember_me = user_data.remember_me;
+    let client = session_client(&req);
+    let ip_address = client.ip_address.clone();
+    match run_blocking(&repo, move |repo| repo.verify_credentials(user_data.email, user_data.password, ip_address)).await {
         Ok(user) => {
-            let csrf_token = req.headers()
-                .get("x-csrf-token")
-                .and_then(|h| h.to_str().ok())
-                .unwrap()
-                .to_string();
-            let session_id = req.cookie("session_id").unwrap().value().to_string();
-            
-            // In case the client already has a valid session, we can't create a new session with the same session_id
-            if repo.validate_session(&session_id).is_err() {
-                repo.create_session(user.id, session_id.clone(), csrf_token).unwrap();
+            // The pre-login session id is replaced rather than promoted, to rule out session fixation
+            let previous_session_id = req.cookie("session_id").map(|c| c.value().to_string());
+            let user_id = user.id;
+            match run_blocking(&repo, move |repo| repo.create_session(user_id, previous_session_id.as_deref(), new_csrf_token(), client, remember_me)).await {
+                Ok(session) => create_auth_response(user, session, StatusCode::OK),
+                Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+                Err(_) => HttpResponse::InternalServerError().finish(),
             }
-            
-            create_auth_response(user, session_id, StatusCode::OK)
-                .unwrap_or_else(|_| HttpResponse::InternalServerError().finish())
         },
         Err(AuthError::InvalidCredentials) => {
             HttpResponse::Unauthorized().json(serde_json::json!({
                 "error": "Invalid credentials"
             }))
         },
+        Err(AuthError::TooManyAttempts(retry_after)) => too_many_attempts(retry_after, "Too many login attempts"),
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish()
     }
 }
 
+fn too_many_attempts(retry_after: chrono::Duration, error: &str) -> HttpResponse {
+    // Round up so clients that honor the header don't come back a moment too early
+    let seconds = (retry_after.num_milliseconds() + 999) / 1000;
+    HttpResponse::TooManyRequests()
+        .insert_header(("Retry-After", seconds.max(1).to_string()))
+        .json(serde_json::json!({
+            "error": error
+        }))
+}
+
 async fn logout<T: AuthRepository>(
     req: HttpRequest,
     repo: web::Data<T>,
 ) -> impl Responder {
     if let Some(cookie) = req.cookie("session_id") {
-        let _ = repo.invalidate_session(cookie.value());  // Best effort deletion
+        let session_token = cookie.value().to_string();
+        let _ = run_blocking(&repo, move |repo| repo.invalidate_session(&session_token)).await;  // Best effort deletion
     }
     HttpResponse::Ok()
         .cookie(
@@ -161,7 +385,8 @@ async fn delete_user<T: AuthRepository>(
     repo: web::Data<T>,
 ) -> impl Responder {
     if let Some(session_cookie) = req.cookie("session_id") {
-        match repo.delete_user(session_cookie.value()) {
+        let session_token = session_cookie.value().to_string();
+        match run_blocking(&repo, move |repo| repo.delete_user(&session_token)).await {
             Ok(_) => HttpResponse::NoContent()
                 .cookie(
                     Cookie::build("session_id", "")
@@ -177,6 +402,7 @@ async fn delete_user<T: AuthRepository>(
                     "error": "Invalid session"
                 }))
             },
+            Err(AuthError::ConnectionUnavailable) => service_unavailable(),
             Err(_) => HttpResponse::InternalServerError().finish()
         }
     } else {
@@ -185,3 +411,566 @@ async fn delete_user<T: AuthRepository>(
         }))
     }
 }
+
+async fn change_password<T: AuthRepository>(
+    password_data: web::Json<ChangePasswordRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let password_data = password_data.into_inner();
+    match run_blocking(&repo, move |repo| repo.change_password(&session_token, password_data.current_password, password_data.new_password)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(AuthError::InvalidCredentials) => {
+            HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "Invalid credentials"
+            }))
+        },
+        Err(AuthError::InvalidPassword) => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Password must not be empty"
+            }))
+        },
+        Err(AuthError::TooManyAttempts(retry_after)) => too_many_attempts(retry_after, "Too many login attempts"),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+/// Emails a link that logs in without the password. Like `forgot_password`, it answers the same
+/// whether or not the email is registered.
+async fn request_login_link<T: AuthRepository>(
+    link_data: web::Json<LoginLinkRequest>,
+    repo: web::Data<T>,
+    outbox: web::Data<Outbox>,
+) -> impl Responder {
+    let email = link_data.into_inner().email;
+    match run_blocking(&repo, move |repo| repo.create_login_link(email)).await {
+        Ok(link) => {
+            if let Some((user, token)) = link {
+                send_in_background(&outbox, "login link email", move |outbox| outbox.send_login_link(&user.email, &token));
+            }
+            HttpResponse::Accepted().json(serde_json::json!({
+                "message": "If the email is registered, a login link has been sent"
+            }))
+        },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Exchanges the token of a login link for a session, like `login` does with a password.
+async fn login_with_link<T: AuthRepository>(
+    link_data: web::Json<LoginLinkVerifyRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let LoginLinkVerifyRequest { token, remember_me } = link_data.into_inner();
+    let user = match run_blocking(&repo, move |repo| repo.redeem_login_link(&token)).await {
+        Ok(user) => user,
+        Err(AuthError::InvalidToken) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid or expired token"
+            }));
+        },
+        Err(AuthError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let previous_session_id = req.cookie("session_id").map(|c| c.value().to_string());
+    let (user_id, client) = (user.id, session_client(&req));
+    match run_blocking(&repo, move |repo| repo.create_session(user_id, previous_session_id.as_deref(), new_csrf_token(), client, remember_me)).await {
+        Ok(session) => create_auth_response(user, session, StatusCode::OK),
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Answers the same whether or not the email is registered, so it can't be used to find accounts.
+async fn forgot_password<T: AuthRepository>(
+    forgot_data: web::Json<ForgotPasswordRequest>,
+    repo: web::Data<T>,
+    outbox: web::Data<Outbox>,
+) -> impl Responder {
+    let email = forgot_data.into_inner().email;
+    match run_blocking(&repo, move |repo| repo.create_password_reset(email)).await {
+        Ok(reset) => {
+            if let Some((user, token)) = reset {
+                send_in_background(&outbox, "password reset email", move |outbox| outbox.send_password_reset(&user.email, &token));
+            }
+            HttpResponse::Accepted().json(serde_json::json!({
+                "message": "If the email is registered, a password reset link has been sent"
+            }))
+        },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn reset_password<T: AuthRepository>(
+    reset_data: web::Json<ResetPasswordRequest>,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let reset_data = reset_data.into_inner();
+    match run_blocking(&repo, move |repo| repo.reset_password(&reset_data.token, reset_data.new_password)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(AuthError::InvalidToken) => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid or expired token"
+            }))
+        },
+        Err(AuthError::InvalidPassword) => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Password must not be empty"
+            }))
+        },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Hands an email to the mailer without waiting for it. Answers to requests for unregistered
+/// addresses send nothing, so waiting on the mail server would tell them apart by their timing.
+/// A failed delivery is only logged, since the user can ask for another email.
+fn send_in_background<F>(outbox: &web::Data<Outbox>, email: &'static str, send: F)
+where
+    F: FnOnce(&Outbox) -> Result<(), MailerError> + Send + 'static,
+{
+    let outbox = outbox.clone();
+    actix_web::rt::spawn(async move {
+        if let Err(err) = run_blocking(&outbox, send).await {
+            log::warn!("Failed to send {}: {}", email, err);
+        }
+    });
+}
+
+/// Issues a verification token for the user and emails the link.
+async fn send_email_verification<T: AuthRepository>(
+    repo: &web::Data<T>,
+    outbox: &web::Data<Outbox>,
+    user_id: i64,
+) -> Result<(), AuthError> {
+    let (user, token) = run_blocking(repo, move |repo| repo.create_email_verification(user_id)).await?;
+    send_in_background(outbox, "verification email", move |outbox| outbox.send_email_verification(&user.email, &token));
+    Ok(())
+}
+
+async fn verify_email<T: AuthRepository>(
+    verify_data: web::Json<VerifyEmailRequest>,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let token = verify_data.into_inner().token;
+    match run_blocking(&repo, move |repo| repo.verify_email(&token)).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(AuthError::InvalidToken) => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid or expired token"
+            }))
+        },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn resend_email_verification<T: AuthRepository>(
+    req: HttpRequest,
+    repo: web::Data<T>,
+    outbox: web::Data<Outbox>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let user_id = match run_blocking(&repo, move |repo| repo.validate_session(&session_token)).await {
+        Ok(user_id) => user_id,
+        Err(err) => return sessions_error_response(err),
+    };
+
+    match send_email_verification(&repo, &outbox, user_id).await {
+        Ok(()) => HttpResponse::Accepted().json(serde_json::json!({
+            "message": "Verification email sent"
+        })),
+        Err(AuthError::AlreadyVerified) => {
+            HttpResponse::Conflict().json(serde_json::json!({
+                "error": "Email address is already verified"
+            }))
+        },
+        Err(AuthError::TooManyAttempts(retry_after)) => too_many_attempts(retry_after, "Too many verification emails"),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+fn two_factor_error_response(err: AuthError) -> HttpResponse {
+    match err {
+        AuthError::InvalidTwoFactorCode => HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Invalid two-factor code"
+        })),
+        AuthError::InvalidCredentials => HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Invalid credentials"
+        })),
+        AuthError::TwoFactorAlreadyEnabled => HttpResponse::Conflict().json(serde_json::json!({
+            "error": "Two-factor authentication is already enabled"
+        })),
+        AuthError::TwoFactorNotEnabled => HttpResponse::Conflict().json(serde_json::json!({
+            "error": "Two-factor authentication is not enabled"
+        })),
+        AuthError::TooManyAttempts(retry_after) => too_many_attempts(retry_after, "Too many two-factor attempts"),
+        err => sessions_error_response(err),
+    }
+}
+
+async fn enroll_two_factor<T: AuthRepository>(
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    match run_blocking(&repo, move |repo| repo.begin_totp_enrollment(&session_token)).await {
+        Ok((user, secret)) => HttpResponse::Ok().json(TwoFactorEnrollmentResponse {
+            otpauth_uri: totp::otpauth_uri(&secret, &user.email),
+            secret,
+        }),
+        Err(err) => two_factor_error_response(err),
+    }
+}
+
+async fn confirm_two_factor<T: AuthRepository>(
+    confirm_data: web::Json<TwoFactorConfirmRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let code = confirm_data.into_inner().code;
+    match run_blocking(&repo, move |repo| repo.confirm_totp_enrollment(&session_token, &code)).await {
+        Ok(recovery_codes) => HttpResponse::Ok().json(serde_json::json!({
+            "recovery_codes": recovery_codes
+        })),
+        Err(err) => two_factor_error_response(err),
+    }
+}
+
+async fn verify_two_factor<T: AuthRepository>(
+    verify_data: web::Json<TwoFactorVerifyRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let code = match verify_data.into_inner() {
+        TwoFactorVerifyRequest { code: Some(code), recovery_code: None } => TwoFactorCode::Totp(code),
+        TwoFactorVerifyRequest { code: None, recovery_code: Some(recovery_code) } => TwoFactorCode::Recovery(recovery_code),
+        _ => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Provide either code or recovery_code"
+            }));
+        },
+    };
+    match run_blocking(&repo, move |repo| repo.verify_two_factor(&session_token, code)).await {
+        Ok((user, session)) => create_auth_response(user, session, StatusCode::OK),
+        Err(err) => two_factor_error_response(err),
+    }
+}
+
+async fn disable_two_factor<T: AuthRepository>(
+    disable_data: web::Json<TwoFactorDisableRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let password = disable_data.into_inner().password;
+    match run_blocking(&repo, move |repo| repo.disable_totp(&session_token, password)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => two_factor_error_response(err),
+    }
+}
+
+fn sessions_error_response(err: AuthError) -> HttpResponse {
+    match err {
+        AuthError::InvalidSession => HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Invalid session"
+        })),
+        AuthError::NotFound => HttpResponse::NotFound().finish(),
+        AuthError::ConnectionUnavailable => service_unavailable(),
+        _ => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn list_sessions<T: AuthRepository>(
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let current_token = session_token.clone();
+    match run_blocking(&repo, move |repo| repo.list_sessions(&session_token)).await {
+        Ok(sessions) => HttpResponse::Ok().json(
+            sessions.iter()
+                .map(|session| SessionResponse::from(session, &current_token))
+                .collect::<Vec<_>>()
+        ),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+async fn revoke_session<T: AuthRepository>(
+    session_uuid: web::Path<uuid::Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    match run_blocking(&repo, move |repo| repo.revoke_session(&session_token, *session_uuid)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+/// Logs out every other device, keeping the session making the request.
+async fn revoke_other_sessions<T: AuthRepository>(
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    match run_blocking(&repo, move |repo| repo.revoke_other_sessions(&session_token)).await {
+        Ok(revoked) => HttpResponse::Ok().json(serde_json::json!({
+            "revoked": revoked
+        })),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+async fn create_api_token<T: AuthRepository>(
+    token_data: web::Json<CreateApiTokenRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    let CreateApiTokenRequest { name, mut scopes, expires_in_days } = token_data.into_inner();
+
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Name must be between 1 and {} characters", MAX_NAME_LENGTH)
+        }));
+    }
+    if scopes.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "At least one scope is required"
+        }));
+    }
+    if let Some(scope) = unknown_scope(&scopes) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown scope: {}", scope)
+        }));
+    }
+    scopes.sort();
+    scopes.dedup();
+    let expires_at = match expires_in_days {
+        Some(days) if !(1..=MAX_LIFETIME.num_days()).contains(&days) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("expires_in_days must be between 1 and {}", MAX_LIFETIME.num_days())
+            }));
+        },
+        Some(days) => Some(chrono::Utc::now().naive_utc() + chrono::Duration::days(days)),
+        None => None,
+    };
+
+    match run_blocking(&repo, move |repo| repo.create_api_token(&session_token, name, scopes, expires_at)).await {
+        Ok((api_token, token)) => HttpResponse::Created().json(ApiTokenResponse::from(&api_token, Some(token))),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+async fn list_api_tokens<T: AuthRepository>(
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    match run_blocking(&repo, move |repo| repo.list_api_tokens(&session_token)).await {
+        Ok(api_tokens) => HttpResponse::Ok().json(
+            api_tokens.iter()
+                .map(|api_token| ApiTokenResponse::from(api_token, None))
+                .collect::<Vec<_>>()
+        ),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+async fn revoke_api_token<T: AuthRepository>(
+    token_uuid: web::Path<uuid::Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let Some(cookie) = req.cookie("session_id") else {
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        }));
+    };
+    let session_token = cookie.value().to_string();
+    match run_blocking(&repo, move |repo| repo.revoke_api_token(&session_token, *token_uuid)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => sessions_error_response(err),
+    }
+}
+
+/// Remembers which sign-in this browser started, for as long as it may take.
+fn oidc_state_cookie(state: String) -> Cookie<'static> {
+    Cookie::build("oidc_state", state)
+        .path("/auth/oidc")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .max_age(Duration::seconds(OIDC_LOGIN_LIFETIME.num_seconds()))
+        .finish()
+}
+
+fn single_sign_on_disabled() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Single sign-on is not configured"
+    }))
+}
+
+fn invalid_oidc_login() -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({
+        "error": "Invalid or expired sign-in"
+    }))
+}
+
+fn identity_provider_error(err: OidcError) -> HttpResponse {
+    log::warn!("Sign-in with the identity provider failed: {}", err);
+    HttpResponse::BadGateway().json(serde_json::json!({
+        "error": "Sign-in with the identity provider failed"
+    }))
+}
+
+/// Returns the identity provider's sign-in page for the client to send the browser to.
+async fn start_oidc_login<T: AuthRepository>(
+    repo: web::Data<T>,
+    provider: Option<web::Data<dyn IdentityProvider>>,
+) -> impl Responder {
+    let Some(provider) = provider else {
+        return single_sign_on_disabled();
+    };
+    let (state, attempt) = match run_blocking(&repo, |repo| repo.create_oidc_login()).await {
+        Ok(created) => created,
+        Err(AuthError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let requested_state = state.clone();
+    let challenge = code_challenge(&attempt.code_verifier);
+    match run_blocking(&provider, move |provider| provider.authorization_url(&requested_state, &attempt.nonce, &challenge)).await {
+        Ok(authorization_url) => HttpResponse::Ok()
+            .cookie(oidc_state_cookie(state))
+            .json(serde_json::json!({
+                "authorization_url": authorization_url
+            })),
+        Err(err) => identity_provider_error(err),
+    }
+}
+
+/// Completes a sign-in once the identity provider sent the browser back, and logs in like
+/// `login` does.
+async fn finish_oidc_login<T: AuthRepository>(
+    callback: web::Json<OidcCallbackRequest>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+    provider: Option<web::Data<dyn IdentityProvider>>,
+) -> impl Responder {
+    let Some(provider) = provider else {
+        return single_sign_on_disabled();
+    };
+    let OidcCallbackRequest { code, state } = callback.into_inner();
+
+    // Only the browser that started the sign-in may finish it. Otherwise an attacker could log
+    // a victim into the attacker's account with a code of their own.
+    let started_here = req.cookie("oidc_state").is_some_and(|cookie| tokens::constant_time_eq(cookie.value(), &state));
+    if !started_here {
+        return invalid_oidc_login();
+    }
+    let attempt = match run_blocking(&repo, move |repo| repo.take_oidc_login(&state)).await {
+        Ok(attempt) => attempt,
+        Err(AuthError::InvalidToken) => return invalid_oidc_login(),
+        Err(AuthError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let claims = match run_blocking(&provider, move |provider| provider.exchange_code(&code, &attempt.code_verifier, &attempt.nonce)).await {
+        Ok(claims) => claims,
+        Err(err) => return identity_provider_error(err),
+    };
+    let user = match run_blocking(&repo, move |repo| repo.sign_in_with_identity(claims)).await {
+        Ok(user) => user,
+        Err(AuthError::MissingEmail) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "The identity provider didn't share an email address"
+            }));
+        },
+        Err(AuthError::DuplicateEmail) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": "Email already exists"
+            }));
+        },
+        Err(AuthError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let previous_session_id = req.cookie("session_id").map(|c| c.value().to_string());
+    let (user_id, client) = (user.id, session_client(&req));
+    match run_blocking(&repo, move |repo| repo.create_session(user_id, previous_session_id.as_deref(), new_csrf_token(), client, false)).await {
+        Ok(session) => {
+            let mut response = create_auth_response(user, session, StatusCode::OK);
+            let _ = response.add_removal_cookie(&oidc_state_cookie(String::new()));  // Best effort, it expires anyway
+            response
+        },
+        Err(AuthError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
@@ -1,14 +1,21 @@
-use actix_web::{cookie::Cookie, test, web, App, HttpResponse};
+use actix_web::{cookie::Cookie, http::Method, test, web, App, HttpResponse};
 use serde_json::json;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use crate::{
-    middleware::{csrf::CsrfProtection, session::SessionProtection}, models::{session::Session, temp_session::TempSession, user::User}, repositories::auth_repository::{AuthError, AuthRepository}, routes::auth
+    mailer::{Email, Mailer, MailerError, Outbox}, middleware::{csrf::CsrfProtection, session::{EmailVerificationPolicy, SessionProtection}}, models::{api_token::{generate_api_token, ApiToken}, oidc_login_attempt::OidcLoginAttempt, user_identity::UserIdentity, login_throttle::{account_key, LoginThrottle, ThrottleScope}, email_token::{resend_retry_after, EmailToken, EmailTokenPurpose, RESEND_LIMIT, RESEND_WINDOW}, two_factor::{generate_recovery_code, normalize_recovery_code, RecoveryCode, TotpCredential, TwoFactorCode, RECOVERY_CODE_COUNT}, session::{Session, SessionClient, SessionConfig}, temp_session::TempSession, user::User}, repositories::auth_repository::{AuthError, AuthRepository}, routes::auth, totp, oidc::{IdentityClaims, IdentityProvider, OidcError}
 };
 
-pub struct MockAuthRepo {
+struct MockAuthRepo {
     users: Mutex<Vec<User>>,
     sessions: Mutex<Vec<Session>>,
     temp_sessions: Mutex<Vec<TempSession>>,
+    throttles: Mutex<Vec<LoginThrottle>>,
+    email_tokens: Mutex<Vec<EmailToken>>,
+    totp_credentials: Mutex<Vec<TotpCredential>>,
+    recovery_codes: Mutex<Vec<RecoveryCode>>,
+    api_tokens: Mutex<Vec<ApiToken>>,
+    oidc_logins: Mutex<Vec<OidcLoginAttempt>>,
+    identities: Mutex<Vec<UserIdentity>>,
 }
 
 impl MockAuthRepo {
@@ -17,8 +24,116 @@ impl MockAuthRepo {
             users: Mutex::new(vec![]),
             sessions: Mutex::new(vec![]),
             temp_sessions: Mutex::new(vec![]),
+            throttles: Mutex::new(vec![]),
+            email_tokens: Mutex::new(vec![]),
+            totp_credentials: Mutex::new(vec![]),
+            recovery_codes: Mutex::new(vec![]),
+            api_tokens: Mutex::new(vec![]),
+            oidc_logins: Mutex::new(vec![]),
+            identities: Mutex::new(vec![]),
         }
     }
+
+    /// The user of a fully logged in session, like `find_active_session` in the real repository.
+    fn active_session_user(&self, session_token: &str) -> Result<i64, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        self.sessions.lock().unwrap().iter()
+            .find(|s| s.token == session_token && s.is_active(now) && !s.two_factor_pending)
+            .map(|s| s.user_id)
+            .ok_or(AuthError::InvalidSession)
+    }
+
+    /// Issues a token of `purpose` under the resend limits, like the real repository.
+    fn issue_email_token(&self, user_id: i64, purpose: EmailTokenPurpose) -> Result<String, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut email_tokens = self.email_tokens.lock().unwrap();
+        let mut sent_at = email_tokens.iter()
+            .filter(|t| t.user_id == user_id && t.purpose == purpose.as_str() && t.created_at > now - RESEND_WINDOW)
+            .map(|t| t.created_at)
+            .collect::<Vec<_>>();
+        sent_at.sort_by(|a, b| b.cmp(a));
+        sent_at.truncate(RESEND_LIMIT);
+        if let Some(retry_after) = resend_retry_after(&sent_at, now) {
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
+
+        let token = crate::tokens::generate_token();
+        let new_token = EmailToken::new(user_id, purpose, &token, now);
+        let id = email_tokens.len() as i64 + 1;
+        email_tokens.push(EmailToken {
+            id,
+            user_id: new_token.user_id,
+            purpose: new_token.purpose,
+            token_hash: new_token.token_hash,
+            expires_at: new_token.expires_at,
+            used_at: None,
+            created_at: new_token.created_at,
+        });
+        Ok(token)
+    }
+
+    fn issue_email_token_quietly(&self, email: &str, purpose: EmailTokenPurpose) -> Result<Option<(User, String)>, AuthError> {
+        let Some(user) = self.users.lock().unwrap().iter().find(|u| u.email == email).cloned() else {
+            return Ok(None);
+        };
+        match self.issue_email_token(user.id, purpose) {
+            Ok(token) => Ok(Some((user, token))),
+            Err(AuthError::TooManyAttempts(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Uses up an unexpired token of `purpose` along with the user's other pending ones.
+    fn redeem_email_token(&self, token: &str, purpose: EmailTokenPurpose) -> Result<i64, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let token_hash = crate::tokens::hash_token(token);
+        let mut email_tokens = self.email_tokens.lock().unwrap();
+        let user_id = email_tokens.iter()
+            .find(|t| t.token_hash == token_hash && t.purpose == purpose.as_str() && t.used_at.is_none() && t.expires_at > now)
+            .ok_or(AuthError::InvalidToken)?
+            .user_id;
+        for email_token in email_tokens.iter_mut().filter(|t| t.user_id == user_id && t.purpose == purpose.as_str() && t.used_at.is_none()) {
+            email_token.used_at = Some(now);
+        }
+        Ok(user_id)
+    }
+}
+
+/// Keeps sent emails so tests can read the links in them.
+#[derive(Default)]
+struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl Mailer for RecordingMailer {
+    fn send(&self, email: &Email) -> Result<(), MailerError> {
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+fn outbox(mailer: Arc<RecordingMailer>) -> web::Data<Outbox> {
+    web::Data::new(Outbox::new(mailer, "https://app.example.com/".to_string()))
+}
+
+impl RecordingMailer {
+    /// Waits until `count` emails were sent, as the routes send them in the background.
+    async fn wait_for(&self, count: usize) {
+        for _ in 0..500 {
+            if self.sent.lock().unwrap().len() >= count {
+                return;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("Expected {} emails to be sent", count);
+    }
+
+    /// The token of the link in the last email sent.
+    fn last_token(&self) -> String {
+        let sent = self.sent.lock().unwrap();
+        let body = &sent.last().expect("No email was sent").body;
+        body.split("token=").nth(1).unwrap().split_whitespace().next().unwrap().to_string()
+    }
 }
 
 impl AuthRepository for MockAuthRepo {
@@ -35,8 +150,15 @@ impl AuthRepository for MockAuthRepo {
         Ok(session)
     }
 
-    fn create_session(&self, user_id: i64, session_id: String, csrf_token: String) -> Result<Session, AuthError> {
-        let new_session = Session::new(user_id, session_id, csrf_token);
+    fn create_session(&self, user_id: i64, previous_session_id: Option<&str>, csrf_token: String, client: SessionClient, remember_me: bool) -> Result<Session, AuthError> {
+        if let Some(previous_session_id) = previous_session_id {
+            self.temp_sessions.lock().unwrap().retain(|s| s.session_id != previous_session_id);
+            self.sessions.lock().unwrap().retain(|s| s.token != previous_session_id);
+        }
+        let mut new_session = Session::new(user_id, crate::tokens::generate_token(), csrf_token, remember_me, &SessionConfig::default());
+        if self.totp_credentials.lock().unwrap().iter().any(|c| c.user_id == user_id && c.is_confirmed()) {
+            new_session = new_session.pending_two_factor();
+        }
         let session = Session {
             id: 1,  // Mock ID
             user_id: new_session.user_id,
@@ -44,25 +166,85 @@ impl AuthRepository for MockAuthRepo {
             csrf_token: new_session.csrf_token,
             expires_at: new_session.expires_at,
             created_at: new_session.created_at,
+            uuid: new_session.uuid,
+            last_seen_at: new_session.last_seen_at,
+            user_agent: client.user_agent,
+            ip_address: client.ip_address,
+            remember_me: new_session.remember_me,
+            absolute_expires_at: new_session.absolute_expires_at,
+            two_factor_pending: new_session.two_factor_pending,
         };
         self.sessions.lock().unwrap().push(session.clone());
         Ok(session)
     } 
 
     fn validate_csrf(&self, session_id: &str, csrf_token: &str) -> Result<(), AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.iter().find(|s| s.token == session_id && s.is_active(now)) {
+            return session.accepts_csrf(csrf_token, now).then_some(()).ok_or(AuthError::InvalidCsrf);
+        }
+
         let temp_sessions = self.temp_sessions.lock().unwrap();
-        temp_sessions.iter()
-            .find(|s| s.session_id == session_id && s.csrf_token == csrf_token)
+        let temp_session = temp_sessions.iter()
+            .find(|s| s.session_id == session_id && s.is_active(now))
             .ok_or(AuthError::InvalidSession)?;
+        temp_session.accepts_csrf(csrf_token, now).then_some(()).ok_or(AuthError::InvalidCsrf)
+    }
+
+    fn session_csrf_token(&self, session_token: &str) -> Result<String, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let sessions = self.sessions.lock().unwrap();
+        sessions.iter()
+            .find(|s| s.token == session_token && s.is_active(now))
+            .map(|s| s.csrf_token.clone())
+            .ok_or(AuthError::InvalidSession)
+    }
+
+    fn rotate_csrf_token(&self, session_token: &str, csrf_token: String) -> Result<(), AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.iter_mut()
+            .find(|s| s.token == session_token && s.is_active(now))
+            .ok_or(AuthError::InvalidSession)?;
+        session.csrf_token = csrf_token;
         Ok(())
     }
 
-    fn verify_credentials(&self, email: String, password: String) -> Result<User, AuthError> {
+    fn verify_credentials(&self, email: String, password: String, ip_address: Option<String>) -> Result<User, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut keys = vec![(ThrottleScope::Account, account_key(&email))];
+        keys.extend(ip_address.map(|ip_address| (ThrottleScope::Ip, ip_address)));
+
+        let mut throttles = self.throttles.lock().unwrap();
+        let lockout = throttles.iter()
+            .filter(|t| keys.iter().any(|(scope, key)| t.scope == scope.as_str() && &t.key == key))
+            .filter_map(|t| t.retry_after(now))
+            .max();
+        if let Some(retry_after) = lockout {
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
+
         let users = self.users.lock().unwrap();
-        users.iter()
-            .find(|u| u.email == email && u.password_hash == password)
-            .cloned()
-            .ok_or(AuthError::InvalidCredentials)
+        match users.iter().find(|u| u.email == email && u.password_hash == password) {
+            Some(user) => {
+                throttles.retain(|t| !(t.scope == keys[0].0.as_str() && t.key == keys[0].1));
+                Ok(user.clone())
+            },
+            None => {
+                for (scope, key) in keys {
+                    let index = match throttles.iter().position(|t| t.scope == scope.as_str() && t.key == key) {
+                        Some(index) => index,
+                        None => {
+                            throttles.push(LoginThrottle::new(scope, key, now));
+                            throttles.len() - 1
+                        },
+                    };
+                    throttles[index].record_failure(&scope.policy(), now);
+                }
+                Err(AuthError::InvalidCredentials)
+            },
+        }
     }
 
     fn validate_session(&self, session_token: &str) -> Result<i64, AuthError> {
@@ -70,6 +252,9 @@ impl AuthRepository for MockAuthRepo {
         let session = sessions.iter()
             .find(|s| s.token == session_token)
             .ok_or(AuthError::InvalidSession)?;
+        if session.two_factor_pending {
+            return Err(AuthError::TwoFactorRequired);
+        }
         Ok(session.user_id)
     }
 
@@ -92,6 +277,7 @@ impl AuthRepository for MockAuthRepo {
             password_hash: new_user.password_hash,
             created_at: new_user.created_at,
             updated_at: new_user.updated_at,
+            email_verified_at: None,
         };
         users.push(user.clone());
         Ok(user)
@@ -114,6 +300,344 @@ impl AuthRepository for MockAuthRepo {
         
         Ok(())
     }
+
+    fn list_sessions(&self, session_token: &str) -> Result<Vec<Session>, AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions.iter().filter(|s| s.user_id == user_id).cloned().collect())
+    }
+
+    fn revoke_session(&self, session_token: &str, session_uuid: uuid::Uuid) -> Result<(), AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let count = sessions.len();
+        sessions.retain(|s| !(s.user_id == user_id && s.uuid == session_uuid));
+        if sessions.len() == count {
+            return Err(AuthError::NotFound);
+        }
+        Ok(())
+    }
+
+    fn revoke_other_sessions(&self, session_token: &str) -> Result<usize, AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let count = sessions.len();
+        sessions.retain(|s| s.user_id != user_id || s.token == session_token);
+        Ok(count - sessions.len())
+    }
+
+    fn change_password(&self, session_token: &str, current_password: String, new_password: String) -> Result<(), AuthError> {
+        if new_password.is_empty() {
+            return Err(AuthError::InvalidPassword);
+        }
+        let user_id = self.active_session_user(session_token)?;
+        let mut sessions = self.sessions.lock().unwrap();
+
+        let mut users = self.users.lock().unwrap();
+        let user = users.iter_mut()
+            .find(|u| u.id == user_id)
+            .ok_or(AuthError::NotFound)?;
+        if user.password_hash != current_password {
+            return Err(AuthError::InvalidCredentials);
+        }
+        user.password_hash = new_password;
+        sessions.retain(|s| s.user_id != user_id || s.token == session_token);
+        self.api_tokens.lock().unwrap().retain(|t| t.user_id != user_id);
+        Ok(())
+    }
+
+    fn create_password_reset(&self, email: String) -> Result<Option<(User, String)>, AuthError> {
+        self.issue_email_token_quietly(&email, EmailTokenPurpose::PasswordReset)
+    }
+
+    fn reset_password(&self, token: &str, new_password: String) -> Result<(), AuthError> {
+        if new_password.is_empty() {
+            return Err(AuthError::InvalidPassword);
+        }
+        let user_id = self.redeem_email_token(token, EmailTokenPurpose::PasswordReset)?;
+
+        let mut users = self.users.lock().unwrap();
+        let user = users.iter_mut()
+            .find(|u| u.id == user_id)
+            .ok_or(AuthError::NotFound)?;
+        user.password_hash = new_password;
+        self.sessions.lock().unwrap().retain(|s| s.user_id != user_id);
+        self.api_tokens.lock().unwrap().retain(|t| t.user_id != user_id);
+        Ok(())
+    }
+
+    fn create_email_verification(&self, user_id: i64) -> Result<(User, String), AuthError> {
+        let user = self.users.lock().unwrap().iter()
+            .find(|u| u.id == user_id)
+            .cloned()
+            .ok_or(AuthError::NotFound)?;
+        if user.is_email_verified() {
+            return Err(AuthError::AlreadyVerified);
+        }
+
+        let token = self.issue_email_token(user_id, EmailTokenPurpose::EmailVerification)?;
+        Ok((user, token))
+    }
+
+    fn verify_email(&self, token: &str) -> Result<User, AuthError> {
+        let user_id = self.redeem_email_token(token, EmailTokenPurpose::EmailVerification)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut users = self.users.lock().unwrap();
+        let user = users.iter_mut()
+            .find(|u| u.id == user_id)
+            .ok_or(AuthError::NotFound)?;
+        user.email_verified_at = Some(now);
+        Ok(user.clone())
+    }
+
+    fn email_verified(&self, user_id: i64) -> Result<bool, AuthError> {
+        self.users.lock().unwrap().iter()
+            .find(|u| u.id == user_id)
+            .map(User::is_email_verified)
+            .ok_or(AuthError::NotFound)
+    }
+
+    fn begin_totp_enrollment(&self, session_token: &str) -> Result<(User, String), AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let user = self.users.lock().unwrap().iter()
+            .find(|u| u.id == user_id)
+            .cloned()
+            .ok_or(AuthError::NotFound)?;
+
+        let mut credentials = self.totp_credentials.lock().unwrap();
+        if credentials.iter().any(|c| c.user_id == user_id && c.is_confirmed()) {
+            return Err(AuthError::TwoFactorAlreadyEnabled);
+        }
+        credentials.retain(|c| c.user_id != user_id);
+        let credential = TotpCredential::new(user_id, totp::generate_secret(), chrono::Utc::now().naive_utc());
+        credentials.push(credential.clone());
+        Ok((user, credential.secret))
+    }
+
+    fn confirm_totp_enrollment(&self, session_token: &str, code: &str) -> Result<Vec<String>, AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut credentials = self.totp_credentials.lock().unwrap();
+        let credential = credentials.iter_mut()
+            .find(|c| c.user_id == user_id)
+            .ok_or(AuthError::TwoFactorNotEnabled)?;
+        if credential.is_confirmed() {
+            return Err(AuthError::TwoFactorAlreadyEnabled);
+        }
+        let step = totp::verify(&credential.secret, code, totp::step_at(now.and_utc().timestamp()), None)
+            .ok_or(AuthError::InvalidTwoFactorCode)?;
+        credential.confirmed_at = Some(now);
+        credential.last_used_step = Some(step);
+
+        // A fast digest is enough for the mock
+        let codes = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect::<Vec<_>>();
+        let mut recovery_codes = self.recovery_codes.lock().unwrap();
+        recovery_codes.retain(|c| c.user_id != user_id);
+        for (id, code) in codes.iter().enumerate() {
+            recovery_codes.push(RecoveryCode {
+                id: id as i64 + 1,
+                user_id,
+                code_hash: crate::tokens::hash_token(&normalize_recovery_code(code)),
+                used_at: None,
+                created_at: now,
+            });
+        }
+        Ok(codes)
+    }
+
+    fn verify_two_factor(&self, session_token: &str, code: TwoFactorCode) -> Result<(User, Session), AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.iter_mut()
+            .find(|s| s.token == session_token && s.is_active(now) && s.two_factor_pending)
+            .ok_or(AuthError::InvalidSession)?;
+        let user = self.users.lock().unwrap().iter()
+            .find(|u| u.id == session.user_id)
+            .cloned()
+            .ok_or(AuthError::NotFound)?;
+
+        let mut credentials = self.totp_credentials.lock().unwrap();
+        let credential = credentials.iter_mut()
+            .find(|c| c.user_id == user.id && c.is_confirmed())
+            .ok_or(AuthError::TwoFactorNotEnabled)?;
+        match code {
+            TwoFactorCode::Totp(code) => {
+                let step = totp::verify(&credential.secret, &code, totp::step_at(now.and_utc().timestamp()), credential.last_used_step)
+                    .ok_or(AuthError::InvalidTwoFactorCode)?;
+                credential.last_used_step = Some(step);
+            },
+            TwoFactorCode::Recovery(code) => {
+                let code_hash = crate::tokens::hash_token(&normalize_recovery_code(&code));
+                let mut recovery_codes = self.recovery_codes.lock().unwrap();
+                let recovery_code = recovery_codes.iter_mut()
+                    .find(|c| c.user_id == user.id && c.used_at.is_none() && c.code_hash == code_hash)
+                    .ok_or(AuthError::InvalidTwoFactorCode)?;
+                recovery_code.used_at = Some(now);
+            },
+        }
+
+        session.token = crate::tokens::generate_token();
+        session.two_factor_pending = false;
+        session.expires_at = session.sliding_expiry(now, &SessionConfig::default());
+        Ok((user, session.clone()))
+    }
+
+    fn disable_totp(&self, session_token: &str, password: String) -> Result<(), AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let password_matches = self.users.lock().unwrap().iter()
+            .any(|u| u.id == user_id && u.password_hash == password);
+        if !password_matches {
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        let mut credentials = self.totp_credentials.lock().unwrap();
+        if !credentials.iter().any(|c| c.user_id == user_id) {
+            return Err(AuthError::TwoFactorNotEnabled);
+        }
+        credentials.retain(|c| c.user_id != user_id);
+        self.recovery_codes.lock().unwrap().retain(|c| c.user_id != user_id);
+        Ok(())
+    }
+
+    fn create_api_token(&self, session_token: &str, name: String, scopes: Vec<String>, expires_at: Option<chrono::NaiveDateTime>) -> Result<(ApiToken, String), AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let token = generate_api_token();
+        let new_token = ApiToken::new(user_id, name, &token, &scopes, expires_at, chrono::Utc::now().naive_utc());
+        let mut api_tokens = self.api_tokens.lock().unwrap();
+        let api_token = ApiToken {
+            id: api_tokens.len() as i64 + 1,
+            uuid: new_token.uuid,
+            user_id,
+            name: new_token.name,
+            token_hash: new_token.token_hash,
+            scopes: new_token.scopes,
+            expires_at: new_token.expires_at,
+            last_used_at: None,
+            created_at: new_token.created_at,
+        };
+        api_tokens.push(api_token.clone());
+        Ok((api_token, token))
+    }
+
+    fn list_api_tokens(&self, session_token: &str) -> Result<Vec<ApiToken>, AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        Ok(self.api_tokens.lock().unwrap().iter().filter(|t| t.user_id == user_id).cloned().collect())
+    }
+
+    fn revoke_api_token(&self, session_token: &str, token_uuid: uuid::Uuid) -> Result<(), AuthError> {
+        let user_id = self.active_session_user(session_token)?;
+        let mut api_tokens = self.api_tokens.lock().unwrap();
+        let count = api_tokens.len();
+        api_tokens.retain(|t| !(t.user_id == user_id && t.uuid == token_uuid));
+        if api_tokens.len() == count {
+            return Err(AuthError::NotFound);
+        }
+        Ok(())
+    }
+
+    fn authenticate_api_token(&self, token: &str) -> Result<ApiToken, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut api_tokens = self.api_tokens.lock().unwrap();
+        let api_token = api_tokens.iter_mut()
+            .find(|t| t.token_hash == crate::tokens::hash_token(token) && t.is_usable(now))
+            .ok_or(AuthError::InvalidToken)?;
+        api_token.last_used_at = Some(now);
+        Ok(api_token.clone())
+    }
+
+    fn create_oidc_login(&self) -> Result<(String, OidcLoginAttempt), AuthError> {
+        let state = crate::tokens::generate_token();
+        let new_attempt = OidcLoginAttempt::new(&state, crate::tokens::generate_token(), crate::oidc::generate_code_verifier(), chrono::Utc::now().naive_utc());
+        let attempt = OidcLoginAttempt {
+            id: 1,  // Mock ID
+            state_hash: new_attempt.state_hash,
+            nonce: new_attempt.nonce,
+            code_verifier: new_attempt.code_verifier,
+            expires_at: new_attempt.expires_at,
+            created_at: new_attempt.created_at,
+        };
+        self.oidc_logins.lock().unwrap().push(attempt.clone());
+        Ok((state, attempt))
+    }
+
+    fn take_oidc_login(&self, state: &str) -> Result<OidcLoginAttempt, AuthError> {
+        let mut attempts = self.oidc_logins.lock().unwrap();
+        let index = attempts.iter()
+            .position(|a| a.state_hash == crate::tokens::hash_token(state))
+            .ok_or(AuthError::InvalidToken)?;
+        let attempt = attempts.remove(index);
+        attempt.is_usable(chrono::Utc::now().naive_utc()).then_some(attempt).ok_or(AuthError::InvalidToken)
+    }
+
+    fn sign_in_with_identity(&self, claims: IdentityClaims) -> Result<User, AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut identities = self.identities.lock().unwrap();
+        let mut users = self.users.lock().unwrap();
+        if let Some(identity) = identities.iter().find(|i| i.issuer == claims.issuer && i.subject == claims.subject) {
+            return users.iter().find(|u| u.id == identity.user_id).cloned().ok_or(AuthError::NotFound);
+        }
+
+        let email = claims.email.clone().ok_or(AuthError::MissingEmail)?;
+        let user = match users.iter_mut().find(|u| u.email == email) {
+            Some(_) if !claims.email_verified => return Err(AuthError::DuplicateEmail),
+            Some(user) if user.is_email_verified() => user.clone(),
+            Some(user) => {
+                user.password_hash = crate::tokens::generate_token();
+                user.email_verified_at = Some(now);
+                let user_id = user.id;
+                self.sessions.lock().unwrap().retain(|s| s.user_id != user_id);
+                self.api_tokens.lock().unwrap().retain(|t| t.user_id != user_id);
+                self.recovery_codes.lock().unwrap().retain(|c| c.user_id != user_id);
+                self.totp_credentials.lock().unwrap().retain(|c| c.user_id != user_id);
+                identities.retain(|i| i.user_id != user_id);
+                user.clone()
+            },
+            None => {
+                let new_user = User::new(email.clone(), crate::tokens::generate_token());
+                let user = User {
+                    id: users.len() as i64 + 1,
+                    uuid: new_user.uuid,
+                    email,
+                    password_hash: new_user.password_hash,
+                    created_at: new_user.created_at,
+                    updated_at: new_user.updated_at,
+                    email_verified_at: claims.email_verified.then_some(now),
+                };
+                users.push(user.clone());
+                user
+            },
+        };
+        let new_identity = UserIdentity::new(user.id, claims.issuer, claims.subject, claims.email, now);
+        let id = identities.len() as i64 + 1;
+        identities.push(UserIdentity {
+            id,
+            user_id: new_identity.user_id,
+            issuer: new_identity.issuer,
+            subject: new_identity.subject,
+            email: new_identity.email,
+            created_at: new_identity.created_at,
+            last_login_at: new_identity.last_login_at,
+        });
+        Ok(user)
+    }
+
+    fn create_login_link(&self, email: String) -> Result<Option<(User, String)>, AuthError> {
+        self.issue_email_token_quietly(&email, EmailTokenPurpose::LoginLink)
+    }
+
+    fn redeem_login_link(&self, token: &str) -> Result<User, AuthError> {
+        let user_id = self.redeem_email_token(token, EmailTokenPurpose::LoginLink)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut users = self.users.lock().unwrap();
+        let user = users.iter_mut()
+            .find(|u| u.id == user_id)
+            .ok_or(AuthError::NotFound)?;
+        user.email_verified_at.get_or_insert(now);
+        Ok(user.clone())
+    }
 }
 
 #[actix_web::test]
@@ -124,6 +648,7 @@ async fn test_csrf_token_flow() {
         App::new()
             .wrap(CsrfProtection::<MockAuthRepo>::new())
             .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
             .service(auth::get_scope::<MockAuthRepo>())
     ).await;
 
@@ -178,6 +703,7 @@ async fn test_login_flow() {
     let app = test::init_service(
         App::new()
             .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
             .service(auth::get_scope::<MockAuthRepo>())
     ).await;
 
@@ -259,6 +785,7 @@ async fn test_guarded_route_on_register() {
     let app = test::init_service(
         App::new()
             .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
             .service(auth::get_scope::<MockAuthRepo>())
             .service(
                 web::scope("/api")
@@ -328,6 +855,7 @@ async fn test_guarded_route_on_login() {
     let app = test::init_service(
         App::new()
             .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
             .service(auth::get_scope::<MockAuthRepo>())
             .service(
                 web::scope("/api")
@@ -412,6 +940,7 @@ async fn test_logout() {
     let app = test::init_service(
         App::new()
             .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
             .service(auth::get_scope::<MockAuthRepo>())
             .service(
                 web::scope("/api")
@@ -479,6 +1008,7 @@ async fn test_delete_user() {
     let app = test::init_service(
         App::new()
             .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
             .service(auth::get_scope::<MockAuthRepo>())
             .service(
                 web::scope("/api")
@@ -542,4 +1072,2117 @@ async fn test_delete_user() {
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 401);
-}
\ No newline at end of file
+}
+/// Behaves like a repository whose connection pool is exhausted.
+struct UnavailableAuthRepo;
+
+impl AuthRepository for UnavailableAuthRepo {
+    fn create_temp_session(&self, _csrf_token: String) -> Result<TempSession, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_session(&self, _user_id: i64, _previous_session_id: Option<&str>, _csrf_token: String, _client: SessionClient, _remember_me: bool) -> Result<Session, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn validate_csrf(&self, _session_id: &str, _csrf_token: &str) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn session_csrf_token(&self, _session_token: &str) -> Result<String, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn rotate_csrf_token(&self, _session_token: &str, _csrf_token: String) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn verify_credentials(&self, _email: String, _password: String, _ip_address: Option<String>) -> Result<User, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_user(&self, _email: String, _password: String) -> Result<User, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn validate_session(&self, _session_token: &str) -> Result<i64, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn invalidate_session(&self, _session_token: &str) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn delete_user(&self, _session_token: &str) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn list_sessions(&self, _session_token: &str) -> Result<Vec<Session>, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn revoke_session(&self, _session_token: &str, _session_uuid: uuid::Uuid) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn revoke_other_sessions(&self, _session_token: &str) -> Result<usize, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn change_password(&self, _session_token: &str, _current_password: String, _new_password: String) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_password_reset(&self, _email: String) -> Result<Option<(User, String)>, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn reset_password(&self, _token: &str, _new_password: String) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_email_verification(&self, _user_id: i64) -> Result<(User, String), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn verify_email(&self, _token: &str) -> Result<User, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn email_verified(&self, _user_id: i64) -> Result<bool, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn begin_totp_enrollment(&self, _session_token: &str) -> Result<(User, String), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn confirm_totp_enrollment(&self, _session_token: &str, _code: &str) -> Result<Vec<String>, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn verify_two_factor(&self, _session_token: &str, _code: TwoFactorCode) -> Result<(User, Session), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn disable_totp(&self, _session_token: &str, _password: String) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_api_token(&self, _session_token: &str, _name: String, _scopes: Vec<String>, _expires_at: Option<chrono::NaiveDateTime>) -> Result<(ApiToken, String), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn list_api_tokens(&self, _session_token: &str) -> Result<Vec<ApiToken>, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn revoke_api_token(&self, _session_token: &str, _token_uuid: uuid::Uuid) -> Result<(), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn authenticate_api_token(&self, _token: &str) -> Result<ApiToken, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_oidc_login(&self) -> Result<(String, OidcLoginAttempt), AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn take_oidc_login(&self, _state: &str) -> Result<OidcLoginAttempt, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn sign_in_with_identity(&self, _claims: IdentityClaims) -> Result<User, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn create_login_link(&self, _email: String) -> Result<Option<(User, String)>, AuthError> { Err(AuthError::ConnectionUnavailable) }
+    fn redeem_login_link(&self, _token: &str) -> Result<User, AuthError> { Err(AuthError::ConnectionUnavailable) }
+}
+
+#[actix_web::test]
+async fn test_connection_unavailable() {
+    let repo = web::Data::new(UnavailableAuthRepo);
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<UnavailableAuthRepo>::new())
+            .app_data(repo.clone())
+            .service(auth::get_scope::<UnavailableAuthRepo>())
+            .service(
+                web::scope("/api")
+                    .wrap(SessionProtection::<UnavailableAuthRepo>::new())
+                    .route("/test", web::get().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/auth/csrf-token")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers().get("retry-after").unwrap(), "1");
+
+    // The session check reports the outage instead of rejecting the session
+    let req = test::TestRequest::get()
+        .uri("/api/test")
+        .cookie(Cookie::new("session_id", "some-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 503);
+
+    // and so does the CSRF check
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .cookie(Cookie::new("session_id", "some-session"))
+        .insert_header(("x-csrf-token", "some-token"))
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 503);
+}
+
+#[actix_web::test]
+async fn test_csrf_outlives_temp_session() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
+            .service(auth::get_scope::<MockAuthRepo>())
+            .route("/api/test", web::post().to(|| async { HttpResponse::Ok().finish() }))
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/auth/csrf-token")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let session_id = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .value()
+        .to_string();
+    let next_cookie = Cookie::new("session_id", session_id);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let csrf_token = body["csrf_token"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/auth/register")
+        .cookie(next_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let next_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let csrf_token = body["csrf_token"].as_str().unwrap().to_string();
+
+    // Let any handshake expire, as it does five minutes after fetching the token
+    for temp_session in mock_repo.temp_sessions.lock().unwrap().iter_mut() {
+        temp_session.expires_at = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(next_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // A logged-in client gets its session's token back instead of a new handshake
+    let req = test::TestRequest::get()
+        .uri("/auth/csrf-token")
+        .cookie(next_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.response().cookies().next().is_none());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["csrf_token"], csrf_token);
+
+    // Once the session itself expires the token is rejected
+    for session in mock_repo.sessions.lock().unwrap().iter_mut() {
+        session.expires_at = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(next_cookie)
+        .insert_header(("x-csrf-token", csrf_token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_csrf_token_rotation() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.sessions.lock().unwrap().push(Session {
+        id: 1,
+        user_id: 1,
+        token: "session-token".to_string(),
+        csrf_token: "old-token".to_string(),
+        expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        created_at: chrono::Utc::now().naive_utc(),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: chrono::Utc::now().naive_utc(),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        two_factor_pending: false,
+    });
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+            .route("/api/test", web::post().to(|| async { HttpResponse::Ok().finish() }))
+    ).await;
+
+    // Rotating requires the current token
+    let req = test::TestRequest::post()
+        .uri("/auth/csrf-token")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("x-csrf-token", "wrong-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/csrf-token")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("x-csrf-token", "old-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let new_token = body["csrf_token"].as_str().unwrap().to_string();
+    assert_ne!(new_token, "old-token");
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("x-csrf-token", "old-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("x-csrf-token", new_token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_csrf_expiry_boundary() {
+    let now = chrono::Utc::now().naive_utc();
+    let second = chrono::Duration::seconds(1);
+    let session = Session {
+        id: 1,
+        user_id: 1,
+        token: "session-token".to_string(),
+        csrf_token: "csrf-token".to_string(),
+        expires_at: now,
+        created_at: now - chrono::Duration::hours(24),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: chrono::Utc::now().naive_utc(),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: now,
+        two_factor_pending: false,
+    };
+    let temp_session = TempSession {
+        id: 1,
+        session_id: "session-token".to_string(),
+        csrf_token: "csrf-token".to_string(),
+        created_at: now - chrono::Duration::minutes(5),
+        expires_at: now,
+    };
+
+    assert!(session.accepts_csrf("csrf-token", now - second));
+    assert!(!session.accepts_csrf("csrf-token", now));
+    assert!(!session.accepts_csrf("csrf-token", now + second));
+    assert!(!session.accepts_csrf("other-token", now - second));
+
+    assert!(temp_session.accepts_csrf("csrf-token", now - second));
+    assert!(!temp_session.accepts_csrf("csrf-token", now));
+    assert!(!temp_session.accepts_csrf("csrf-token", now + second));
+    assert!(!temp_session.accepts_csrf("other-token", now - second));
+}
+
+#[actix_web::test]
+async fn test_csrf_state_changing_methods() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.sessions.lock().unwrap().push(Session {
+        id: 1,
+        user_id: 1,
+        token: "session-token".to_string(),
+        csrf_token: "csrf-token".to_string(),
+        expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        created_at: chrono::Utc::now().naive_utc(),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: chrono::Utc::now().naive_utc(),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        two_factor_pending: false,
+    });
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new().ignore(["/api/token"]))
+            .app_data(mock_repo.clone())
+            .route("/api/test", web::route().to(|| async { HttpResponse::Ok().finish() }))
+            .route("/api/token", web::route().to(|| async { HttpResponse::Ok().finish() }))
+    ).await;
+
+    for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+        let req = test::TestRequest::default()
+            .method(method.clone())
+            .uri("/api/test")
+            .cookie(Cookie::new("session_id", "session-token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401, "{} without a CSRF token", method);
+
+        let req = test::TestRequest::default()
+            .method(method.clone())
+            .uri("/api/test")
+            .cookie(Cookie::new("session_id", "session-token"))
+            .insert_header(("x-csrf-token", "wrong-token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401, "{} with a wrong CSRF token", method);
+
+        let req = test::TestRequest::default()
+            .method(method.clone())
+            .uri("/api/test")
+            .cookie(Cookie::new("session_id", "session-token"))
+            .insert_header(("x-csrf-token", "csrf-token"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200, "{} with the CSRF token", method);
+
+        let req = test::TestRequest::default()
+            .method(method.clone())
+            .uri("/api/token")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200, "{} to an ignored path", method);
+    }
+
+    for method in [Method::GET, Method::HEAD, Method::OPTIONS] {
+        let req = test::TestRequest::default()
+            .method(method.clone())
+            .uri("/api/test")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200, "{} needs no CSRF token", method);
+    }
+}
+
+#[actix_web::test]
+async fn test_csrf_ignored_path_prefixes() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.sessions.lock().unwrap().push(Session {
+        id: 1,
+        user_id: 1,
+        token: "session-token".to_string(),
+        csrf_token: "csrf-token".to_string(),
+        expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        created_at: chrono::Utc::now().naive_utc(),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: chrono::Utc::now().naive_utc(),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        two_factor_pending: false,
+    });
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new().ignore(["/api/token"]))
+            .app_data(mock_repo.clone())
+            .route("/api/token/{id}", web::route().to(|| async { HttpResponse::Ok().finish() }))
+            .route("/api/tokens", web::route().to(|| async { HttpResponse::Ok().finish() }))
+    ).await;
+
+    // Paths below an ignored prefix need no CSRF token without the session cookie
+    let req = test::TestRequest::post()
+        .uri("/api/token/1")
+        .insert_header(("Authorization", "Bearer fwt_token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // With the cookie they could be forged, so the check applies again
+    let req = test::TestRequest::post()
+        .uri("/api/token/1")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("Authorization", "Bearer fwt_token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/api/token/1")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("x-csrf-token", "csrf-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // Prefixes match whole path segments only
+    let req = test::TestRequest::post()
+        .uri("/api/tokens")
+        .insert_header(("Authorization", "Bearer fwt_token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_delete_user_requires_csrf() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.sessions.lock().unwrap().push(Session {
+        id: 1,
+        user_id: 1,
+        token: "session-token".to_string(),
+        csrf_token: "csrf-token".to_string(),
+        expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        created_at: chrono::Utc::now().naive_utc(),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: chrono::Utc::now().naive_utc(),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        two_factor_pending: false,
+    });
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let req = test::TestRequest::delete()
+        .uri("/auth/user")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    assert_eq!(mock_repo.sessions.lock().unwrap().len(), 1);
+
+    let req = test::TestRequest::delete()
+        .uri("/auth/user")
+        .cookie(Cookie::new("session_id", "session-token"))
+        .insert_header(("x-csrf-token", "csrf-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+}
+
+#[actix_web::test]
+async fn test_session_id_rotation() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .app_data(outbox(Default::default()))
+            .service(auth::get_scope::<MockAuthRepo>())
+            .service(
+                web::scope("/api")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .route("/test", web::post().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/auth/csrf-token")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let pre_auth_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let pre_auth_csrf = body["csrf_token"].as_str().unwrap().to_string();
+
+    // Register issues a new session id and CSRF token and drops the temp session
+    let req = test::TestRequest::post()
+        .uri("/auth/register")
+        .cookie(pre_auth_cookie.clone())
+        .insert_header(("x-csrf-token", pre_auth_csrf.clone()))
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let registered_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let registered_csrf = body["csrf_token"].as_str().unwrap().to_string();
+    assert_ne!(registered_cookie.value(), pre_auth_cookie.value());
+    assert_ne!(registered_csrf, pre_auth_csrf);
+    assert!(mock_repo.temp_sessions.lock().unwrap().is_empty());
+
+    // The pre-auth id does not grant access
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(pre_auth_cookie.clone())
+        .insert_header(("x-csrf-token", pre_auth_csrf.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(registered_cookie.clone())
+        .insert_header(("x-csrf-token", registered_csrf.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // Logging in again rotates the id once more and ends the previous session
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .cookie(registered_cookie.clone())
+        .insert_header(("x-csrf-token", registered_csrf.clone()))
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let login_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let login_csrf = body["csrf_token"].as_str().unwrap().to_string();
+    assert_ne!(login_cookie.value(), registered_cookie.value());
+    assert_ne!(login_csrf, registered_csrf);
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(registered_cookie)
+        .insert_header(("x-csrf-token", registered_csrf))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/api/test")
+        .cookie(login_cookie)
+        .insert_header(("x-csrf-token", login_csrf))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_session_management() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    mock_repo.sessions.lock().unwrap().push(Session {
+        id: 99,
+        user_id: 2,
+        token: "other-user-session".to_string(),
+        csrf_token: "other-user-csrf".to_string(),
+        expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        created_at: chrono::Utc::now().naive_utc(),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: chrono::Utc::now().naive_utc(),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+        two_factor_pending: false,
+    });
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let mut devices = vec![];
+    for (user_agent, ip) in [("Gym Tablet", "10.0.0.1"), ("Phone", "10.0.0.2"), ("Laptop", "10.0.0.3")] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .insert_header(("user-agent", user_agent))
+            .peer_addr(format!("{}:4000", ip).parse().unwrap())
+            .set_json(json!({
+                "email": "test@example.com",
+                "password": "password123"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        devices.push(resp.response().cookies()
+            .find(|c| c.name() == "session_id")
+            .expect("Session cookie not found")
+            .into_owned());
+    }
+    let (tablet, phone, laptop) = (devices[0].clone(), devices[1].clone(), devices[2].clone());
+
+    let req = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .cookie(phone.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let sessions: serde_json::Value = test::read_body_json(resp).await;
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 3);
+    let current = sessions.iter().find(|s| s["current"] == true).unwrap();
+    assert_eq!(current["user_agent"], "Phone");
+    assert_eq!(current["ip_address"], "10.0.0.2");
+    assert!(current["created_at"].is_string());
+    assert!(current["last_seen_at"].is_string());
+    assert!(current.get("token").is_none());
+    let tablet_id = sessions.iter()
+        .find(|s| s["user_agent"] == "Gym Tablet")
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Sessions of other users can't be revoked
+    let other_user_id = mock_repo.sessions.lock().unwrap()[0].uuid;
+    let req = test::TestRequest::delete()
+        .uri(&format!("/auth/sessions/{}", other_user_id))
+        .cookie(phone.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // Kill the stale login on the gym tablet
+    let req = test::TestRequest::delete()
+        .uri(&format!("/auth/sessions/{}", tablet_id))
+        .cookie(phone.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .cookie(tablet)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    // Log out everywhere else
+    let req = test::TestRequest::delete()
+        .uri("/auth/sessions")
+        .cookie(phone.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["revoked"], 1);
+
+    let req = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .cookie(laptop)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .cookie(phone)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let sessions: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+
+    // The other user's session is untouched
+    assert!(mock_repo.sessions.lock().unwrap().iter().any(|s| s.token == "other-user-session"));
+
+    let req = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_remember_me() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let cookie = resp.response().cookies().find(|c| c.name() == "session_id").unwrap();
+    assert_eq!(cookie.max_age(), None);
+    assert!(!mock_repo.sessions.lock().unwrap()[0].remember_me);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123",
+            "remember_me": true
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let cookie = resp.response().cookies().find(|c| c.name() == "session_id").unwrap();
+    let max_age = cookie.max_age().expect("remember me cookie should persist");
+    let absolute = SessionConfig::default().remember_me.absolute.num_seconds();
+    assert!((absolute - 60..=absolute).contains(&max_age.whole_seconds()));
+
+    let sessions = mock_repo.sessions.lock().unwrap();
+    let session = sessions.iter().find(|s| s.token == cookie.value()).unwrap();
+    assert!(session.remember_me);
+}
+
+#[actix_web::test]
+async fn test_sliding_expiry() {
+    let config = SessionConfig::default();
+    let now = chrono::Utc::now().naive_utc();
+    let mut session = Session {
+        id: 1,
+        user_id: 1,
+        token: "session-token".to_string(),
+        csrf_token: "csrf-token".to_string(),
+        expires_at: now + chrono::Duration::hours(1),
+        created_at: now - chrono::Duration::hours(23),
+        uuid: uuid::Uuid::new_v4(),
+        last_seen_at: now - chrono::Duration::seconds(59),
+        user_agent: None,
+        ip_address: None,
+        remember_me: false,
+        absolute_expires_at: now + chrono::Duration::days(6),
+        two_factor_pending: false,
+    };
+
+    // Activity is only written back once a minute
+    assert!(!session.needs_touch(now));
+    assert!(session.needs_touch(now + chrono::Duration::seconds(1)));
+
+    // Each refresh restarts the idle timeout
+    assert_eq!(session.sliding_expiry(now, &config), now + config.standard.idle);
+
+    // but never past the absolute limit
+    session.absolute_expires_at = now + chrono::Duration::hours(2);
+    assert_eq!(session.sliding_expiry(now, &config), session.absolute_expires_at);
+
+    session.remember_me = true;
+    session.absolute_expires_at = now + config.remember_me.absolute;
+    assert_eq!(session.sliding_expiry(now, &config), now + config.remember_me.idle);
+
+    let new_session = Session::new(1, "token".to_string(), "csrf".to_string(), false, &config);
+    assert_eq!(new_session.expires_at - new_session.created_at, config.standard.idle);
+    assert_eq!(new_session.absolute_expires_at - new_session.created_at, config.standard.absolute);
+}
+
+#[actix_web::test]
+async fn test_login_throttling() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let login = |email: &str, password: &str, ip: &str| test::TestRequest::post()
+        .uri("/auth/login")
+        .peer_addr(format!("{}:4000", ip).parse().unwrap())
+        .set_json(json!({
+            "email": email,
+            "password": password
+        }))
+        .to_request();
+
+    // Failures within the free attempts are plain 401s
+    let free_attempts = ThrottleScope::Account.policy().free_attempts;
+    for _ in 0..=free_attempts {
+        let resp = test::call_service(&app, login("test@example.com", "wrong", "10.0.0.1")).await;
+        assert_eq!(resp.status(), 401);
+    }
+
+    // Now the account is locked, even for the right password, from any address and spelling
+    let resp = test::call_service(&app, login("Test@Example.com", "password123", "10.0.0.2")).await;
+    assert_eq!(resp.status(), 429);
+    let retry_after: i64 = resp.headers().get("retry-after").unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=30).contains(&retry_after));
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Too many login attempts");
+
+    // After the lockout the right password works and resets the account
+    for throttle in mock_repo.throttles.lock().unwrap().iter_mut() {
+        throttle.locked_until = Some(chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1));
+    }
+    let resp = test::call_service(&app, login("test@example.com", "password123", "10.0.0.1")).await;
+    assert_eq!(resp.status(), 200);
+    assert!(!mock_repo.throttles.lock().unwrap().iter().any(|t| t.scope == "account"));
+    let resp = test::call_service(&app, login("test@example.com", "wrong", "10.0.0.3")).await;
+    assert_eq!(resp.status(), 401);
+
+    // Spraying many accounts from one address locks out that address
+    let free_attempts = ThrottleScope::Ip.policy().free_attempts;
+    for i in 0..=free_attempts {
+        let resp = test::call_service(&app, login(&format!("user{}@example.com", i), "wrong", "10.0.0.9")).await;
+        assert_eq!(resp.status(), 401);
+    }
+    let resp = test::call_service(&app, login("test@example.com", "password123", "10.0.0.9")).await;
+    assert_eq!(resp.status(), 429);
+    let resp = test::call_service(&app, login("test@example.com", "password123", "10.0.0.1")).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_login_backoff() {
+    let policy = ThrottleScope::Account.policy();
+    assert_eq!(policy.lockout(policy.free_attempts), None);
+    assert_eq!(policy.lockout(policy.free_attempts + 1), Some(policy.base_delay));
+    assert_eq!(policy.lockout(policy.free_attempts + 2), Some(policy.base_delay * 2));
+    assert_eq!(policy.lockout(policy.free_attempts + 3), Some(policy.base_delay * 4));
+    assert_eq!(policy.lockout(policy.free_attempts + 50), Some(policy.max_delay));
+
+    let now = chrono::Utc::now().naive_utc();
+    let mut throttle = LoginThrottle::new(ThrottleScope::Account, "test@example.com".to_string(), now);
+    for _ in 0..=policy.free_attempts {
+        throttle.record_failure(&policy, now);
+    }
+    assert_eq!(throttle.retry_after(now), Some(policy.base_delay));
+    assert_eq!(throttle.retry_after(now + policy.base_delay), None);
+
+    // Old failures are forgotten
+    let later = now + policy.reset_after + chrono::Duration::seconds(1);
+    throttle.record_failure(&policy, later);
+    assert_eq!(throttle.failures, 1);
+    assert_eq!(throttle.retry_after(later), None);
+}
+
+#[actix_web::test]
+async fn test_change_password() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let mut devices = vec![];
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({
+                "email": "test@example.com",
+                "password": "password123"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        devices.push(resp.response().cookies()
+            .find(|c| c.name() == "session_id")
+            .expect("Session cookie not found")
+            .into_owned());
+    }
+    let (current, other) = (devices[0].clone(), devices[1].clone());
+    let (_, api_token) = mock_repo.create_api_token(current.value(), "CLI".to_string(), vec!["workouts:read".to_string()], None).unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password")
+        .cookie(current.clone())
+        .set_json(json!({
+            "current_password": "wrong",
+            "new_password": "newpassword456"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password")
+        .cookie(current.clone())
+        .set_json(json!({
+            "current_password": "password123",
+            "new_password": ""
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password")
+        .set_json(json!({
+            "current_password": "password123",
+            "new_password": "newpassword456"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password")
+        .cookie(current.clone())
+        .set_json(json!({
+            "current_password": "password123",
+            "new_password": "newpassword456"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    // Only the session that changed the password survives; API tokens end with the others
+    assert!(mock_repo.validate_session(current.value()).is_ok());
+    assert!(matches!(mock_repo.validate_session(other.value()), Err(AuthError::InvalidSession)));
+    assert!(matches!(mock_repo.authenticate_api_token(&api_token), Err(AuthError::InvalidToken)));
+
+    for (password, status) in [("password123", 401), ("newpassword456", 200)] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({
+                "email": "test@example.com",
+                "password": password
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+}
+
+#[actix_web::test]
+async fn test_password_reset() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    let mailer = Arc::new(RecordingMailer::default());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .app_data(outbox(mailer.clone()))
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let session_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+
+    // Unknown emails get the same answer, but no email
+    let req = test::TestRequest::post()
+        .uri("/auth/password/forgot")
+        .set_json(json!({ "email": "nobody@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 202);
+    let unknown_body: serde_json::Value = test::read_body_json(resp).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password/forgot")
+        .set_json(json!({ "email": "test@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 202);
+    let known_body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(known_body, unknown_body);
+    mailer.wait_for(1).await;
+    {
+        let sent = mailer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "test@example.com");
+        assert!(sent[0].body.contains("https://app.example.com/reset-password?token="));
+    }
+    let token = mailer.last_token();
+    // Only a hash of the token is stored
+    assert!(mock_repo.email_tokens.lock().unwrap().iter().all(|t| t.token_hash != token));
+
+    for (body, error) in [
+        (json!({ "token": "not-a-token", "new_password": "newpassword456" }), "Invalid or expired token"),
+        (json!({ "token": token, "new_password": "" }), "Password must not be empty"),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/auth/password/reset")
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], error);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password/reset")
+        .set_json(json!({ "token": token, "new_password": "newpassword456" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+    assert!(matches!(mock_repo.validate_session(session_cookie.value()), Err(AuthError::InvalidSession)));
+
+    // The token is single-use
+    let req = test::TestRequest::post()
+        .uri("/auth/password/reset")
+        .set_json(json!({ "token": token, "new_password": "anotherpassword789" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    for (password, status) in [("password123", 401), ("newpassword456", 200)] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({
+                "email": "test@example.com",
+                "password": password
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+}
+
+#[actix_web::test]
+async fn test_password_reset_token_expiry() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    // Another reset right away is dropped quietly, like verification emails
+    let create_reset = || {
+        let created = mock_repo.create_password_reset("test@example.com".to_string()).unwrap().unwrap();
+        assert!(mock_repo.create_password_reset("test@example.com".to_string()).unwrap().is_none());
+        for token in mock_repo.email_tokens.lock().unwrap().iter_mut() {
+            token.created_at -= chrono::Duration::minutes(2);
+        }
+        created.1
+    };
+    let (first, second, third) = (create_reset(), create_reset(), create_reset());
+    assert_eq!(mock_repo.email_tokens.lock().unwrap().len(), 3);
+
+    let now = chrono::Utc::now().naive_utc();
+    mock_repo.email_tokens.lock().unwrap()[0].expires_at = now;
+    assert!(matches!(mock_repo.reset_password(&first, "newpassword456".to_string()), Err(AuthError::InvalidToken)));
+
+    // Redeeming one token retires the others
+    mock_repo.reset_password(&second, "newpassword456".to_string()).unwrap();
+    assert!(matches!(mock_repo.reset_password(&third, "anotherpassword789".to_string()), Err(AuthError::InvalidToken)));
+}
+
+#[actix_web::test]
+async fn test_email_verification() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let mailer = Arc::new(RecordingMailer::default());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .app_data(outbox(mailer.clone()))
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let session_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email_verified"], false);
+    mailer.wait_for(1).await;
+    {
+        let sent = mailer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "test@example.com");
+        assert!(sent[0].body.contains("https://app.example.com/verify-email?token="));
+    }
+    let first_token = mailer.last_token();
+
+    // Resending right away is refused
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verification")
+        .cookie(session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 429);
+    assert!(resp.headers().contains_key("Retry-After"));
+    assert_eq!(mailer.sent.lock().unwrap().len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verification")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    // Once the interval has passed another email goes out
+    mock_repo.email_tokens.lock().unwrap()[0].created_at -= chrono::Duration::minutes(2);
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verification")
+        .cookie(session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 202);
+    mailer.wait_for(2).await;
+    assert_eq!(mailer.sent.lock().unwrap().len(), 2);
+    let second_token = mailer.last_token();
+
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verify")
+        .set_json(json!({ "token": "not-a-token" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Any link that was sent works, and using one retires the others
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verify")
+        .set_json(json!({ "token": first_token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verify")
+        .set_json(json!({ "token": second_token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/email/verification")
+        .cookie(session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({
+            "email": "test@example.com",
+            "password": "password123"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email_verified"], true);
+}
+
+#[actix_web::test]
+async fn test_email_verification_resend_limit() {
+    let mock_repo = MockAuthRepo::new();
+    let user = mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    mock_repo.create_email_verification(user.id).unwrap();
+
+    // Spread out over the hour, the interval alone never kicks in
+    for _ in 1..RESEND_LIMIT {
+        for token in mock_repo.email_tokens.lock().unwrap().iter_mut() {
+            token.created_at -= chrono::Duration::minutes(5);
+        }
+        mock_repo.create_email_verification(user.id).unwrap();
+    }
+    for token in mock_repo.email_tokens.lock().unwrap().iter_mut() {
+        token.created_at -= chrono::Duration::minutes(5);
+    }
+
+    // The oldest email was sent 25 minutes ago, so the window frees up in 35
+    let Err(AuthError::TooManyAttempts(retry_after)) = mock_repo.create_email_verification(user.id) else {
+        panic!("Expected the hourly limit to apply");
+    };
+    assert!(retry_after > chrono::Duration::minutes(34) && retry_after <= chrono::Duration::minutes(35));
+
+    mock_repo.email_tokens.lock().unwrap()[0].created_at -= chrono::Duration::minutes(36);
+    assert!(mock_repo.create_email_verification(user.id).is_ok());
+}
+
+#[actix_web::test]
+async fn test_email_verification_policy() {
+    for (policy, get_status, post_status) in [
+        (EmailVerificationPolicy::Optional, 200, 200),
+        (EmailVerificationPolicy::ReadOnly, 200, 403),
+        (EmailVerificationPolicy::Required, 403, 403),
+    ] {
+        let mock_repo = web::Data::new(MockAuthRepo::new());
+        let mailer = Arc::new(RecordingMailer::default());
+
+        let app = test::init_service(
+            App::new()
+                .app_data(mock_repo.clone())
+                .app_data(outbox(mailer.clone()))
+                .service(auth::get_scope::<MockAuthRepo>())
+                .service(
+                    web::scope("/api")
+                        .wrap(SessionProtection::<MockAuthRepo>::new().verification_policy(policy))
+                        .route("/test", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                        .route("/test", web::post().to(|| async { HttpResponse::Ok().finish() }))
+                )
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(json!({
+                "email": "test@example.com",
+                "password": "password123"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let session_cookie = resp.response().cookies()
+            .find(|c| c.name() == "session_id")
+            .expect("Session cookie not found")
+            .into_owned();
+
+        for (method, status) in [(Method::GET, get_status), (Method::POST, post_status)] {
+            let req = test::TestRequest::default()
+                .method(method)
+                .uri("/api/test")
+                .cookie(session_cookie.clone())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status, "{:?}", policy);
+        }
+
+        // Verification endpoints stay reachable, and verified users pass every policy
+        mailer.wait_for(1).await;
+        let req = test::TestRequest::post()
+            .uri("/auth/email/verify")
+            .set_json(json!({ "token": mailer.last_token() }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 204);
+
+        for method in [Method::GET, Method::POST] {
+            let req = test::TestRequest::default()
+                .method(method)
+                .uri("/api/test")
+                .cookie(session_cookie.clone())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 200, "{:?}", policy);
+        }
+    }
+}
+
+/// Logs in as the test user and evaluates to the session cookie and the response body.
+macro_rules! login_for_two_factor {
+    ($app:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({
+                "email": "test@example.com",
+                "password": "password123"
+            }))
+            .to_request();
+        let resp = test::call_service($app, req).await;
+        assert_eq!(resp.status(), 200);
+        let cookie = resp.response().cookies()
+            .find(|c| c.name() == "session_id")
+            .expect("Session cookie not found")
+            .into_owned();
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        (cookie, body)
+    }};
+}
+
+/// A code the real clock accepts next: one step ahead is within the allowed drift and later
+/// than any step used before.
+fn next_totp_code(secret: &str) -> String {
+    let step = totp::step_at(chrono::Utc::now().timestamp()) + 1;
+    totp::code_at(secret, step).unwrap()
+}
+
+#[actix_web::test]
+async fn test_two_factor_login() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+            .service(
+                web::scope("/api")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .route("/test", web::get().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    let (session_cookie, body) = login_for_two_factor!(&app);
+    assert_eq!(body["two_factor_required"], false);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/enroll")
+        .cookie(session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let secret = body["secret"].as_str().unwrap().to_string();
+    assert_eq!(
+        body["otpauth_uri"],
+        format!("otpauth://totp/Fitness%20Tracker:test%40example.com?secret={}&issuer=Fitness%20Tracker&algorithm=SHA1&digits=6&period=30", secret)
+    );
+
+    // Until enrollment is confirmed, logins stay single-factor
+    let (_, body) = login_for_two_factor!(&app);
+    assert_eq!(body["two_factor_required"], false);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/confirm")
+        .cookie(session_cookie.clone())
+        .set_json(json!({ "code": "abcdef" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/confirm")
+        .cookie(session_cookie.clone())
+        .set_json(json!({ "code": totp::code_at(&secret, totp::step_at(chrono::Utc::now().timestamp())).unwrap() }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let recovery_codes = body["recovery_codes"].as_array().unwrap().clone();
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+    // Only hashes are kept
+    assert!(mock_repo.recovery_codes.lock().unwrap().iter().all(|c| recovery_codes.iter().all(|code| c.code_hash != *code)));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/enroll")
+        .cookie(session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    // The password alone now only gets a pending session
+    let (pending_cookie, body) = login_for_two_factor!(&app);
+    assert_eq!(body["two_factor_required"], true);
+    assert!(pending_cookie.max_age().is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/api/test")
+        .cookie(pending_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Two-factor authentication required");
+
+    let req = test::TestRequest::get()
+        .uri("/auth/sessions")
+        .cookie(pending_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/verify")
+        .cookie(pending_cookie.clone())
+        .set_json(json!({ "code": "123", "recovery_code": "abc" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/verify")
+        .cookie(pending_cookie.clone())
+        .set_json(json!({ "code": "abcdef" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let code = next_totp_code(&secret);
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/verify")
+        .cookie(pending_cookie.clone())
+        .set_json(json!({ "code": code }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let verified_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    assert_ne!(verified_cookie.value(), pending_cookie.value());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["two_factor_required"], false);
+
+    for (cookie, status) in [(verified_cookie, 200), (pending_cookie, 401)] {
+        let req = test::TestRequest::get()
+            .uri("/api/test")
+            .cookie(cookie)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+
+    // A code can't be replayed for another login
+    let (pending_cookie, _) = login_for_two_factor!(&app);
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/verify")
+        .cookie(pending_cookie.clone())
+        .set_json(json!({ "code": code }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    // Recovery codes work once, however they are typed
+    let recovery_code = recovery_codes[0].as_str().unwrap().to_string();
+    for status in [200, 401] {
+        let (pending_cookie, _) = login_for_two_factor!(&app);
+        let req = test::TestRequest::post()
+            .uri("/auth/2fa/verify")
+            .cookie(pending_cookie)
+            .set_json(json!({ "recovery_code": format!(" {} ", recovery_code.to_uppercase()) }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+}
+
+#[actix_web::test]
+async fn test_two_factor_disable() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    let (session_cookie, _) = login_for_two_factor!(&app);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/disable")
+        .cookie(session_cookie.clone())
+        .set_json(json!({ "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    let (_, secret) = mock_repo.begin_totp_enrollment(session_cookie.value()).unwrap();
+    mock_repo.confirm_totp_enrollment(session_cookie.value(), &next_totp_code(&secret)).unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/disable")
+        .cookie(session_cookie.clone())
+        .set_json(json!({ "password": "wrong" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/2fa/disable")
+        .cookie(session_cookie.clone())
+        .set_json(json!({ "password": "password123" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+    assert!(mock_repo.recovery_codes.lock().unwrap().is_empty());
+
+    let (_, body) = login_for_two_factor!(&app);
+    assert_eq!(body["two_factor_required"], false);
+}
+
+#[actix_web::test]
+async fn test_api_token_management() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let user = mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    let session = mock_repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+    let session_cookie = Cookie::new("session_id", session.token.clone());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    for (body, error) in [
+        (json!({ "name": " ", "scopes": ["workouts:read"] }), "Name must be between 1 and 100 characters"),
+        (json!({ "name": "CLI", "scopes": [] }), "At least one scope is required"),
+        (json!({ "name": "CLI", "scopes": ["workouts:read", "admin"] }), "Unknown scope: admin"),
+        (json!({ "name": "CLI", "scopes": ["workouts:read"], "expires_in_days": 0 }), "expires_in_days must be between 1 and 365"),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/auth/tokens")
+            .cookie(session_cookie.clone())
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], error);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/auth/tokens")
+        .set_json(json!({ "name": "CLI", "scopes": ["workouts:read"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/tokens")
+        .cookie(session_cookie.clone())
+        .set_json(json!({
+            "name": "Watch sync",
+            "scopes": ["workouts:write", "workouts:read", "workouts:write"],
+            "expires_in_days": 30
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    let token = created["token"].as_str().unwrap();
+    assert!(token.starts_with("fwt_"));
+    assert_eq!(created["name"], "Watch sync");
+    assert_eq!(created["scopes"], json!(["workouts:read", "workouts:write"]));
+    assert!(created["expires_at"].is_string());
+    assert!(created["last_used_at"].is_null());
+    // Only the hash is kept
+    assert!(mock_repo.api_tokens.lock().unwrap().iter().all(|t| t.token_hash != token));
+
+    let req = test::TestRequest::get()
+        .uri("/auth/tokens")
+        .cookie(session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let listed = body.as_array().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["id"], created["id"]);
+    assert!(listed[0].get("token").is_none());
+
+    let uri = format!("/auth/tokens/{}", created["id"].as_str().unwrap());
+    for status in [204, 404] {
+        let req = test::TestRequest::delete()
+            .uri(&uri)
+            .cookie(session_cookie.clone())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+    }
+    assert!(mock_repo.api_tokens.lock().unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_api_token_authentication() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let user = mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    let session = mock_repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+    let scopes = |scopes: &[&str]| scopes.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let (_, read_token) = mock_repo.create_api_token(&session.token, "Reader".to_string(), scopes(&["workouts:read"]), None).unwrap();
+    let (_, write_token) = mock_repo.create_api_token(&session.token, "Writer".to_string(), scopes(&["workouts:write"]), None).unwrap();
+    let (_, expired_token) = mock_repo.create_api_token(
+        &session.token,
+        "Expired".to_string(),
+        scopes(&["workouts:read"]),
+        Some(chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1)),
+    ).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new().ignore(["/workouts", "/reports"]))
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .route("/workouts", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                    .route("/workouts", web::post().to(|| async { HttpResponse::Created().finish() }))
+                    .route("/reports/exercises", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                    .route("/other", web::get().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    let bearer = |token: &str| ("Authorization", format!("Bearer {}", token));
+
+    // No CSRF token is needed with a bearer token, but the scope has to match
+    for (method, uri, token, status, required_scope) in [
+        (Method::GET, "/workouts", &read_token, 200, None),
+        (Method::POST, "/workouts", &read_token, 403, Some("workouts:write")),
+        (Method::POST, "/workouts", &write_token, 201, None),
+        (Method::GET, "/workouts", &write_token, 403, Some("workouts:read")),
+        (Method::GET, "/reports/exercises", &read_token, 403, Some("reports:read")),
+        (Method::GET, "/other", &read_token, 403, None),
+    ] {
+        let req = test::TestRequest::default()
+            .method(method.clone())
+            .uri(uri)
+            .insert_header(bearer(token))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status, "{} {}", method, uri);
+        if status == 403 {
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["error"], "Insufficient scope");
+            assert_eq!(body["required_scope"], json!(required_scope));
+        }
+    }
+    assert!(mock_repo.api_tokens.lock().unwrap().iter().all(|t| t.name == "Expired" || t.last_used_at.is_some()));
+
+    for token in ["fwt_unknown", expired_token.as_str()] {
+        let req = test::TestRequest::get()
+            .uri("/workouts")
+            .insert_header(bearer(token))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Invalid token");
+    }
+
+    // With the session cookie along, the CSRF check still applies
+    let req = test::TestRequest::post()
+        .uri("/workouts")
+        .cookie(Cookie::new("session_id", session.token.clone()))
+        .insert_header(bearer(&write_token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Missing CSRF token or session");
+
+    // Tokens can't manage sessions or other tokens
+    let req = test::TestRequest::get()
+        .uri("/auth/tokens")
+        .insert_header(bearer(&read_token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+/// Stands in for the identity provider. Like a real one, it only redeems a code together with
+/// the verifier and nonce of a sign-in that was started with it.
+struct FakeIdentityProvider {
+    claims: Mutex<IdentityClaims>,
+    /// The nonce and code challenge of every authorization request.
+    requests: Mutex<Vec<(String, String)>>,
+}
+
+impl FakeIdentityProvider {
+    fn new(subject: &str, email: Option<&str>, email_verified: bool) -> Self {
+        Self {
+            claims: Mutex::new(IdentityClaims {
+                issuer: "https://idp.example.com".to_string(),
+                subject: subject.to_string(),
+                email: email.map(String::from),
+                email_verified,
+            }),
+            requests: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl IdentityProvider for FakeIdentityProvider {
+    fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, OidcError> {
+        self.requests.lock().unwrap().push((nonce.to_string(), code_challenge.to_string()));
+        Ok(format!("https://idp.example.com/authorize?state={}", state))
+    }
+
+    fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdentityClaims, OidcError> {
+        let challenge = crate::oidc::code_challenge(code_verifier);
+        let requested = self.requests.lock().unwrap().iter().any(|(n, c)| n == nonce && *c == challenge);
+        if code != "valid-code" || !requested {
+            return Err(OidcError::Request("invalid_grant".to_string()));
+        }
+        Ok(self.claims.lock().unwrap().clone())
+    }
+}
+
+fn identity_provider(provider: Arc<FakeIdentityProvider>) -> web::Data<dyn IdentityProvider> {
+    web::Data::from(provider as Arc<dyn IdentityProvider>)
+}
+
+/// Starts a sign-in from a fresh browser and evaluates to its session cookie, CSRF token and
+/// `oidc_state` cookie.
+macro_rules! start_oidc_login {
+    ($app:expr) => {{
+        let req = test::TestRequest::get()
+            .uri("/auth/csrf-token")
+            .to_request();
+        let resp = test::call_service($app, req).await;
+        let session_cookie = resp.response().cookies()
+            .find(|c| c.name() == "session_id")
+            .expect("Session cookie not found")
+            .into_owned();
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let csrf_token = body["csrf_token"].as_str().unwrap().to_string();
+
+        let req = test::TestRequest::post()
+            .uri("/auth/oidc/authorize")
+            .cookie(session_cookie.clone())
+            .insert_header(("x-csrf-token", csrf_token.clone()))
+            .to_request();
+        let resp = test::call_service($app, req).await;
+        assert_eq!(resp.status(), 200);
+        let state_cookie = resp.response().cookies()
+            .find(|c| c.name() == "oidc_state")
+            .expect("State cookie not found")
+            .into_owned();
+        assert_eq!(state_cookie.path(), Some("/auth/oidc"));
+        assert_eq!(state_cookie.http_only(), Some(true));
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["authorization_url"], format!("https://idp.example.com/authorize?state={}", state_cookie.value()));
+        (session_cookie, csrf_token, state_cookie)
+    }};
+}
+
+#[actix_web::test]
+async fn test_oidc_login() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let provider = Arc::new(FakeIdentityProvider::new("staff-1", Some("staff@example.com"), true));
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .app_data(identity_provider(provider.clone()))
+            .service(auth::get_scope::<MockAuthRepo>())
+            .service(
+                web::scope("/api")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .route("/test", web::get().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    let (session_cookie, csrf_token, state_cookie) = start_oidc_login!(&app);
+    let state = state_cookie.value().to_string();
+
+    // The callback has to come from the browser that started the sign-in
+    for (cookie, body) in [
+        (Some(state_cookie.clone()), json!({ "code": "valid-code", "state": "other-state" })),
+        (None, json!({ "code": "valid-code", "state": state })),
+    ] {
+        let mut req = test::TestRequest::post()
+            .uri("/auth/oidc/callback")
+            .cookie(session_cookie.clone())
+            .insert_header(("x-csrf-token", csrf_token.clone()))
+            .set_json(body);
+        if let Some(cookie) = cookie {
+            req = req.cookie(cookie);
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Invalid or expired sign-in");
+    }
+
+    // A code the provider rejects uses up the sign-in
+    let req = test::TestRequest::post()
+        .uri("/auth/oidc/callback")
+        .cookie(session_cookie.clone())
+        .cookie(state_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .set_json(json!({ "code": "wrong-code", "state": state }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 502);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Sign-in with the identity provider failed");
+    assert!(mock_repo.oidc_logins.lock().unwrap().is_empty());
+
+    let (session_cookie, csrf_token, state_cookie) = start_oidc_login!(&app);
+    let callback = json!({ "code": "valid-code", "state": state_cookie.value() });
+    let req = test::TestRequest::post()
+        .uri("/auth/oidc/callback")
+        .cookie(session_cookie.clone())
+        .cookie(state_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .set_json(&callback)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let new_session_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    assert_ne!(new_session_cookie.value(), session_cookie.value());
+    assert!(resp.response().cookies().any(|c| c.name() == "oidc_state" && c.value().is_empty()));
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email"], "staff@example.com");
+    assert_eq!(body["email_verified"], true);
+
+    let req = test::TestRequest::get()
+        .uri("/api/test")
+        .cookie(new_session_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    {
+        let users = mock_repo.users.lock().unwrap();
+        assert_eq!(users.len(), 1);
+        assert!(users[0].email_verified_at.is_some());
+        let identities = mock_repo.identities.lock().unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!((identities[0].user_id, identities[0].subject.as_str()), (users[0].id, "staff-1"));
+    }
+
+    // The state can't be replayed
+    let req = test::TestRequest::post()
+        .uri("/auth/oidc/callback")
+        .cookie(new_session_cookie.clone())
+        .cookie(state_cookie.clone())
+        .insert_header(("x-csrf-token", body["csrf_token"].as_str().unwrap()))
+        .set_json(&callback)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Signing in again finds the linked account, even after the email changed at the provider
+    provider.claims.lock().unwrap().email = Some("renamed@example.com".to_string());
+    let (session_cookie, csrf_token, state_cookie) = start_oidc_login!(&app);
+    let req = test::TestRequest::post()
+        .uri("/auth/oidc/callback")
+        .cookie(session_cookie)
+        .cookie(state_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token))
+        .set_json(json!({ "code": "valid-code", "state": state_cookie.value() }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email"], "staff@example.com");
+    assert_eq!(mock_repo.users.lock().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_oidc_login_linking() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let user = mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+    let provider = Arc::new(FakeIdentityProvider::new("staff-1", Some("test@example.com"), false));
+    // Whoever registered the address without verifying it may not own it
+    let squatter_session = mock_repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+    mock_repo.create_api_token(&squatter_session.token, "CLI".to_string(), vec!["workouts:read".to_string()], None).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .app_data(identity_provider(provider.clone()))
+            .service(auth::get_scope::<MockAuthRepo>())
+            .service(
+                web::scope("/api")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .route("/test", web::get().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    // An existing account is only linked when the provider vouches for the email address
+    for (email, email_verified, status, error) in [
+        (None, true, 400, Some("The identity provider didn't share an email address")),
+        (Some("test@example.com"), false, 409, Some("Email already exists")),
+        (Some("test@example.com"), true, 200, None),
+    ] {
+        {
+            let mut claims = provider.claims.lock().unwrap();
+            claims.email = email.map(String::from);
+            claims.email_verified = email_verified;
+        }
+        let (session_cookie, csrf_token, state_cookie) = start_oidc_login!(&app);
+        let req = test::TestRequest::post()
+            .uri("/auth/oidc/callback")
+            .cookie(session_cookie)
+            .cookie(state_cookie.clone())
+            .insert_header(("x-csrf-token", csrf_token))
+            .set_json(json!({ "code": "valid-code", "state": state_cookie.value() }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        match error {
+            Some(error) => assert_eq!(body["error"], error),
+            None => assert_eq!(body["uuid"], user.uuid.to_string()),
+        }
+    }
+
+    {
+        let users = mock_repo.users.lock().unwrap();
+        assert_eq!(users.len(), 1);
+        assert!(users[0].email_verified_at.is_some());
+        assert_eq!(mock_repo.identities.lock().unwrap()[0].user_id, user.id);
+    }
+
+    // The earlier password, session and API token no longer get in
+    let result = mock_repo.verify_credentials("test@example.com".to_string(), "password123".to_string(), None);
+    assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+    let req = test::TestRequest::get()
+        .uri("/api/test")
+        .cookie(Cookie::new("session_id", squatter_session.token.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    assert!(mock_repo.api_tokens.lock().unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_oidc_login_not_configured() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    for (uri, body) in [
+        ("/auth/oidc/authorize", json!({})),
+        ("/auth/oidc/callback", json!({ "code": "valid-code", "state": "state" })),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(uri)
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Single sign-on is not configured");
+    }
+    assert!(mock_repo.oidc_logins.lock().unwrap().is_empty());
+}
+
+/// Runs the pre-login CSRF handshake and evaluates to the temp session cookie and CSRF token.
+macro_rules! csrf_handshake {
+    ($app:expr) => {{
+        let req = test::TestRequest::get()
+            .uri("/auth/csrf-token")
+            .to_request();
+        let resp = test::call_service($app, req).await;
+        let cookie = resp.response().cookies()
+            .find(|c| c.name() == "session_id")
+            .expect("Session cookie not found")
+            .into_owned();
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        (cookie, body["csrf_token"].as_str().unwrap().to_string())
+    }};
+}
+
+#[actix_web::test]
+async fn test_login_link() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let mailer = Arc::new(RecordingMailer::default());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(CsrfProtection::<MockAuthRepo>::new())
+            .app_data(mock_repo.clone())
+            .app_data(outbox(mailer.clone()))
+            .service(auth::get_scope::<MockAuthRepo>())
+            .service(
+                web::scope("/api")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .route("/test", web::get().to(|| async { HttpResponse::Ok().finish() }))
+            )
+    ).await;
+
+    let (temp_cookie, csrf_token) = csrf_handshake!(&app);
+
+    // Requesting a link is part of the CSRF handshake like logging in
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link")
+        .cookie(temp_cookie.clone())
+        .set_json(json!({ "email": "test@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    // Unknown addresses get the same answer, but no email
+    for email in ["unknown@example.com", "test@example.com"] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login/link")
+            .cookie(temp_cookie.clone())
+            .insert_header(("x-csrf-token", csrf_token.clone()))
+            .set_json(json!({ "email": email }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["message"], "If the email is registered, a login link has been sent");
+    }
+    mailer.wait_for(1).await;
+    {
+        let sent = mailer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "test@example.com");
+        assert_eq!(sent[0].subject, "Your login link");
+        assert!(sent[0].body.contains("https://app.example.com/login-link?token="));
+    }
+    let token = mailer.last_token();
+    // Only the hash is kept
+    assert!(mock_repo.email_tokens.lock().unwrap().iter().all(|t| t.token_hash != token));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link/verify")
+        .cookie(temp_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .set_json(json!({ "token": "wrong-token" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Invalid or expired token");
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link/verify")
+        .cookie(temp_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .set_json(json!({ "token": token, "remember_me": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let session_cookie = resp.response().cookies()
+        .find(|c| c.name() == "session_id")
+        .expect("Session cookie not found")
+        .into_owned();
+    // The temp session is replaced, not promoted
+    assert_ne!(session_cookie.value(), temp_cookie.value());
+    assert!(session_cookie.max_age().is_some());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email"], "test@example.com");
+    // Following the link proved the address
+    assert_eq!(body["email_verified"], true);
+
+    let req = test::TestRequest::get()
+        .uri("/api/test")
+        .cookie(session_cookie)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // Links work once
+    let (temp_cookie, csrf_token) = csrf_handshake!(&app);
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link/verify")
+        .cookie(temp_cookie.clone())
+        .insert_header(("x-csrf-token", csrf_token.clone()))
+        .set_json(json!({ "token": token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_login_link_limits() {
+    let mock_repo = web::Data::new(MockAuthRepo::new());
+    let mailer = Arc::new(RecordingMailer::default());
+    mock_repo.create_user("test@example.com".to_string(), "password123".to_string()).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(mock_repo.clone())
+            .app_data(outbox(mailer.clone()))
+            .service(auth::get_scope::<MockAuthRepo>())
+    ).await;
+
+    // Another link right away is dropped quietly
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/auth/login/link")
+            .set_json(json!({ "email": "test@example.com" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+    }
+    mailer.wait_for(1).await;
+    assert_eq!(mailer.sent.lock().unwrap().len(), 1);
+    let first_token = mailer.last_token();
+
+    for token in mock_repo.email_tokens.lock().unwrap().iter_mut() {
+        token.created_at -= chrono::Duration::minutes(2);
+    }
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link")
+        .set_json(json!({ "email": "test@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 202);
+    mailer.wait_for(2).await;
+    assert_eq!(mailer.sent.lock().unwrap().len(), 2);
+    let second_token = mailer.last_token();
+
+    // Expired links are refused
+    mock_repo.email_tokens.lock().unwrap()[1].expires_at = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1);
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link/verify")
+        .set_json(json!({ "token": second_token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Using one link uses up the others
+    let req = test::TestRequest::post()
+        .uri("/auth/login/link/verify")
+        .set_json(json!({ "token": first_token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert!(mock_repo.email_tokens.lock().unwrap().iter().all(|t| t.used_at.is_some()));
+}
@@ -0,0 +1,157 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Resource, Responder, Scope};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{
+    ics,
+    models::scheduled_workout::ScheduleFilter,
+    repositories::{
+        calendar_feed_repository::{CalendarFeedError, CalendarFeedRepository},
+        recurring_schedule_repository::{RecurringScheduleError, RecurringScheduleRepository},
+        schedule_repository::{ScheduleError, ScheduleRepository},
+    },
+    routes::{run_blocking, service_unavailable},
+};
+
+const ICS_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+#[derive(Serialize)]
+struct FeedTokenResponse {
+    token: String,
+    url: String,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct FeedStatusResponse {
+    created_at: NaiveDateTime,
+}
+
+/// Session-protected management of the subscription URL and a direct `.ics` export.
+pub fn get_scope<F, S, R>() -> Scope
+where
+    F: CalendarFeedRepository + 'static,
+    S: ScheduleRepository + 'static,
+    R: RecurringScheduleRepository + 'static,
+{
+    web::scope("/calendar")
+        .route("/feed", web::get().to(get_feed::<F>))
+        .route("/feed", web::post().to(rotate_feed::<F>))
+        .route("/feed", web::delete().to(revoke_feed::<F>))
+        .route("/export.ics", web::get().to(export_calendar::<S, R>))
+}
+
+/// Public subscription URL. The token in the path authenticates the request, so this must be
+/// registered outside of `SessionProtection`.
+pub fn get_resource_feed<F, S, R>() -> Resource
+where
+    F: CalendarFeedRepository + 'static,
+    S: ScheduleRepository + 'static,
+    R: RecurringScheduleRepository + 'static,
+{
+    web::resource("/calendar/feed/{token}.ics")
+        .route(web::get().to(subscribe_feed::<F, S, R>))
+}
+
+fn error_response(err: CalendarFeedError) -> HttpResponse {
+    match err {
+        CalendarFeedError::NotFound => HttpResponse::NotFound().finish(),
+        CalendarFeedError::ConnectionUnavailable => service_unavailable(),
+        CalendarFeedError::DatabaseError(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn render_calendar<S: ScheduleRepository, R: RecurringScheduleRepository>(
+    user_id: i64,
+    schedule_repo: &web::Data<S>,
+    recurring_repo: &web::Data<R>,
+) -> HttpResponse {
+    let scheduled = match run_blocking(schedule_repo, move |repo| repo.list_scheduled_workouts(user_id, ScheduleFilter::default())).await {
+        Ok(scheduled) => scheduled,
+        Err(ScheduleError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    let recurring = match run_blocking(recurring_repo, move |repo| repo.list_recurring_schedules(user_id)).await {
+        Ok(recurring) => recurring,
+        Err(RecurringScheduleError::ConnectionUnavailable) => return service_unavailable(),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    HttpResponse::Ok()
+        .content_type(ICS_CONTENT_TYPE)
+        .body(ics::render_calendar(&scheduled, &recurring))
+}
+
+async fn get_feed<F: CalendarFeedRepository>(
+    req: HttpRequest,
+    repo: web::Data<F>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.get_feed_token(user_id)).await {
+        Ok(feed_token) => HttpResponse::Ok().json(FeedStatusResponse {
+            created_at: feed_token.created_at,
+        }),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn rotate_feed<F: CalendarFeedRepository>(
+    req: HttpRequest,
+    repo: web::Data<F>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.rotate_feed_token(user_id)).await {
+        Ok((feed_token, token)) => {
+            let connection_info = req.connection_info();
+            let url = format!(
+                "{}://{}/calendar/feed/{}.ics",
+                connection_info.scheme(),
+                connection_info.host(),
+                token
+            );
+            HttpResponse::Created().json(FeedTokenResponse {
+                token,
+                url,
+                created_at: feed_token.created_at,
+            })
+        },
+        Err(e) => error_response(e),
+    }
+}
+
+async fn revoke_feed<F: CalendarFeedRepository>(
+    req: HttpRequest,
+    repo: web::Data<F>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.revoke_feed_token(user_id)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn export_calendar<S: ScheduleRepository, R: RecurringScheduleRepository>(
+    req: HttpRequest,
+    schedule_repo: web::Data<S>,
+    recurring_repo: web::Data<R>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    render_calendar(user_id, &schedule_repo, &recurring_repo).await
+}
+
+async fn subscribe_feed<F, S, R>(
+    token: web::Path<String>,
+    feed_repo: web::Data<F>,
+    schedule_repo: web::Data<S>,
+    recurring_repo: web::Data<R>,
+) -> impl Responder
+where
+    F: CalendarFeedRepository,
+    S: ScheduleRepository,
+    R: RecurringScheduleRepository,
+{
+    match run_blocking(&feed_repo, move |repo| repo.find_user_by_feed_token(&token)).await {
+        Ok(user_id) => render_calendar(user_id, &schedule_repo, &recurring_repo).await,
+        Err(e) => error_response(e),
+    }
+}
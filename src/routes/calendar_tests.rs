@@ -0,0 +1,342 @@
+use actix_web::{cookie::Cookie, test, web, App};
+use chrono::NaiveDateTime;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    middleware::session::SessionProtection,
+    models::{
+        calendar_feed_token::CalendarFeedToken,
+        recurring_schedule::{CreateRecurringSchedule, CreateScheduleException, RecurringSchedule, RecurringScheduleDetail, RecurringScheduleException},
+        scheduled_workout::{CreateScheduledWorkout, ScheduleFilter, ScheduledWorkout, UpdateScheduledWorkout},
+        workout::Workout,
+    },
+    repositories::{
+        calendar_feed_repository::{CalendarFeedError, CalendarFeedRepository},
+        recurring_schedule_repository::{RecurringScheduleError, RecurringScheduleRepository},
+        schedule_repository::{ScheduleError, ScheduleRepository},
+    },
+    routes::test_support::{schedule_matches, MockAuthRepo},
+    tokens,
+};
+
+struct MockCalendarFeedRepo {
+    tokens: Mutex<Vec<CalendarFeedToken>>,
+}
+
+impl MockCalendarFeedRepo {
+    pub fn new() -> Self {
+        Self {
+            tokens: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl CalendarFeedRepository for MockCalendarFeedRepo {
+    fn rotate_feed_token(&self, user_id: i64) -> Result<(CalendarFeedToken, String), CalendarFeedError> {
+        let mut feed_tokens = self.tokens.lock().unwrap();
+        feed_tokens.retain(|t| t.user_id != user_id);
+
+        let token = tokens::generate_token();
+        let new_token = CalendarFeedToken::new(user_id, &token);
+        let feed_token = CalendarFeedToken {
+            id: (feed_tokens.len() + 1) as i64,
+            user_id,
+            token_hash: new_token.token_hash,
+            created_at: new_token.created_at,
+        };
+        feed_tokens.push(feed_token.clone());
+        Ok((feed_token, token))
+    }
+
+    fn get_feed_token(&self, user_id: i64) -> Result<CalendarFeedToken, CalendarFeedError> {
+        self.tokens.lock().unwrap().iter()
+            .find(|t| t.user_id == user_id)
+            .cloned()
+            .ok_or(CalendarFeedError::NotFound)
+    }
+
+    fn revoke_feed_token(&self, user_id: i64) -> Result<(), CalendarFeedError> {
+        let mut feed_tokens = self.tokens.lock().unwrap();
+        let initial_len = feed_tokens.len();
+        feed_tokens.retain(|t| t.user_id != user_id);
+
+        if feed_tokens.len() < initial_len {
+            Ok(())
+        } else {
+            Err(CalendarFeedError::NotFound)
+        }
+    }
+
+    fn find_user_by_feed_token(&self, token: &str) -> Result<i64, CalendarFeedError> {
+        let token_hash = tokens::hash_token(token);
+        self.tokens.lock().unwrap().iter()
+            .find(|t| t.token_hash == token_hash)
+            .map(|t| t.user_id)
+            .ok_or(CalendarFeedError::NotFound)
+    }
+}
+
+fn datetime(value: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").unwrap()
+}
+
+fn workout(user_id: i64, name: &str, description: Option<&str>) -> Workout {
+    Workout {
+        id: user_id,
+        uuid: Uuid::new_v4(),
+        user_id,
+        name: name.to_string(),
+        description: description.map(str::to_string),
+        created_at: chrono::Utc::now().naive_utc(),
+        updated_at: chrono::Utc::now().naive_utc(),
+    }
+}
+
+/// Read-only calendar data: user 1 has a planned and a skipped one-off workout.
+struct MockScheduleRepo {
+    scheduled: Vec<(ScheduledWorkout, Workout)>,
+}
+
+impl MockScheduleRepo {
+    pub fn new() -> Self {
+        let workout = workout(1, "Push Day, heavy", Some("Bench; overhead press"));
+        let scheduled = [("2030-01-07T07:00:00", "planned"), ("2030-01-09T07:00:00", "skipped")]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (scheduled_at, status))| {
+                let scheduled = ScheduledWorkout {
+                    id: i as i64 + 1,
+                    uuid: Uuid::new_v4(),
+                    user_id: 1,
+                    workout_id: workout.id,
+                    scheduled_at: datetime(scheduled_at),
+                    status: status.to_string(),
+                    notes: Some("Before work".to_string()),
+                    created_at: chrono::Utc::now().naive_utc(),
+                    updated_at: chrono::Utc::now().naive_utc(),
+                };
+                (scheduled, workout.clone())
+            })
+            .collect();
+        Self { scheduled }
+    }
+}
+
+impl ScheduleRepository for MockScheduleRepo {
+    fn list_scheduled_workouts(&self, user_id: i64, filter: ScheduleFilter) -> Result<Vec<(ScheduledWorkout, Workout)>, ScheduleError> {
+        Ok(self.scheduled.iter()
+            .filter(|(s, _)| s.user_id == user_id && schedule_matches(&filter, s))
+            .cloned()
+            .collect())
+    }
+
+    fn schedule_workout(&self, _user_id: i64, _scheduled: CreateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError> { unimplemented!() }
+    fn get_scheduled_workout(&self, _user_id: i64, _scheduled_uuid: Uuid) -> Result<(ScheduledWorkout, Workout), ScheduleError> { unimplemented!() }
+    fn update_scheduled_workout(&self, _user_id: i64, _scheduled_uuid: Uuid, _scheduled: UpdateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError> { unimplemented!() }
+    fn delete_scheduled_workout(&self, _user_id: i64, _scheduled_uuid: Uuid) -> Result<(), ScheduleError> { unimplemented!() }
+}
+
+/// Read-only calendar data: user 1 has a weekly schedule with one skipped and one rescheduled
+/// occurrence.
+struct MockRecurringScheduleRepo {
+    schedules: Vec<RecurringScheduleDetail>,
+}
+
+impl MockRecurringScheduleRepo {
+    pub fn new() -> Self {
+        let now = chrono::Utc::now().naive_utc();
+        let schedule = RecurringSchedule {
+            id: 1,
+            uuid: Uuid::new_v4(),
+            user_id: 1,
+            workout_id: 1,
+            starts_at: datetime("2030-01-06T18:00:00"),
+            rrule: "FREQ=WEEKLY;BYDAY=MO,TH".to_string(),
+            notes: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let exceptions = vec![
+            RecurringScheduleException {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                recurring_schedule_id: 1,
+                occurrence_at: datetime("2030-01-09T18:00:00"),
+                action: "skip".to_string(),
+                rescheduled_at: None,
+                created_at: now,
+            },
+            RecurringScheduleException {
+                id: 2,
+                uuid: Uuid::new_v4(),
+                recurring_schedule_id: 1,
+                occurrence_at: datetime("2030-01-13T18:00:00"),
+                action: "reschedule".to_string(),
+                rescheduled_at: Some(datetime("2030-01-14T07:30:00")),
+                created_at: now,
+            },
+        ];
+        Self {
+            schedules: vec![RecurringScheduleDetail {
+                schedule,
+                workout: workout(1, "Leg Day", None),
+                exceptions,
+            }],
+        }
+    }
+}
+
+impl RecurringScheduleRepository for MockRecurringScheduleRepo {
+    fn list_recurring_schedules(&self, user_id: i64) -> Result<Vec<RecurringScheduleDetail>, RecurringScheduleError> {
+        Ok(self.schedules.iter()
+            .filter(|d| d.schedule.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    fn create_recurring_schedule(&self, _user_id: i64, _schedule: CreateRecurringSchedule) -> Result<RecurringScheduleDetail, RecurringScheduleError> { unimplemented!() }
+    fn get_recurring_schedule(&self, _user_id: i64, _schedule_uuid: Uuid) -> Result<RecurringScheduleDetail, RecurringScheduleError> { unimplemented!() }
+    fn delete_recurring_schedule(&self, _user_id: i64, _schedule_uuid: Uuid) -> Result<(), RecurringScheduleError> { unimplemented!() }
+    fn add_exception(&self, _user_id: i64, _schedule_uuid: Uuid, _exception: CreateScheduleException) -> Result<RecurringScheduleException, RecurringScheduleError> { unimplemented!() }
+    fn remove_exception(&self, _user_id: i64, _schedule_uuid: Uuid, _exception_uuid: Uuid) -> Result<(), RecurringScheduleError> { unimplemented!() }
+}
+
+#[actix_web::test]
+async fn test_calendar_feed_token_lifecycle() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(MockAuthRepo::new()))
+            .app_data(web::Data::new(MockCalendarFeedRepo::new()))
+            .app_data(web::Data::new(MockScheduleRepo::new()))
+            .app_data(web::Data::new(MockRecurringScheduleRepo::new()))
+            .service(crate::routes::calendar::get_resource_feed::<MockCalendarFeedRepo, MockScheduleRepo, MockRecurringScheduleRepo>())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .service(crate::routes::calendar::get_scope::<MockCalendarFeedRepo, MockScheduleRepo, MockRecurringScheduleRepo>())
+            )
+    ).await;
+
+    // No subscription URL yet
+    let req = test::TestRequest::get()
+        .uri("/calendar/feed")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // Create one
+    let req = test::TestRequest::post()
+        .uri("/calendar/feed")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let feed: serde_json::Value = test::read_body_json(resp).await;
+    let token = feed["token"].as_str().unwrap().to_string();
+    assert!(feed["url"].as_str().unwrap().ends_with(&format!("/calendar/feed/{}.ics", token)));
+
+    // The feed is readable without a session cookie
+    let req = test::TestRequest::get()
+        .uri(&format!("/calendar/feed/{}.ics", token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/calendar; charset=utf-8");
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.starts_with("BEGIN:VCALENDAR\r\n"));
+
+    // Rotating invalidates the old URL
+    let req = test::TestRequest::post()
+        .uri("/calendar/feed")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let feed: serde_json::Value = test::read_body_json(resp).await;
+    let rotated = feed["token"].as_str().unwrap().to_string();
+    assert_ne!(rotated, token);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/calendar/feed/{}.ics", token))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/calendar/feed/{}.ics", rotated))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // Revoking disables the feed
+    let req = test::TestRequest::delete()
+        .uri("/calendar/feed")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/calendar/feed/{}.ics", rotated))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // Managing the feed still requires a session
+    let req = test::TestRequest::post()
+        .uri("/calendar/feed")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_calendar_export_contents() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(MockAuthRepo::new()))
+            .app_data(web::Data::new(MockCalendarFeedRepo::new()))
+            .app_data(web::Data::new(MockScheduleRepo::new()))
+            .app_data(web::Data::new(MockRecurringScheduleRepo::new()))
+            .service(crate::routes::calendar::get_resource_feed::<MockCalendarFeedRepo, MockScheduleRepo, MockRecurringScheduleRepo>())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .service(crate::routes::calendar::get_scope::<MockCalendarFeedRepo, MockScheduleRepo, MockRecurringScheduleRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/calendar/export.ics")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+
+    assert!(body.ends_with("END:VCALENDAR\r\n"));
+    assert!(body.lines().all(|line| line.len() <= 75));
+    assert_eq!(body.matches("BEGIN:VEVENT").count(), 4);
+
+    // One-off workouts, with text escaped and skipped ones cancelled
+    assert!(body.contains("DTSTART:20300107T070000Z\r\n"));
+    assert!(body.contains("SUMMARY:Push Day\\, heavy\r\n"));
+    assert!(body.contains("DESCRIPTION:Bench\\; overhead press\\n\\nBefore work\r\n"));
+    assert_eq!(body.matches("STATUS:CANCELLED").count(), 1);
+
+    // Recurring schedule with its exceptions
+    assert!(body.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TH\r\n"));
+    assert!(body.contains("EXDATE:20300109T180000Z\r\n"));
+    assert!(body.contains("RECURRENCE-ID:20300113T180000Z\r\nDTSTART:20300114T073000Z\r\n"));
+
+    // Other users get an empty calendar
+    let req = test::TestRequest::get()
+        .uri("/calendar/export.ics")
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(!body.contains("BEGIN:VEVENT"));
+}
@@ -3,8 +3,12 @@ use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
 use crate::{
-    models::exercise::{CreateExercise, Exercise, UpdateExercise},
+    models::{
+        exercise::{CreateExercise, ExerciseDetail, ExerciseFilter, UpdateExercise},
+        muscle_group::{MuscleGroup, MuscleRole},
+    },
     repositories::exercise_repository::{ExerciseError, ExerciseRepository},
+    routes::{run_blocking, service_unavailable},
 };
 
 #[derive(Serialize, Deserialize)]
@@ -12,14 +16,38 @@ pub struct ExerciseResponse {
     pub uuid: Uuid,
     pub name: String,
     pub description: Option<String>,
+    pub catalog: bool,
+    pub category: String,
+    pub primary_muscles: Vec<String>,
+    pub secondary_muscles: Vec<String>,
 }
 
 impl ExerciseResponse {
-    fn from(exercise: &Exercise) -> Self {
+    fn from(detail: &ExerciseDetail) -> Self {
+        let exercise = &detail.exercise;
         Self {
             uuid: exercise.uuid,
             name: exercise.name.clone(),
             description: exercise.description.clone(),
+            catalog: exercise.is_catalog(),
+            category: exercise.category.clone(),
+            primary_muscles: detail.muscles(MuscleRole::Primary),
+            secondary_muscles: detail.muscles(MuscleRole::Secondary),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MuscleGroupResponse {
+    slug: String,
+    name: String,
+}
+
+impl MuscleGroupResponse {
+    fn from(muscle_group: &MuscleGroup) -> Self {
+        Self {
+            slug: muscle_group.slug.clone(),
+            name: muscle_group.name.clone(),
         }
     }
 }
@@ -31,6 +59,16 @@ pub fn get_scope_exercise_id<T: ExerciseRepository + 'static>() -> Resource {
         .route(web::delete().to(delete_exercise::<T>))
 }
 
+pub fn get_scope_exercise_id_fork<T: ExerciseRepository + 'static>() -> Resource {
+    web::resource("/exercises/{exercise_uuid}/fork")
+        .route(web::post().to(fork_exercise::<T>))
+}
+
+pub fn get_scope_muscle_groups<T: ExerciseRepository + 'static>() -> Resource {
+    web::resource("/muscle-groups")
+        .route(web::get().to(list_muscle_groups::<T>))
+}
+
 pub fn get_scope<T: ExerciseRepository + 'static>() -> Scope {
     web::scope("/exercises")
         .route("", web::post().to(create_exercise::<T>))
@@ -43,22 +81,27 @@ async fn create_exercise<T: ExerciseRepository>(
     repo: web::Data<T>,
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
-    match repo.create_exercise(user_id, exercise.0) {
+    match run_blocking(&repo, move |repo| repo.create_exercise(user_id, exercise.0)).await {
         Ok(exercise) => HttpResponse::Created().json(ExerciseResponse::from(&exercise)),
+        Err(err @ (ExerciseError::InvalidExercise | ExerciseError::InvalidMuscleGroup(_))) => invalid_exercise_response(err),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
 
 async fn list_exercises<T: ExerciseRepository>(
+    filter: web::Query<ExerciseFilter>,
     req: HttpRequest,
     repo: web::Data<T>,
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
 
-    match repo.list_exercises(user_id) {
+    match run_blocking(&repo, move |repo| repo.list_exercises(user_id, filter.into_inner())).await {
         Ok(exercises) => HttpResponse::Ok().json(
             exercises.iter().map(ExerciseResponse::from).collect::<Vec<_>>()
         ),
+        Err(err @ ExerciseError::InvalidMuscleGroup(_)) => invalid_exercise_response(err),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -70,10 +113,11 @@ async fn get_exercise<T: ExerciseRepository>(
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
 
-    match repo.get_exercise(user_id, *exercise_uuid) {
+    match run_blocking(&repo, move |repo| repo.get_exercise(user_id, *exercise_uuid)).await {
         Ok(exercise) => HttpResponse::Ok().json(ExerciseResponse::from(&exercise)),
         Err(ExerciseError::NotFound) => HttpResponse::NotFound().finish(),
         Err(ExerciseError::Unauthorized) => HttpResponse::Unauthorized().finish(),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -86,10 +130,13 @@ async fn update_exercise<T: ExerciseRepository>(
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
 
-    match repo.update_exercise(user_id, *exercise_uuid, exercise.0) {
+    match run_blocking(&repo, move |repo| repo.update_exercise(user_id, *exercise_uuid, exercise.0)).await {
         Ok(exercise) => HttpResponse::Ok().json(ExerciseResponse::from(&exercise)),
         Err(ExerciseError::NotFound) => HttpResponse::NotFound().finish(),
         Err(ExerciseError::Unauthorized) => HttpResponse::Unauthorized().finish(),
+        Err(ExerciseError::CatalogExercise) => catalog_exercise_response(),
+        Err(err @ (ExerciseError::InvalidExercise | ExerciseError::InvalidMuscleGroup(_))) => invalid_exercise_response(err),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -101,10 +148,53 @@ async fn delete_exercise<T: ExerciseRepository>(
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
 
-    match repo.delete_exercise(user_id, *exercise_uuid) {
+    match run_blocking(&repo, move |repo| repo.delete_exercise(user_id, *exercise_uuid)).await {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(ExerciseError::NotFound) => HttpResponse::NotFound().finish(),
         Err(ExerciseError::Unauthorized) => HttpResponse::Unauthorized().finish(),
+        Err(ExerciseError::CatalogExercise) => catalog_exercise_response(),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
-} 
\ No newline at end of file
+}
+
+async fn fork_exercise<T: ExerciseRepository>(
+    exercise_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+
+    match run_blocking(&repo, move |repo| repo.fork_exercise(user_id, *exercise_uuid)).await {
+        Ok(exercise) => HttpResponse::Created().json(ExerciseResponse::from(&exercise)),
+        Err(ExerciseError::NotFound) => HttpResponse::NotFound().finish(),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn list_muscle_groups<T: ExerciseRepository>(
+    repo: web::Data<T>,
+) -> impl Responder {
+    match run_blocking(&repo, move |repo| repo.list_muscle_groups()).await {
+        Ok(muscle_groups) => HttpResponse::Ok().json(
+            muscle_groups.iter().map(MuscleGroupResponse::from).collect::<Vec<_>>()
+        ),
+        Err(ExerciseError::ConnectionUnavailable) => service_unavailable(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn invalid_exercise_response(err: ExerciseError) -> HttpResponse {
+    let message = match err {
+        ExerciseError::InvalidMuscleGroup(slug) => format!("Unknown muscle group: {}", slug),
+        _ => "Exercise name must not be blank and a muscle group may be listed only once".to_string(),
+    };
+    HttpResponse::BadRequest().json(serde_json::json!({ "error": message }))
+}
+
+fn catalog_exercise_response() -> HttpResponse {
+    HttpResponse::Forbidden().json(serde_json::json!({
+        "error": "Catalog exercises cannot be modified; fork it into a personal exercise instead"
+    }))
+}
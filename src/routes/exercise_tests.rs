@@ -6,126 +6,181 @@ use uuid::Uuid;
 
 use crate::{
     middleware::session::SessionProtection,
-    models::{exercise::{CreateExercise, Exercise, UpdateExercise}, session::Session, user::User},
-    repositories::{auth_repository::{AuthError, AuthRepository}, exercise_repository::{ExerciseError, ExerciseRepository}}, routes::exercise::ExerciseResponse,
+    models::{
+        exercise::{muscles_are_valid, CreateExercise, Exercise, ExerciseDetail, ExerciseFilter, UpdateExercise},
+        muscle_group::{ExerciseMuscleGroup, MuscleGroup, MuscleRole},
+    },
+    repositories::exercise_repository::{ExerciseError, ExerciseRepository},
+    routes::{exercise::ExerciseResponse, test_support::{is_visible_to, MockAuthRepo}},
 };
 
-pub struct MockAuthRepo {
-  sessions: Mutex<Vec<Session>>,
+
+struct MockExerciseRepo {
+  exercises: Mutex<Vec<ExerciseDetail>>,
+  muscle_groups: Vec<MuscleGroup>,
 }
 
-impl MockAuthRepo {
+impl MockExerciseRepo {
   pub fn new() -> Self {
-      let mut sessions = vec![];
-      sessions.push(Session {
-          id: 1,
-          user_id: 1,
-          token: "user1-session".to_string(),
-          csrf_token: "user1-csrf".to_string(),
-          expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
-          created_at: chrono::Utc::now().naive_utc(),
-      });
-      sessions.push(Session {
-          id: 2,
-          user_id: 2,
-          token: "user2-session".to_string(),
-          csrf_token: "user2-csrf".to_string(),
-          expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
-          created_at: chrono::Utc::now().naive_utc(),
-      });
+      let muscle_groups = ["chest", "triceps", "shoulders", "quadriceps", "glutes"].iter()
+          .enumerate()
+          .map(|(i, slug)| MuscleGroup { id: (i + 1) as i64, slug: slug.to_string(), name: slug.to_string() })
+          .collect();
       Self {
-          sessions: Mutex::new(sessions),
+          exercises: Mutex::new(vec![]),
+          muscle_groups,
       }
   }
-}
 
-impl AuthRepository for MockAuthRepo {
-  fn validate_session(&self, session_token: &str) -> Result<i64, AuthError> {
-      let sessions = self.sessions.lock().unwrap();
-      let session = sessions.iter()
-          .find(|s| s.token == session_token)
-          .ok_or(AuthError::InvalidSession)?;
-      Ok(session.user_id)
+  fn add_catalog_exercise(&self, name: &str) -> Uuid {
+      let mut exercises = self.exercises.lock().unwrap();
+      let exercise = Exercise {
+          id: (exercises.len() + 1) as i64,
+          uuid: Uuid::new_v4(),
+          user_id: None,
+          name: name.to_string(),
+          description: Some("Catalog description".to_string()),
+          created_at: chrono::Utc::now().naive_utc(),
+          updated_at: chrono::Utc::now().naive_utc(),
+          category: "strength".to_string(),
+      };
+      let muscle_groups = vec![(self.muscle_groups[0].clone(), ExerciseMuscleGroup::new(exercise.id, 1, MuscleRole::Primary))];
+      exercises.push(ExerciseDetail { exercise: exercise.clone(), muscle_groups });
+      exercise.uuid
   }
 
-  // Implement other required methods with empty/mock implementations
-  fn create_temp_session(&self, _csrf_token: String) -> Result<crate::models::temp_session::TempSession, AuthError> { unimplemented!() }
-  fn create_session(&self, _user_id: i64, _session_id: String, _csrf_token: String) -> Result<Session, AuthError> { unimplemented!() }
-  fn validate_csrf(&self, _session_id: &str, _csrf_token: &str) -> Result<(), AuthError> { unimplemented!() }
-  fn verify_credentials(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
-  fn invalidate_session(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
-  fn create_user(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
-  fn delete_user(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
-}
+  fn check_owned(exercises: &[ExerciseDetail], user_id: i64, exercise_uuid: Uuid) -> Result<(), ExerciseError> {
+      let exercise = exercises.iter()
+          .map(|d| &d.exercise)
+          .find(|e| e.uuid == exercise_uuid && is_visible_to(e, user_id))
+          .ok_or(ExerciseError::NotFound)?;
+      if exercise.is_catalog() {
+          return Err(ExerciseError::CatalogExercise);
+      }
+      Ok(())
+  }
 
-pub struct MockExerciseRepo {
-  exercises: Mutex<Vec<Exercise>>,
+  fn resolve_muscles(&self, exercise_id: i64, primary: &[String], secondary: &[String]) -> Result<Vec<(MuscleGroup, ExerciseMuscleGroup)>, ExerciseError> {
+      let roles = primary.iter().map(|m| (m, MuscleRole::Primary))
+          .chain(secondary.iter().map(|m| (m, MuscleRole::Secondary)));
+      roles.map(|(slug, role)| {
+          let muscle_group = self.muscle_groups.iter()
+              .find(|m| &m.slug == slug)
+              .ok_or(ExerciseError::InvalidMuscleGroup(slug.clone()))?;
+          Ok((muscle_group.clone(), ExerciseMuscleGroup::new(exercise_id, muscle_group.id, role)))
+      }).collect()
+  }
 }
 
-impl MockExerciseRepo {
-  pub fn new() -> Self {
-      Self {
-          exercises: Mutex::new(vec![]),
-      }
-  }
+/// Applies an exercise filter the way `PgExerciseRepository` does in SQL.
+fn filter_matches(filter: &ExerciseFilter, detail: &ExerciseDetail) -> bool {
+  filter.category.is_none_or(|category| detail.exercise.category == category.as_str())
+      && filter.muscle.as_ref().is_none_or(|muscle| {
+          detail.muscle_groups.iter().any(|(muscle_group, _)| &muscle_group.slug == muscle)
+      })
 }
 
 impl ExerciseRepository for MockExerciseRepo {
-  fn create_exercise(&self, user_id: i64, exercise: CreateExercise) -> Result<Exercise, ExerciseError> {
+  fn create_exercise(&self, user_id: i64, exercise: CreateExercise) -> Result<ExerciseDetail, ExerciseError> {
+      if !exercise.is_valid() {
+          return Err(ExerciseError::InvalidExercise);
+      }
       let mut exercises = self.exercises.lock().unwrap();
-      let new_exercise = Exercise::new(user_id, exercise.name, exercise.description);
-      let exercise = Exercise {
-          id: (exercises.len() + 1) as i64,
-          uuid: new_exercise.uuid,
-          user_id: new_exercise.user_id,
-          name: new_exercise.name,
-          description: new_exercise.description,
-          created_at: new_exercise.created_at,
-          updated_at: new_exercise.updated_at,
+      let new_exercise = Exercise::new(user_id, exercise.name, exercise.description, exercise.category);
+      let id = (exercises.len() + 1) as i64;
+      let detail = ExerciseDetail {
+          exercise: Exercise {
+              id,
+              uuid: new_exercise.uuid,
+              user_id: new_exercise.user_id,
+              name: new_exercise.name,
+              description: new_exercise.description,
+              created_at: new_exercise.created_at,
+              updated_at: new_exercise.updated_at,
+              category: new_exercise.category,
+          },
+          muscle_groups: self.resolve_muscles(id, &exercise.primary_muscles, &exercise.secondary_muscles)?,
       };
-      exercises.push(exercise.clone());
-      Ok(exercise)
+      exercises.push(detail.clone());
+      Ok(detail)
   }
 
-  fn get_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<Exercise, ExerciseError> {
+  fn get_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<ExerciseDetail, ExerciseError> {
       let exercises = self.exercises.lock().unwrap();
       exercises.iter()
-          .find(|e| e.uuid == exercise_uuid && e.user_id == user_id)
+          .find(|d| d.exercise.uuid == exercise_uuid && is_visible_to(&d.exercise, user_id))
           .cloned()
           .ok_or(ExerciseError::NotFound)
   }
 
-  fn list_exercises(&self, user_id: i64) -> Result<Vec<Exercise>, ExerciseError> {
+  fn list_exercises(&self, user_id: i64, filter: ExerciseFilter) -> Result<Vec<ExerciseDetail>, ExerciseError> {
+      if let Some(muscle) = &filter.muscle {
+          self.resolve_muscles(0, std::slice::from_ref(muscle), &[])?;
+      }
       let exercises = self.exercises.lock().unwrap();
-      Ok(exercises.iter().filter(|e| e.user_id == user_id).cloned().collect())
+      Ok(exercises.iter()
+          .filter(|d| is_visible_to(&d.exercise, user_id) && filter_matches(&filter, d))
+          .cloned()
+          .collect())
   }
 
-  fn update_exercise(&self, user_id: i64, exercise_uuid: Uuid, exercise: UpdateExercise) -> Result<Exercise, ExerciseError> {
+  fn update_exercise(&self, user_id: i64, exercise_uuid: Uuid, exercise: UpdateExercise) -> Result<ExerciseDetail, ExerciseError> {
+      if !exercise.is_valid() {
+          return Err(ExerciseError::InvalidExercise);
+      }
       let mut exercises = self.exercises.lock().unwrap();
+      Self::check_owned(&exercises, user_id, exercise_uuid)?;
       let exercise_index = exercises.iter()
-          .position(|e| e.uuid == exercise_uuid && e.user_id == user_id)
+          .position(|d| d.exercise.uuid == exercise_uuid && d.exercise.user_id == Some(user_id))
           .ok_or(ExerciseError::NotFound)?;
-      
+      let detail = &mut exercises[exercise_index];
+
+      let primary = exercise.primary_muscles.unwrap_or_else(|| detail.muscles(MuscleRole::Primary));
+      let secondary = exercise.secondary_muscles.unwrap_or_else(|| detail.muscles(MuscleRole::Secondary));
+      if !muscles_are_valid(&primary, &secondary) {
+          return Err(ExerciseError::InvalidExercise);
+      }
+      detail.muscle_groups = self.resolve_muscles(detail.exercise.id, &primary, &secondary)?;
+
       // Update the exercise fields
-      exercises[exercise_index].name = exercise.name.unwrap_or(exercises[exercise_index].name.clone());
+      detail.exercise.name = exercise.name.unwrap_or(detail.exercise.name.clone());
       if let Some(description) = exercise.description {
-          exercises[exercise_index].description = Some(description);
+          detail.exercise.description = Some(description);
+      }
+      if let Some(category) = exercise.category {
+          detail.exercise.category = category.as_str().to_string();
       }
-      exercises[exercise_index].updated_at = chrono::Utc::now().naive_utc();
-      
-      Ok(exercises[exercise_index].clone())
+      detail.exercise.updated_at = chrono::Utc::now().naive_utc();
+
+      Ok(detail.clone())
   }
 
   fn delete_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<(), ExerciseError> {
       let mut exercises = self.exercises.lock().unwrap();
+      Self::check_owned(&exercises, user_id, exercise_uuid)?;
       let initial_len = exercises.len();
-      exercises.retain(|e| !(e.uuid == exercise_uuid && e.user_id == user_id));
+      exercises.retain(|d| !(d.exercise.uuid == exercise_uuid && d.exercise.user_id == Some(user_id)));
       if exercises.len() < initial_len {
           Ok(())
       } else {
           Err(ExerciseError::NotFound)
       }
   }
+
+  fn fork_exercise(&self, user_id: i64, exercise_uuid: Uuid) -> Result<ExerciseDetail, ExerciseError> {
+      let source = self.get_exercise(user_id, exercise_uuid)?;
+      self.create_exercise(user_id, CreateExercise {
+          name: source.exercise.name.clone(),
+          description: source.exercise.description.clone(),
+          category: serde_json::from_value(json!(source.exercise.category)).unwrap(),
+          primary_muscles: source.muscles(MuscleRole::Primary),
+          secondary_muscles: source.muscles(MuscleRole::Secondary),
+      })
+  }
+
+  fn list_muscle_groups(&self) -> Result<Vec<MuscleGroup>, ExerciseError> {
+      Ok(self.muscle_groups.clone())
+  }
 }
 
 #[actix_web::test]
@@ -292,4 +347,256 @@ async fn test_exercise_isolation() {
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 404);
-} 
\ No newline at end of file
+}
+
+#[actix_web::test]
+async fn test_exercise_catalog() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let exercise_repo = web::Data::new(MockExerciseRepo::new());
+    let catalog_uuid = exercise_repo.add_catalog_exercise("Bench Press");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .app_data(exercise_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .service(crate::routes::exercise::get_scope_exercise_id_fork::<MockExerciseRepo>())
+                    .service(crate::routes::exercise::get_scope_exercise_id::<MockExerciseRepo>())
+                    .service(crate::routes::exercise::get_scope::<MockExerciseRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exercises")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "name": "Personal Exercise" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+
+    // Catalog and personal exercises are listed together
+    let req = test::TestRequest::get()
+        .uri("/exercises")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let exercises: Vec<ExerciseResponse> = test::read_body_json(resp).await;
+    assert_eq!(exercises.len(), 2);
+    assert!(exercises.iter().any(|e| e.uuid == catalog_uuid && e.catalog));
+    assert!(exercises.iter().any(|e| e.name == "Personal Exercise" && !e.catalog));
+
+    // Every user can read the catalog
+    let req = test::TestRequest::get()
+        .uri(&format!("/exercises/{}", catalog_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // but nobody can change it
+    let req = test::TestRequest::put()
+        .uri(&format!("/exercises/{}", catalog_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "name": "My Bench Press" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/exercises/{}", catalog_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    // Forking creates an editable personal copy
+    let req = test::TestRequest::post()
+        .uri(&format!("/exercises/{}/fork", catalog_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let fork: ExerciseResponse = test::read_body_json(resp).await;
+    assert_ne!(fork.uuid, catalog_uuid);
+    assert_eq!(fork.name, "Bench Press");
+    assert_eq!(fork.description.as_deref(), Some("Catalog description"));
+    assert!(!fork.catalog);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/exercises/{}", fork.uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "name": "Paused Bench Press" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    // The fork is personal to its owner
+    let req = test::TestRequest::get()
+        .uri(&format!("/exercises/{}", fork.uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_exercise_taxonomy() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let exercise_repo = web::Data::new(MockExerciseRepo::new());
+    let catalog_uuid = exercise_repo.add_catalog_exercise("Bench Press");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .app_data(exercise_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .service(crate::routes::exercise::get_scope_exercise_id_fork::<MockExerciseRepo>())
+                    .service(crate::routes::exercise::get_scope_exercise_id::<MockExerciseRepo>())
+                    .service(crate::routes::exercise::get_scope::<MockExerciseRepo>())
+                    .service(crate::routes::exercise::get_scope_muscle_groups::<MockExerciseRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exercises")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "name": "Squat",
+            "category": "strength",
+            "primary_muscles": ["quadriceps"],
+            "secondary_muscles": ["glutes"]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let squat: ExerciseResponse = test::read_body_json(resp).await;
+    assert_eq!(squat.category, "strength");
+    assert_eq!(squat.primary_muscles, vec!["quadriceps"]);
+    assert_eq!(squat.secondary_muscles, vec!["glutes"]);
+
+    let req = test::TestRequest::post()
+        .uri("/exercises")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "name": "Box Jump", "category": "plyometric", "primary_muscles": ["quadriceps"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+
+    // Category defaults to strength
+    let req = test::TestRequest::post()
+        .uri("/exercises")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "name": "Plank" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let plank: ExerciseResponse = test::read_body_json(resp).await;
+    assert_eq!(plank.category, "strength");
+    assert!(plank.primary_muscles.is_empty());
+
+    // Filter by category and muscle group, catalog included
+    let req = test::TestRequest::get()
+        .uri("/exercises?category=plyometric")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let exercises: Vec<ExerciseResponse> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(exercises.len(), 1);
+    assert_eq!(exercises[0].name, "Box Jump");
+
+    let req = test::TestRequest::get()
+        .uri("/exercises?muscle=glutes")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let exercises: Vec<ExerciseResponse> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(exercises.len(), 1);
+    assert_eq!(exercises[0].uuid, squat.uuid);
+
+    let req = test::TestRequest::get()
+        .uri("/exercises?category=strength&muscle=chest")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let exercises: Vec<ExerciseResponse> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(exercises.len(), 1);
+    assert_eq!(exercises[0].uuid, catalog_uuid);
+
+    // Unknown filters are rejected
+    let req = test::TestRequest::get()
+        .uri("/exercises?category=yoga")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::get()
+        .uri("/exercises?muscle=wings")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Invalid taxonomy on create
+    for body in [
+        json!({ "name": "Dip", "category": "gymnastics" }),
+        json!({ "name": "Dip", "primary_muscles": ["wings"] }),
+        json!({ "name": "Dip", "primary_muscles": ["triceps"], "secondary_muscles": ["triceps"] }),
+        json!({ "name": "Dip", "primary_muscles": ["triceps", "triceps"] }),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/exercises")
+            .cookie(Cookie::new("session_id", "user1-session"))
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    // Moving a muscle group between roles replaces both lists
+    let req = test::TestRequest::put()
+        .uri(&format!("/exercises/{}", squat.uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "primary_muscles": ["quadriceps", "glutes"], "secondary_muscles": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let updated: ExerciseResponse = test::read_body_json(resp).await;
+    assert_eq!(updated.primary_muscles, vec!["quadriceps", "glutes"]);
+    assert!(updated.secondary_muscles.is_empty());
+
+    // but a muscle group may not end up in both roles
+    let req = test::TestRequest::put()
+        .uri(&format!("/exercises/{}", squat.uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "secondary_muscles": ["glutes"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/exercises/{}", squat.uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "category": "cardio" }))
+        .to_request();
+    let updated: ExerciseResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(updated.category, "cardio");
+    assert_eq!(updated.primary_muscles, vec!["quadriceps", "glutes"]);
+
+    // Forks keep the taxonomy of their source
+    let req = test::TestRequest::post()
+        .uri(&format!("/exercises/{}/fork", catalog_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let fork: ExerciseResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fork.category, "strength");
+    assert_eq!(fork.primary_muscles, vec!["chest"]);
+
+    let req = test::TestRequest::get()
+        .uri("/muscle-groups")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let muscle_groups: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(muscle_groups.len(), 5);
+    assert_eq!(muscle_groups[0]["slug"], "chest");
+}
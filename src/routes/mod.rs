@@ -13,3 +13,48 @@ pub mod exercise_tests;
 pub mod workout_exercise;
 #[cfg(test)]
 pub mod workout_exercise_tests;
+pub mod workout_log;
+#[cfg(test)]
+pub mod workout_log_tests;
+pub mod schedule;
+#[cfg(test)]
+pub mod schedule_tests;
+pub mod recurring_schedule;
+#[cfg(test)]
+pub mod recurring_schedule_tests;
+pub mod calendar;
+#[cfg(test)]
+pub mod calendar_tests;
+pub mod report;
+#[cfg(test)]
+pub mod report_tests;
+pub mod personal_record;
+#[cfg(test)]
+pub mod personal_record_tests;
+#[cfg(test)]
+pub mod test_support;
+
+use actix_web::{web, HttpResponse};
+
+/// Response for requests that could not get a pooled database connection in time.
+pub(crate) fn service_unavailable() -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header(("Retry-After", "1"))
+        .json(serde_json::json!({
+            "error": "Service temporarily unavailable"
+        }))
+}
+
+/// Runs a blocking repository call on actix's blocking thread pool so a slow query doesn't
+/// stall the other requests served by the same worker. A panic inside `call` is re-raised.
+pub(crate) async fn run_blocking<T, R, F>(repo: &web::Data<T>, call: F) -> R
+where
+    T: ?Sized + Send + Sync + 'static,
+    R: Send + 'static,
+    F: FnOnce(&T) -> R + Send + 'static,
+{
+    let repo = repo.clone();
+    web::block(move || call(&repo))
+        .await
+        .expect("blocking repository call panicked")
+}
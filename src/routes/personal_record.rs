@@ -0,0 +1,128 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Resource, Responder, Scope};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{exercise::Exercise, personal_record::{PersonalRecord, RecordFeedQuery, RecordType}},
+    repositories::personal_record_repository::{PersonalRecordError, PersonalRecordRepository},
+    routes::{run_blocking, service_unavailable},
+};
+
+const DEFAULT_FEED_LIMIT: i64 = 50;
+const MAX_FEED_LIMIT: i64 = 200;
+
+/// Shared with the workout log routes, which report the records a new log has set.
+#[derive(Serialize)]
+pub(crate) struct PersonalRecordResponse {
+    uuid: Uuid,
+    exercise_uuid: Uuid,
+    exercise_name: String,
+    record_type: String,
+    value: f64,
+    reps: Option<i32>,
+    weight: Option<f64>,
+    achieved_at: NaiveDateTime,
+}
+
+impl PersonalRecordResponse {
+    pub(crate) fn from(exercise: &Exercise, record: &PersonalRecord) -> Self {
+        Self {
+            uuid: record.uuid,
+            exercise_uuid: exercise.uuid,
+            exercise_name: exercise.name.clone(),
+            record_type: record.record_type.clone(),
+            value: record.value,
+            reps: record.reps,
+            weight: record.weight,
+            achieved_at: record.achieved_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExerciseRecordsResponse {
+    exercise_uuid: Uuid,
+    exercise_name: String,
+    /// The standing record of each type; `max_reps` has one entry per weight.
+    current: Vec<PersonalRecordResponse>,
+    history: Vec<PersonalRecordResponse>,
+}
+
+impl ExerciseRecordsResponse {
+    fn from(exercise: &Exercise, records: &[PersonalRecord]) -> Self {
+        // The best value of each type (and weight, for rep records) is the standing one. A
+        // backdated log can set a record that is older than the one it beat.
+        let mut current: Vec<&PersonalRecord> = vec![];
+        for record in records {
+            let standing = current.iter_mut().find(|standing| {
+                standing.record_type == record.record_type
+                    && (record.record_type != RecordType::MaxReps.as_str() || standing.weight == record.weight)
+            });
+            match standing {
+                Some(standing) if record.value > standing.value => *standing = record,
+                Some(_) => {},
+                None => current.push(record),
+            }
+        }
+
+        Self {
+            exercise_uuid: exercise.uuid,
+            exercise_name: exercise.name.clone(),
+            current: current.into_iter().map(|record| PersonalRecordResponse::from(exercise, record)).collect(),
+            history: records.iter().map(|record| PersonalRecordResponse::from(exercise, record)).collect(),
+        }
+    }
+}
+
+pub fn get_scope_exercise_id_records<T: PersonalRecordRepository + 'static>() -> Resource {
+    web::resource("/exercises/{exercise_uuid}/records")
+        .route(web::get().to(list_exercise_records::<T>))
+}
+
+pub fn get_scope<T: PersonalRecordRepository + 'static>() -> Scope {
+    web::scope("/records")
+        .route("", web::get().to(list_records::<T>))
+}
+
+fn error_response(err: PersonalRecordError) -> HttpResponse {
+    match err {
+        PersonalRecordError::NotFound => HttpResponse::NotFound().finish(),
+        PersonalRecordError::ConnectionUnavailable => service_unavailable(),
+        PersonalRecordError::DatabaseError(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn list_exercise_records<T: PersonalRecordRepository>(
+    exercise_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.list_exercise_records(user_id, *exercise_uuid)).await {
+        Ok((exercise, records)) => HttpResponse::Ok().json(ExerciseRecordsResponse::from(&exercise, &records)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_records<T: PersonalRecordRepository>(
+    query: web::Query<RecordFeedQuery>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_LIMIT);
+    if !(1..=MAX_FEED_LIMIT).contains(&limit) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("limit must be between 1 and {}", MAX_FEED_LIMIT)
+        }));
+    }
+    match run_blocking(&repo, move |repo| repo.list_records(user_id, limit)).await {
+        Ok(records) => HttpResponse::Ok().json(
+            records.iter()
+                .map(|(exercise, record)| PersonalRecordResponse::from(exercise, record))
+                .collect::<Vec<_>>()
+        ),
+        Err(e) => error_response(e),
+    }
+}
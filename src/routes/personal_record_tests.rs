@@ -0,0 +1,214 @@
+use actix_web::{cookie::Cookie, test, web, App};
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::{
+    middleware::session::SessionProtection,
+    models::{exercise::Exercise, personal_record::{PersonalRecord, RecordType}},
+    repositories::personal_record_repository::{PersonalRecordError, PersonalRecordRepository},
+    routes::test_support::{is_visible_to, MockAuthRepo},
+};
+
+fn datetime(value: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").unwrap()
+}
+
+/// User 1 has a bench press record history and two squat records, the heavier one from a backdated
+/// log; user 2 has no records.
+struct MockPersonalRecordRepo {
+    exercises: Vec<Exercise>,
+    records: Vec<PersonalRecord>,
+}
+
+impl MockPersonalRecordRepo {
+    pub fn new() -> Self {
+        let exercise = |id: i64, user_id: i64, name: &str| Exercise {
+            id,
+            uuid: Uuid::new_v4(),
+            user_id: Some(user_id),
+            name: name.to_string(),
+            description: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            category: "strength".to_string(),
+        };
+        let record = |id: i64, exercise_id: i64, record_type: RecordType, value: f64, reps: Option<i32>, weight: Option<f64>, achieved_at: &str| PersonalRecord {
+            id,
+            uuid: Uuid::new_v4(),
+            user_id: 1,
+            exercise_id,
+            workout_log_id: id,
+            record_type: record_type.as_str().to_string(),
+            value,
+            reps,
+            weight,
+            achieved_at: datetime(achieved_at),
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+
+        Self {
+            exercises: vec![exercise(1, 1, "Bench Press"), exercise(2, 1, "Squat"), exercise(3, 2, "Deadlift")],
+            records: vec![
+                record(1, 1, RecordType::MaxWeight, 80.0, Some(5), Some(80.0), "2025-01-06T18:00:00"),
+                record(2, 1, RecordType::MaxReps, 5.0, Some(5), Some(80.0), "2025-01-06T18:00:00"),
+                record(3, 1, RecordType::MaxReps, 8.0, Some(8), Some(80.0), "2025-01-08T18:00:00"),
+                record(4, 1, RecordType::MaxReps, 3.0, Some(3), Some(90.0), "2025-01-10T18:00:00"),
+                record(5, 1, RecordType::MaxWeight, 90.0, Some(3), Some(90.0), "2025-01-10T18:00:00"),
+                record(6, 2, RecordType::MaxWeight, 140.0, Some(1), Some(140.0), "2025-01-12T18:00:00"),
+                record(7, 2, RecordType::MaxWeight, 150.0, Some(1), Some(150.0), "2025-01-05T18:00:00"),
+            ],
+        }
+    }
+
+    fn exercise_uuid(&self, name: &str) -> Uuid {
+        self.exercises.iter().find(|e| e.name == name).unwrap().uuid
+    }
+}
+
+impl PersonalRecordRepository for MockPersonalRecordRepo {
+    fn list_exercise_records(&self, user_id: i64, exercise_uuid: Uuid) -> Result<(Exercise, Vec<PersonalRecord>), PersonalRecordError> {
+        let exercise = self.exercises.iter()
+            .find(|e| e.uuid == exercise_uuid && is_visible_to(e, user_id))
+            .ok_or(PersonalRecordError::NotFound)?;
+        let mut records = self.records.iter()
+            .filter(|r| r.exercise_id == exercise.id && r.user_id == user_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        records.sort_by_key(|r| std::cmp::Reverse((r.achieved_at, r.id)));
+        Ok((exercise.clone(), records))
+    }
+
+    fn list_records(&self, user_id: i64, limit: i64) -> Result<Vec<(Exercise, PersonalRecord)>, PersonalRecordError> {
+        let mut records = self.records.iter()
+            .filter(|r| r.user_id == user_id)
+            .map(|r| (self.exercises.iter().find(|e| e.id == r.exercise_id).unwrap().clone(), r.clone()))
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(_, r)| std::cmp::Reverse((r.achieved_at, r.id)));
+        records.truncate(limit as usize);
+        Ok(records)
+    }
+}
+
+#[actix_web::test]
+async fn test_exercise_records() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let record_repo = web::Data::new(MockPersonalRecordRepo::new());
+    let bench_uuid = record_repo.exercise_uuid("Bench Press");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(record_repo.clone())
+                    .service(crate::routes::personal_record::get_scope_exercise_id_records::<MockPersonalRecordRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exercises/{}/records", bench_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let records: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(records["exercise_name"], "Bench Press");
+    assert_eq!(records["history"].as_array().unwrap().len(), 5);
+
+    // Standing records: heaviest weight, plus the best rep count at each weight
+    let current = records["current"].as_array().unwrap();
+    assert_eq!(current.len(), 3);
+    let max_weight = current.iter().find(|r| r["record_type"] == "max_weight").unwrap();
+    assert_eq!(max_weight["value"], 90.0);
+    let reps_at_80 = current.iter().find(|r| r["record_type"] == "max_reps" && r["weight"] == 80.0).unwrap();
+    assert_eq!(reps_at_80["reps"], 8);
+    assert!(current.iter().any(|r| r["record_type"] == "max_reps" && r["weight"] == 90.0));
+
+    // Other users cannot see them
+    let req = test::TestRequest::get()
+        .uri(&format!("/exercises/{}/records", bench_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_backdated_record_stands() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let record_repo = web::Data::new(MockPersonalRecordRepo::new());
+    let squat_uuid = record_repo.exercise_uuid("Squat");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(record_repo.clone())
+                    .service(crate::routes::personal_record::get_scope_exercise_id_records::<MockPersonalRecordRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exercises/{}/records", squat_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let records: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(records["history"][0]["value"], 140.0);
+
+    // The backdated 150 beat the 140 logged later, so it is the standing record
+    let current = records["current"].as_array().unwrap();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0]["value"], 150.0);
+    assert_eq!(current[0]["achieved_at"], "2025-01-05T18:00:00");
+}
+
+#[actix_web::test]
+async fn test_records_feed() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let record_repo = web::Data::new(MockPersonalRecordRepo::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(record_repo.clone())
+                    .service(crate::routes::personal_record::get_scope::<MockPersonalRecordRepo>())
+            )
+    ).await;
+
+    // Most recent first, across exercises
+    let req = test::TestRequest::get()
+        .uri("/records?limit=2")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let records: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["exercise_name"], "Squat");
+    assert_eq!(records[1]["exercise_name"], "Bench Press");
+    assert_eq!(records[1]["record_type"], "max_weight");
+
+    // Limits are bounded
+    let req = test::TestRequest::get()
+        .uri("/records?limit=0")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::get()
+        .uri("/records")
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let records: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(records.is_empty());
+}
@@ -0,0 +1,247 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, Scope};
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    models::recurring_schedule::{
+        CreateRecurringSchedule, CreateScheduleException, Occurrence, OccurrenceRange,
+        RecurringScheduleDetail, RecurringScheduleException,
+    },
+    repositories::recurring_schedule_repository::{RecurringScheduleError, RecurringScheduleRepository},
+    routes::{run_blocking, service_unavailable},
+};
+
+/// Window used for occurrence listings when the client does not pass `to`.
+const DEFAULT_OCCURRENCE_WINDOW_DAYS: i64 = 30;
+
+#[derive(Serialize)]
+struct ExceptionResponse {
+    uuid: Uuid,
+    occurrence_at: NaiveDateTime,
+    action: String,
+    rescheduled_at: Option<NaiveDateTime>,
+}
+
+impl ExceptionResponse {
+    fn from(exception: &RecurringScheduleException) -> Self {
+        Self {
+            uuid: exception.uuid,
+            occurrence_at: exception.occurrence_at,
+            action: exception.action.clone(),
+            rescheduled_at: exception.rescheduled_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RecurringScheduleResponse {
+    uuid: Uuid,
+    workout_uuid: Uuid,
+    workout_name: String,
+    starts_at: NaiveDateTime,
+    rrule: String,
+    notes: Option<String>,
+    exceptions: Vec<ExceptionResponse>,
+}
+
+impl RecurringScheduleResponse {
+    fn from(detail: &RecurringScheduleDetail) -> Self {
+        Self {
+            uuid: detail.schedule.uuid,
+            workout_uuid: detail.workout.uuid,
+            workout_name: detail.workout.name.clone(),
+            starts_at: detail.schedule.starts_at,
+            rrule: detail.schedule.rrule.clone(),
+            notes: detail.schedule.notes.clone(),
+            exceptions: detail.exceptions.iter().map(ExceptionResponse::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OccurrenceResponse {
+    schedule_uuid: Uuid,
+    workout_uuid: Uuid,
+    workout_name: String,
+    occurrence_at: NaiveDateTime,
+    starts_at: NaiveDateTime,
+    rescheduled: bool,
+}
+
+impl OccurrenceResponse {
+    fn from(detail: &RecurringScheduleDetail, occurrence: &Occurrence) -> Self {
+        Self {
+            schedule_uuid: detail.schedule.uuid,
+            workout_uuid: detail.workout.uuid,
+            workout_name: detail.workout.name.clone(),
+            occurrence_at: occurrence.occurrence_at,
+            starts_at: occurrence.starts_at,
+            rescheduled: occurrence.rescheduled,
+        }
+    }
+}
+
+pub fn get_scope<T: RecurringScheduleRepository + 'static>() -> Scope {
+    web::scope("/recurring-schedules")
+        .route("", web::post().to(create_recurring_schedule::<T>))
+        .route("", web::get().to(list_recurring_schedules::<T>))
+        .route("/occurrences", web::get().to(list_all_occurrences::<T>))
+        .route("/{schedule_uuid}", web::get().to(get_recurring_schedule::<T>))
+        .route("/{schedule_uuid}", web::delete().to(delete_recurring_schedule::<T>))
+        .route("/{schedule_uuid}/occurrences", web::get().to(list_occurrences::<T>))
+        .route("/{schedule_uuid}/exceptions", web::post().to(add_exception::<T>))
+        .route("/{schedule_uuid}/exceptions/{exception_uuid}", web::delete().to(remove_exception::<T>))
+}
+
+fn error_response(err: RecurringScheduleError) -> HttpResponse {
+    match err {
+        RecurringScheduleError::NotFound => HttpResponse::NotFound().finish(),
+        RecurringScheduleError::WorkoutNotFound => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Workout not found"
+        })),
+        RecurringScheduleError::InvalidRule(reason) => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid recurrence rule: {}", reason)
+        })),
+        RecurringScheduleError::InvalidException => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "occurrence_at must be an occurrence of the schedule, and rescheduled_at is required only when rescheduling"
+        })),
+        RecurringScheduleError::DuplicateException => HttpResponse::Conflict().json(serde_json::json!({
+            "error": "Occurrence already has an exception"
+        })),
+        RecurringScheduleError::ConnectionUnavailable => service_unavailable(),
+        RecurringScheduleError::DatabaseError(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn resolve_range(range: &OccurrenceRange) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let from = range.from.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+    let to = match range.to {
+        Some(to) => to,
+        None => from.checked_add_signed(Duration::days(DEFAULT_OCCURRENCE_WINDOW_DAYS))?,
+    };
+    (from < to).then_some((from, to))
+}
+
+fn invalid_range_response() -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({
+        "error": "from must be before to, and within the supported date range"
+    }))
+}
+
+async fn create_recurring_schedule<T: RecurringScheduleRepository>(
+    schedule: web::Json<CreateRecurringSchedule>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.create_recurring_schedule(user_id, schedule.0)).await {
+        Ok(detail) => HttpResponse::Created().json(RecurringScheduleResponse::from(&detail)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_recurring_schedules<T: RecurringScheduleRepository>(
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.list_recurring_schedules(user_id)).await {
+        Ok(schedules) => HttpResponse::Ok().json(
+            schedules.iter().map(RecurringScheduleResponse::from).collect::<Vec<_>>()
+        ),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn get_recurring_schedule<T: RecurringScheduleRepository>(
+    schedule_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.get_recurring_schedule(user_id, *schedule_uuid)).await {
+        Ok(detail) => HttpResponse::Ok().json(RecurringScheduleResponse::from(&detail)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn delete_recurring_schedule<T: RecurringScheduleRepository>(
+    schedule_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.delete_recurring_schedule(user_id, *schedule_uuid)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_occurrences<T: RecurringScheduleRepository>(
+    schedule_uuid: web::Path<Uuid>,
+    range: web::Query<OccurrenceRange>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    let Some((from, to)) = resolve_range(&range) else {
+        return invalid_range_response();
+    };
+    match run_blocking(&repo, move |repo| repo.get_recurring_schedule(user_id, *schedule_uuid)).await {
+        Ok(detail) => HttpResponse::Ok().json(
+            detail.occurrences(from, to).iter()
+                .map(|occurrence| OccurrenceResponse::from(&detail, occurrence))
+                .collect::<Vec<_>>()
+        ),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_all_occurrences<T: RecurringScheduleRepository>(
+    range: web::Query<OccurrenceRange>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    let Some((from, to)) = resolve_range(&range) else {
+        return invalid_range_response();
+    };
+    match run_blocking(&repo, move |repo| repo.list_recurring_schedules(user_id)).await {
+        Ok(schedules) => {
+            let mut occurrences = schedules.iter()
+                .flat_map(|detail| detail.occurrences(from, to).into_iter()
+                    .map(move |occurrence| OccurrenceResponse::from(detail, &occurrence)))
+                .collect::<Vec<_>>();
+            occurrences.sort_by_key(|occurrence| occurrence.starts_at);
+            HttpResponse::Ok().json(occurrences)
+        },
+        Err(e) => error_response(e),
+    }
+}
+
+async fn add_exception<T: RecurringScheduleRepository>(
+    schedule_uuid: web::Path<Uuid>,
+    exception: web::Json<CreateScheduleException>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.add_exception(user_id, *schedule_uuid, exception.0)).await {
+        Ok(exception) => HttpResponse::Created().json(ExceptionResponse::from(&exception)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn remove_exception<T: RecurringScheduleRepository>(
+    path: web::Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let (schedule_uuid, exception_uuid) = path.into_inner();
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.remove_exception(user_id, schedule_uuid, exception_uuid)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
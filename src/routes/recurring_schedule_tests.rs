@@ -0,0 +1,377 @@
+use actix_web::{cookie::Cookie, test, web, App};
+use serde_json::json;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    middleware::session::SessionProtection,
+    models::{
+        recurrence_rule::RecurrenceRule,
+        recurring_schedule::{CreateRecurringSchedule, CreateScheduleException, RecurringSchedule, RecurringScheduleDetail, RecurringScheduleException},
+        workout::Workout,
+    },
+    repositories::recurring_schedule_repository::{RecurringScheduleError, RecurringScheduleRepository},
+    routes::test_support::MockAuthRepo,
+};
+
+struct MockRecurringScheduleRepo {
+    state: Mutex<(Vec<Workout>, Vec<RecurringScheduleDetail>)>,
+}
+
+impl MockRecurringScheduleRepo {
+    pub fn new() -> Self {
+        let workouts = vec![
+            Workout {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                user_id: 1,
+                name: "Push Day".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+            },
+        ];
+        Self {
+            state: Mutex::new((workouts, vec![])),
+        }
+    }
+
+    fn workout_uuid(&self) -> Uuid {
+        self.state.lock().unwrap().0[0].uuid
+    }
+}
+
+impl RecurringScheduleRepository for MockRecurringScheduleRepo {
+    fn create_recurring_schedule(&self, user_id: i64, schedule: CreateRecurringSchedule) -> Result<RecurringScheduleDetail, RecurringScheduleError> {
+        let rule = RecurrenceRule::parse(&schedule.rrule)
+            .map_err(|err| RecurringScheduleError::InvalidRule(err.0))?;
+
+        let mut state = self.state.lock().unwrap();
+        let (workouts, schedules) = &mut *state;
+        let workout = workouts.iter()
+            .find(|w| w.uuid == schedule.workout_uuid && w.user_id == user_id)
+            .ok_or(RecurringScheduleError::WorkoutNotFound)?
+            .clone();
+
+        let new_schedule = RecurringSchedule::new(user_id, workout.id, schedule.starts_at, &rule, schedule.notes);
+        let detail = RecurringScheduleDetail {
+            schedule: RecurringSchedule {
+                id: (schedules.len() + 1) as i64,
+                uuid: new_schedule.uuid,
+                user_id,
+                workout_id: workout.id,
+                starts_at: new_schedule.starts_at,
+                rrule: new_schedule.rrule,
+                notes: new_schedule.notes,
+                created_at: new_schedule.created_at,
+                updated_at: new_schedule.updated_at,
+            },
+            workout,
+            exceptions: vec![],
+        };
+        schedules.push(detail.clone());
+        Ok(detail)
+    }
+
+    fn get_recurring_schedule(&self, user_id: i64, schedule_uuid: Uuid) -> Result<RecurringScheduleDetail, RecurringScheduleError> {
+        let state = self.state.lock().unwrap();
+        state.1.iter()
+            .find(|d| d.schedule.uuid == schedule_uuid && d.schedule.user_id == user_id)
+            .cloned()
+            .ok_or(RecurringScheduleError::NotFound)
+    }
+
+    fn list_recurring_schedules(&self, user_id: i64) -> Result<Vec<RecurringScheduleDetail>, RecurringScheduleError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.1.iter()
+            .filter(|d| d.schedule.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    fn delete_recurring_schedule(&self, user_id: i64, schedule_uuid: Uuid) -> Result<(), RecurringScheduleError> {
+        let mut state = self.state.lock().unwrap();
+        let initial_len = state.1.len();
+        state.1.retain(|d| !(d.schedule.uuid == schedule_uuid && d.schedule.user_id == user_id));
+
+        if state.1.len() < initial_len {
+            Ok(())
+        } else {
+            Err(RecurringScheduleError::NotFound)
+        }
+    }
+
+    fn add_exception(&self, user_id: i64, schedule_uuid: Uuid, exception: CreateScheduleException) -> Result<RecurringScheduleException, RecurringScheduleError> {
+        let mut state = self.state.lock().unwrap();
+        let detail = state.1.iter_mut()
+            .find(|d| d.schedule.uuid == schedule_uuid && d.schedule.user_id == user_id)
+            .ok_or(RecurringScheduleError::NotFound)?;
+
+        if !exception.is_valid() || !detail.schedule.rule().is_occurrence(detail.schedule.starts_at, exception.occurrence_at) {
+            return Err(RecurringScheduleError::InvalidException);
+        }
+        if detail.exceptions.iter().any(|e| e.occurrence_at == exception.occurrence_at) {
+            return Err(RecurringScheduleError::DuplicateException);
+        }
+
+        let new_exception = RecurringScheduleException::new(detail.schedule.id, &exception);
+        let exception = RecurringScheduleException {
+            id: (detail.exceptions.len() + 1) as i64,
+            uuid: new_exception.uuid,
+            recurring_schedule_id: new_exception.recurring_schedule_id,
+            occurrence_at: new_exception.occurrence_at,
+            action: new_exception.action,
+            rescheduled_at: new_exception.rescheduled_at,
+            created_at: new_exception.created_at,
+        };
+        detail.exceptions.push(exception.clone());
+        Ok(exception)
+    }
+
+    fn remove_exception(&self, user_id: i64, schedule_uuid: Uuid, exception_uuid: Uuid) -> Result<(), RecurringScheduleError> {
+        let mut state = self.state.lock().unwrap();
+        let detail = state.1.iter_mut()
+            .find(|d| d.schedule.uuid == schedule_uuid && d.schedule.user_id == user_id)
+            .ok_or(RecurringScheduleError::NotFound)?;
+
+        let initial_len = detail.exceptions.len();
+        detail.exceptions.retain(|e| e.uuid != exception_uuid);
+
+        if detail.exceptions.len() < initial_len {
+            Ok(())
+        } else {
+            Err(RecurringScheduleError::NotFound)
+        }
+    }
+}
+
+fn starts(occurrences: &[serde_json::Value]) -> Vec<&str> {
+    occurrences.iter().map(|o| o["starts_at"].as_str().unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn test_recurring_schedule_occurrences_and_exceptions() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let recurring_schedule_repo = web::Data::new(MockRecurringScheduleRepo::new());
+    let workout_uuid = recurring_schedule_repo.workout_uuid();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(recurring_schedule_repo.clone())
+                    .service(crate::routes::recurring_schedule::get_scope::<MockRecurringScheduleRepo>())
+            )
+    ).await;
+
+    // Push day every Monday and Thursday until the end of January
+    let req = test::TestRequest::post()
+        .uri("/recurring-schedules")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "workout_uuid": workout_uuid,
+            "starts_at": "2025-01-06T18:00:00",
+            "rrule": "RRULE:FREQ=WEEKLY;BYDAY=TH,MO;UNTIL=20250131"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let schedule: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(schedule["rrule"], "FREQ=WEEKLY;BYDAY=MO,TH;UNTIL=20250131T235959Z");
+    let schedule_uuid = schedule["uuid"].as_str().unwrap().to_string();
+
+    let occurrences_uri = format!("/recurring-schedules/{}/occurrences?from=2025-01-01T00:00:00&to=2025-03-01T00:00:00", schedule_uuid);
+    let req = test::TestRequest::get()
+        .uri(&occurrences_uri)
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let occurrences: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(starts(&occurrences), vec![
+        "2025-01-06T18:00:00", "2025-01-09T18:00:00", "2025-01-13T18:00:00", "2025-01-16T18:00:00",
+        "2025-01-20T18:00:00", "2025-01-23T18:00:00", "2025-01-27T18:00:00", "2025-01-30T18:00:00",
+    ]);
+
+    // Skip one occurrence and move another to Saturday morning
+    let req = test::TestRequest::post()
+        .uri(&format!("/recurring-schedules/{}/exceptions", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "occurrence_at": "2025-01-09T18:00:00", "action": "skip" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/recurring-schedules/{}/exceptions", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "occurrence_at": "2025-01-30T18:00:00",
+            "action": "reschedule",
+            "rescheduled_at": "2025-02-01T09:00:00"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let reschedule: serde_json::Value = test::read_body_json(resp).await;
+
+    // Exceptions must target a real occurrence, once
+    let req = test::TestRequest::post()
+        .uri(&format!("/recurring-schedules/{}/exceptions", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "occurrence_at": "2025-01-10T18:00:00", "action": "skip" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/recurring-schedules/{}/exceptions", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "occurrence_at": "2025-01-09T18:00:00", "action": "skip" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    let req = test::TestRequest::get()
+        .uri(&occurrences_uri)
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let occurrences: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(starts(&occurrences), vec![
+        "2025-01-06T18:00:00", "2025-01-13T18:00:00", "2025-01-16T18:00:00", "2025-01-20T18:00:00",
+        "2025-01-23T18:00:00", "2025-01-27T18:00:00", "2025-02-01T09:00:00",
+    ]);
+    assert_eq!(occurrences[6]["occurrence_at"], "2025-01-30T18:00:00");
+    assert_eq!(occurrences[6]["rescheduled"], true);
+
+    // Removing the reschedule restores the original slot
+    let req = test::TestRequest::delete()
+        .uri(&format!("/recurring-schedules/{}/exceptions/{}", schedule_uuid, reschedule["uuid"].as_str().unwrap()))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::get()
+        .uri("/recurring-schedules/occurrences?from=2025-01-28T00:00:00&to=2025-03-01T00:00:00")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let occurrences: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(starts(&occurrences), vec!["2025-01-30T18:00:00"]);
+    assert_eq!(occurrences[0]["workout_name"], "Push Day");
+}
+
+#[actix_web::test]
+async fn test_recurring_schedule_rules_and_isolation() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let recurring_schedule_repo = web::Data::new(MockRecurringScheduleRepo::new());
+    let workout_uuid = recurring_schedule_repo.workout_uuid();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(recurring_schedule_repo.clone())
+                    .service(crate::routes::recurring_schedule::get_scope::<MockRecurringScheduleRepo>())
+            )
+    ).await;
+
+    // Unsupported and contradictory rules are rejected
+    for rrule in ["FREQ=YEARLY", "FREQ=DAILY;COUNT=3;UNTIL=20250101", "FREQ=WEEKLY;BYDAY=XX", "INTERVAL=2"] {
+        let req = test::TestRequest::post()
+            .uri("/recurring-schedules")
+            .cookie(Cookie::new("session_id", "user1-session"))
+            .set_json(json!({ "workout_uuid": workout_uuid, "starts_at": "2025-01-31T07:00:00", "rrule": rrule }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", rrule);
+    }
+
+    // Monthly on the 31st skips short months and stops after COUNT occurrences
+    let req = test::TestRequest::post()
+        .uri("/recurring-schedules")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "workout_uuid": workout_uuid, "starts_at": "2025-01-31T07:00:00", "rrule": "FREQ=MONTHLY;COUNT=3" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let schedule: serde_json::Value = test::read_body_json(resp).await;
+    let schedule_uuid = schedule["uuid"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/recurring-schedules/{}/occurrences?from=2025-01-01T00:00:00&to=2026-01-01T00:00:00", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let occurrences: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(starts(&occurrences), vec!["2025-01-31T07:00:00", "2025-03-31T07:00:00", "2025-05-31T07:00:00"]);
+
+    // Every other day
+    let req = test::TestRequest::post()
+        .uri("/recurring-schedules")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "workout_uuid": workout_uuid, "starts_at": "2025-01-01T06:30:00", "rrule": "FREQ=DAILY;INTERVAL=2" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let schedule: serde_json::Value = test::read_body_json(resp).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/recurring-schedules/{}/occurrences?from=2025-03-01T00:00:00&to=2025-03-06T00:00:00", schedule["uuid"].as_str().unwrap()))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let occurrences: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(starts(&occurrences), vec!["2025-03-02T06:30:00", "2025-03-04T06:30:00"]);
+
+    // Inverted ranges are rejected
+    let req = test::TestRequest::get()
+        .uri(&format!("/recurring-schedules/{}/occurrences?from=2025-03-01T00:00:00&to=2025-02-01T00:00:00", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // So are ranges whose default end would be past the last representable date
+    let req = test::TestRequest::get()
+        .uri(&format!("/recurring-schedules/{}/occurrences?from=%2B262142-12-15T00:00:00", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // User 2 can neither schedule user 1's workout nor see user 1's schedules
+    let req = test::TestRequest::post()
+        .uri("/recurring-schedules")
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .set_json(json!({ "workout_uuid": workout_uuid, "starts_at": "2025-01-01T06:30:00", "rrule": "FREQ=DAILY" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/recurring-schedules/{}", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/recurring-schedules/{}", schedule_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    let req = test::TestRequest::get()
+        .uri("/recurring-schedules")
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let schedules: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(schedules.is_empty());
+}
@@ -0,0 +1,130 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, Scope};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    models::report::{BestSet, ExerciseProgress, ReportRange, SessionProgress},
+    repositories::report_repository::{ReportError, ReportRepository},
+    routes::{run_blocking, service_unavailable},
+};
+
+#[derive(Serialize)]
+struct BestSetResponse {
+    log_uuid: Uuid,
+    performed_at: NaiveDateTime,
+    reps: i32,
+    weight: f64,
+    estimated_one_rep_max: f64,
+}
+
+impl BestSetResponse {
+    fn from(best_set: &BestSet) -> Self {
+        Self {
+            log_uuid: best_set.log_uuid,
+            performed_at: best_set.performed_at,
+            reps: best_set.reps,
+            weight: best_set.weight,
+            estimated_one_rep_max: best_set.estimated_one_rep_max,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TrendPointResponse {
+    log_uuid: Uuid,
+    performed_at: NaiveDateTime,
+    volume: f64,
+    estimated_one_rep_max: Option<f64>,
+}
+
+impl TrendPointResponse {
+    fn from(session: &SessionProgress) -> Self {
+        Self {
+            log_uuid: session.log_uuid,
+            performed_at: session.performed_at,
+            volume: session.volume,
+            estimated_one_rep_max: session.estimated_one_rep_max,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExerciseProgressResponse {
+    exercise_uuid: Uuid,
+    exercise_name: String,
+    session_count: usize,
+    set_count: usize,
+    total_reps: i64,
+    total_volume: f64,
+    best_set: Option<BestSetResponse>,
+    estimated_one_rep_max_trend: Vec<TrendPointResponse>,
+}
+
+impl ExerciseProgressResponse {
+    fn from(progress: &ExerciseProgress) -> Self {
+        Self {
+            exercise_uuid: progress.exercise.uuid,
+            exercise_name: progress.exercise.name.clone(),
+            session_count: progress.session_count,
+            set_count: progress.set_count,
+            total_reps: progress.total_reps,
+            total_volume: progress.total_volume,
+            best_set: progress.best_set.as_ref().map(BestSetResponse::from),
+            estimated_one_rep_max_trend: progress.sessions.iter().map(TrendPointResponse::from).collect(),
+        }
+    }
+}
+
+pub fn get_scope<T: ReportRepository + 'static>() -> Scope {
+    web::scope("/reports")
+        .route("/exercises", web::get().to(list_exercise_progress::<T>))
+        .route("/exercises/{exercise_uuid}", web::get().to(get_exercise_progress::<T>))
+}
+
+fn error_response(err: ReportError) -> HttpResponse {
+    match err {
+        ReportError::NotFound => HttpResponse::NotFound().finish(),
+        ReportError::ConnectionUnavailable => service_unavailable(),
+        ReportError::DatabaseError(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn invalid_range_response() -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({
+        "error": "from must be before to"
+    }))
+}
+
+async fn list_exercise_progress<T: ReportRepository>(
+    range: web::Query<ReportRange>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    if !range.is_valid() {
+        return invalid_range_response();
+    }
+    match run_blocking(&repo, move |repo| repo.list_exercise_progress(user_id, &range)).await {
+        Ok(progress) => HttpResponse::Ok().json(
+            progress.iter().map(ExerciseProgressResponse::from).collect::<Vec<_>>()
+        ),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn get_exercise_progress<T: ReportRepository>(
+    exercise_uuid: web::Path<Uuid>,
+    range: web::Query<ReportRange>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    if !range.is_valid() {
+        return invalid_range_response();
+    }
+    match run_blocking(&repo, move |repo| repo.get_exercise_progress(user_id, *exercise_uuid, &range)).await {
+        Ok(progress) => HttpResponse::Ok().json(ExerciseProgressResponse::from(&progress)),
+        Err(e) => error_response(e),
+    }
+}
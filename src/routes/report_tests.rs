@@ -0,0 +1,224 @@
+use actix_web::{cookie::Cookie, test, web, App};
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::{
+    middleware::session::SessionProtection,
+    models::{
+        exercise::Exercise,
+        report::{ExerciseProgress, ReportRange},
+        workout_log::{WorkoutLog, WorkoutLogSet},
+    },
+    repositories::report_repository::{ReportError, ReportRepository},
+    routes::test_support::{is_visible_to, MockAuthRepo},
+};
+
+fn datetime(value: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").unwrap()
+}
+
+/// User 1 has squatted in two sessions and benched in one; user 2 has an exercise but no logs.
+struct MockReportRepo {
+    exercises: Vec<Exercise>,
+    sets: Vec<(WorkoutLog, WorkoutLogSet)>,
+}
+
+impl MockReportRepo {
+    pub fn new() -> Self {
+        let exercise = |id: i64, user_id: i64, name: &str| Exercise {
+            id,
+            uuid: Uuid::new_v4(),
+            user_id: Some(user_id),
+            name: name.to_string(),
+            description: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            category: "strength".to_string(),
+        };
+        let log = |id: i64, performed_at: &str| WorkoutLog {
+            id,
+            uuid: Uuid::new_v4(),
+            user_id: 1,
+            workout_id: 1,
+            performed_at: datetime(performed_at),
+            notes: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        let set = |workout_log_id: i64, exercise_id: i64, set_number: i32, reps: i32, weight: Option<f64>| WorkoutLogSet {
+            id: 0,
+            workout_log_id,
+            exercise_id,
+            user_id: 1,
+            set_number,
+            reps,
+            weight,
+            rpe: None,
+        };
+
+        let first = log(1, "2030-01-06T18:00:00");
+        let second = log(2, "2030-01-13T18:00:00");
+        Self {
+            exercises: vec![exercise(1, 1, "Squat"), exercise(2, 1, "Bench Press"), exercise(3, 2, "Deadlift")],
+            sets: vec![
+                (first.clone(), set(1, 1, 1, 5, Some(100.0))),
+                (first.clone(), set(1, 1, 2, 5, Some(100.0))),
+                (first.clone(), set(1, 2, 1, 10, None)),
+                (second.clone(), set(2, 1, 1, 3, Some(120.0))),
+                (second.clone(), set(2, 1, 2, 8, Some(90.0))),
+            ],
+        }
+    }
+
+    fn exercise_uuid(&self, name: &str) -> Uuid {
+        self.exercises.iter().find(|e| e.name == name).unwrap().uuid
+    }
+
+    fn progress(&self, exercise: &Exercise, range: &ReportRange) -> ExerciseProgress {
+        let sets = self.sets.iter()
+            .filter(|(log, set)| set.exercise_id == exercise.id && range.contains(log.performed_at))
+            .cloned()
+            .collect::<Vec<_>>();
+        ExerciseProgress::from_sets(exercise.clone(), &sets)
+    }
+}
+
+impl ReportRepository for MockReportRepo {
+    fn list_exercise_progress(&self, user_id: i64, range: &ReportRange) -> Result<Vec<ExerciseProgress>, ReportError> {
+        let mut progress = self.exercises.iter()
+            .filter(|e| e.user_id == Some(user_id))
+            .map(|e| self.progress(e, range))
+            .filter(|p| p.set_count > 0)
+            .collect::<Vec<_>>();
+        progress.sort_by(|a, b| a.exercise.name.cmp(&b.exercise.name));
+        Ok(progress)
+    }
+
+    fn get_exercise_progress(&self, user_id: i64, exercise_uuid: Uuid, range: &ReportRange) -> Result<ExerciseProgress, ReportError> {
+        let exercise = self.exercises.iter()
+            .find(|e| e.uuid == exercise_uuid && is_visible_to(e, user_id))
+            .ok_or(ReportError::NotFound)?;
+        Ok(self.progress(exercise, range))
+    }
+}
+
+#[actix_web::test]
+async fn test_exercise_progress_report() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let report_repo = web::Data::new(MockReportRepo::new());
+    let squat_uuid = report_repo.exercise_uuid("Squat");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(report_repo.clone())
+                    .service(crate::routes::report::get_scope::<MockReportRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/reports/exercises/{}", squat_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let progress: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(progress["exercise_name"], "Squat");
+    assert_eq!(progress["session_count"], 2);
+    assert_eq!(progress["set_count"], 4);
+    assert_eq!(progress["total_reps"], 21);
+    assert_eq!(progress["total_volume"], 2080.0);
+
+    // 3 x 120 (e1RM 132) beats 5 x 100 (e1RM 116.7) and 8 x 90 (e1RM 114)
+    assert_eq!(progress["best_set"]["reps"], 3);
+    assert_eq!(progress["best_set"]["weight"], 120.0);
+    assert_eq!(progress["best_set"]["estimated_one_rep_max"], 132.0);
+
+    let trend = progress["estimated_one_rep_max_trend"].as_array().unwrap();
+    assert_eq!(trend.len(), 2);
+    assert_eq!(trend[0]["performed_at"], "2030-01-06T18:00:00");
+    assert_eq!(trend[0]["volume"], 1000.0);
+    assert!((trend[0]["estimated_one_rep_max"].as_f64().unwrap() - 116.67).abs() < 0.01);
+    assert_eq!(trend[1]["estimated_one_rep_max"], 132.0);
+
+    // Range filtering
+    let req = test::TestRequest::get()
+        .uri(&format!("/reports/exercises/{}?from=2030-01-10T00:00:00", squat_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let progress: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(progress["session_count"], 1);
+    assert_eq!(progress["total_volume"], 1080.0);
+
+    // Other users cannot read it
+    let req = test::TestRequest::get()
+        .uri(&format!("/reports/exercises/{}", squat_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // Inverted ranges are rejected
+    let req = test::TestRequest::get()
+        .uri("/reports/exercises?from=2030-02-01T00:00:00&to=2030-01-01T00:00:00")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_exercise_progress_listing() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let report_repo = web::Data::new(MockReportRepo::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(report_repo.clone())
+                    .service(crate::routes::report::get_scope::<MockReportRepo>())
+            )
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reports/exercises")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let progress: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(progress.len(), 2);
+
+    // Bodyweight sets count towards reps but have no volume or estimated max
+    assert_eq!(progress[0]["exercise_name"], "Bench Press");
+    assert_eq!(progress[0]["total_reps"], 10);
+    assert_eq!(progress[0]["total_volume"], 0.0);
+    assert!(progress[0]["best_set"].is_null());
+    assert_eq!(progress[1]["exercise_name"], "Squat");
+
+    // Sessions outside the range are excluded entirely
+    let req = test::TestRequest::get()
+        .uri("/reports/exercises?from=2030-01-10T00:00:00&to=2030-02-01T00:00:00")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let progress: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(progress.len(), 1);
+    assert_eq!(progress[0]["exercise_name"], "Squat");
+
+    // Users without logs get an empty report
+    let req = test::TestRequest::get()
+        .uri("/reports/exercises")
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let progress: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(progress.is_empty());
+}
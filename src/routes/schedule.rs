@@ -0,0 +1,154 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, Scope};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        scheduled_workout::{CreateScheduledWorkout, ScheduleFilter, ScheduledWorkout, UpdateScheduledWorkout},
+        workout::Workout,
+    },
+    repositories::schedule_repository::{ScheduleError, ScheduleRepository},
+    routes::{run_blocking, service_unavailable},
+};
+
+#[derive(Serialize)]
+struct ScheduledWorkoutResponse {
+    uuid: Uuid,
+    workout_uuid: Uuid,
+    workout_name: String,
+    scheduled_at: NaiveDateTime,
+    status: String,
+    notes: Option<String>,
+}
+
+impl ScheduledWorkoutResponse {
+    fn from((scheduled, workout): &(ScheduledWorkout, Workout)) -> Self {
+        Self {
+            uuid: scheduled.uuid,
+            workout_uuid: workout.uuid,
+            workout_name: workout.name.clone(),
+            scheduled_at: scheduled.scheduled_at,
+            status: scheduled.status.clone(),
+            notes: scheduled.notes.clone(),
+        }
+    }
+}
+
+pub fn get_scope<T: ScheduleRepository + 'static>() -> Scope {
+    web::scope("/schedule")
+        .route("", web::post().to(schedule_workout::<T>))
+        .route("", web::get().to(list_scheduled_workouts::<T>))
+        .route("/upcoming", web::get().to(list_upcoming::<T>))
+        .route("/past", web::get().to(list_past::<T>))
+        .route("/{scheduled_uuid}", web::get().to(get_scheduled_workout::<T>))
+        .route("/{scheduled_uuid}", web::put().to(update_scheduled_workout::<T>))
+        .route("/{scheduled_uuid}", web::delete().to(delete_scheduled_workout::<T>))
+}
+
+fn error_response(err: ScheduleError) -> HttpResponse {
+    match err {
+        ScheduleError::NotFound => HttpResponse::NotFound().finish(),
+        ScheduleError::WorkoutNotFound => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Workout not found"
+        })),
+        ScheduleError::ConnectionUnavailable => service_unavailable(),
+        ScheduleError::DatabaseError(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn list_response<T: ScheduleRepository>(repo: &web::Data<T>, user_id: i64, filter: ScheduleFilter, newest_first: bool) -> HttpResponse {
+    match run_blocking(repo, move |repo| repo.list_scheduled_workouts(user_id, filter)).await {
+        Ok(mut scheduled) => {
+            if newest_first {
+                scheduled.reverse();
+            }
+            HttpResponse::Ok().json(
+                scheduled.iter().map(ScheduledWorkoutResponse::from).collect::<Vec<_>>()
+            )
+        },
+        Err(e) => error_response(e),
+    }
+}
+
+async fn schedule_workout<T: ScheduleRepository>(
+    scheduled: web::Json<CreateScheduledWorkout>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.schedule_workout(user_id, scheduled.0)).await {
+        Ok(scheduled) => HttpResponse::Created().json(ScheduledWorkoutResponse::from(&scheduled)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_scheduled_workouts<T: ScheduleRepository>(
+    filter: web::Query<ScheduleFilter>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    list_response(&repo, user_id, filter.into_inner(), false).await
+}
+
+async fn list_upcoming<T: ScheduleRepository>(
+    filter: web::Query<ScheduleFilter>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    let now = chrono::Utc::now().naive_utc();
+    let mut filter = filter.into_inner();
+    filter.from = Some(filter.from.map_or(now, |from| from.max(now)));
+    list_response(&repo, user_id, filter, false).await
+}
+
+async fn list_past<T: ScheduleRepository>(
+    filter: web::Query<ScheduleFilter>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    let now = chrono::Utc::now().naive_utc();
+    let mut filter = filter.into_inner();
+    filter.to = Some(filter.to.map_or(now, |to| to.min(now)));
+    list_response(&repo, user_id, filter, true).await
+}
+
+async fn get_scheduled_workout<T: ScheduleRepository>(
+    scheduled_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.get_scheduled_workout(user_id, *scheduled_uuid)).await {
+        Ok(scheduled) => HttpResponse::Ok().json(ScheduledWorkoutResponse::from(&scheduled)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn update_scheduled_workout<T: ScheduleRepository>(
+    scheduled_uuid: web::Path<Uuid>,
+    scheduled: web::Json<UpdateScheduledWorkout>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.update_scheduled_workout(user_id, *scheduled_uuid, scheduled.0)).await {
+        Ok(scheduled) => HttpResponse::Ok().json(ScheduledWorkoutResponse::from(&scheduled)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn delete_scheduled_workout<T: ScheduleRepository>(
+    scheduled_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.delete_scheduled_workout(user_id, *scheduled_uuid)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
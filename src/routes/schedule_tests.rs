@@ -0,0 +1,286 @@
+use actix_web::{cookie::Cookie, test, web, App};
+use serde_json::json;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    middleware::session::SessionProtection,
+    models::{
+        scheduled_workout::{CreateScheduledWorkout, ScheduleFilter, ScheduledWorkout, UpdateScheduledWorkout},
+        workout::Workout,
+    },
+    repositories::schedule_repository::{ScheduleError, ScheduleRepository},
+    routes::test_support::{schedule_matches, MockAuthRepo},
+};
+
+struct MockScheduleRepo {
+    state: Mutex<(Vec<Workout>, Vec<ScheduledWorkout>)>,
+}
+
+impl MockScheduleRepo {
+    pub fn new() -> Self {
+        let workouts = vec![
+            Workout {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                user_id: 1,
+                name: "Push Day".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+            },
+            Workout {
+                id: 2,
+                uuid: Uuid::new_v4(),
+                user_id: 2,
+                name: "Leg Day".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+            },
+        ];
+        Self {
+            state: Mutex::new((workouts, vec![])),
+        }
+    }
+
+    fn workout_uuid(&self, user_id: i64) -> Uuid {
+        self.state.lock().unwrap().0.iter().find(|w| w.user_id == user_id).unwrap().uuid
+    }
+
+    fn find(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        let state = self.state.lock().unwrap();
+        let (workouts, scheduled) = &*state;
+        let scheduled = scheduled.iter()
+            .find(|s| s.uuid == scheduled_uuid && s.user_id == user_id)
+            .ok_or(ScheduleError::NotFound)?;
+        let workout = workouts.iter().find(|w| w.id == scheduled.workout_id).unwrap();
+        Ok((scheduled.clone(), workout.clone()))
+    }
+}
+
+impl ScheduleRepository for MockScheduleRepo {
+    fn schedule_workout(&self, user_id: i64, scheduled: CreateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        let mut state = self.state.lock().unwrap();
+        let (workouts, all_scheduled) = &mut *state;
+
+        let workout = workouts.iter()
+            .find(|w| w.uuid == scheduled.workout_uuid && w.user_id == user_id)
+            .ok_or(ScheduleError::WorkoutNotFound)?;
+
+        let new_scheduled = ScheduledWorkout::new(user_id, workout.id, scheduled.scheduled_at, scheduled.notes);
+        let scheduled = ScheduledWorkout {
+            id: (all_scheduled.len() + 1) as i64,
+            uuid: new_scheduled.uuid,
+            user_id,
+            workout_id: workout.id,
+            scheduled_at: new_scheduled.scheduled_at,
+            status: new_scheduled.status,
+            notes: new_scheduled.notes,
+            created_at: new_scheduled.created_at,
+            updated_at: new_scheduled.updated_at,
+        };
+        all_scheduled.push(scheduled.clone());
+        Ok((scheduled, workout.clone()))
+    }
+
+    fn get_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        self.find(user_id, scheduled_uuid)
+    }
+
+    fn list_scheduled_workouts(&self, user_id: i64, filter: ScheduleFilter) -> Result<Vec<(ScheduledWorkout, Workout)>, ScheduleError> {
+        let state = self.state.lock().unwrap();
+        let (workouts, scheduled) = &*state;
+        let mut result: Vec<(ScheduledWorkout, Workout)> = scheduled.iter()
+            .filter(|s| s.user_id == user_id && schedule_matches(&filter, s))
+            .map(|s| (s.clone(), workouts.iter().find(|w| w.id == s.workout_id).unwrap().clone()))
+            .collect();
+        result.sort_by_key(|(s, _)| s.scheduled_at);
+        Ok(result)
+    }
+
+    fn update_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid, update: UpdateScheduledWorkout) -> Result<(ScheduledWorkout, Workout), ScheduleError> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let scheduled = state.1.iter_mut()
+                .find(|s| s.uuid == scheduled_uuid && s.user_id == user_id)
+                .ok_or(ScheduleError::NotFound)?;
+            if let Some(scheduled_at) = update.scheduled_at {
+                scheduled.scheduled_at = scheduled_at;
+            }
+            if let Some(status) = update.status {
+                scheduled.status = status.as_str().to_string();
+            }
+            if update.notes.is_some() {
+                scheduled.notes = update.notes;
+            }
+        }
+        self.find(user_id, scheduled_uuid)
+    }
+
+    fn delete_scheduled_workout(&self, user_id: i64, scheduled_uuid: Uuid) -> Result<(), ScheduleError> {
+        let mut state = self.state.lock().unwrap();
+        let initial_len = state.1.len();
+        state.1.retain(|s| !(s.uuid == scheduled_uuid && s.user_id == user_id));
+
+        if state.1.len() < initial_len {
+            Ok(())
+        } else {
+            Err(ScheduleError::NotFound)
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_schedule_crud_and_status() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let schedule_repo = web::Data::new(MockScheduleRepo::new());
+    let workout_uuid = schedule_repo.workout_uuid(1);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(schedule_repo.clone())
+                    .service(crate::routes::schedule::get_scope::<MockScheduleRepo>())
+            )
+    ).await;
+
+    // Schedule a workout
+    let req = test::TestRequest::post()
+        .uri("/schedule")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "workout_uuid": workout_uuid,
+            "scheduled_at": "2030-01-07T07:00:00",
+            "notes": "Before work"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let scheduled: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(scheduled["status"], "planned");
+    assert_eq!(scheduled["workout_name"], "Push Day");
+    let scheduled_uuid = scheduled["uuid"].as_str().unwrap().to_string();
+
+    // Mark it completed
+    let req = test::TestRequest::put()
+        .uri(&format!("/schedule/{}", scheduled_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "status": "completed" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let scheduled: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(scheduled["status"], "completed");
+    assert_eq!(scheduled["notes"], "Before work");
+
+    // Unknown statuses are rejected
+    let req = test::TestRequest::put()
+        .uri(&format!("/schedule/{}", scheduled_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({ "status": "postponed" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Other users cannot see it
+    let req = test::TestRequest::get()
+        .uri(&format!("/schedule/{}", scheduled_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // Delete it
+    let req = test::TestRequest::delete()
+        .uri(&format!("/schedule/{}", scheduled_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/schedule/{}", scheduled_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_schedule_listing_filters() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let schedule_repo = web::Data::new(MockScheduleRepo::new());
+    let workout_uuid = schedule_repo.workout_uuid(1);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(schedule_repo.clone())
+                    .service(crate::routes::schedule::get_scope::<MockScheduleRepo>())
+            )
+    ).await;
+
+    for scheduled_at in ["2020-03-01T08:00:00", "2020-03-08T08:00:00", "2030-03-01T08:00:00", "2030-03-08T08:00:00"] {
+        let req = test::TestRequest::post()
+            .uri("/schedule")
+            .cookie(Cookie::new("session_id", "user1-session"))
+            .set_json(json!({ "workout_uuid": workout_uuid, "scheduled_at": scheduled_at }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 201);
+    }
+
+    // Upcoming is soonest first
+    let req = test::TestRequest::get()
+        .uri("/schedule/upcoming")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let upcoming: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(upcoming.len(), 2);
+    assert_eq!(upcoming[0]["scheduled_at"], "2030-03-01T08:00:00");
+
+    // Past is most recent first
+    let req = test::TestRequest::get()
+        .uri("/schedule/past")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let past: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(past.len(), 2);
+    assert_eq!(past[0]["scheduled_at"], "2020-03-08T08:00:00");
+
+    // Range filter
+    let req = test::TestRequest::get()
+        .uri("/schedule?from=2020-03-05T00:00:00&to=2030-03-05T00:00:00")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let ranged: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(ranged.len(), 2);
+
+    // Status filter
+    let req = test::TestRequest::get()
+        .uri("/schedule?status=completed")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let completed: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(completed.is_empty());
+
+    // Other users have an empty calendar
+    let req = test::TestRequest::get()
+        .uri("/schedule")
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let others: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(others.is_empty());
+}
@@ -0,0 +1,114 @@
+//! Doubles shared by the route tests.
+
+use chrono::NaiveDateTime;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        api_token::ApiToken, exercise::Exercise, oidc_login_attempt::OidcLoginAttempt, scheduled_workout::{ScheduleFilter, ScheduledWorkout},
+        session::{Session, SessionClient}, temp_session::TempSession, two_factor::TwoFactorCode, user::User,
+    },
+    oidc::IdentityClaims,
+    repositories::auth_repository::{AuthError, AuthRepository},
+};
+
+/// Knows the sessions `user1-session` and `user2-session` (CSRF tokens `user1-csrf` and
+/// `user2-csrf`) and nothing else; routes outside `/auth` only validate sessions.
+pub(crate) struct MockAuthRepo {
+  sessions: Mutex<Vec<Session>>,
+}
+
+impl MockAuthRepo {
+  pub(crate) fn new() -> Self {
+      let sessions = vec![
+          Session {
+              id: 1,
+              user_id: 1,
+              token: "user1-session".to_string(),
+              csrf_token: "user1-csrf".to_string(),
+              expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+              created_at: chrono::Utc::now().naive_utc(),
+              uuid: Uuid::new_v4(),
+              last_seen_at: chrono::Utc::now().naive_utc(),
+              user_agent: None,
+              ip_address: None,
+              remember_me: false,
+              absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+              two_factor_pending: false,
+          },
+          Session {
+              id: 2,
+              user_id: 2,
+              token: "user2-session".to_string(),
+              csrf_token: "user2-csrf".to_string(),
+              expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+              created_at: chrono::Utc::now().naive_utc(),
+              uuid: Uuid::new_v4(),
+              last_seen_at: chrono::Utc::now().naive_utc(),
+              user_agent: None,
+              ip_address: None,
+              remember_me: false,
+              absolute_expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
+              two_factor_pending: false,
+          },
+      ];
+      Self {
+          sessions: Mutex::new(sessions),
+      }
+  }
+}
+
+impl AuthRepository for MockAuthRepo {
+  fn validate_session(&self, session_token: &str) -> Result<i64, AuthError> {
+      let sessions = self.sessions.lock().unwrap();
+      let session = sessions.iter()
+          .find(|s| s.token == session_token)
+          .ok_or(AuthError::InvalidSession)?;
+      Ok(session.user_id)
+  }
+
+  fn create_temp_session(&self, _csrf_token: String) -> Result<TempSession, AuthError> { unimplemented!() }
+  fn create_session(&self, _user_id: i64, _previous_session_id: Option<&str>, _csrf_token: String, _client: SessionClient, _remember_me: bool) -> Result<Session, AuthError> { unimplemented!() }
+  fn validate_csrf(&self, _session_id: &str, _csrf_token: &str) -> Result<(), AuthError> { unimplemented!() }
+  fn session_csrf_token(&self, _session_token: &str) -> Result<String, AuthError> { unimplemented!() }
+  fn rotate_csrf_token(&self, _session_token: &str, _csrf_token: String) -> Result<(), AuthError> { unimplemented!() }
+  fn verify_credentials(&self, _email: String, _password: String, _ip_address: Option<String>) -> Result<User, AuthError> { unimplemented!() }
+  fn invalidate_session(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
+  fn create_user(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
+  fn delete_user(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
+  fn list_sessions(&self, _session_token: &str) -> Result<Vec<Session>, AuthError> { unimplemented!() }
+  fn revoke_session(&self, _session_token: &str, _session_uuid: Uuid) -> Result<(), AuthError> { unimplemented!() }
+  fn revoke_other_sessions(&self, _session_token: &str) -> Result<usize, AuthError> { unimplemented!() }
+  fn change_password(&self, _session_token: &str, _current_password: String, _new_password: String) -> Result<(), AuthError> { unimplemented!() }
+  fn create_password_reset(&self, _email: String) -> Result<Option<(User, String)>, AuthError> { unimplemented!() }
+  fn reset_password(&self, _token: &str, _new_password: String) -> Result<(), AuthError> { unimplemented!() }
+  fn create_email_verification(&self, _user_id: i64) -> Result<(User, String), AuthError> { unimplemented!() }
+  fn verify_email(&self, _token: &str) -> Result<User, AuthError> { unimplemented!() }
+  fn email_verified(&self, _user_id: i64) -> Result<bool, AuthError> { unimplemented!() }
+  fn begin_totp_enrollment(&self, _session_token: &str) -> Result<(User, String), AuthError> { unimplemented!() }
+  fn confirm_totp_enrollment(&self, _session_token: &str, _code: &str) -> Result<Vec<String>, AuthError> { unimplemented!() }
+  fn verify_two_factor(&self, _session_token: &str, _code: TwoFactorCode) -> Result<(User, Session), AuthError> { unimplemented!() }
+  fn disable_totp(&self, _session_token: &str, _password: String) -> Result<(), AuthError> { unimplemented!() }
+  fn create_api_token(&self, _session_token: &str, _name: String, _scopes: Vec<String>, _expires_at: Option<NaiveDateTime>) -> Result<(ApiToken, String), AuthError> { unimplemented!() }
+  fn list_api_tokens(&self, _session_token: &str) -> Result<Vec<ApiToken>, AuthError> { unimplemented!() }
+  fn revoke_api_token(&self, _session_token: &str, _token_uuid: Uuid) -> Result<(), AuthError> { unimplemented!() }
+  fn authenticate_api_token(&self, _token: &str) -> Result<ApiToken, AuthError> { unimplemented!() }
+  fn create_oidc_login(&self) -> Result<(String, OidcLoginAttempt), AuthError> { unimplemented!() }
+  fn take_oidc_login(&self, _state: &str) -> Result<OidcLoginAttempt, AuthError> { unimplemented!() }
+  fn sign_in_with_identity(&self, _claims: IdentityClaims) -> Result<User, AuthError> { unimplemented!() }
+  fn create_login_link(&self, _email: String) -> Result<Option<(User, String)>, AuthError> { unimplemented!() }
+  fn redeem_login_link(&self, _token: &str) -> Result<User, AuthError> { unimplemented!() }
+}
+
+/// Applies a schedule filter the way `PgScheduleRepository` does in SQL.
+pub(crate) fn schedule_matches(filter: &ScheduleFilter, scheduled: &ScheduledWorkout) -> bool {
+  filter.from.is_none_or(|from| scheduled.scheduled_at >= from)
+      && filter.to.is_none_or(|to| scheduled.scheduled_at < to)
+      && filter.status.is_none_or(|status| scheduled.status == status.as_str())
+}
+
+/// Catalog exercises are visible to everyone, personal ones only to their owner.
+pub(crate) fn is_visible_to(exercise: &Exercise, user_id: i64) -> bool {
+  exercise.user_id.is_none_or(|owner| owner == user_id)
+}
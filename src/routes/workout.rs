@@ -5,6 +5,7 @@ use uuid::Uuid;
 use crate::{
     models::workout::{CreateWorkout, UpdateWorkout, Workout},
     repositories::workout_repository::{WorkoutError, WorkoutRepository},
+    routes::{run_blocking, service_unavailable},
 };
 
 #[derive(Serialize)]
@@ -43,8 +44,9 @@ async fn create_workout<T: WorkoutRepository>(
     repo: web::Data<T>,
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
-    match repo.create_workout(user_id, workout.0) {
+    match run_blocking(&repo, move |repo| repo.create_workout(user_id, workout.0)).await {
         Ok(workout) => HttpResponse::Created().json(WorkoutResponse::from(&workout)),
+        Err(WorkoutError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -55,10 +57,11 @@ async fn list_workouts<T: WorkoutRepository>(
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
     
-    match repo.list_workouts(user_id) {
+    match run_blocking(&repo, move |repo| repo.list_workouts(user_id)).await {
         Ok(workouts) => HttpResponse::Ok().json(
             workouts.iter().map(WorkoutResponse::from).collect::<Vec<_>>()
         ),
+        Err(WorkoutError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -69,10 +72,11 @@ async fn get_workout<T: WorkoutRepository>(
     repo: web::Data<T>,
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
-    match repo.get_workout(user_id, *workout_uuid) {
+    match run_blocking(&repo, move |repo| repo.get_workout(user_id, *workout_uuid)).await {
         Ok(workout) => HttpResponse::Ok().json(WorkoutResponse::from(&workout)),
         Err(WorkoutError::NotFound) => HttpResponse::NotFound().finish(),
         Err(WorkoutError::Unauthorized) => HttpResponse::Unauthorized().finish(),
+        Err(WorkoutError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -85,10 +89,11 @@ async fn update_workout<T: WorkoutRepository>(
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
     
-    match repo.update_workout(user_id, *workout_uuid, workout.0) {
+    match run_blocking(&repo, move |repo| repo.update_workout(user_id, *workout_uuid, workout.0)).await {
         Ok(workout) => HttpResponse::Ok().json(WorkoutResponse::from(&workout)),
         Err(WorkoutError::NotFound) => HttpResponse::NotFound().finish(),
         Err(WorkoutError::Unauthorized) => HttpResponse::Unauthorized().finish(),
+        Err(WorkoutError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -100,10 +105,11 @@ async fn delete_workout<T: WorkoutRepository>(
 ) -> impl Responder {
     let user_id = *req.extensions().get::<i64>().unwrap();
     
-    match repo.delete_workout(user_id, *workout_uuid) {
+    match run_blocking(&repo, move |repo| repo.delete_workout(user_id, *workout_uuid)).await {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(WorkoutError::NotFound) => HttpResponse::NotFound().finish(),
         Err(WorkoutError::Unauthorized) => HttpResponse::Unauthorized().finish(),
+        Err(WorkoutError::ConnectionUnavailable) => service_unavailable(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
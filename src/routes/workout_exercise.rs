@@ -1,11 +1,35 @@
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Resource, Responder};
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::{
-  models::workout_exercise::AddExerciseRequest,
+  models::{exercise::Exercise, workout_exercise::{AddExerciseRequest, Prescription, WorkoutExercise}},
   repositories::workout_exercise_repository::{WorkoutExerciseError, WorkoutExerciseRepository},
+  routes::{run_blocking, service_unavailable},
 };
 
+#[derive(Serialize)]
+struct WorkoutExerciseResponse {
+  uuid: Uuid,
+  name: String,
+  description: Option<String>,
+  order: i32,
+  #[serde(flatten)]
+  prescription: Prescription,
+}
+
+impl WorkoutExerciseResponse {
+  fn from((exercise, workout_exercise): &(Exercise, WorkoutExercise)) -> Self {
+    Self {
+      uuid: exercise.uuid,
+      name: exercise.name.clone(),
+      description: exercise.description.clone(),
+      order: workout_exercise.order,
+      prescription: workout_exercise.prescription(),
+    }
+  }
+}
+
 pub fn get_scope_workout_id_exercises_exercise_id<T: WorkoutExerciseRepository + 'static>() -> Resource {
   web::resource("/workouts/{workout_uuid}/exercises/{exercise_uuid}")
       .route(web::delete().to(remove_exercise_from_workout::<T>))
@@ -24,12 +48,17 @@ async fn add_exercise_to_workout<T: WorkoutExerciseRepository>(
   repo: web::Data<T>,
 ) -> impl Responder {
   let user_id = *req.extensions().get::<i64>().unwrap();
-  match repo.add_exercise_to_workout(user_id, *workout_uuid, exercise.exercise_uuid, exercise.order) {
+  let exercise = exercise.into_inner();
+  match run_blocking(&repo, move |repo| repo.add_exercise_to_workout(user_id, *workout_uuid, exercise.exercise_uuid, exercise.order, exercise.prescription)).await {
     Ok(()) => HttpResponse::Created().finish(),
     Err(e) => match e {
       WorkoutExerciseError::WorkoutNotFound => HttpResponse::NotFound().finish(),
       WorkoutExerciseError::ExerciseNotFound => HttpResponse::NotFound().finish(),
+      WorkoutExerciseError::InvalidPrescription => HttpResponse::BadRequest().json(serde_json::json!({
+        "error": "Sets and reps must be positive; weight and rest seconds must not be negative"
+      })),
       WorkoutExerciseError::Unauthorized => HttpResponse::Forbidden().finish(),
+      WorkoutExerciseError::ConnectionUnavailable => service_unavailable(),
       _ => HttpResponse::InternalServerError().finish(),
     }
   }
@@ -41,11 +70,14 @@ async fn list_workout_exercises<T: WorkoutExerciseRepository>(
   repo: web::Data<T>,
 ) -> impl Responder {
   let user_id = *req.extensions().get::<i64>().unwrap();
-  match repo.list_workout_exercises(user_id, *workout_uuid) {
-    Ok(exercises) => HttpResponse::Ok().json(exercises),
+  match run_blocking(&repo, move |repo| repo.list_workout_exercises(user_id, *workout_uuid)).await {
+    Ok(exercises) => HttpResponse::Ok().json(
+      exercises.iter().map(WorkoutExerciseResponse::from).collect::<Vec<_>>()
+    ),
     Err(e) => match e {
       WorkoutExerciseError::WorkoutNotFound => HttpResponse::NotFound().finish(),
       WorkoutExerciseError::Unauthorized => HttpResponse::Forbidden().finish(),
+      WorkoutExerciseError::ConnectionUnavailable => service_unavailable(),
       _ => HttpResponse::InternalServerError().finish(),
     }
   }
@@ -58,13 +90,14 @@ async fn remove_exercise_from_workout<T: WorkoutExerciseRepository>(
 ) -> impl Responder {
   let (workout_uuid, exercise_uuid) = path.into_inner();
   let user_id = *req.extensions().get::<i64>().unwrap();
-  match repo.remove_exercise_from_workout(user_id, workout_uuid, exercise_uuid) {
+  match run_blocking(&repo, move |repo| repo.remove_exercise_from_workout(user_id, workout_uuid, exercise_uuid)).await {
     Ok(()) => HttpResponse::NoContent().finish(),
     Err(e) => match e {
       WorkoutExerciseError::NotFound => HttpResponse::NotFound().finish(),
       WorkoutExerciseError::WorkoutNotFound => HttpResponse::NotFound().finish(),
       WorkoutExerciseError::ExerciseNotFound => HttpResponse::NotFound().finish(),
       WorkoutExerciseError::Unauthorized => HttpResponse::Forbidden().finish(),
+      WorkoutExerciseError::ConnectionUnavailable => service_unavailable(),
       _ => HttpResponse::InternalServerError().finish(),
     }
   }
@@ -5,61 +5,13 @@ use uuid::Uuid;
 
 use crate::{
     middleware::session::SessionProtection,
-    models::{exercise::Exercise, session::Session, user::User, workout::Workout, workout_exercise::WorkoutExercise},
-    repositories::{
-        auth_repository::{AuthError, AuthRepository}, workout_exercise_repository::{WorkoutExerciseError, WorkoutExerciseRepository}
-    }
+    models::{exercise::Exercise, workout::Workout, workout_exercise::{Prescription, WorkoutExercise}},
+    repositories::workout_exercise_repository::{WorkoutExerciseError, WorkoutExerciseRepository},
+    routes::test_support::{is_visible_to, MockAuthRepo},
 };
 
-pub struct MockAuthRepo {
-  sessions: Mutex<Vec<Session>>,
-}
-
-impl MockAuthRepo {
-  pub fn new() -> Self {
-      let mut sessions = vec![];
-      sessions.push(Session {
-          id: 1,
-          user_id: 1,
-          token: "user1-session".to_string(),
-          csrf_token: "user1-csrf".to_string(),
-          expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
-          created_at: chrono::Utc::now().naive_utc(),
-      });
-      sessions.push(Session {
-          id: 2,
-          user_id: 2,
-          token: "user2-session".to_string(),
-          csrf_token: "user2-csrf".to_string(),
-          expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
-          created_at: chrono::Utc::now().naive_utc(),
-      });
-      Self {
-          sessions: Mutex::new(sessions),
-      }
-  }
-}
 
-impl AuthRepository for MockAuthRepo {
-  fn validate_session(&self, session_token: &str) -> Result<i64, AuthError> {
-      let sessions = self.sessions.lock().unwrap();
-      let session = sessions.iter()
-          .find(|s| s.token == session_token)
-          .ok_or(AuthError::InvalidSession)?;
-      Ok(session.user_id)
-  }
-
-  // Implement other required methods with empty/mock implementations
-  fn create_temp_session(&self, _csrf_token: String) -> Result<crate::models::temp_session::TempSession, AuthError> { unimplemented!() }
-  fn create_session(&self, _user_id: i64, _session_id: String, _csrf_token: String) -> Result<Session, AuthError> { unimplemented!() }
-  fn validate_csrf(&self, _session_id: &str, _csrf_token: &str) -> Result<(), AuthError> { unimplemented!() }
-  fn verify_credentials(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
-  fn invalidate_session(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
-  fn create_user(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
-  fn delete_user(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
-}
-
-pub struct MockWorkoutExerciseRepo {
+struct MockWorkoutExerciseRepo {
     state: Mutex<(Vec<Workout>, Vec<Exercise>, Vec<WorkoutExercise>)>,
 }
 
@@ -89,20 +41,32 @@ impl MockWorkoutExerciseRepo {
             Exercise {
                 id: 1,
                 uuid: Uuid::new_v4(),
-                user_id: 1,
+                user_id: Some(1),
                 name: "Test Exercise 1 for user 1".to_string(),
                 description: None,
                 created_at: chrono::Utc::now().naive_utc(),
                 updated_at: chrono::Utc::now().naive_utc(),
+                category: "strength".to_string(),
             },
             Exercise {
                 id: 2,
                 uuid: Uuid::new_v4(),
-                user_id: 1,
+                user_id: Some(1),
                 name: "Test Exercise 2 for user 1".to_string(),
                 description: None,
                 created_at: chrono::Utc::now().naive_utc(),
                 updated_at: chrono::Utc::now().naive_utc(),
+                category: "strength".to_string(),
+            },
+            Exercise {
+                id: 3,
+                uuid: Uuid::new_v4(),
+                user_id: None,
+                name: "Catalog Exercise".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+                category: "strength".to_string(),
             },
         ];
         Self {
@@ -112,7 +76,11 @@ impl MockWorkoutExerciseRepo {
 }
 
 impl WorkoutExerciseRepository for MockWorkoutExerciseRepo {
-    fn add_exercise_to_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid, order: i32) -> Result<(), WorkoutExerciseError> {
+    fn add_exercise_to_workout(&self, user_id: i64, workout_uuid: Uuid, exercise_uuid: Uuid, order: i32, prescription: Prescription) -> Result<(), WorkoutExerciseError> {
+        if !prescription.is_valid() {
+            return Err(WorkoutExerciseError::InvalidPrescription);
+        }
+
         let mut state = self.state.lock().unwrap();
         let (workouts, exercises, workout_exercises) = &mut *state;
 
@@ -124,16 +92,11 @@ impl WorkoutExerciseRepository for MockWorkoutExerciseRepo {
 
         let exercise_id = exercises
             .iter()
-            .find(|e| e.uuid == exercise_uuid && e.user_id == user_id)
+            .find(|e| e.uuid == exercise_uuid && is_visible_to(e, user_id))
             .ok_or(WorkoutExerciseError::ExerciseNotFound)?
             .id;
 
-        workout_exercises.push(WorkoutExercise {
-            workout_id,
-            exercise_id,
-            user_id,
-            order,
-        });
+        workout_exercises.push(WorkoutExercise::new(workout_id, exercise_id, user_id, order, prescription));
         Ok(())
     }
 
@@ -149,7 +112,7 @@ impl WorkoutExerciseRepository for MockWorkoutExerciseRepo {
 
         let exercise_id = exercises
             .iter()
-            .find(|e| e.uuid == exercise_uuid && e.user_id == user_id)
+            .find(|e| e.uuid == exercise_uuid && is_visible_to(e, user_id))
             .ok_or(WorkoutExerciseError::ExerciseNotFound)?
             .id;
 
@@ -167,7 +130,7 @@ impl WorkoutExerciseRepository for MockWorkoutExerciseRepo {
         }
     }
 
-    fn list_workout_exercises(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<(Exercise, i32)>, WorkoutExerciseError> {
+    fn list_workout_exercises(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<(Exercise, WorkoutExercise)>, WorkoutExerciseError> {
         let state = self.state.lock().unwrap();
         let (workouts, exercises, workout_exercises) = &*state;
 
@@ -177,14 +140,14 @@ impl WorkoutExerciseRepository for MockWorkoutExerciseRepo {
             .ok_or(WorkoutExerciseError::WorkoutNotFound)?
             .id;
 
-        let result: Vec<(Exercise, i32)> = workout_exercises
+        let result: Vec<(Exercise, WorkoutExercise)> = workout_exercises
             .iter()
             .filter(|we| we.workout_id == workout_id && we.user_id == user_id)
             .filter_map(|we| {
                 exercises
                     .iter()
                     .find(|e| e.id == we.exercise_id)
-                    .map(|e| (e.clone(), we.order))
+                    .map(|e| (e.clone(), we.clone()))
             })
             .collect();
 
@@ -204,7 +167,7 @@ async fn test_workout_exercises() {
         let state = workout_exercise_repo.state.lock().unwrap();
         let (workouts, exercises, _) = &*state;
         workout_uuid = workouts.iter().find(|w| w.user_id == 1).unwrap().uuid;
-        exercise_uuid = exercises.iter().find(|e| e.user_id == 1).unwrap().uuid;
+        exercise_uuid = exercises.iter().find(|e| e.user_id == Some(1)).unwrap().uuid;
     }  // Lock is released here
 
     let app = test::init_service(
@@ -277,7 +240,7 @@ async fn test_workout_exercise_isolation() {
             .map(|w| w.uuid)
             .unwrap();
         user1_exercise_uuid = exercises.iter()
-            .find(|e| e.user_id == 1)
+            .find(|e| e.user_id == Some(1))
             .map(|e| e.uuid)
             .unwrap();
     }  // Lock is released here
@@ -333,4 +296,127 @@ async fn test_workout_exercise_isolation() {
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_client_error());
-} 
\ No newline at end of file
+}
+
+#[actix_web::test]
+async fn test_workout_exercise_prescription() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let workout_exercise_repo = web::Data::new(MockWorkoutExerciseRepo::new());
+
+    let (workout_uuid, exercise_uuid);
+    {
+        let state = workout_exercise_repo.state.lock().unwrap();
+        let (workouts, exercises, _) = &*state;
+        workout_uuid = workouts.iter().find(|w| w.user_id == 1).unwrap().uuid;
+        exercise_uuid = exercises.iter().find(|e| e.user_id == Some(1)).unwrap().uuid;
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(workout_exercise_repo.clone())
+                    .service(crate::routes::workout_exercise::get_scope_workout_id_exercises::<MockWorkoutExerciseRepo>())
+            )
+    ).await;
+
+    // Negative reps are rejected
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/exercises", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "exercise_uuid": exercise_uuid,
+            "order": 1,
+            "sets": 3,
+            "reps": -5
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Add exercise with a full prescription
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/exercises", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "exercise_uuid": exercise_uuid,
+            "order": 1,
+            "sets": 3,
+            "reps": 8,
+            "weight": 62.5,
+            "rest_seconds": 90
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+
+    // Prescription is returned alongside the exercise
+    let req = test::TestRequest::get()
+        .uri(&format!("/workouts/{}/exercises", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let exercises: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(exercises.len(), 1);
+    assert_eq!(exercises[0]["uuid"], json!(exercise_uuid));
+    assert_eq!(exercises[0]["order"], 1);
+    assert_eq!(exercises[0]["sets"], 3);
+    assert_eq!(exercises[0]["reps"], 8);
+    assert_eq!(exercises[0]["weight"], 62.5);
+    assert_eq!(exercises[0]["rest_seconds"], 90);
+}
+
+#[actix_web::test]
+async fn test_workout_catalog_exercises() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let workout_exercise_repo = web::Data::new(MockWorkoutExerciseRepo::new());
+
+    let workout_uuid;
+    let catalog_uuid;
+    {
+        let state = workout_exercise_repo.state.lock().unwrap();
+        let (workouts, exercises, _) = &*state;
+        workout_uuid = workouts.iter().find(|w| w.user_id == 2).unwrap().uuid;
+        catalog_uuid = exercises.iter().find(|e| e.is_catalog()).unwrap().uuid;
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(workout_exercise_repo.clone())
+                    .service(crate::routes::workout_exercise::get_scope_workout_id_exercises_exercise_id::<MockWorkoutExerciseRepo>())
+                    .service(crate::routes::workout_exercise::get_scope_workout_id_exercises::<MockWorkoutExerciseRepo>())
+            )
+    ).await;
+
+    // Any user can add a catalog exercise to their workout
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/exercises", workout_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .set_json(json!({ "exercise_uuid": catalog_uuid, "order": 1, "sets": 3, "reps": 5 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/workouts/{}/exercises", workout_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let exercises: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(exercises.len(), 1);
+    assert_eq!(exercises[0]["name"], "Catalog Exercise");
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/workouts/{}/exercises/{}", workout_uuid, catalog_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
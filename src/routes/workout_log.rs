@@ -0,0 +1,129 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Resource, Responder};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    models::workout_log::{CreateWorkoutLog, WorkoutLogDetail},
+    repositories::workout_log_repository::{WorkoutLogError, WorkoutLogRepository},
+    routes::personal_record::PersonalRecordResponse,
+    routes::{run_blocking, service_unavailable},
+};
+
+#[derive(Serialize)]
+struct WorkoutLogSetResponse {
+    exercise_uuid: Uuid,
+    exercise_name: String,
+    set_number: i32,
+    reps: i32,
+    weight: Option<f64>,
+    rpe: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct WorkoutLogResponse {
+    uuid: Uuid,
+    performed_at: NaiveDateTime,
+    notes: Option<String>,
+    sets: Vec<WorkoutLogSetResponse>,
+    personal_records: Vec<PersonalRecordResponse>,
+}
+
+impl WorkoutLogResponse {
+    fn from(detail: &WorkoutLogDetail) -> Self {
+        Self {
+            uuid: detail.log.uuid,
+            performed_at: detail.log.performed_at,
+            notes: detail.log.notes.clone(),
+            sets: detail.sets.iter().map(|(exercise, set)| WorkoutLogSetResponse {
+                exercise_uuid: exercise.uuid,
+                exercise_name: exercise.name.clone(),
+                set_number: set.set_number,
+                reps: set.reps,
+                weight: set.weight,
+                rpe: set.rpe,
+            }).collect(),
+            personal_records: detail.personal_records.iter()
+                .map(|(exercise, record)| PersonalRecordResponse::from(exercise, record))
+                .collect(),
+        }
+    }
+}
+
+pub fn get_scope_workout_id_logs_log_id<T: WorkoutLogRepository + 'static>() -> Resource {
+    web::resource("/workouts/{workout_uuid}/logs/{log_uuid}")
+        .route(web::get().to(get_workout_log::<T>))
+        .route(web::delete().to(delete_workout_log::<T>))
+}
+
+pub fn get_scope_workout_id_logs<T: WorkoutLogRepository + 'static>() -> Resource {
+    web::resource("/workouts/{workout_uuid}/logs")
+        .route(web::get().to(list_workout_logs::<T>))
+        .route(web::post().to(create_workout_log::<T>))
+}
+
+fn error_response(err: WorkoutLogError) -> HttpResponse {
+    match err {
+        WorkoutLogError::NotFound => HttpResponse::NotFound().finish(),
+        WorkoutLogError::WorkoutNotFound => HttpResponse::NotFound().finish(),
+        WorkoutLogError::ExerciseNotFound => HttpResponse::NotFound().finish(),
+        WorkoutLogError::InvalidSet => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Each set needs a positive unique set number, non-negative reps and weight, and an RPE between 1 and 10"
+        })),
+        WorkoutLogError::ConnectionUnavailable => service_unavailable(),
+        WorkoutLogError::DatabaseError(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn create_workout_log<T: WorkoutLogRepository>(
+    workout_uuid: web::Path<Uuid>,
+    log: web::Json<CreateWorkoutLog>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.create_workout_log(user_id, *workout_uuid, log.0)).await {
+        Ok(detail) => HttpResponse::Created().json(WorkoutLogResponse::from(&detail)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn list_workout_logs<T: WorkoutLogRepository>(
+    workout_uuid: web::Path<Uuid>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.list_workout_logs(user_id, *workout_uuid)).await {
+        Ok(logs) => HttpResponse::Ok().json(
+            logs.iter().map(WorkoutLogResponse::from).collect::<Vec<_>>()
+        ),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn get_workout_log<T: WorkoutLogRepository>(
+    path: web::Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let (workout_uuid, log_uuid) = path.into_inner();
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.get_workout_log(user_id, workout_uuid, log_uuid)).await {
+        Ok(detail) => HttpResponse::Ok().json(WorkoutLogResponse::from(&detail)),
+        Err(e) => error_response(e),
+    }
+}
+
+async fn delete_workout_log<T: WorkoutLogRepository>(
+    path: web::Path<(Uuid, Uuid)>,
+    req: HttpRequest,
+    repo: web::Data<T>,
+) -> impl Responder {
+    let (workout_uuid, log_uuid) = path.into_inner();
+    let user_id = *req.extensions().get::<i64>().unwrap();
+    match run_blocking(&repo, move |repo| repo.delete_workout_log(user_id, workout_uuid, log_uuid)).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
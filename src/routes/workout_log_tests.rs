@@ -0,0 +1,386 @@
+use actix_web::{cookie::Cookie, test, web, App};
+use serde_json::json;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    middleware::session::SessionProtection,
+    models::{
+        exercise::Exercise, personal_record::PersonalRecord, workout::Workout,
+        workout_log::{CreateWorkoutLog, WorkoutLog, WorkoutLogDetail, WorkoutLogSet},
+    },
+    repositories::workout_log_repository::{WorkoutLogError, WorkoutLogRepository},
+    routes::test_support::{is_visible_to, MockAuthRepo},
+};
+
+struct MockWorkoutLogRepo {
+    state: Mutex<(Vec<Workout>, Vec<Exercise>, Vec<WorkoutLogDetail>)>,
+}
+
+impl MockWorkoutLogRepo {
+    pub fn new() -> Self {
+        let workouts = vec![
+            Workout {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                user_id: 1,
+                name: "Test Workout for user 1".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+            },
+            Workout {
+                id: 2,
+                uuid: Uuid::new_v4(),
+                user_id: 2,
+                name: "Test Workout for user 2".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+            },
+        ];
+        let exercises = vec![
+            Exercise {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                user_id: Some(1),
+                name: "Test Exercise 1 for user 1".to_string(),
+                description: None,
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+                category: "strength".to_string(),
+            },
+        ];
+        Self {
+            state: Mutex::new((workouts, exercises, vec![])),
+        }
+    }
+
+    fn workout_uuid(&self, user_id: i64) -> Uuid {
+        self.state.lock().unwrap().0.iter().find(|w| w.user_id == user_id).unwrap().uuid
+    }
+
+    fn exercise_uuid(&self, user_id: i64) -> Uuid {
+        self.state.lock().unwrap().1.iter().find(|e| e.user_id == Some(user_id)).unwrap().uuid
+    }
+}
+
+impl WorkoutLogRepository for MockWorkoutLogRepo {
+    fn create_workout_log(&self, user_id: i64, workout_uuid: Uuid, log: CreateWorkoutLog) -> Result<WorkoutLogDetail, WorkoutLogError> {
+        if !log.is_valid() {
+            return Err(WorkoutLogError::InvalidSet);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let (workouts, exercises, logs) = &mut *state;
+
+        let workout_id = workouts
+            .iter()
+            .find(|w| w.uuid == workout_uuid && w.user_id == user_id)
+            .ok_or(WorkoutLogError::WorkoutNotFound)?
+            .id;
+
+        let new_log = WorkoutLog::new(user_id, workout_id, log.performed_at, log.notes);
+        let workout_log = WorkoutLog {
+            id: (logs.len() + 1) as i64,
+            uuid: new_log.uuid,
+            user_id,
+            workout_id,
+            performed_at: new_log.performed_at,
+            notes: new_log.notes,
+            created_at: new_log.created_at,
+            updated_at: new_log.updated_at,
+        };
+
+        let mut sets = vec![];
+        for (index, set) in log.sets.iter().enumerate() {
+            let exercise = exercises
+                .iter()
+                .find(|e| e.uuid == set.exercise_uuid && is_visible_to(e, user_id))
+                .ok_or(WorkoutLogError::ExerciseNotFound)?;
+            let new_set = WorkoutLogSet::new(workout_log.id, exercise.id, user_id, set);
+            sets.push((exercise.clone(), WorkoutLogSet {
+                id: index as i64 + 1,
+                workout_log_id: new_set.workout_log_id,
+                exercise_id: new_set.exercise_id,
+                user_id,
+                set_number: new_set.set_number,
+                reps: new_set.reps,
+                weight: new_set.weight,
+                rpe: new_set.rpe,
+            }));
+        }
+
+        let mut personal_records = vec![];
+        for exercise in exercises.iter().filter(|e| sets.iter().any(|(set_exercise, _)| set_exercise.id == e.id)) {
+            let previous = logs.iter()
+                .flat_map(|detail| detail.personal_records.iter())
+                .filter(|(record_exercise, _)| record_exercise.id == exercise.id)
+                .map(|(_, record)| record.clone())
+                .collect::<Vec<_>>();
+            let exercise_sets = sets.iter()
+                .filter(|(set_exercise, _)| set_exercise.id == exercise.id)
+                .map(|(_, set)| set.clone())
+                .collect::<Vec<_>>();
+            for record in PersonalRecord::detect(&workout_log, exercise.id, &exercise_sets, &previous) {
+                personal_records.push((exercise.clone(), PersonalRecord {
+                    id: (previous.len() + personal_records.len() + 1) as i64,
+                    uuid: record.uuid,
+                    user_id,
+                    exercise_id: record.exercise_id,
+                    workout_log_id: record.workout_log_id,
+                    record_type: record.record_type,
+                    value: record.value,
+                    reps: record.reps,
+                    weight: record.weight,
+                    achieved_at: record.achieved_at,
+                    created_at: record.created_at,
+                }));
+            }
+        }
+
+        let detail = WorkoutLogDetail { log: workout_log, sets, personal_records };
+        logs.push(detail.clone());
+        Ok(detail)
+    }
+
+    fn get_workout_log(&self, user_id: i64, workout_uuid: Uuid, log_uuid: Uuid) -> Result<WorkoutLogDetail, WorkoutLogError> {
+        self.list_workout_logs(user_id, workout_uuid)?
+            .into_iter()
+            .find(|detail| detail.log.uuid == log_uuid)
+            .ok_or(WorkoutLogError::NotFound)
+    }
+
+    fn list_workout_logs(&self, user_id: i64, workout_uuid: Uuid) -> Result<Vec<WorkoutLogDetail>, WorkoutLogError> {
+        let state = self.state.lock().unwrap();
+        let (workouts, _, logs) = &*state;
+
+        let workout_id = workouts
+            .iter()
+            .find(|w| w.uuid == workout_uuid && w.user_id == user_id)
+            .ok_or(WorkoutLogError::WorkoutNotFound)?
+            .id;
+
+        Ok(logs
+            .iter()
+            .filter(|detail| detail.log.workout_id == workout_id && detail.log.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    fn delete_workout_log(&self, user_id: i64, workout_uuid: Uuid, log_uuid: Uuid) -> Result<(), WorkoutLogError> {
+        let mut state = self.state.lock().unwrap();
+        let (workouts, _, logs) = &mut *state;
+
+        let workout_id = workouts
+            .iter()
+            .find(|w| w.uuid == workout_uuid && w.user_id == user_id)
+            .ok_or(WorkoutLogError::WorkoutNotFound)?
+            .id;
+
+        let initial_len = logs.len();
+        logs.retain(|detail|
+            !(detail.log.uuid == log_uuid &&
+              detail.log.workout_id == workout_id &&
+              detail.log.user_id == user_id)
+        );
+
+        if logs.len() < initial_len {
+            Ok(())
+        } else {
+            Err(WorkoutLogError::NotFound)
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_workout_logs() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let workout_log_repo = web::Data::new(MockWorkoutLogRepo::new());
+    let workout_uuid = workout_log_repo.workout_uuid(1);
+    let exercise_uuid = workout_log_repo.exercise_uuid(1);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(workout_log_repo.clone())
+                    .service(crate::routes::workout_log::get_scope_workout_id_logs_log_id::<MockWorkoutLogRepo>())
+                    .service(crate::routes::workout_log::get_scope_workout_id_logs::<MockWorkoutLogRepo>())
+            )
+    ).await;
+
+    // Log a performed session
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/logs", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "performed_at": "2025-01-04T18:30:00",
+            "notes": "Felt strong",
+            "sets": [
+                { "exercise_uuid": exercise_uuid, "set_number": 1, "reps": 8, "weight": 60.0, "rpe": 7.5 },
+                { "exercise_uuid": exercise_uuid, "set_number": 2, "reps": 6, "weight": 62.5, "rpe": 9 }
+            ]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let log: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(log["performed_at"], "2025-01-04T18:30:00");
+    assert_eq!(log["sets"].as_array().unwrap().len(), 2);
+    assert_eq!(log["sets"][1]["weight"], 62.5);
+    assert_eq!(log["sets"][1]["rpe"], 9.0);
+    let log_uuid = log["uuid"].as_str().unwrap().to_string();
+
+    // List logs
+    let req = test::TestRequest::get()
+        .uri(&format!("/workouts/{}/logs", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let logs: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    assert_eq!(logs.len(), 1);
+
+    // Get a single log
+    let req = test::TestRequest::get()
+        .uri(&format!("/workouts/{}/logs/{}", workout_uuid, log_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let log: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(log["notes"], "Felt strong");
+    assert_eq!(log["sets"][0]["exercise_uuid"], json!(exercise_uuid));
+
+    // Delete the log
+    let req = test::TestRequest::delete()
+        .uri(&format!("/workouts/{}/logs/{}", workout_uuid, log_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/workouts/{}/logs/{}", workout_uuid, log_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_workout_log_validation_and_isolation() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let workout_log_repo = web::Data::new(MockWorkoutLogRepo::new());
+    let workout_uuid = workout_log_repo.workout_uuid(1);
+    let exercise_uuid = workout_log_repo.exercise_uuid(1);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(workout_log_repo.clone())
+                    .service(crate::routes::workout_log::get_scope_workout_id_logs_log_id::<MockWorkoutLogRepo>())
+                    .service(crate::routes::workout_log::get_scope_workout_id_logs::<MockWorkoutLogRepo>())
+            )
+    ).await;
+
+    // RPE outside 1-10 is rejected
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/logs", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "sets": [{ "exercise_uuid": exercise_uuid, "set_number": 1, "reps": 5, "rpe": 11 }]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // Duplicate set numbers for the same exercise are rejected
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/logs", workout_uuid))
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .set_json(json!({
+            "sets": [
+                { "exercise_uuid": exercise_uuid, "set_number": 1, "reps": 5 },
+                { "exercise_uuid": exercise_uuid, "set_number": 1, "reps": 5 }
+            ]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    // User 2 cannot log against user 1's workout
+    let req = test::TestRequest::post()
+        .uri(&format!("/workouts/{}/logs", workout_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .set_json(json!({ "sets": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+
+    // User 2 cannot list user 1's logs
+    let req = test::TestRequest::get()
+        .uri(&format!("/workouts/{}/logs", workout_uuid))
+        .cookie(Cookie::new("session_id", "user2-session"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_workout_log_personal_records() {
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let workout_log_repo = web::Data::new(MockWorkoutLogRepo::new());
+    let workout_uuid = workout_log_repo.workout_uuid(1);
+    let exercise_uuid = workout_log_repo.exercise_uuid(1);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .app_data(workout_log_repo.clone())
+                    .service(crate::routes::workout_log::get_scope_workout_id_logs::<MockWorkoutLogRepo>())
+            )
+    ).await;
+
+    let mut record_types = vec![];
+    for (performed_at, reps, weight) in [
+        ("2025-01-06T18:00:00", 5, 100.0),
+        ("2025-01-08T18:00:00", 3, 100.0),
+        ("2025-01-10T18:00:00", 6, 100.0),
+        ("2025-01-13T18:00:00", 1, 110.0),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/workouts/{}/logs", workout_uuid))
+            .cookie(Cookie::new("session_id", "user1-session"))
+            .set_json(json!({
+                "performed_at": performed_at,
+                "sets": [{ "exercise_uuid": exercise_uuid, "set_number": 1, "reps": reps, "weight": weight }]
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 201);
+        let log: serde_json::Value = test::read_body_json(resp).await;
+        record_types.push(log["personal_records"].as_array().unwrap().iter()
+            .map(|record| record["record_type"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>());
+    }
+
+    // The first session sets every record
+    assert_eq!(record_types[0], ["max_weight", "max_reps", "estimated_one_rep_max", "max_volume"]);
+    // A weaker session sets none
+    assert!(record_types[1].is_empty());
+    // More reps at the same weight
+    assert_eq!(record_types[2], ["max_reps", "estimated_one_rep_max", "max_volume"]);
+    // A heavier single is a weight record and the first rep record at that weight, but its
+    // estimated max (110) and volume do not beat 6 x 100
+    assert_eq!(record_types[3], ["max_weight", "max_reps"]);
+}
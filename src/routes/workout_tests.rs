@@ -1,66 +1,17 @@
-use actix_web::{cookie::Cookie, test, web, App};
+use actix_web::{cookie::Cookie, test, web, App, HttpResponse};
 use serde_json::json;
 use uuid::Uuid;
-use std::sync::Mutex;
+use std::{sync::{mpsc, Mutex}, time::Duration};
 
 use crate::{
     middleware::session::SessionProtection,
-    models::{session::Session, user::User, workout::Workout},
-    repositories::{
-        auth_repository::{AuthError, AuthRepository},
-        workout_repository::{WorkoutError, WorkoutRepository},
-    },
+    models::workout::Workout,
+    repositories::workout_repository::{WorkoutError, WorkoutRepository},
+    routes::test_support::MockAuthRepo,
 };
 
-pub struct MockAuthRepo {
-  sessions: Mutex<Vec<Session>>,
-}
-
-impl MockAuthRepo {
-  pub fn new() -> Self {
-      let mut sessions = vec![];
-      sessions.push(Session {
-          id: 1,
-          user_id: 1,
-          token: "user1-session".to_string(),
-          csrf_token: "user1-csrf".to_string(),
-          expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
-          created_at: chrono::Utc::now().naive_utc(),
-      });
-      sessions.push(Session {
-          id: 2,
-          user_id: 2,
-          token: "user2-session".to_string(),
-          csrf_token: "user2-csrf".to_string(),
-          expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
-          created_at: chrono::Utc::now().naive_utc(),
-      });
-      Self {
-          sessions: Mutex::new(sessions),
-      }
-  }
-}
-
-impl AuthRepository for MockAuthRepo {
-  fn validate_session(&self, session_token: &str) -> Result<i64, AuthError> {
-      let sessions = self.sessions.lock().unwrap();
-      let session = sessions.iter()
-          .find(|s| s.token == session_token)
-          .ok_or(AuthError::InvalidSession)?;
-      Ok(session.user_id)
-  }
-
-  // Implement other required methods with empty/mock implementations
-  fn create_temp_session(&self, _csrf_token: String) -> Result<crate::models::temp_session::TempSession, AuthError> { unimplemented!() }
-  fn create_session(&self, _user_id: i64, _session_id: String, _csrf_token: String) -> Result<Session, AuthError> { unimplemented!() }
-  fn validate_csrf(&self, _session_id: &str, _csrf_token: &str) -> Result<(), AuthError> { unimplemented!() }
-  fn verify_credentials(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
-  fn invalidate_session(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
-  fn create_user(&self, _email: String, _password: String) -> Result<User, AuthError> { unimplemented!() }
-  fn delete_user(&self, _session_token: &str) -> Result<(), AuthError> { unimplemented!() }
-}
 
-pub struct MockWorkoutRepo {
+struct MockWorkoutRepo {
     workouts: Mutex<Vec<Workout>>,
 }
 
@@ -171,7 +122,7 @@ async fn test_workout_crud() {
     let workout: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(workout["name"], "Test Workout");
     let workout_uuid = workout["uuid"].as_str().unwrap();
-    assert!(workout_uuid.len() > 0);
+    assert!(!workout_uuid.is_empty());
 
     // Test List
     let req = test::TestRequest::get()
@@ -317,4 +268,63 @@ async fn test_workout_isolation() {
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 404);
-} 
\ No newline at end of file
+}
+
+/// Answers `list_workouts` only once the test releases it, like a slow query.
+struct SlowWorkoutRepo {
+    release: Mutex<mpsc::Receiver<()>>,
+}
+
+impl WorkoutRepository for SlowWorkoutRepo {
+    fn list_workouts(&self, _user_id: i64) -> Result<Vec<Workout>, WorkoutError> {
+        self.release.lock().unwrap()
+            .recv_timeout(Duration::from_secs(5))
+            .map(|_| vec![])
+            .map_err(|_| WorkoutError::NotFound)
+    }
+
+    fn create_workout(&self, _user_id: i64, _workout: crate::models::workout::CreateWorkout) -> Result<Workout, WorkoutError> { unimplemented!() }
+    fn get_workout(&self, _user_id: i64, _workout_uuid: Uuid) -> Result<Workout, WorkoutError> { unimplemented!() }
+    fn update_workout(&self, _user_id: i64, _workout_uuid: Uuid, _update: crate::models::workout::UpdateWorkout) -> Result<Workout, WorkoutError> { unimplemented!() }
+    fn delete_workout(&self, _user_id: i64, _workout_uuid: Uuid) -> Result<(), WorkoutError> { unimplemented!() }
+}
+
+#[actix_web::test]
+async fn test_slow_query_does_not_block_worker() {
+    let (sender, receiver) = mpsc::channel();
+    let auth_repo = web::Data::new(MockAuthRepo::new());
+    let workout_repo = web::Data::new(SlowWorkoutRepo { release: Mutex::new(receiver) });
+    let sender = web::Data::new(Mutex::new(sender));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_repo.clone())
+            .app_data(workout_repo.clone())
+            .app_data(sender.clone())
+            .route("/release", web::post().to(|sender: web::Data<Mutex<mpsc::Sender<()>>>| async move {
+                sender.lock().unwrap().send(()).unwrap();
+                HttpResponse::Ok().finish()
+            }))
+            .service(
+                web::scope("")
+                    .wrap(SessionProtection::<MockAuthRepo>::new())
+                    .service(crate::routes::workout::get_scope::<SlowWorkoutRepo>())
+            )
+    ).await;
+
+    // Both requests run on the single test worker; the release can only be handled
+    // while the slow listing is waiting if the listing doesn't block the worker.
+    let slow = test::TestRequest::get()
+        .uri("/workouts")
+        .cookie(Cookie::new("session_id", "user1-session"))
+        .to_request();
+    let release = test::TestRequest::post()
+        .uri("/release")
+        .to_request();
+    let (slow, release) = futures::join!(
+        test::call_service(&app, slow),
+        test::call_service(&app, release),
+    );
+    assert_eq!(release.status(), 200);
+    assert_eq!(slow.status(), 200);
+}
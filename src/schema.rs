@@ -2,14 +2,159 @@
 
 pub mod public {
     diesel::table! {
-        exercises (id) {
+        api_tokens (id) {
             id -> Int8,
             uuid -> Uuid,
             user_id -> Int8,
             name -> Varchar,
+            token_hash -> Varchar,
+            scopes -> Varchar,
+            expires_at -> Nullable<Timestamp>,
+            last_used_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        calendar_feed_tokens (id) {
+            id -> Int8,
+            user_id -> Int8,
+            token_hash -> Varchar,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        email_tokens (id) {
+            id -> Int8,
+            user_id -> Int8,
+            #[max_length = 32]
+            purpose -> Varchar,
+            token_hash -> Varchar,
+            expires_at -> Timestamp,
+            used_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        exercise_muscle_groups (exercise_id, muscle_group_id) {
+            exercise_id -> Int8,
+            muscle_group_id -> Int8,
+            #[max_length = 16]
+            role -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        exercises (id) {
+            id -> Int8,
+            uuid -> Uuid,
+            user_id -> Nullable<Int8>,
+            name -> Varchar,
             description -> Nullable<Varchar>,
             created_at -> Timestamp,
             updated_at -> Timestamp,
+            #[max_length = 32]
+            category -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        login_throttles (scope, key) {
+            #[max_length = 16]
+            scope -> Varchar,
+            key -> Varchar,
+            failures -> Int4,
+            last_failure_at -> Timestamp,
+            locked_until -> Nullable<Timestamp>,
+        }
+    }
+
+    diesel::table! {
+        muscle_groups (id) {
+            id -> Int8,
+            #[max_length = 32]
+            slug -> Varchar,
+            name -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        oidc_login_attempts (id) {
+            id -> Int8,
+            state_hash -> Varchar,
+            nonce -> Varchar,
+            code_verifier -> Varchar,
+            expires_at -> Timestamp,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        personal_records (id) {
+            id -> Int8,
+            uuid -> Uuid,
+            user_id -> Int8,
+            exercise_id -> Int8,
+            workout_log_id -> Int8,
+            #[max_length = 32]
+            record_type -> Varchar,
+            value -> Float8,
+            reps -> Nullable<Int4>,
+            weight -> Nullable<Float8>,
+            achieved_at -> Timestamp,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        recovery_codes (id) {
+            id -> Int8,
+            user_id -> Int8,
+            code_hash -> Varchar,
+            used_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        recurring_schedule_exceptions (id) {
+            id -> Int8,
+            uuid -> Uuid,
+            recurring_schedule_id -> Int8,
+            occurrence_at -> Timestamp,
+            action -> Varchar,
+            rescheduled_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        recurring_schedules (id) {
+            id -> Int8,
+            uuid -> Uuid,
+            user_id -> Int8,
+            workout_id -> Int8,
+            starts_at -> Timestamp,
+            rrule -> Varchar,
+            notes -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        scheduled_workouts (id) {
+            id -> Int8,
+            uuid -> Uuid,
+            user_id -> Int8,
+            workout_id -> Int8,
+            scheduled_at -> Timestamp,
+            status -> Varchar,
+            notes -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
         }
     }
 
@@ -21,6 +166,14 @@ pub mod public {
             csrf_token -> Varchar,
             expires_at -> Timestamp,
             created_at -> Timestamp,
+            uuid -> Uuid,
+            last_seen_at -> Timestamp,
+            user_agent -> Nullable<Text>,
+            #[max_length = 45]
+            ip_address -> Nullable<Varchar>,
+            remember_me -> Bool,
+            absolute_expires_at -> Timestamp,
+            two_factor_pending -> Bool,
         }
     }
 
@@ -34,6 +187,28 @@ pub mod public {
         }
     }
 
+    diesel::table! {
+        totp_credentials (user_id) {
+            user_id -> Int8,
+            secret -> Varchar,
+            confirmed_at -> Nullable<Timestamp>,
+            last_used_step -> Nullable<Int8>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        user_identities (id) {
+            id -> Int8,
+            user_id -> Int8,
+            issuer -> Varchar,
+            subject -> Varchar,
+            email -> Nullable<Varchar>,
+            created_at -> Timestamp,
+            last_login_at -> Timestamp,
+        }
+    }
+
     diesel::table! {
         users (id) {
             id -> Int8,
@@ -42,6 +217,7 @@ pub mod public {
             password_hash -> Varchar,
             created_at -> Timestamp,
             updated_at -> Timestamp,
+            email_verified_at -> Nullable<Timestamp>,
         }
     }
 
@@ -51,6 +227,36 @@ pub mod public {
             exercise_id -> Int8,
             user_id -> Int8,
             order -> Int4,
+            sets -> Nullable<Int4>,
+            reps -> Nullable<Int4>,
+            weight -> Nullable<Float8>,
+            rest_seconds -> Nullable<Int4>,
+        }
+    }
+
+    diesel::table! {
+        workout_log_sets (id) {
+            id -> Int8,
+            workout_log_id -> Int8,
+            exercise_id -> Int8,
+            user_id -> Int8,
+            set_number -> Int4,
+            reps -> Int4,
+            weight -> Nullable<Float8>,
+            rpe -> Nullable<Float8>,
+        }
+    }
+
+    diesel::table! {
+        workout_logs (id) {
+            id -> Int8,
+            uuid -> Uuid,
+            user_id -> Int8,
+            workout_id -> Int8,
+            performed_at -> Timestamp,
+            notes -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
         }
     }
 
@@ -66,19 +272,56 @@ pub mod public {
         }
     }
 
+    diesel::joinable!(api_tokens -> users (user_id));
+    diesel::joinable!(calendar_feed_tokens -> users (user_id));
+    diesel::joinable!(email_tokens -> users (user_id));
+    diesel::joinable!(exercise_muscle_groups -> exercises (exercise_id));
+    diesel::joinable!(exercise_muscle_groups -> muscle_groups (muscle_group_id));
     diesel::joinable!(exercises -> users (user_id));
+    diesel::joinable!(personal_records -> exercises (exercise_id));
+    diesel::joinable!(personal_records -> users (user_id));
+    diesel::joinable!(personal_records -> workout_logs (workout_log_id));
+    diesel::joinable!(recovery_codes -> users (user_id));
+    diesel::joinable!(recurring_schedule_exceptions -> recurring_schedules (recurring_schedule_id));
+    diesel::joinable!(recurring_schedules -> users (user_id));
+    diesel::joinable!(recurring_schedules -> workouts (workout_id));
+    diesel::joinable!(scheduled_workouts -> users (user_id));
+    diesel::joinable!(scheduled_workouts -> workouts (workout_id));
     diesel::joinable!(sessions -> users (user_id));
+    diesel::joinable!(totp_credentials -> users (user_id));
+    diesel::joinable!(user_identities -> users (user_id));
     diesel::joinable!(workout_exercises -> exercises (exercise_id));
     diesel::joinable!(workout_exercises -> users (user_id));
     diesel::joinable!(workout_exercises -> workouts (workout_id));
+    diesel::joinable!(workout_log_sets -> exercises (exercise_id));
+    diesel::joinable!(workout_log_sets -> users (user_id));
+    diesel::joinable!(workout_log_sets -> workout_logs (workout_log_id));
+    diesel::joinable!(workout_logs -> users (user_id));
+    diesel::joinable!(workout_logs -> workouts (workout_id));
     diesel::joinable!(workouts -> users (user_id));
 
     diesel::allow_tables_to_appear_in_same_query!(
+        api_tokens,
+        calendar_feed_tokens,
+        email_tokens,
+        exercise_muscle_groups,
         exercises,
+        login_throttles,
+        muscle_groups,
+        oidc_login_attempts,
+        personal_records,
+        recovery_codes,
+        recurring_schedule_exceptions,
+        recurring_schedules,
+        scheduled_workouts,
         sessions,
         temp_sessions,
+        totp_credentials,
+        user_identities,
         users,
         workout_exercises,
+        workout_log_sets,
+        workout_logs,
         workouts,
     );
 }
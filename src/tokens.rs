@@ -0,0 +1,21 @@
+use sha2::{Digest, Sha256};
+
+/// Generates a random, URL-safe secret token (256 bits, hex encoded).
+pub fn generate_token() -> String {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hashes a secret token for storage. Tokens are high-entropy, so a fast unsalted
+/// digest is sufficient and lets us look the hash up directly.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Compares two secrets without returning early on the first differing byte.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,66 @@
+//! Time-based one-time passwords (RFC 6238) as used by authenticator apps: HMAC-SHA1,
+//! six digits, 30 second steps.
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+pub const ISSUER: &str = "Fitness Tracker";
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes from one step before or after the current one are accepted to allow for clock drift.
+const ALLOWED_DRIFT: i64 = 1;
+
+/// Generates a random 160-bit secret, base32 encoded as authenticator apps expect it.
+pub fn generate_secret() -> String {
+    base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &rand::random::<[u8; 20]>())
+}
+
+/// The time step a Unix timestamp falls into.
+pub fn step_at(unix_seconds: i64) -> i64 {
+    unix_seconds.div_euclid(STEP_SECONDS)
+}
+
+/// The code for `step`, or `None` if `secret` isn't valid base32.
+pub fn code_at(secret: &str, step: i64) -> Option<String> {
+    let key = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, secret)?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).ok()?;
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation, RFC 4226 section 5.3
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]) & 0x7fff_ffff;
+    Some(format!("{:0width$}", binary % 10_u32.pow(DIGITS), width = DIGITS as usize))
+}
+
+/// Checks `code` around `current_step` and returns the step it belongs to. Steps up to
+/// `last_used_step` are refused so an observed code can't be replayed.
+pub fn verify(secret: &str, code: &str, current_step: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+    (current_step - ALLOWED_DRIFT..=current_step + ALLOWED_DRIFT)
+        .filter(|step| last_used_step.is_none_or(|last_used_step| *step > last_used_step))
+        .find(|step| code_at(secret, *step).is_some_and(|expected| crate::tokens::constant_time_eq(&expected, code)))
+}
+
+/// The `otpauth://` URI authenticator apps import, usually through a QR code.
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(ISSUER),
+        percent_encode(account),
+        secret,
+        percent_encode(ISSUER),
+        DIGITS,
+        STEP_SECONDS,
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,65 @@
+//! Checks personal access tokens in `PgAuthRepository`.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test api_tokens
+//! ```
+
+mod common;
+
+use common::{PASSWORD, create_user, delete_users, pool};
+
+use diesel::prelude::*;
+use fitness_workout_tracker_api_rust::{
+    models::session::{SessionClient, SessionConfig},
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::api_tokens,
+};
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_api_token_lifecycle() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let user = create_user(&repo);
+    let session = repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+    let scopes = vec!["workouts:read".to_string(), "reports:read".to_string()];
+
+    let (created, token) = repo.create_api_token(&session.token, "CLI".to_string(), scopes, None).unwrap();
+    assert_ne!(created.token_hash, token);
+    assert_eq!(created.scopes(), ["workouts:read", "reports:read"]);
+    assert!(created.last_used_at.is_none());
+
+    let authenticated = repo.authenticate_api_token(&token).unwrap();
+    assert_eq!(authenticated.user_id, user.id);
+    assert!(authenticated.allows("reports:read") && !authenticated.allows("workouts:write"));
+    let listed = repo.list_api_tokens(&session.token).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].last_used_at.is_some());
+
+    diesel::update(api_tokens::table.find(created.id))
+        .set(api_tokens::expires_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+        .unwrap();
+    assert!(matches!(repo.authenticate_api_token(&token), Err(AuthError::InvalidToken)));
+
+    repo.revoke_api_token(&session.token, created.uuid).unwrap();
+    assert!(matches!(repo.revoke_api_token(&session.token, created.uuid), Err(AuthError::NotFound)));
+    assert!(repo.list_api_tokens(&session.token).unwrap().is_empty());
+
+    // Changing the password revokes tokens along with the other sessions
+    let (_, token) = repo.create_api_token(&session.token, "CLI".to_string(), vec!["workouts:read".to_string()], None).unwrap();
+    repo.change_password(&session.token, PASSWORD.to_string(), "newpassword123".to_string()).unwrap();
+    assert!(matches!(repo.authenticate_api_token(&token), Err(AuthError::InvalidToken)));
+    assert!(repo.list_api_tokens(&session.token).unwrap().is_empty());
+
+    // Resetting the password ends every way into the account
+    let (_, token) = repo.create_api_token(&session.token, "CLI".to_string(), vec!["workouts:read".to_string()], None).unwrap();
+    let (_, reset_token) = repo.create_password_reset(user.email.clone()).unwrap().unwrap();
+    repo.reset_password(&reset_token, "newpassword456".to_string()).unwrap();
+    assert!(matches!(repo.authenticate_api_token(&token), Err(AuthError::InvalidToken)));
+
+    delete_users(&mut conn, &[user.id]);
+}
@@ -0,0 +1,37 @@
+//! Setup shared by the database tests. They need a migrated PostgreSQL database in
+//! `DATABASE_URL`; `scripts/run_db_tests.sh` migrates it and runs them all.
+
+// Each test file uses its own subset of these helpers
+#![allow(dead_code)]
+
+use diesel::{pg::PgConnection, prelude::*};
+use fitness_workout_tracker_api_rust::{
+    db::config::{create_pool, DbPool, PoolConfig},
+    models::user::User,
+    repositories::auth_repository::{AuthRepository, PgAuthRepository},
+    schema::public::users,
+};
+use uuid::Uuid;
+
+pub const PASSWORD: &str = "password123";
+
+pub fn pool() -> DbPool {
+    create_pool(&PoolConfig::from_env()).expect("Failed to create database connection pool")
+}
+
+/// An email address no other test uses.
+pub fn unique_email() -> String {
+    format!("{}@example.com", Uuid::new_v4())
+}
+
+/// Registers a user with a unique email and `PASSWORD`.
+pub fn create_user(repo: &PgAuthRepository) -> User {
+    repo.create_user(unique_email(), PASSWORD.to_string()).unwrap()
+}
+
+/// Deletes the users, and with them everything that belongs to them.
+pub fn delete_users(conn: &mut PgConnection, ids: &[i64]) {
+    diesel::delete(users::table.filter(users::id.eq_any(ids)))
+        .execute(conn)
+        .unwrap();
+}
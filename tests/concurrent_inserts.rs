@@ -0,0 +1,106 @@
+//! Checks that inserts into the same table don't wait on each other.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test concurrent_inserts
+//! ```
+
+mod common;
+
+use common::{delete_users, pool, unique_email};
+
+use diesel::{pg::PgConnection, prelude::*, result::Error};
+use fitness_workout_tracker_api_rust::{
+    db::config::DbPool,
+    models::{session::{Session, SessionConfig}, temp_session::TempSession, user::User, workout::Workout},
+    schema::public::{sessions, temp_sessions, users, workouts},
+};
+use uuid::Uuid;
+
+fn insert_user(conn: &mut PgConnection) -> QueryResult<i64> {
+    diesel::insert_into(users::table)
+        .values(&User::new(unique_email(), "hash".to_string()))
+        .returning(users::id)
+        .get_result(conn)
+}
+
+/// Inserts a row inside an open transaction, then inserts another one from a second
+/// connection that refuses to wait for locks. Both transactions are rolled back.
+fn assert_inserts_do_not_serialize<F>(pool: &DbPool, table: &str, insert: F)
+where
+    F: Fn(&mut PgConnection) -> QueryResult<i64>,
+{
+    let mut first = pool.get().unwrap();
+    let mut second = pool.get().unwrap();
+
+    let result = first.transaction(|first| {
+        let first_id = insert(first)?;
+
+        second.transaction(|second| {
+            diesel::sql_query("SET LOCAL lock_timeout = '1s'").execute(second)?;
+            let second_id = insert(second).unwrap_or_else(|err| {
+                panic!("insert into {} waited on a concurrent transaction: {}", table, err)
+            });
+            assert_ne!(first_id, second_id);
+            Err::<(), _>(Error::RollbackTransaction)
+        })
+    });
+    assert!(matches!(result, Err(Error::RollbackTransaction)));
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_concurrent_inserts() {
+    let pool = pool();
+    let mut conn = pool.get().unwrap();
+    let user_id = insert_user(&mut conn).unwrap();
+
+    assert_inserts_do_not_serialize(&pool, "users", insert_user);
+    assert_inserts_do_not_serialize(&pool, "sessions", |conn| {
+        diesel::insert_into(sessions::table)
+            .values(&Session::new(user_id, Uuid::new_v4().to_string(), "csrf".to_string(), false, &SessionConfig::default()))
+            .returning(sessions::id)
+            .get_result(conn)
+    });
+    assert_inserts_do_not_serialize(&pool, "temp_sessions", |conn| {
+        diesel::insert_into(temp_sessions::table)
+            .values(&TempSession::new(Uuid::new_v4().to_string(), "csrf".to_string()))
+            .returning(temp_sessions::id)
+            .get_result(conn)
+    });
+    assert_inserts_do_not_serialize(&pool, "workouts", |conn| {
+        diesel::insert_into(workouts::table)
+            .values(&Workout::new(user_id, "Workout".to_string(), None))
+            .returning(workouts::id)
+            .get_result(conn)
+    });
+
+    delete_users(&mut conn, &[user_id]);
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_parallel_inserts_get_unique_ids() {
+    let pool = pool();
+
+    let handles = (0..8)
+        .map(|_| {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                let mut conn = pool.get().unwrap();
+                (0..10).map(|_| insert_user(&mut conn).unwrap()).collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut ids = handles.into_iter()
+        .flat_map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+
+    let inserted = ids.len();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), inserted);
+
+    delete_users(&mut pool.get().unwrap(), &ids);
+}
@@ -0,0 +1,56 @@
+//! Checks email verification tokens and resend limits in `PgAuthRepository`.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test email_verification
+//! ```
+
+mod common;
+
+use common::{create_user, delete_users, pool};
+
+use diesel::prelude::*;
+use fitness_workout_tracker_api_rust::{
+    models::session::SessionConfig,
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::email_tokens,
+};
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_verification_and_resend_limit() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let user = create_user(&repo);
+    assert!(!repo.email_verified(user.id).unwrap());
+
+    let (_, first) = repo.create_email_verification(user.id).unwrap();
+
+    // Concurrent resends right after the first email are all refused
+    let results = std::thread::scope(|scope| {
+        let handles = (0..4)
+            .map(|_| {
+                let repo = &repo;
+                scope.spawn(move || repo.create_email_verification(user.id))
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+    });
+    assert!(results.iter().all(|result| matches!(result, Err(AuthError::TooManyAttempts(_)))));
+
+    diesel::update(email_tokens::table.filter(email_tokens::user_id.eq(user.id)))
+        .set(email_tokens::created_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::minutes(2)))
+        .execute(&mut conn)
+        .unwrap();
+    let (_, second) = repo.create_email_verification(user.id).unwrap();
+
+    let verified = repo.verify_email(&first).unwrap();
+    assert!(verified.is_email_verified());
+    assert!(repo.email_verified(user.id).unwrap());
+    assert!(matches!(repo.verify_email(&second), Err(AuthError::InvalidToken)));
+    assert!(matches!(repo.create_email_verification(user.id), Err(AuthError::AlreadyVerified)));
+
+    delete_users(&mut conn, &[user.id]);
+}
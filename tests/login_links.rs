@@ -0,0 +1,72 @@
+//! Checks login links in `PgAuthRepository`.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test login_links
+//! ```
+
+mod common;
+
+use common::{create_user, delete_users, pool, unique_email};
+
+use diesel::prelude::*;
+use fitness_workout_tracker_api_rust::{
+    models::session::SessionConfig,
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::email_tokens,
+};
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_login_link_lifecycle() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let user = create_user(&repo);
+    assert!(!user.is_email_verified());
+
+    assert!(repo.create_login_link(unique_email()).unwrap().is_none());
+    let (linked_user, first_token) = repo.create_login_link(user.email.clone()).unwrap().unwrap();
+    assert_eq!(linked_user.id, user.id);
+    // Another link right away isn't sent
+    assert!(repo.create_login_link(user.email.clone()).unwrap().is_none());
+    // Other kinds of emails have limits of their own, and their tokens don't log in
+    let (_, reset_token) = repo.create_password_reset(user.email.clone()).unwrap().unwrap();
+    assert!(matches!(repo.redeem_login_link(&reset_token), Err(AuthError::InvalidToken)));
+
+    diesel::update(email_tokens::table.filter(email_tokens::user_id.eq(user.id)))
+        .set(email_tokens::created_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::minutes(2)))
+        .execute(&mut conn)
+        .unwrap();
+    let (_, second_token) = repo.create_login_link(user.email.clone()).unwrap().unwrap();
+
+    assert!(matches!(repo.redeem_login_link("unknown"), Err(AuthError::InvalidToken)));
+    let logged_in = repo.redeem_login_link(&second_token).unwrap();
+    assert_eq!(logged_in.id, user.id);
+    assert!(logged_in.is_email_verified());
+
+    // Each link works once, and using one uses up the others
+    assert!(matches!(repo.redeem_login_link(&second_token), Err(AuthError::InvalidToken)));
+    assert!(matches!(repo.redeem_login_link(&first_token), Err(AuthError::InvalidToken)));
+
+    delete_users(&mut conn, &[user.id]);
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_expired_login_link() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let user = create_user(&repo);
+
+    let (_, token) = repo.create_login_link(user.email.clone()).unwrap().unwrap();
+    diesel::update(email_tokens::table.filter(email_tokens::user_id.eq(user.id)))
+        .set(email_tokens::expires_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+        .unwrap();
+    assert!(matches!(repo.redeem_login_link(&token), Err(AuthError::InvalidToken)));
+
+    delete_users(&mut conn, &[user.id]);
+}
@@ -0,0 +1,108 @@
+//! Checks that login throttling is shared through the database.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test login_throttle
+//! ```
+
+mod common;
+
+use common::{PASSWORD, pool, unique_email};
+
+use diesel::{pg::PgConnection, prelude::*};
+use fitness_workout_tracker_api_rust::{
+    models::{login_throttle::{LoginThrottle, ThrottleScope}, session::SessionConfig},
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::{login_throttles, users},
+};
+use uuid::Uuid;
+
+/// Each repository gets its own pool, like separate server processes would.
+fn repo() -> PgAuthRepository {
+    PgAuthRepository::new(pool(), SessionConfig::default())
+}
+
+fn cleanup(conn: &mut PgConnection, email: &str, ip_address: &str) {
+    diesel::delete(users::table.filter(users::email.eq(email)))
+        .execute(conn)
+        .unwrap();
+    diesel::delete(login_throttles::table.filter(login_throttles::key.eq_any([email, ip_address])))
+        .execute(conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_lockout_is_shared_between_processes() {
+    let mut conn = pool().get().unwrap();
+    let (first, second) = (repo(), repo());
+    let email = unique_email();
+    let ip_address = Uuid::new_v4().to_string();
+    first.create_user(email.clone(), PASSWORD.to_string()).unwrap();
+
+    // Failures are spread over both processes
+    let free_attempts = ThrottleScope::Account.policy().free_attempts;
+    for attempt in 0..=free_attempts {
+        let repo = if attempt % 2 == 0 { &first } else { &second };
+        let result = repo.verify_credentials(email.clone(), "wrong".to_string(), Some(ip_address.clone()));
+        assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+    }
+
+    for repo in [&first, &second] {
+        let result = repo.verify_credentials(email.clone(), PASSWORD.to_string(), None);
+        assert!(matches!(result, Err(AuthError::TooManyAttempts(_))));
+    }
+
+    // Once the lockout passes, a successful login resets the account
+    diesel::update(login_throttles::table.filter(login_throttles::key.eq(&email)))
+        .set(login_throttles::locked_until.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+        .unwrap();
+    second.verify_credentials(email.clone(), PASSWORD.to_string(), Some(ip_address.clone())).unwrap();
+
+    let remaining = login_throttles::table
+        .filter(login_throttles::key.eq_any([&email, &ip_address]))
+        .load::<LoginThrottle>(&mut conn)
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].scope, ThrottleScope::Ip.as_str());
+
+    cleanup(&mut conn, &email, &ip_address);
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_concurrent_failures_are_all_counted() {
+    let ip_address = Uuid::new_v4().to_string();
+
+    // Every thread targets its own account, staying below the account lockout
+    let handles = (0..4)
+        .map(|_| {
+            let ip_address = ip_address.clone();
+            std::thread::spawn(move || {
+                let repo = repo();
+                let email = unique_email();
+                for _ in 0..3 {
+                    let result = repo.verify_credentials(email.clone(), "wrong".to_string(), Some(ip_address.clone()));
+                    assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+                }
+                email
+            })
+        })
+        .collect::<Vec<_>>();
+    let emails = handles.into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+
+    let mut conn = pool().get().unwrap();
+    let ip_throttle = login_throttles::table
+        .find((ThrottleScope::Ip.as_str(), &ip_address))
+        .first::<LoginThrottle>(&mut conn)
+        .unwrap();
+    assert_eq!(ip_throttle.failures, 12);
+
+    for email in emails {
+        cleanup(&mut conn, &email, &ip_address);
+    }
+}
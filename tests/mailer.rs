@@ -0,0 +1,121 @@
+//! Checks the mailers against a temporary directory and a minimal SMTP server on localhost.
+
+use fitness_workout_tracker_api_rust::mailer::{Email, FileMailer, Mailer, MailerError, SmtpConfig, SmtpMailer, SmtpTls};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+fn email() -> Email {
+    Email {
+        to: "member@example.com".to_string(),
+        subject: "Reset your password".to_string(),
+        body: "https://app.example.com/reset-password?token=abc".to_string(),
+    }
+}
+
+/// Accepts one SMTP conversation and returns the commands and message it received. Recipients
+/// in `rejected` are refused like an unknown mailbox would be.
+fn smtp_stand_in(rejected: &'static [&'static str]) -> (u16, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut received = vec![];
+        let mut in_data = false;
+
+        writer.write_all(b"220 localhost ESMTP stand-in\r\n").unwrap();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            received.push(line.clone());
+
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                b"250 Queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250 localhost\r\n"
+            } else if line.starts_with("RCPT TO") && rejected.iter().any(|address| line.contains(address)) {
+                b"550 No such mailbox\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 End data with <CR><LF>.<CR><LF>\r\n"
+            } else if line == "QUIT" {
+                writer.write_all(b"221 Bye\r\n").unwrap();
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            writer.write_all(reply).unwrap();
+        }
+        received
+    });
+
+    (port, handle)
+}
+
+fn smtp_mailer(port: u16) -> SmtpMailer {
+    SmtpMailer::new(&SmtpConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        username: None,
+        password: None,
+        tls: SmtpTls::None,
+        from: "Fitness Tracker <no-reply@example.com>".to_string(),
+        timeout: Duration::from_secs(5),
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_file_mailer_writes_each_email() {
+    let dir = std::env::temp_dir().join(format!("mailer-{}", uuid::Uuid::new_v4()));
+    let mailer = FileMailer::new(dir.clone());
+
+    mailer.send(&email()).unwrap();
+    mailer.send(&email()).unwrap();
+
+    let files = std::fs::read_dir(&dir).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(files.len(), 2);
+    let contents = std::fs::read_to_string(files[0].path()).unwrap();
+    assert!(contents.starts_with("To: member@example.com\nSubject: Reset your password\n\n"));
+    assert!(contents.contains("reset-password?token=abc"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_smtp_mailer_delivers() {
+    let (port, server) = smtp_stand_in(&[]);
+
+    smtp_mailer(port).send(&email()).unwrap();
+
+    let received = server.join().unwrap();
+    assert!(received.contains(&"MAIL FROM:<no-reply@example.com>".to_string()));
+    assert!(received.contains(&"RCPT TO:<member@example.com>".to_string()));
+    assert!(received.contains(&"Subject: Reset your password".to_string()));
+    assert!(received.iter().any(|line| line.contains("reset-password?token=abc")));
+}
+
+#[test]
+fn test_smtp_mailer_reports_rejection() {
+    let (port, server) = smtp_stand_in(&["member@example.com"]);
+
+    let result = smtp_mailer(port).send(&email());
+    assert!(matches!(result, Err(MailerError::DeliveryFailed(_))));
+    server.join().unwrap();
+
+    let result = smtp_mailer(port).send(&Email { to: "not an address".to_string(), ..email() });
+    assert!(matches!(result, Err(MailerError::InvalidAddress(_))));
+}
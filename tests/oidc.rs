@@ -0,0 +1,371 @@
+//! Runs the OpenID Connect client against a mock issuer on localhost, which serves discovery and
+//! a token endpoint that checks PKCE like a real provider.
+
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use fitness_workout_tracker_api_rust::oidc::{code_challenge, IdentityClaims, IdentityProvider, OidcClient, OidcConfig, OidcError};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use url::{form_urlencoded, Url};
+
+const CLIENT_ID: &str = "fitness-tracker";
+const REDIRECT_URI: &str = "https://app.example.com/oidc/callback";
+
+#[derive(Default)]
+struct IssuerOptions {
+    /// Claimed in the discovery document instead of the issuer's own URL.
+    claimed_issuer: Option<String>,
+    token_endpoint_auth_methods: Option<Vec<&'static str>>,
+    chunked: bool,
+    /// Serves discovery from `/discovery` and redirects there from the well-known path.
+    moved_discovery: bool,
+    /// Bytes of filler added to the discovery document.
+    padding: usize,
+}
+
+/// A login the user completed at the issuer, redeemable once with its code.
+struct AuthorizedLogin {
+    code_challenge: String,
+    claims: serde_json::Value,
+}
+
+#[derive(Default)]
+struct IssuerState {
+    logins: HashMap<String, AuthorizedLogin>,
+    issued_codes: usize,
+    /// The headers and form of every token request.
+    token_requests: Vec<(Vec<String>, HashMap<String, String>)>,
+}
+
+struct MockIssuer {
+    url: String,
+    state: Arc<Mutex<IssuerState>>,
+}
+
+impl MockIssuer {
+    fn start(options: IssuerOptions) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let state = Arc::new(Mutex::new(IssuerState::default()));
+
+        let (issuer, shared) = (url.clone(), state.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                serve(stream.unwrap(), &issuer, &options, &shared);
+            }
+        });
+
+        Self { url, state }
+    }
+
+    fn client(&self, client_secret: Option<&str>) -> OidcClient {
+        OidcClient::new(OidcConfig {
+            issuer: self.url.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: client_secret.map(String::from),
+            redirect_uri: REDIRECT_URI.to_string(),
+            scopes: "openid email".to_string(),
+            timeout: Duration::from_secs(5),
+        })
+        .unwrap()
+    }
+
+    /// Plays the user signing in at `authorization_url`, checks the request and returns the code
+    /// the browser is sent back with. `claims` are merged into the ID token's claims.
+    fn authorize(&self, authorization_url: &str, state: &str, claims: serde_json::Value) -> String {
+        assert!(authorization_url.starts_with(&format!("{}/authorize?", self.url)));
+        let url = Url::parse(authorization_url).unwrap();
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["redirect_uri"], REDIRECT_URI);
+        assert_eq!(params["scope"], "openid email");
+        assert_eq!(params["state"], state);
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let mut id_token_claims = json!({
+            "iss": self.url,
+            "sub": "staff-1",
+            "aud": CLIENT_ID,
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "iat": chrono::Utc::now().timestamp(),
+            "nonce": params["nonce"],
+            "email": "staff@example.com",
+            "email_verified": true,
+        });
+        for (name, value) in claims.as_object().unwrap() {
+            id_token_claims[name] = value.clone();
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.issued_codes += 1;
+        let code = format!("code-{}", state.issued_codes);
+        state.logins.insert(code.clone(), AuthorizedLogin {
+            code_challenge: params["code_challenge"].clone(),
+            claims: id_token_claims,
+        });
+        code
+    }
+}
+
+fn serve(stream: TcpStream, issuer: &str, options: &IssuerOptions, state: &Mutex<IssuerState>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim_end().is_empty() {
+            break;
+        }
+        headers.push(line.trim_end().to_string());
+    }
+    let length = headers.iter()
+        .find_map(|header| header.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+
+    let discovery_path = if options.moved_discovery { "/discovery" } else { "/.well-known/openid-configuration" };
+    let (status, response) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", path] if path == discovery_path => ("200 OK", json!({
+            "issuer": options.claimed_issuer.as_deref().unwrap_or(issuer),
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "token_endpoint_auth_methods_supported": options.token_endpoint_auth_methods,
+            "code_challenge_methods_supported": ["S256"],
+            "padding": "x".repeat(options.padding),
+        })),
+        ["GET", "/.well-known/openid-configuration"] => {
+            write!(&stream, "HTTP/1.1 308 Permanent Redirect\r\nLocation: /discovery\r\nContent-Length: 0\r\n\r\n").unwrap();
+            return;
+        },
+        ["POST", "/token"] => {
+            let form = form_urlencoded::parse(&body).into_owned().collect::<HashMap<_, _>>();
+            let mut state = state.lock().unwrap();
+            state.token_requests.push((headers, form.clone()));
+            redeem_code(&mut state, &form)
+        },
+        _ => ("404 Not Found", json!({})),
+    };
+
+    let response = response.to_string();
+    let mut writer = stream;
+    if options.chunked {
+        let (first, second) = response.split_at(response.len() / 2);
+        write!(
+            writer,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x};ext=1\r\n{}\r\n0\r\n\r\n",
+            status, first.len(), first, second.len(), second,
+        ).unwrap();
+    } else {
+        write!(
+            writer,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status, response.len(), response,
+        ).unwrap();
+    }
+}
+
+fn redeem_code(state: &mut IssuerState, form: &HashMap<String, String>) -> (&'static str, serde_json::Value) {
+    let invalid_grant = ("400 Bad Request", json!({ "error": "invalid_grant" }));
+    if form.get("grant_type").map(String::as_str) != Some("authorization_code") || form.get("redirect_uri").map(String::as_str) != Some(REDIRECT_URI) {
+        return invalid_grant;
+    }
+    let Some(login) = form.get("code").and_then(|code| state.logins.remove(code)) else {
+        return invalid_grant;
+    };
+    let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+    if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) != login.code_challenge {
+        return invalid_grant;
+    }
+
+    let id_token = format!(
+        "{}.{}.signature",
+        URL_SAFE_NO_PAD.encode(json!({ "alg": "RS256", "typ": "JWT" }).to_string()),
+        URL_SAFE_NO_PAD.encode(login.claims.to_string()),
+    );
+    ("200 OK", json!({
+        "access_token": "access-token",
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token,
+    }))
+}
+
+/// Starts a login the way the routes do and returns the code, verifier and nonce to finish it.
+fn sign_in(issuer: &MockIssuer, client: &OidcClient, claims: serde_json::Value) -> (String, String, String) {
+    let (state, nonce) = ("some-state".to_string(), "some-nonce".to_string());
+    let verifier = fitness_workout_tracker_api_rust::oidc::generate_code_verifier();
+    let authorization_url = client.authorization_url(&state, &nonce, &code_challenge(&verifier)).unwrap();
+    let code = issuer.authorize(&authorization_url, &state, claims);
+    (code, verifier, nonce)
+}
+
+#[test]
+fn test_code_challenge() {
+    // RFC 7636, Appendix B
+    assert_eq!(code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+
+    let verifier = fitness_workout_tracker_api_rust::oidc::generate_code_verifier();
+    assert_eq!(verifier.len(), 43);
+    assert_ne!(verifier, fitness_workout_tracker_api_rust::oidc::generate_code_verifier());
+}
+
+#[test]
+fn test_sign_in() {
+    let issuer = MockIssuer::start(IssuerOptions::default());
+    let client = issuer.client(None);
+
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({}));
+    let claims = client.exchange_code(&code, &verifier, &nonce).unwrap();
+    assert_eq!(claims, IdentityClaims {
+        issuer: issuer.url.clone(),
+        subject: "staff-1".to_string(),
+        email: Some("staff@example.com".to_string()),
+        email_verified: true,
+    });
+
+    // Public clients only prove themselves with the verifier
+    {
+        let state = issuer.state.lock().unwrap();
+        let (headers, form) = &state.token_requests[0];
+        assert!(!headers.iter().any(|header| header.to_ascii_lowercase().starts_with("authorization:")));
+        assert_eq!(form["client_id"], CLIENT_ID);
+        assert!(!form.contains_key("client_secret"));
+    }
+
+    // The code is single use, and only good with the verifier it was requested with
+    let result = client.exchange_code(&code, &verifier, &nonce);
+    assert!(matches!(result, Err(OidcError::Request(_))));
+    let (code, _, nonce) = sign_in(&issuer, &client, json!({}));
+    let result = client.exchange_code(&code, &verifier, &nonce);
+    assert!(matches!(result, Err(OidcError::Request(_))));
+
+    // Providers that send the flag as a string or leave out the email are understood
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({ "email_verified": "false" }));
+    assert!(!client.exchange_code(&code, &verifier, &nonce).unwrap().email_verified);
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({ "email": null, "email_verified": null }));
+    let claims = client.exchange_code(&code, &verifier, &nonce).unwrap();
+    assert_eq!((claims.email, claims.email_verified), (None, false));
+}
+
+#[test]
+fn test_id_token_validation() {
+    let issuer = MockIssuer::start(IssuerOptions::default());
+    let client = issuer.client(None);
+
+    let (code, verifier, _) = sign_in(&issuer, &client, json!({}));
+    let result = client.exchange_code(&code, &verifier, "another-nonce");
+    assert!(matches!(result, Err(OidcError::InvalidResponse(_))));
+
+    for claims in [
+        json!({ "iss": "https://other-issuer.example.com" }),
+        json!({ "aud": "other-client" }),
+        json!({ "aud": [CLIENT_ID, "other-client"] }),
+        json!({ "aud": [CLIENT_ID, "other-client"], "azp": "other-client" }),
+        json!({ "exp": chrono::Utc::now().timestamp() - 120 }),
+        json!({ "nonce": null }),
+    ] {
+        let (code, verifier, nonce) = sign_in(&issuer, &client, claims.clone());
+        let result = client.exchange_code(&code, &verifier, &nonce);
+        assert!(matches!(result, Err(OidcError::InvalidResponse(_))), "{}", claims);
+    }
+
+    for claims in [
+        json!({ "aud": [CLIENT_ID] }),
+        json!({ "aud": [CLIENT_ID, "other-client"], "azp": CLIENT_ID }),
+        // Within the allowed clock skew
+        json!({ "exp": chrono::Utc::now().timestamp() - 30 }),
+    ] {
+        let (code, verifier, nonce) = sign_in(&issuer, &client, claims.clone());
+        assert!(client.exchange_code(&code, &verifier, &nonce).is_ok(), "{}", claims);
+    }
+}
+
+#[test]
+fn test_client_authentication() {
+    // Without a list of methods, HTTP Basic is assumed
+    let issuer = MockIssuer::start(IssuerOptions::default());
+    let client = issuer.client(Some("se cret:1"));
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({}));
+    client.exchange_code(&code, &verifier, &nonce).unwrap();
+    {
+        let state = issuer.state.lock().unwrap();
+        let (headers, form) = &state.token_requests[0];
+        let expected = format!("Basic {}", STANDARD.encode("fitness-tracker:se+cret%3A1"));
+        assert!(headers.iter().any(|header| {
+            header.split_once(':').is_some_and(|(name, value)| name.eq_ignore_ascii_case("authorization") && value.trim() == expected)
+        }));
+        assert!(!form.contains_key("client_secret"));
+    }
+
+    let issuer = MockIssuer::start(IssuerOptions {
+        token_endpoint_auth_methods: Some(vec!["client_secret_post"]),
+        ..Default::default()
+    });
+    let client = issuer.client(Some("se cret:1"));
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({}));
+    client.exchange_code(&code, &verifier, &nonce).unwrap();
+    let state = issuer.state.lock().unwrap();
+    let (headers, form) = &state.token_requests[0];
+    assert!(!headers.iter().any(|header| header.to_ascii_lowercase().starts_with("authorization:")));
+    assert_eq!(form["client_secret"], "se cret:1");
+}
+
+#[test]
+fn test_chunked_responses() {
+    let issuer = MockIssuer::start(IssuerOptions { chunked: true, ..Default::default() });
+    let client = issuer.client(None);
+
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({}));
+    assert_eq!(client.exchange_code(&code, &verifier, &nonce).unwrap().subject, "staff-1");
+}
+
+#[test]
+fn test_moved_discovery_document() {
+    let issuer = MockIssuer::start(IssuerOptions { moved_discovery: true, ..Default::default() });
+    let client = issuer.client(None);
+
+    let (code, verifier, nonce) = sign_in(&issuer, &client, json!({}));
+    assert_eq!(client.exchange_code(&code, &verifier, &nonce).unwrap().subject, "staff-1");
+}
+
+#[test]
+fn test_oversized_response() {
+    let issuer = MockIssuer::start(IssuerOptions { padding: 2 * 1024 * 1024, ..Default::default() });
+    let result = issuer.client(None).authorization_url("state", "nonce", "challenge");
+    assert!(matches!(result, Err(OidcError::Request(_))));
+}
+
+#[test]
+fn test_issuer_checks() {
+    let issuer = MockIssuer::start(IssuerOptions {
+        claimed_issuer: Some("https://idp.example.com".to_string()),
+        ..Default::default()
+    });
+    let result = issuer.client(None).authorization_url("state", "nonce", "challenge");
+    assert!(matches!(result, Err(OidcError::InvalidResponse(_))));
+
+    let config = |issuer: &str| OidcConfig {
+        issuer: issuer.to_string(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: None,
+        redirect_uri: REDIRECT_URI.to_string(),
+        scopes: "openid".to_string(),
+        timeout: Duration::from_secs(1),
+    };
+    // Plain HTTP is only allowed for a mock issuer on this machine
+    assert!(matches!(OidcClient::new(config("http://idp.example.com")), Err(OidcError::InvalidConfig(_))));
+    assert!(matches!(OidcClient::new(config("not a url")), Err(OidcError::InvalidConfig(_))));
+    assert!(OidcClient::new(config("https://idp.example.com")).is_ok());
+    assert!(OidcClient::new(config("http://localhost:8081")).is_ok());
+    assert!(OidcClient::new(config("http://[::1]:8081")).is_ok());
+}
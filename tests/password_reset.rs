@@ -0,0 +1,64 @@
+//! Checks that password reset tokens in `PgAuthRepository` are single-use.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test password_reset
+//! ```
+
+mod common;
+
+use common::{PASSWORD, delete_users, pool, unique_email};
+
+use diesel::prelude::*;
+use fitness_workout_tracker_api_rust::{
+    models::{email_token::EmailToken, session::{SessionClient, SessionConfig}},
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::email_tokens,
+};
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_reset_token_is_single_use() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let email = unique_email();
+    let user = repo.create_user(email.clone(), PASSWORD.to_string()).unwrap();
+    let session = repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+
+    assert!(repo.create_password_reset(unique_email()).unwrap().is_none());
+    let (_, token) = repo.create_password_reset(email.clone()).unwrap().unwrap();
+    // Another reset email right away isn't sent
+    assert!(repo.create_password_reset(email.clone()).unwrap().is_none());
+    diesel::update(email_tokens::table.filter(email_tokens::user_id.eq(user.id)))
+        .set(email_tokens::created_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::minutes(2)))
+        .execute(&mut conn)
+        .unwrap();
+    let (_, other_token) = repo.create_password_reset(email.clone()).unwrap().unwrap();
+
+    let stored = email_tokens::table
+        .filter(email_tokens::user_id.eq(user.id))
+        .load::<EmailToken>(&mut conn)
+        .unwrap();
+    assert_eq!(stored.len(), 2);
+    assert!(stored.iter().all(|t| t.token_hash != token && t.used_at.is_none()));
+
+    // Concurrent attempts with the same token: exactly one wins
+    let results = std::thread::scope(|scope| {
+        let handles = (0..4)
+            .map(|i| {
+                let (repo, token) = (&repo, &token);
+                scope.spawn(move || repo.reset_password(token, format!("newpassword{}", i)))
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+    });
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    assert!(results.iter().all(|result| result.is_ok() || matches!(result, Err(AuthError::InvalidToken))));
+
+    assert!(matches!(repo.validate_session(&session.token), Err(AuthError::InvalidSession)));
+    assert!(matches!(repo.reset_password(&other_token, "newpassword".to_string()), Err(AuthError::InvalidToken)));
+
+    delete_users(&mut conn, &[user.id]);
+}
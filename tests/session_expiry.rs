@@ -0,0 +1,100 @@
+//! Checks sliding session expiry in `PgAuthRepository::validate_session`.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test session_expiry
+//! ```
+
+mod common;
+
+use common::{create_user, delete_users, pool};
+
+use chrono::{Duration, NaiveDateTime};
+use diesel::prelude::*;
+use fitness_workout_tracker_api_rust::{
+    db::config::DbPool,
+    models::session::{Session, SessionClient, SessionConfig, SessionLifetime},
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::sessions,
+};
+
+fn config() -> SessionConfig {
+    let lifetime = SessionLifetime {
+        idle: Duration::hours(1),
+        absolute: Duration::hours(8),
+    };
+    SessionConfig { standard: lifetime, remember_me: lifetime }
+}
+
+fn setup() -> (DbPool, PgAuthRepository, Session) {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), config());
+    let user = create_user(&repo);
+    let session = repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+    (pool, repo, session)
+}
+
+fn reload(pool: &DbPool, session: &Session) -> Session {
+    sessions::table
+        .filter(sessions::id.eq(session.id))
+        .first(&mut pool.get().unwrap())
+        .unwrap()
+}
+
+/// Pretends the session was last used `ago` before now.
+fn backdate(pool: &DbPool, session: &Session, ago: Duration, expires_at: NaiveDateTime) {
+    let last_seen_at = chrono::Utc::now().naive_utc() - ago;
+    diesel::update(sessions::table.filter(sessions::id.eq(session.id)))
+        .set((sessions::last_seen_at.eq(last_seen_at), sessions::expires_at.eq(expires_at)))
+        .execute(&mut pool.get().unwrap())
+        .unwrap();
+}
+
+fn cleanup(pool: &DbPool, session: &Session) {
+    delete_users(&mut pool.get().unwrap(), &[session.user_id]);
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_activity_slides_expiry() {
+    let (pool, repo, session) = setup();
+    let soon = chrono::Utc::now().naive_utc() + Duration::minutes(5);
+
+    // Within the throttle window nothing is written
+    backdate(&pool, &session, Duration::seconds(30), soon);
+    let before = reload(&pool, &session);
+    repo.validate_session(&session.token).unwrap();
+    assert_eq!(reload(&pool, &session).expires_at, before.expires_at);
+    assert_eq!(reload(&pool, &session).last_seen_at, before.last_seen_at);
+
+    backdate(&pool, &session, Duration::minutes(55), soon);
+    repo.validate_session(&session.token).unwrap();
+    let refreshed = reload(&pool, &session);
+    assert!(refreshed.expires_at > soon + Duration::minutes(50));
+    assert!(refreshed.last_seen_at > soon - Duration::minutes(6));
+
+    cleanup(&pool, &session);
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_expiry_is_capped_and_final() {
+    let (pool, repo, session) = setup();
+
+    // Close to the absolute limit the expiry can only reach that limit
+    diesel::update(sessions::table.filter(sessions::id.eq(session.id)))
+        .set(sessions::absolute_expires_at.eq(chrono::Utc::now().naive_utc() + Duration::minutes(10)))
+        .execute(&mut pool.get().unwrap())
+        .unwrap();
+    let absolute_expires_at = reload(&pool, &session).absolute_expires_at;
+    backdate(&pool, &session, Duration::minutes(2), absolute_expires_at - Duration::minutes(5));
+    repo.validate_session(&session.token).unwrap();
+    assert_eq!(reload(&pool, &session).expires_at, absolute_expires_at);
+
+    // An idle session that has expired is not revived
+    backdate(&pool, &session, Duration::hours(2), chrono::Utc::now().naive_utc() - Duration::seconds(1));
+    assert!(matches!(repo.validate_session(&session.token), Err(AuthError::InvalidSession)));
+
+    cleanup(&pool, &session);
+}
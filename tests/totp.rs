@@ -0,0 +1,45 @@
+//! Checks the TOTP implementation against the test vectors of RFC 6238, appendix B.
+
+use fitness_workout_tracker_api_rust::totp;
+
+/// The SHA1 key of the RFC, "12345678901234567890", in base32.
+const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+#[test]
+fn test_rfc_6238_vectors() {
+    // The RFC lists eight digits; authenticator apps use the last six
+    for (unix_seconds, code) in [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+        (20000000000, "353130"),
+    ] {
+        assert_eq!(totp::code_at(SECRET, totp::step_at(unix_seconds)).as_deref(), Some(code), "{}", unix_seconds);
+    }
+}
+
+#[test]
+fn test_verify_allows_drift_and_refuses_replay() {
+    let step = totp::step_at(1111111111);
+    let code = totp::code_at(SECRET, step).unwrap();
+
+    assert_eq!(totp::verify(SECRET, &code, step, None), Some(step));
+    assert_eq!(totp::verify(SECRET, &format!(" {} ", code), step + 1, None), Some(step));
+    assert_eq!(totp::verify(SECRET, &code, step + 2, None), None);
+    assert_eq!(totp::verify(SECRET, &code, step, Some(step)), None);
+    assert_eq!(totp::verify(SECRET, "12345", step, None), None);
+    assert_eq!(totp::code_at("not base32!", step), None);
+}
+
+#[test]
+fn test_otpauth_uri() {
+    let secret = totp::generate_secret();
+    assert_eq!(secret.len(), 32);
+
+    assert_eq!(
+        totp::otpauth_uri(&secret, "coach+1@example.com"),
+        format!("otpauth://totp/Fitness%20Tracker:coach%2B1%40example.com?secret={}&issuer=Fitness%20Tracker&algorithm=SHA1&digits=6&period=30", secret),
+    );
+}
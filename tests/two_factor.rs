@@ -0,0 +1,111 @@
+//! Checks the TOTP second login step in `PgAuthRepository`.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test two_factor
+//! ```
+
+mod common;
+
+use common::{PASSWORD, create_user, delete_users, pool};
+use fitness_workout_tracker_api_rust::{
+    models::{session::{Session, SessionClient, SessionConfig}, two_factor::TwoFactorCode, user::User},
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    totp,
+};
+
+fn login(repo: &PgAuthRepository, user: &User) -> Session {
+    repo.create_session(user.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap()
+}
+
+fn current_step() -> i64 {
+    totp::step_at(chrono::Utc::now().timestamp())
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_second_login_step() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let user = create_user(&repo);
+
+    let session = login(&repo, &user);
+    let (_, secret) = repo.begin_totp_enrollment(&session.token).unwrap();
+    assert!(!login(&repo, &user).two_factor_pending);
+
+    let step = current_step();
+    let recovery_codes = repo
+        .confirm_totp_enrollment(&session.token, &totp::code_at(&secret, step).unwrap())
+        .unwrap();
+    assert!(matches!(repo.begin_totp_enrollment(&session.token), Err(AuthError::TwoFactorAlreadyEnabled)));
+
+    let pending = login(&repo, &user);
+    assert!(pending.two_factor_pending);
+    assert!(matches!(repo.validate_session(&pending.token), Err(AuthError::TwoFactorRequired)));
+    assert!(matches!(repo.list_sessions(&pending.token), Err(AuthError::InvalidSession)));
+
+    // The step used at confirmation can't complete a login
+    let used = totp::code_at(&secret, step).unwrap();
+    assert!(matches!(
+        repo.verify_two_factor(&pending.token, TwoFactorCode::Totp(used)),
+        Err(AuthError::InvalidTwoFactorCode)
+    ));
+
+    let code = totp::code_at(&secret, step + 1).unwrap();
+    let (_, verified) = repo.verify_two_factor(&pending.token, TwoFactorCode::Totp(code.clone())).unwrap();
+    assert_ne!(verified.token, pending.token);
+    assert!(!verified.two_factor_pending);
+    assert_eq!(repo.validate_session(&verified.token).unwrap(), user.id);
+    assert!(matches!(repo.validate_session(&pending.token), Err(AuthError::InvalidSession)));
+
+    let pending = login(&repo, &user);
+    assert!(matches!(
+        repo.verify_two_factor(&pending.token, TwoFactorCode::Totp(code)),
+        Err(AuthError::InvalidTwoFactorCode)
+    ));
+
+    let recovery_code = recovery_codes[0].clone();
+    assert!(repo.verify_two_factor(&pending.token, TwoFactorCode::Recovery(recovery_code.clone())).is_ok());
+    let pending = login(&repo, &user);
+    assert!(matches!(
+        repo.verify_two_factor(&pending.token, TwoFactorCode::Recovery(recovery_code)),
+        Err(AuthError::InvalidTwoFactorCode)
+    ));
+
+    assert!(matches!(repo.disable_totp(&verified.token, "wrong".to_string()), Err(AuthError::InvalidCredentials)));
+    repo.disable_totp(&verified.token, PASSWORD.to_string()).unwrap();
+    assert!(!login(&repo, &user).two_factor_pending);
+
+    delete_users(&mut conn, &[user.id]);
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_second_login_step_throttling() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let user = create_user(&repo);
+
+    let session = login(&repo, &user);
+    let (_, secret) = repo.begin_totp_enrollment(&session.token).unwrap();
+    repo.confirm_totp_enrollment(&session.token, &totp::code_at(&secret, current_step()).unwrap()).unwrap();
+
+    // Guessing codes locks the second step, even for a fresh login and the right code
+    let pending = login(&repo, &user);
+    let results = (0..7)
+        .map(|_| repo.verify_two_factor(&pending.token, TwoFactorCode::Totp("000000".to_string())))
+        .collect::<Vec<_>>();
+    assert!(matches!(results.last(), Some(Err(AuthError::TooManyAttempts(_)))));
+
+    let pending = login(&repo, &user);
+    let code = totp::code_at(&secret, current_step() + 1).unwrap();
+    assert!(matches!(
+        repo.verify_two_factor(&pending.token, TwoFactorCode::Totp(code)),
+        Err(AuthError::TooManyAttempts(_))
+    ));
+
+    delete_users(&mut conn, &[user.id]);
+}
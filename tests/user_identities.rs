@@ -0,0 +1,129 @@
+//! Checks single sign-on in `PgAuthRepository`.
+//!
+//! These tests need a migrated PostgreSQL database in `DATABASE_URL`:
+//!
+//! ```bash
+//! scripts/run_db_tests.sh --test user_identities
+//! ```
+
+mod common;
+
+use common::{PASSWORD, create_user, delete_users, pool, unique_email};
+
+use diesel::prelude::*;
+use fitness_workout_tracker_api_rust::{
+    models::session::{SessionClient, SessionConfig},
+    oidc::IdentityClaims,
+    repositories::auth_repository::{AuthError, AuthRepository, PgAuthRepository},
+    schema::public::{oidc_login_attempts, user_identities, users},
+};
+use uuid::Uuid;
+
+fn claims(subject: &str, email: Option<&str>, email_verified: bool) -> IdentityClaims {
+    IdentityClaims {
+        issuer: "https://idp.example.com".to_string(),
+        subject: subject.to_string(),
+        email: email.map(String::from),
+        email_verified,
+    }
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_oidc_login_is_single_use() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+
+    let (state, attempt) = repo.create_oidc_login().unwrap();
+    assert_ne!(attempt.state_hash, state);
+    assert_ne!(attempt.nonce, attempt.code_verifier);
+    assert_eq!(repo.take_oidc_login(&state).unwrap().id, attempt.id);
+    assert!(matches!(repo.take_oidc_login(&state), Err(AuthError::InvalidToken)));
+
+    // Expired attempts are refused, and cleaned up by the next login
+    let (state, attempt) = repo.create_oidc_login().unwrap();
+    diesel::update(oidc_login_attempts::table.find(attempt.id))
+        .set(oidc_login_attempts::expires_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1)))
+        .execute(&mut conn)
+        .unwrap();
+    assert!(matches!(repo.take_oidc_login(&state), Err(AuthError::InvalidToken)));
+
+    let (_, expired) = repo.create_oidc_login().unwrap();
+    diesel::update(oidc_login_attempts::table.find(expired.id))
+        .set(oidc_login_attempts::expires_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::seconds(1)))
+        .execute(&mut conn)
+        .unwrap();
+    let (state, _) = repo.create_oidc_login().unwrap();
+    let remaining = oidc_login_attempts::table
+        .find(expired.id)
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(remaining, 0);
+    repo.take_oidc_login(&state).unwrap();
+}
+
+#[test]
+#[ignore = "requires a migrated PostgreSQL database in DATABASE_URL"]
+fn test_sign_in_with_identity() {
+    let pool = pool();
+    let repo = PgAuthRepository::new(pool.clone(), SessionConfig::default());
+    let mut conn = pool.get().unwrap();
+    let subject = Uuid::new_v4().to_string();
+    let email = unique_email();
+
+    assert!(matches!(repo.sign_in_with_identity(claims(&subject, None, true)), Err(AuthError::MissingEmail)));
+
+    // The first sign-in creates the account
+    let created = repo.sign_in_with_identity(claims(&subject, Some(&email), true)).unwrap();
+    assert_eq!(created.email, email);
+    assert!(created.is_email_verified());
+    assert!(matches!(repo.verify_credentials(email.clone(), PASSWORD.to_string(), None), Err(AuthError::InvalidCredentials)));
+
+    // Later ones find it by subject, whatever the email is now
+    let renamed = unique_email();
+    let user = repo.sign_in_with_identity(claims(&subject, Some(&renamed), false)).unwrap();
+    assert_eq!(user.id, created.id);
+    let identity_email = user_identities::table
+        .filter(user_identities::user_id.eq(created.id))
+        .select(user_identities::email)
+        .first::<Option<String>>(&mut conn)
+        .unwrap();
+    assert_eq!(identity_email, Some(renamed));
+
+    // Existing accounts are only linked on a verified email address
+    let local = create_user(&repo);
+    let session = repo.create_session(local.id, None, "csrf".to_string(), SessionClient::default(), false).unwrap();
+    let (_, api_token) = repo.create_api_token(&session.token, "CLI".to_string(), vec!["workouts:read".to_string()], None).unwrap();
+    let other_subject = Uuid::new_v4().to_string();
+    let result = repo.sign_in_with_identity(claims(&other_subject, Some(&local.email), false));
+    assert!(matches!(result, Err(AuthError::DuplicateEmail)));
+    let linked = repo.sign_in_with_identity(claims(&other_subject, Some(&local.email), true)).unwrap();
+    assert_eq!(linked.id, local.id);
+    assert!(linked.is_email_verified());
+
+    // The address was never verified, so whoever registered it loses their way in
+    let result = repo.verify_credentials(local.email.clone(), PASSWORD.to_string(), None);
+    assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+    assert!(matches!(repo.validate_session(&session.token), Err(AuthError::InvalidSession)));
+    assert!(matches!(repo.authenticate_api_token(&api_token), Err(AuthError::InvalidToken)));
+
+    // Verified accounts keep their password when they are linked
+    let verified = create_user(&repo);
+    diesel::update(users::table.find(verified.id))
+        .set(users::email_verified_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&mut conn)
+        .unwrap();
+    let third_subject = Uuid::new_v4().to_string();
+    assert_eq!(repo.sign_in_with_identity(claims(&third_subject, Some(&verified.email), true)).unwrap().id, verified.id);
+    repo.verify_credentials(verified.email.clone(), PASSWORD.to_string(), None).unwrap();
+
+    delete_users(&mut conn, &[created.id, local.id, verified.id]);
+    let identities = user_identities::table
+        .filter(user_identities::subject.eq_any([subject, other_subject, third_subject]))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(identities, 0);
+}